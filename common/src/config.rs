@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::time::Duration;
 
 const CONFIG_TEMPLATE: &str = include_str!("../config.toml");
 
@@ -8,6 +9,16 @@ pub struct ServerConfig {
     pub server: ServerSettings,
     pub paths: PathConfig,
     pub database: DatabaseConfig,
+    #[serde(default)]
+    pub commands: CommandConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub capture: CaptureConfig,
+    #[serde(default)]
+    pub summon: SummonConfig,
+    #[serde(default)]
+    pub combat: CombatConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +41,183 @@ pub struct DatabaseConfig {
     pub path: PathBuf,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandConfig {
+    /// What to do when the client sends a cmd with no handler
+    pub unhandled_policy: UnhandledCmdPolicy,
+    /// result_code sent back for unhandled cmds
+    pub unhandled_result_code: i16,
+    /// result_code sent back when a handler fails
+    pub error_result_code: i16,
+    /// Optional json file the unhandled cmd counters are dumped to
+    pub unhandled_report: Option<PathBuf>,
+}
+
+impl Default for CommandConfig {
+    fn default() -> Self {
+        Self {
+            unhandled_policy: UnhandledCmdPolicy::StaticJson,
+            unhandled_result_code: 1,
+            error_result_code: 1,
+            unhandled_report: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnhandledCmdPolicy {
+    /// Reply with an empty body and `unhandled_result_code`
+    EmptyReply,
+    /// Serve `static_data/<CmdName>.json` if present, otherwise behave like `EmptyReply`
+    StaticJson,
+    /// Drop the connection (old behaviour)
+    Disconnect,
+}
+
+/// Limits for the TCP game port. Timeouts of 0 disable that check.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Largest frame accepted from a client, length prefix included
+    pub max_frame_size: usize,
+    /// Seconds a client gets to finish sending a frame once its length prefix arrived
+    pub read_timeout_secs: u64,
+    /// Seconds without any frame before the connection is dropped.
+    /// The client heartbeats with GetServerTimeCmd, so this also catches dead clients.
+    pub idle_timeout_secs: u64,
+    /// Seconds a single packet may take to write before the peer is considered gone
+    pub write_timeout_secs: u64,
+    /// TCP keepalive idle time, lets the OS notice half-open sockets
+    pub tcp_keepalive_secs: u64,
+    /// Simultaneous connections allowed from one IP, 0 = unlimited
+    pub max_connections_per_ip: usize,
+    /// Seconds a closed connection's session is kept for a reconnect,
+    /// 0 = not kept
+    pub reconnect_window_secs: u64,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            max_frame_size: 1024 * 1024,
+            read_timeout_secs: 30,
+            idle_timeout_secs: 180,
+            write_timeout_secs: 30,
+            tcp_keepalive_secs: 60,
+            max_connections_per_ip: 16,
+            reconnect_window_secs: 300,
+        }
+    }
+}
+
+impl NetworkConfig {
+    pub fn read_timeout(&self) -> Option<Duration> {
+        secs(self.read_timeout_secs)
+    }
+
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        secs(self.idle_timeout_secs)
+    }
+
+    pub fn write_timeout(&self) -> Option<Duration> {
+        secs(self.write_timeout_secs)
+    }
+
+    pub fn tcp_keepalive(&self) -> Option<Duration> {
+        secs(self.tcp_keepalive_secs)
+    }
+
+    pub fn reconnect_window(&self) -> Option<Duration> {
+        secs(self.reconnect_window_secs)
+    }
+}
+
+fn secs(value: u64) -> Option<Duration> {
+    (value > 0).then(|| Duration::from_secs(value))
+}
+
+/// Per-session packet capture, see `utils::capture` in the gameserver
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    pub enabled: bool,
+    /// One file per connection is written here
+    pub directory: PathBuf,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: PathBuf::from("./captures"),
+        }
+    }
+}
+
+/// Overrides for the gacha odds every pool reads from `summon_pool`.
+/// Unset keys keep the table's values.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SummonConfig {
+    /// Base 6* odds, 0.015 = 1.5%
+    pub six_star_rate: Option<f64>,
+    /// Base 5* odds
+    pub five_star_rate: Option<f64>,
+    /// Dry pulls before the 6* odds start climbing
+    pub soft_pity_start: Option<u32>,
+    /// Added to the 6* odds by every pull past `soft_pity_start`
+    pub soft_pity_step: Option<f64>,
+    /// Pull on which a 6* is certain
+    pub hard_pity: Option<u32>,
+    /// Chance a 6* from a rate-up pool is an UP hero, a miss guarantees the next one
+    pub six_up_chance: Option<f64>,
+    /// Chance a 5* from a rate-up pool is an UP hero
+    pub five_up_chance: Option<f64>,
+}
+
+/// Skill multipliers the excel export has no column for, as shares of the
+/// caster's attack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CombatConfig {
+    /// By `skill.skillRank` from rank 1, higher ranks use the last one
+    pub rank_rates: Vec<f32>,
+    /// A hero's ultimate
+    pub ex_rate: f32,
+    /// Heals, as a share of the skill's rate
+    pub heal_ratio: f32,
+    /// Share of max hp a cloth heal restores
+    pub cloth_heal_ratio: f32,
+}
+
+impl Default for CombatConfig {
+    fn default() -> Self {
+        Self {
+            rank_rates: vec![1.8, 3.0, 5.0],
+            ex_rate: 6.0,
+            heal_ratio: 0.5,
+            cloth_heal_ratio: 0.2,
+        }
+    }
+}
+
+impl CombatConfig {
+    pub fn rank_rate(&self, rank: i32, is_ex: bool) -> f32 {
+        if is_ex {
+            return self.ex_rate;
+        }
+
+        let idx = (rank.max(1) - 1) as usize;
+        self.rank_rates
+            .get(idx)
+            .or(self.rank_rates.last())
+            .copied()
+            .unwrap_or(1.0)
+    }
+}
+
 impl ServerConfig {
     pub fn ensure_exists(path: &PathBuf) -> anyhow::Result<()> {
         if path.exists() {
@@ -99,6 +287,14 @@ impl ServerConfig {
         if self.paths.static_data.is_relative() {
             self.paths.static_data = config_dir.join(&self.paths.static_data);
         }
+        if let Some(report) = &self.commands.unhandled_report
+            && report.is_relative()
+        {
+            self.commands.unhandled_report = Some(config_dir.join(report));
+        }
+        if self.capture.directory.is_relative() {
+            self.capture.directory = config_dir.join(&self.capture.directory);
+        }
         Ok(())
     }
 
@@ -40,6 +40,26 @@ pub fn excel_data_directory() -> &'static PathBuf {
     &config().paths.excel_data
 }
 
+pub fn command_config() -> &'static config::CommandConfig {
+    &config().commands
+}
+
+pub fn network_config() -> &'static config::NetworkConfig {
+    &config().network
+}
+
+pub fn capture_config() -> &'static config::CaptureConfig {
+    &config().capture
+}
+
+pub fn summon_config() -> &'static config::SummonConfig {
+    &config().summon
+}
+
+pub fn combat_config() -> &'static config::CombatConfig {
+    &config().combat
+}
+
 pub fn init_tracing() {
     #[cfg(target_os = "windows")]
     let _ = ansi_term::enable_ansi_support();
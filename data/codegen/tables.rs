@@ -12,6 +12,8 @@ pub const FILTER_TABLES: &[&str] = &[
     "skill_passive_level",
     "skill",
     "skill_ex_level",
+    "skill_effect",
+    "skill_buff",
     "talent_scheme",
     "item",
     "power_item",
@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS battle_records (
+    user_id INTEGER NOT NULL,
+    episode_id INTEGER NOT NULL,
+    battle_id INTEGER NOT NULL,        -- same id as battle_replays.battle_id
+    seed INTEGER NOT NULL,             -- fight RNG seed
+    act_point INTEGER NOT NULL,
+    fight TEXT NOT NULL,               -- JSON Fight at the start
+    card_deck TEXT NOT NULL,           -- JSON array of CardInfo, the opening hand
+    final_fight TEXT NULL,             -- JSON Fight after the last round, set once it is won
+    version INTEGER NOT NULL,          -- simulator version the fight was played on
+    created_at INTEGER NOT NULL,
+    PRIMARY KEY (user_id, episode_id, battle_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_battle_records ON battle_records(user_id, episode_id);
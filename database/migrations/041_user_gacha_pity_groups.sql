@@ -0,0 +1,53 @@
+-- Pity is shared by every pool of the same summon_pool type, so a new limited
+-- banner carries on from the last one instead of starting over
+CREATE TABLE IF NOT EXISTS user_gacha_pity (
+    user_id         INTEGER NOT NULL,
+    pity_group      INTEGER NOT NULL,            -- summon_pool type
+
+    pity_6          INTEGER NOT NULL DEFAULT 0,  -- pulls since last 6*
+    up_guaranteed   INTEGER NOT NULL DEFAULT 0,  -- 0 = false, 1 = true
+    summon_count    INTEGER NOT NULL DEFAULT 0,  -- pulls across the group
+
+    last_pull_at    INTEGER,                     -- unix sec (optional, analytics/debug)
+
+    PRIMARY KEY (user_id, pity_group),
+
+    FOREIGN KEY (user_id)
+        REFERENCES users(id)
+        ON DELETE CASCADE
+);
+
+-- Fold the per-pool rows into their group. Every pull wrote a history row next
+-- to the pity, so the history knows each pool's type. The pool furthest along
+-- keeps its pity and UP guarantee together, and the group's pulls add up.
+-- A pool without history keeps its own row under group -pool_id, the server
+-- folds those in once it knows the pool's type from summon_pool.
+INSERT INTO user_gacha_pity (user_id, pity_group, pity_6, up_guaranteed, summon_count, last_pull_at)
+SELECT
+    user_id,
+    pool_type,
+    MAX(CASE WHEN furthest = 1 THEN pity_6 END),
+    MAX(CASE WHEN furthest = 1 THEN up_guaranteed END),
+    SUM(summon_count),
+    MAX(last_pull_at)
+FROM (
+    SELECT
+        s.user_id,
+        COALESCE(t.pool_type, -s.pool_id) AS pool_type,
+        s.pity_6,
+        s.up_guaranteed,
+        COALESCE(p.summon_count, 0) AS summon_count,
+        s.last_pull_at,
+        ROW_NUMBER() OVER (
+            PARTITION BY s.user_id, COALESCE(t.pool_type, -s.pool_id)
+            ORDER BY s.pity_6 DESC, s.up_guaranteed DESC
+        ) AS furthest
+    FROM user_gacha_state s
+    LEFT JOIN (
+        SELECT DISTINCT user_id, pool_id, pool_type FROM user_summon_history
+    ) t ON t.user_id = s.user_id AND t.pool_id = s.pool_id
+    LEFT JOIN user_summon_pools p ON p.user_id = s.user_id AND p.pool_id = s.pool_id
+)
+GROUP BY user_id, pool_type;
+
+DROP TABLE user_gacha_state;
@@ -0,0 +1,2 @@
+-- The hero picked when the bag was opened, 0 while it's still closed
+ALTER TABLE user_single_bags ADD COLUMN hero_id INTEGER NOT NULL DEFAULT 0;
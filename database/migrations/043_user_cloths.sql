@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS user_cloths (
+    player_id    INTEGER NOT NULL,
+    cloth_id     INTEGER NOT NULL,
+
+    level        INTEGER NOT NULL DEFAULT 1,
+    exp          INTEGER NOT NULL DEFAULT 0,
+
+    PRIMARY KEY (player_id, cloth_id),
+    FOREIGN KEY (player_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Players created before cloths were stored start with the starter ones
+INSERT OR IGNORE INTO user_cloths (player_id, cloth_id, level, exp)
+SELECT id, cloth_id, 1, 0
+FROM users, (SELECT 1 AS cloth_id UNION ALL SELECT 2 UNION ALL SELECT 6);
@@ -0,0 +1,2 @@
+-- The player's level of the fight's cloth, replays fight with it again
+ALTER TABLE battle_records ADD COLUMN cloth_level INTEGER NOT NULL DEFAULT 1;
@@ -31,6 +31,7 @@ pub async fn save_round_operations(
     Ok(())
 }
 
+/// Rounds of the latest won battle on the episode, the one `load_battle_record` returns
 pub async fn load_battle_replay(
     pool: &SqlitePool,
     user_id: i64,
@@ -47,11 +48,18 @@ pub async fn load_battle_replay(
     let rows: Vec<ReplayRow> = sqlx::query_as(
         "SELECT round_number, cloth_skill_opers, opers
          FROM battle_replays
-         WHERE user_id = ? AND episode_id = ?
+         WHERE user_id = ? AND episode_id = ? AND battle_id = (
+             SELECT battle_id FROM battle_records
+             WHERE user_id = ? AND episode_id = ? AND final_fight IS NOT NULL
+             ORDER BY created_at DESC, battle_id DESC
+             LIMIT 1
+         )
          ORDER BY round_number",
     )
     .bind(user_id)
     .bind(episode_id)
+    .bind(user_id)
+    .bind(episode_id)
     .fetch_all(pool)
     .await?;
 
@@ -69,3 +77,124 @@ pub async fn load_battle_replay(
 
     Ok(records)
 }
+
+/// Everything needed to run a fight again: the seed and the state it started from
+#[derive(Debug, Clone)]
+pub struct BattleRecord {
+    pub battle_id: i64,
+    pub seed: u64,
+    pub act_point: i32,
+    pub fight: sonettobuf::Fight,
+    pub card_deck: Vec<sonettobuf::CardInfo>,
+    pub final_fight: Option<sonettobuf::Fight>,
+    /// Simulator version the fight was played on
+    pub version: i32,
+    /// The player's level of the fight's cloth
+    pub cloth_level: i32,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn save_battle_record(
+    pool: &SqlitePool,
+    user_id: i64,
+    episode_id: i32,
+    battle_id: i64,
+    seed: u64,
+    act_point: i32,
+    fight: &sonettobuf::Fight,
+    card_deck: &[sonettobuf::CardInfo],
+    version: i32,
+    cloth_level: i32,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT OR REPLACE INTO battle_records
+         (user_id, episode_id, battle_id, seed, act_point, fight, card_deck, final_fight, version, cloth_level, created_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, NULL, ?, ?, ?)",
+    )
+    .bind(user_id)
+    .bind(episode_id)
+    .bind(battle_id)
+    .bind(seed as i64) // stored as the same 64 bits
+    .bind(act_point)
+    .bind(serde_json::to_string(fight)?)
+    .bind(serde_json::to_string(card_deck)?)
+    .bind(version)
+    .bind(cloth_level)
+    .bind(chrono::Utc::now().timestamp())
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Stores the end state of a won fight, which makes it the episode's replay
+pub async fn finish_battle_record(
+    pool: &SqlitePool,
+    user_id: i64,
+    episode_id: i32,
+    battle_id: i64,
+    final_fight: &sonettobuf::Fight,
+) -> Result<()> {
+    sqlx::query(
+        "UPDATE battle_records SET final_fight = ?, created_at = ?
+         WHERE user_id = ? AND episode_id = ? AND battle_id = ?",
+    )
+    .bind(serde_json::to_string(final_fight)?)
+    .bind(chrono::Utc::now().timestamp())
+    .bind(user_id)
+    .bind(episode_id)
+    .bind(battle_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Latest won battle on the episode
+pub async fn load_battle_record(
+    pool: &SqlitePool,
+    user_id: i64,
+    episode_id: i32,
+) -> Result<Option<BattleRecord>> {
+    #[derive(sqlx::FromRow)]
+    struct RecordRow {
+        battle_id: i64,
+        seed: i64,
+        act_point: i32,
+        fight: String,
+        card_deck: String,
+        final_fight: Option<String>,
+        version: i32,
+        cloth_level: i32,
+    }
+
+    let row: Option<RecordRow> = sqlx::query_as(
+        "SELECT battle_id, seed, act_point, fight, card_deck, final_fight, version, cloth_level
+         FROM battle_records
+         WHERE user_id = ? AND episode_id = ? AND final_fight IS NOT NULL
+         ORDER BY created_at DESC, battle_id DESC
+         LIMIT 1",
+    )
+    .bind(user_id)
+    .bind(episode_id)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    Ok(Some(BattleRecord {
+        battle_id: row.battle_id,
+        seed: row.seed as u64,
+        act_point: row.act_point,
+        fight: serde_json::from_str(&row.fight)?,
+        card_deck: serde_json::from_str(&row.card_deck)?,
+        final_fight: row
+            .final_fight
+            .map(|f| serde_json::from_str(&f))
+            .transpose()?,
+        version: row.version,
+        cloth_level: row.cloth_level,
+    }))
+}
@@ -0,0 +1,35 @@
+use crate::models::game::cloths::UserCloth;
+use anyhow::Result;
+use sonettobuf::PlayerCloth;
+use sqlx::SqlitePool;
+
+pub async fn load_cloths(pool: &SqlitePool, player_id: i64) -> Result<Vec<PlayerCloth>> {
+    let cloths = sqlx::query_as::<_, UserCloth>(
+        r#"
+            SELECT player_id, cloth_id, level, exp
+            FROM user_cloths
+            WHERE player_id = ?
+            ORDER BY cloth_id
+            "#,
+    )
+    .bind(player_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(cloths.into_iter().map(Into::into).collect())
+}
+
+/// None when the player doesn't have the cloth
+pub async fn get_cloth_level(
+    pool: &SqlitePool,
+    player_id: i64,
+    cloth_id: i32,
+) -> Result<Option<i32>> {
+    Ok(
+        sqlx::query_scalar("SELECT level FROM user_cloths WHERE player_id = ? AND cloth_id = ?")
+            .bind(player_id)
+            .bind(cloth_id)
+            .fetch_optional(pool)
+            .await?,
+    )
+}
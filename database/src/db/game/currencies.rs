@@ -1,6 +1,6 @@
 use crate::models::game::currencies::Currency;
 use common::time::ServerTime;
-use sqlx::SqlitePool;
+use sqlx::{Sqlite, SqlitePool, Transaction};
 
 pub async fn get_currencies(
     pool: &SqlitePool,
@@ -69,7 +69,7 @@ pub async fn save_currency(pool: &SqlitePool, currency: &Currency) -> sqlx::Resu
 }
 
 pub async fn add_currency(
-    pool: &SqlitePool,
+    tx: &mut Transaction<'_, Sqlite>,
     user_id: i64,
     currency_id: i32,
     amount: i32,
@@ -87,13 +87,13 @@ pub async fn add_currency(
     .bind(currency_id)
     .bind(amount)
     .bind(timestamp as i64)
-    .execute(pool)
+    .execute(&mut **tx)
     .await?;
     Ok(())
 }
 
 pub async fn remove_currency(
-    pool: &SqlitePool,
+    tx: &mut Transaction<'_, Sqlite>,
     user_id: i64,
     currency_id: i32,
     amount: i32,
@@ -102,7 +102,7 @@ pub async fn remove_currency(
         sqlx::query_scalar("SELECT quantity FROM currencies WHERE user_id = ? AND currency_id = ?")
             .bind(user_id)
             .bind(currency_id)
-            .fetch_optional(pool)
+            .fetch_optional(&mut **tx)
             .await?;
 
     if current.unwrap_or(0) < amount {
@@ -119,7 +119,7 @@ pub async fn remove_currency(
     .bind(timestamp as i64)
     .bind(user_id)
     .bind(currency_id)
-    .execute(pool)
+    .execute(&mut **tx)
     .await?;
 
     Ok(true)
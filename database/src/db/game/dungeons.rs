@@ -6,7 +6,7 @@ use crate::{
 };
 
 use anyhow::Result;
-use sqlx::{SqlitePool, prelude::FromRow};
+use sqlx::{Sqlite, SqlitePool, Transaction, prelude::FromRow};
 
 pub async fn get_user_dungeons_chunked(
     pool: &SqlitePool,
@@ -34,7 +34,7 @@ pub async fn get_user_dungeons(pool: &SqlitePool, user_id: i64) -> Result<Vec<Us
     Ok(dungeons)
 }
 
-#[derive(Debug, Clone, FromRow)]
+#[derive(Debug, Clone, Default, FromRow)]
 pub struct UserDungeonInfo {
     pub star: i32,
     pub challenge_count: i32,
@@ -55,10 +55,11 @@ pub async fn get_user_dungeon(
     .bind(user_id)
     .bind(chapter_id)
     .bind(episode_id)
-    .fetch_one(pool)
+    .fetch_optional(pool)
     .await?;
 
-    Ok(dungeon)
+    // no row until the episode is cleared once
+    Ok(dungeon.unwrap_or_default())
 }
 
 pub async fn get_dungeon_last_hero_groups(
@@ -179,15 +180,64 @@ pub async fn get_finished_puzzles(pool: &SqlitePool, user_id: i64) -> Result<Vec
     Ok(puzzles)
 }
 
+/// An episode's best stars and clear count before a clear was recorded
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PreviousClear {
+    pub star: i32,
+    pub challenge_count: i32,
+}
+
+/// Records an attempt in the caller's transaction and returns where the episode
+/// stood before it. Only a clear counts towards stars and the clear count,
+/// stars gained on the episode also go to the chapter's reward points
 pub async fn update_dungeon_progress(
-    pool: &SqlitePool,
+    tx: &mut Transaction<'_, Sqlite>,
     user_id: i64,
     chapter_id: i32,
     episode_id: i32,
+    cleared: bool,
     stars_earned: i32,
-) -> Result<()> {
+) -> Result<PreviousClear> {
     let now = common::time::ServerTime::now_ms();
 
+    let previous = sqlx::query_as::<_, (i32, i32)>(
+        "SELECT star, challenge_count FROM user_dungeons
+         WHERE user_id = ? AND chapter_id = ? AND episode_id = ?",
+    )
+    .bind(user_id)
+    .bind(chapter_id)
+    .bind(episode_id)
+    .fetch_optional(&mut **tx)
+    .await?
+    .map(|(star, challenge_count)| PreviousClear {
+        star,
+        challenge_count,
+    })
+    .unwrap_or_default();
+
+    if !cleared {
+        sqlx::query(
+            r#"
+            INSERT INTO user_dungeons
+            (user_id, chapter_id, episode_id, star, challenge_count, has_record,
+             left_return_all_num, today_pass_num, today_total_num, created_at, updated_at)
+            VALUES (?, ?, ?, 0, 0, 0, 1, 0, 1, ?, ?)
+            ON CONFLICT(user_id, chapter_id, episode_id) DO UPDATE SET
+                today_total_num = today_total_num + 1,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(chapter_id)
+        .bind(episode_id)
+        .bind(now)
+        .bind(now)
+        .execute(&mut **tx)
+        .await?;
+
+        return Ok(previous);
+    }
+
     sqlx::query(
         r#"
         INSERT INTO user_dungeons
@@ -209,10 +259,31 @@ pub async fn update_dungeon_progress(
     .bind(stars_earned)
     .bind(now)
     .bind(now)
-    .execute(pool)
+    .execute(&mut **tx)
     .await?;
 
-    Ok(())
+    let gained = stars_earned - previous.star;
+    if gained > 0 {
+        sqlx::query(
+            r#"
+            INSERT INTO user_dungeon_reward_points
+            (user_id, chapter_id, reward_point, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(user_id, chapter_id) DO UPDATE SET
+                reward_point = reward_point + excluded.reward_point,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(chapter_id)
+        .bind(gained)
+        .bind(now)
+        .bind(now)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(previous)
 }
 
 pub async fn load_dungeon_record(
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use data::exceldb;
-use sqlx::SqlitePool;
+use sqlx::{Sqlite, SqlitePool, Transaction};
 
 pub use crate::models::game::heros::*;
 
@@ -429,20 +429,24 @@ pub async fn get_birthday_info(pool: &SqlitePool, user_id: i64) -> Result<Vec<(i
     Ok(info)
 }
 
-pub async fn has_hero(pool: &sqlx::SqlitePool, user_id: i64, hero_id: i32) -> sqlx::Result<bool> {
+pub async fn has_hero(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: i64,
+    hero_id: i32,
+) -> sqlx::Result<bool> {
     let exists = sqlx::query_scalar::<_, i64>(
         "SELECT COUNT(*) FROM heroes WHERE user_id = ? AND hero_id = ?",
     )
     .bind(user_id)
     .bind(hero_id)
-    .fetch_one(pool)
+    .fetch_one(&mut **tx)
     .await?;
 
     Ok(exists > 0)
 }
 
 pub async fn add_hero_duplicate(
-    pool: &sqlx::SqlitePool,
+    tx: &mut Transaction<'_, Sqlite>,
     user_id: i64,
     hero_id: i32,
 ) -> sqlx::Result<i32> {
@@ -455,7 +459,7 @@ pub async fn add_hero_duplicate(
     )
     .bind(user_id)
     .bind(hero_id)
-    .execute(pool)
+    .execute(&mut **tx)
     .await?;
 
     let new_count = sqlx::query_scalar::<_, i32>(
@@ -463,21 +467,25 @@ pub async fn add_hero_duplicate(
     )
     .bind(user_id)
     .bind(hero_id)
-    .fetch_one(pool)
+    .fetch_one(&mut **tx)
     .await?;
 
     Ok(new_count)
 }
 
 /// Create a single hero with specified parameters (not maxed out)
-pub async fn create_hero(pool: &SqlitePool, user_id: i64, hero_id: i32) -> sqlx::Result<i64> {
+pub async fn create_hero(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: i64,
+    hero_id: i32,
+) -> sqlx::Result<i64> {
     let game_data = exceldb::get();
     let now = common::time::ServerTime::now_ms();
 
     // Get the last hero UID from database and increment
     let last_hero_uid: Option<i64> =
         sqlx::query_scalar("SELECT uid FROM heroes ORDER BY uid DESC LIMIT 1")
-            .fetch_optional(pool)
+            .fetch_optional(&mut **tx)
             .await?;
 
     let hero_uid = match last_hero_uid {
@@ -690,7 +698,7 @@ pub async fn create_hero(pool: &SqlitePool, user_id: i64, hero_id: i32) -> sqlx:
     .bind(final_cri_def)
     .bind(final_add_dmg)
     .bind(final_drop_dmg)
-    .execute(pool)
+    .execute(&mut **tx)
     .await?;
 
     // Insert passive skill levels (starting at level 1)
@@ -717,7 +725,7 @@ pub async fn create_hero(pool: &SqlitePool, user_id: i64, hero_id: i32) -> sqlx:
         .bind(hero_uid)
         .bind(skill_group - 1)
         .bind(min_level)
-        .execute(pool)
+        .execute(&mut **tx)
         .await?;
     }
 
@@ -733,7 +741,7 @@ pub async fn create_hero(pool: &SqlitePool, user_id: i64, hero_id: i32) -> sqlx:
         sqlx::query("INSERT INTO hero_voices (hero_uid, voice_id) VALUES (?, ?)")
             .bind(hero_uid)
             .bind(voice.audio)
-            .execute(pool)
+            .execute(&mut **tx)
             .await?;
     }
 
@@ -742,7 +750,7 @@ pub async fn create_hero(pool: &SqlitePool, user_id: i64, hero_id: i32) -> sqlx:
         sqlx::query("INSERT INTO hero_item_unlocks (hero_uid, item_id) VALUES (?, ?)")
             .bind(hero_uid)
             .bind(item_id)
-            .execute(pool)
+            .execute(&mut **tx)
             .await?;
     }
 
@@ -764,7 +772,7 @@ pub async fn create_hero(pool: &SqlitePool, user_id: i64, hero_id: i32) -> sqlx:
         "#,
     )
     .bind(hero_uid)
-    .execute(pool)
+    .execute(&mut **tx)
     .await?;
 
     // Birthday info
@@ -774,7 +782,7 @@ pub async fn create_hero(pool: &SqlitePool, user_id: i64, hero_id: i32) -> sqlx:
     .bind(user_id)
     .bind(hero_id)
     .bind(0) // Starting at 0 birthday celebrations
-    .execute(pool)
+    .execute(&mut **tx)
     .await?;
 
     // Insert destiny stone unlocks (empty initially - player needs to unlock)
@@ -786,7 +794,7 @@ pub async fn create_hero(pool: &SqlitePool, user_id: i64, hero_id: i32) -> sqlx:
                 )
                 .bind(hero_uid)
                 .bind(stone_id)
-                .execute(pool)
+                .execute(&mut **tx)
                 .await?;
             }
         }
@@ -801,12 +809,12 @@ pub async fn create_hero(pool: &SqlitePool, user_id: i64, hero_id: i32) -> sqlx:
         .bind(template_id)
         .bind("") // Empty name
         .bind(0) // Style 0
-        .execute(pool)
+        .execute(&mut **tx)
         .await?;
     }
 
     // Update player info hero count based on rarity
-    update_player_hero_count(pool, user_id, rare, now).await?;
+    update_player_hero_count(tx, user_id, rare, now).await?;
 
     tracing::info!(
         "Created hero {} (uid {}) for user {}",
@@ -820,7 +828,7 @@ pub async fn create_hero(pool: &SqlitePool, user_id: i64, hero_id: i32) -> sqlx:
 
 /// Helper function to update player hero count
 async fn update_player_hero_count(
-    pool: &SqlitePool,
+    tx: &mut Transaction<'_, Sqlite>,
     user_id: i64,
     rarity: usize,
     now: i64,
@@ -845,7 +853,7 @@ async fn update_player_hero_count(
     ))
     .bind(now)
     .bind(user_id)
-    .execute(pool)
+    .execute(&mut **tx)
     .await?;
 
     Ok(())
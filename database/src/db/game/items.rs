@@ -1,6 +1,6 @@
 use crate::models::game::items::{InsightItem, Item, PowerItem};
 use common::time::ServerTime;
-use sqlx::SqlitePool;
+use sqlx::{Sqlite, SqlitePool, Transaction};
 // Items
 pub async fn get_all_items(pool: &SqlitePool, user_id: i64) -> sqlx::Result<Vec<Item>> {
     sqlx::query_as("SELECT * FROM items WHERE user_id = ? ORDER BY item_id")
@@ -39,7 +39,7 @@ pub async fn save_item(pool: &SqlitePool, item: &Item) -> sqlx::Result<()> {
 }
 
 pub async fn add_item_quantity(
-    pool: &SqlitePool,
+    tx: &mut Transaction<'_, Sqlite>,
     user_id: i64,
     item_id: u32,
     amount: i32,
@@ -57,13 +57,13 @@ pub async fn add_item_quantity(
     .bind(amount)
     .bind(ServerTime::now_ms())
     .bind(amount as i64)
-    .execute(pool)
+    .execute(&mut **tx)
     .await?;
     Ok(())
 }
 
 pub async fn remove_item_quantity(
-    pool: &SqlitePool,
+    tx: &mut Transaction<'_, Sqlite>,
     user_id: i64,
     item_id: u32,
     amount: i32,
@@ -72,7 +72,7 @@ pub async fn remove_item_quantity(
         sqlx::query_scalar("SELECT quantity FROM items WHERE user_id = ? AND item_id = ?")
             .bind(user_id)
             .bind(item_id as i64)
-            .fetch_optional(pool)
+            .fetch_optional(&mut **tx)
             .await?;
 
     if current.unwrap_or(0) < amount {
@@ -86,7 +86,7 @@ pub async fn remove_item_quantity(
         .bind(timestamp as i64)
         .bind(user_id)
         .bind(item_id as i64)
-        .execute(pool)
+        .execute(&mut **tx)
         .await?;
 
     Ok(true)
@@ -7,6 +7,7 @@ pub mod block_packages;
 pub mod buildings;
 pub mod character_interactions;
 pub mod charges;
+pub mod cloths;
 pub mod command_post;
 pub mod critters;
 pub mod currencies;
@@ -1,7 +1,7 @@
 use crate::models::game::summon::*;
 use anyhow::Result;
 use sonettobuf::SummonResult;
-use sqlx::SqlitePool;
+use sqlx::{Sqlite, SqlitePool, Transaction};
 
 pub async fn get_summon_stats(pool: &SqlitePool, user_id: i64) -> Result<UserSummonStats> {
     let stats =
@@ -132,8 +132,60 @@ pub async fn get_sp_pool_info(
     }
 }
 
+/// Discounted ten-pulls left on the pool, `initial` before the first pull on it
+pub async fn get_discount_time(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: i64,
+    pool_id: i32,
+    initial: i32,
+) -> sqlx::Result<i32> {
+    let discount_time: Option<i32> = sqlx::query_scalar(
+        "SELECT discount_time FROM user_summon_pools WHERE user_id = ? AND pool_id = ?",
+    )
+    .bind(user_id)
+    .bind(pool_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(discount_time.unwrap_or(initial))
+}
+
+/// Adds the pulls to the pool's count and stores the discounted ten-pulls left
+pub async fn record_pool_pulls(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: i64,
+    pool_id: i32,
+    pulls: i32,
+    discount_time: i32,
+) -> sqlx::Result<()> {
+    let now = common::time::ServerTime::now_ms();
+
+    sqlx::query(
+        r#"
+        INSERT INTO user_summon_pools (
+            user_id, pool_id, discount_time, summon_count, created_at, updated_at
+        )
+        VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT(user_id, pool_id) DO UPDATE SET
+            discount_time = excluded.discount_time,
+            summon_count = summon_count + excluded.summon_count,
+            updated_at = excluded.updated_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(pool_id)
+    .bind(discount_time)
+    .bind(pulls)
+    .bind(now)
+    .bind(now)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
 pub async fn add_summon_history(
-    pool: &SqlitePool,
+    tx: &mut Transaction<'_, Sqlite>,
     user_id: i64,
     pool_id: i32,
     pool_name: &str,
@@ -159,7 +211,7 @@ pub async fn add_summon_history(
     .bind(pool_type)
     .bind(pool_name)
     .bind(now)
-    .fetch_one(pool)
+    .fetch_one(&mut **tx)
     .await?;
 
     // Insert gained items (heroes from results)
@@ -177,7 +229,7 @@ pub async fn add_summon_history(
             .bind(history_id)
             .bind(idx as i32)
             .bind(hero_id)
-            .execute(pool)
+            .execute(&mut **tx)
             .await?;
         }
     }
@@ -225,3 +277,154 @@ pub async fn update_sp_pool_up_heroes(
 
     Ok(())
 }
+
+/// Pulls made on the pool itself, for its progress rewards
+pub async fn get_pool_summon_count(
+    pool: &SqlitePool,
+    user_id: i64,
+    pool_id: i32,
+) -> sqlx::Result<i32> {
+    let summon_count: Option<i32> = sqlx::query_scalar(
+        "SELECT summon_count FROM user_summon_pools WHERE user_id = ? AND pool_id = ?",
+    )
+    .bind(user_id)
+    .bind(pool_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(summon_count.unwrap_or(0))
+}
+
+pub async fn get_reward_progresses(
+    pool: &SqlitePool,
+    user_id: i64,
+    pool_id: i32,
+) -> sqlx::Result<Vec<i32>> {
+    sqlx::query_scalar(
+        "SELECT progress_id FROM user_sp_pool_reward_progress WHERE user_id = ? AND pool_id = ? ORDER BY progress_id",
+    )
+    .bind(user_id)
+    .bind(pool_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn add_reward_progress(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: i64,
+    pool_id: i32,
+    progress_id: i32,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        r#"
+        INSERT OR IGNORE INTO user_sp_pool_reward_progress (user_id, pool_id, progress_id)
+        VALUES (?, ?, ?)
+        "#,
+    )
+    .bind(user_id)
+    .bind(pool_id)
+    .bind(progress_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Every bag the pool already gave the player, opened or not
+pub async fn get_lucky_bag_ids(
+    pool: &SqlitePool,
+    user_id: i64,
+    pool_id: i32,
+) -> sqlx::Result<Vec<i32>> {
+    sqlx::query_scalar(
+        "SELECT bag_id FROM user_single_bags WHERE user_id = ? AND pool_id = ? ORDER BY bag_id",
+    )
+    .bind(user_id)
+    .bind(pool_id)
+    .fetch_all(pool)
+    .await
+}
+
+/// Counts the pool's pulls since its last bag and hands over `bag_id` when one came out
+pub async fn record_lucky_bag_pulls(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: i64,
+    pool_id: i32,
+    misses: i32,
+    bag_id: Option<i32>,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO user_lucky_bags (user_id, pool_id, count, not_ssr_count)
+        VALUES (?, ?, ?, ?)
+        ON CONFLICT(user_id, pool_id) DO UPDATE SET
+            count = count + excluded.count,
+            not_ssr_count = CASE
+                WHEN excluded.count > 0 THEN excluded.not_ssr_count
+                ELSE not_ssr_count + excluded.not_ssr_count
+            END
+        "#,
+    )
+    .bind(user_id)
+    .bind(pool_id)
+    .bind(i32::from(bag_id.is_some()))
+    .bind(misses)
+    .execute(&mut **tx)
+    .await?;
+
+    if let Some(bag_id) = bag_id {
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO user_single_bags (user_id, pool_id, bag_id, is_open)
+            VALUES (?, ?, ?, 0)
+            "#,
+        )
+        .bind(user_id)
+        .bind(pool_id)
+        .bind(bag_id)
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// The pool of the player's closed bag `bag_id`
+pub async fn get_closed_lucky_bag_pool(
+    pool: &SqlitePool,
+    user_id: i64,
+    bag_id: i32,
+) -> sqlx::Result<Option<i32>> {
+    sqlx::query_scalar(
+        "SELECT pool_id FROM user_single_bags WHERE user_id = ? AND bag_id = ? AND is_open = 0 ORDER BY pool_id LIMIT 1",
+    )
+    .bind(user_id)
+    .bind(bag_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Opens the bag on `hero_id`, false when it was already open
+pub async fn open_lucky_bag(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: i64,
+    pool_id: i32,
+    bag_id: i32,
+    hero_id: i32,
+) -> sqlx::Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE user_single_bags
+        SET is_open = 1, hero_id = ?
+        WHERE user_id = ? AND pool_id = ? AND bag_id = ? AND is_open = 0
+        "#,
+    )
+    .bind(hero_id)
+    .bind(user_id)
+    .bind(pool_id)
+    .bind(bag_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
@@ -263,10 +263,10 @@ pub async fn update_tower_layer_score(
     layer_id: i32,
     score: i32,
 ) -> sqlx::Result<()> {
-    // Update current and history high scores
+    // Update current and history high scores, a weaker run never lowers them
     sqlx::query(
         "UPDATE user_tower_layers
-         SET curr_high_score = ?,
+         SET curr_high_score = MAX(curr_high_score, ?),
              history_high_score = MAX(history_high_score, ?)
          WHERE user_id = ? AND tower_type = ? AND tower_id = ? AND layer_id = ?",
     )
@@ -2344,6 +2344,44 @@ pub async fn load_dialog_info(tx: &mut Transaction<'_, Sqlite>, user_id: i64) ->
     Ok(())
 }
 
+pub async fn load_starter_cloths(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: i64,
+) -> sqlx::Result<()> {
+    let json_str = include_str!("../../../data/starter/cloth_info.json");
+    let data: Value = match serde_json::from_str(json_str) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("load_starter_cloths: failed to parse JSON: {e}");
+            return Ok(());
+        }
+    };
+
+    if let Some(clothes) = data
+        .pointer("/clothInfos/clothes")
+        .and_then(|v| v.as_array())
+    {
+        for cloth in clothes {
+            let cloth_id = cloth.get("clothId").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
+            let level = cloth.get("level").and_then(|v| v.as_i64()).unwrap_or(1) as i32;
+            let exp = cloth.get("exp").and_then(|v| v.as_i64()).unwrap_or(0) as i32;
+
+            sqlx::query(
+                "INSERT INTO user_cloths (player_id, cloth_id, level, exp) VALUES (?, ?, ?, ?)",
+            )
+            .bind(user_id)
+            .bind(cloth_id)
+            .bind(level)
+            .bind(exp)
+            .execute(&mut **tx)
+            .await?;
+        }
+    }
+
+    tracing::info!("Loaded starter cloths for user {}", user_id);
+    Ok(())
+}
+
 /// Load starter antiques from antique table
 pub async fn load_starter_antiques(
     tx: &mut Transaction<'_, Sqlite>,
@@ -3981,6 +4019,7 @@ pub async fn load_all_starter_data(pool: &SqlitePool, uid: i64) -> sqlx::Result<
     load_summon_history(&mut tx, uid).await?;
     load_achievement_info(&mut tx, uid).await?;
     load_dialog_info(&mut tx, uid).await?;
+    load_starter_cloths(&mut tx, uid).await?;
     load_starter_antiques(&mut tx, uid).await?;
     load_weekwalk_info(&mut tx, uid).await?;
     load_weekwalk_v2_info(&mut tx, uid).await?;
@@ -0,0 +1,20 @@
+use sonettobuf;
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct UserCloth {
+    pub player_id: i64,
+    pub cloth_id: i32,
+    pub level: i32,
+    pub exp: i32,
+}
+
+impl From<UserCloth> for sonettobuf::PlayerCloth {
+    fn from(cloth: UserCloth) -> Self {
+        sonettobuf::PlayerCloth {
+            cloth_id: Some(cloth.cloth_id),
+            level: Some(cloth.level),
+            exp: Some(cloth.exp),
+        }
+    }
+}
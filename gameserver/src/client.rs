@@ -1,34 +1,49 @@
-use crate::{handler, state::ConnectionContext};
-use byteorder::{BE, ByteOrder};
+use crate::codec::{ClientFrameCodec, ServerFrameCodec};
+use crate::packet::ClientPacket;
+use crate::utils::capture::CapturedFrame;
+use crate::{handler, state::CommandPacket, state::ConnectionContext};
+use bytes::BytesMut;
+use common::network_config;
+use futures::SinkExt;
 use std::sync::Arc;
-use tokio::{io::AsyncReadExt, sync::Mutex};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::{Mutex, mpsc};
+use tokio::time::Instant;
+use tokio_util::codec::{Decoder, FramedWrite};
 
-pub async fn handle_client(ctx: Arc<Mutex<ConnectionContext>>) -> anyhow::Result<()> {
-    loop {
-        let packet = {
-            let ctx_guard = ctx.lock().await;
-            let mut socket = ctx_guard.socket.lock().await;
+/// Reader side of a connection. The context is only locked while a packet is
+/// dispatched, never while waiting on the socket, so other tasks can push freely.
+pub async fn handle_client(
+    ctx: Arc<Mutex<ConnectionContext>>,
+    socket: OwnedReadHalf,
+) -> anyhow::Result<()> {
+    let shutdown = {
+        let ctx_guard = ctx.lock().await;
+        ctx_guard.shutdown_signal()
+    };
 
-            let mut header = [0u8; 4];
-            if let Err(e) = socket.read_exact(&mut header).await {
-                tracing::debug!("Client disconnected: {e}");
-                return Ok(());
-            }
+    let mut reader = FrameReader::new(socket);
 
-            let packet_len = BE::read_i32(&header) as usize;
-            let mut buffer = vec![0u8; packet_len];
-            if let Err(e) = socket.read_exact(&mut buffer).await {
-                tracing::warn!("Failed to read packet body ({} bytes): {e}", packet_len);
+    loop {
+        let packet = tokio::select! {
+            _ = shutdown.notified() => {
+                tracing::info!("Connection closed by server");
                 return Ok(());
             }
-
-            let mut packet = Vec::with_capacity(4 + packet_len);
-            packet.extend_from_slice(&header);
-            packet.extend_from_slice(&buffer);
-            packet
+            packet = reader.next_packet() => match packet {
+                Some(packet) => packet,
+                None => return Ok(()),
+            },
         };
 
-        if let Err(e) = handler::dispatch_command(ctx.clone(), &packet[..]).await {
+        {
+            let mut ctx_guard = ctx.lock().await;
+            ctx_guard.capture(|| CapturedFrame::up(&packet));
+        }
+
+        if let Err(e) = handler::dispatch_command(ctx.clone(), packet).await {
             tracing::error!("Dispatch error: {e}");
             break;
         }
@@ -44,3 +59,106 @@ pub async fn handle_client(ctx: Arc<Mutex<ConnectionContext>>) -> anyhow::Result
 
     Ok(())
 }
+
+/// Pulls client frames off the socket through `ClientFrameCodec`, applying the
+/// `[network]` idle and read timeouts on top of the codec's size checks.
+struct FrameReader {
+    socket: OwnedReadHalf,
+    buffer: BytesMut,
+    codec: ClientFrameCodec,
+}
+
+impl FrameReader {
+    fn new(socket: OwnedReadHalf) -> Self {
+        Self {
+            socket,
+            buffer: BytesMut::with_capacity(4096),
+            codec: ClientFrameCodec::new(network_config().max_frame_size),
+        }
+    }
+
+    /// Next packet, None once the client is gone or broke one of the limits.
+    /// Cancel safe, bytes already read stay buffered.
+    async fn next_packet(&mut self) -> Option<ClientPacket> {
+        let cfg = network_config();
+        // once part of a frame is in, the rest has to follow within read_timeout
+        let mut deadline = None;
+
+        loop {
+            match self.codec.decode(&mut self.buffer) {
+                Ok(Some(packet)) => return Some(packet),
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!("Rejecting client frame: {e}");
+                    return None;
+                }
+            }
+
+            let limit = if self.buffer.is_empty() {
+                cfg.idle_timeout()
+            } else {
+                let deadline =
+                    *deadline.get_or_insert_with(|| cfg.read_timeout().map(|t| Instant::now() + t));
+                deadline.map(|d| d.saturating_duration_since(Instant::now()))
+            };
+
+            match with_timeout(limit, self.socket.read_buf(&mut self.buffer)).await {
+                Some(Ok(0)) => {
+                    tracing::debug!("Client disconnected");
+                    return None;
+                }
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    tracing::debug!("Client disconnected: {e}");
+                    return None;
+                }
+                None if self.buffer.is_empty() => {
+                    tracing::info!("Client idle for {}s, closing", cfg.idle_timeout_secs);
+                    return None;
+                }
+                None => {
+                    tracing::warn!(
+                        "Frame not completed within {}s ({} bytes buffered), closing",
+                        cfg.read_timeout_secs,
+                        self.buffer.len()
+                    );
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Runs `fut` under an optional deadline, None if it ran out
+async fn with_timeout<F: Future>(limit: Option<Duration>, fut: F) -> Option<F::Output> {
+    match limit {
+        Some(limit) => tokio::time::timeout(limit, fut).await.ok(),
+        None => Some(fut.await),
+    }
+}
+
+/// Writer side of a connection, owns the write half and drains the outbound channel
+/// until every sender (the connection context) is gone.
+pub async fn write_packets(
+    socket: OwnedWriteHalf,
+    mut outbound: mpsc::Receiver<CommandPacket>,
+) -> anyhow::Result<()> {
+    let cfg = network_config();
+    let mut framed = FramedWrite::new(socket, ServerFrameCodec::default());
+
+    while let Some(packet) = outbound.recv().await {
+        // a peer that stopped reading would otherwise park the writer (and the queue) forever
+        match with_timeout(
+            cfg.write_timeout(),
+            framed.send(packet.into_server_packet()),
+        )
+        .await
+        {
+            Some(result) => result?,
+            None => anyhow::bail!("write timed out after {}s", cfg.write_timeout_secs),
+        }
+    }
+
+    framed.close().await?;
+    Ok(())
+}
@@ -1,30 +1,24 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::achievements;
-use sonettobuf::{CmdId, GetAchievementInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetAchievementInfoReply, GetAchievementInfoRequest};
 
-pub async fn on_get_achievement_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let achievement_infos = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetAchievementInfo;
 
-        achievements::get_achievements(&ctx_guard.state.db, player_id).await?
-    };
+impl CmdHandler for GetAchievementInfo {
+    const CMD: CmdId = CmdId::GetAchievementInfoCmd;
+    type Request = GetAchievementInfoRequest;
+    type Reply = GetAchievementInfoReply;
 
-    let reply = GetAchievementInfoReply {
-        infos: achievement_infos.into_iter().map(Into::into).collect(),
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetAchievementInfoRequest,
+    ) -> Result<GetAchievementInfoReply, AppError> {
+        let achievement_infos =
+            achievements::get_achievements(session.db(), session.player_id()?).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetAchievementInfoCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetAchievementInfoReply {
+            infos: achievement_infos.into_iter().map(Into::into).collect(),
+        })
+    }
 }
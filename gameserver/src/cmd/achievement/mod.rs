@@ -1,3 +1,3 @@
 mod get_achievement_info;
 
-pub use get_achievement_info::on_get_achievement_info;
+pub use get_achievement_info::GetAchievementInfo;
@@ -1,21 +1,9 @@
-use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::send_reply;
-use crate::state::ConnectionContext;
-use sonettobuf::{CmdId, GetActivityInfosReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::static_reply;
+use sonettobuf::{CmdId, GetActivityInfosReply, GetActivityInfosRequest};
 
-pub async fn on_get_activity_infos(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    send_reply!(
-        ctx,
-        req.up_tag,
-        CmdId::GetActivityInfosCmd,
-        GetActivityInfosReply,
-        "activity/activity_infos.json"
-    );
-    Ok(())
-}
+static_reply!(
+    GetActivityInfos,
+    CmdId::GetActivityInfosCmd,
+    GetActivityInfosRequest => GetActivityInfosReply,
+    "activity/activity_infos.json"
+);
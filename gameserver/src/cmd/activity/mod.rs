@@ -1,3 +1,3 @@
 mod get_activity_infos;
 
-pub use get_activity_infos::on_get_activity_infos;
+pub use get_activity_infos::GetActivityInfos;
@@ -1,46 +1,39 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::activity101;
-use prost::Message;
 use sonettobuf::{Act101Info, CmdId, Get101InfosReply, Get101InfosRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_get101_infos(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = Get101InfosRequest::decode(&req.data[..])?;
-    let activity_id = request.activity_id.unwrap_or(13108);
+pub struct Get101Infos;
 
-    tracing::info!("Requested activity_id: {}", activity_id);
+impl CmdHandler for Get101Infos {
+    const CMD: CmdId = CmdId::Get101InfosCmd;
+    type Request = Get101InfosRequest;
+    type Reply = Get101InfosReply;
 
-    let (infos, login_count, got_once_bonus) = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+    async fn handle(
+        session: &mut Session,
+        request: Get101InfosRequest,
+    ) -> Result<Get101InfosReply, AppError> {
+        let activity_id = request.activity_id.unwrap_or(13108);
 
-        activity101::get_activity101_info(&ctx_guard.state.db, player_id, activity_id).await?
-    };
+        tracing::info!("Requested activity_id: {}", activity_id);
 
-    let reply = Get101InfosReply {
-        infos: infos
-            .into_iter()
-            .map(|(id, state)| Act101Info {
-                id: Some(id as u32),
-                state: Some(state as u32),
-            })
-            .collect(),
-        sp_infos: vec![],
-        login_count: Some(login_count as u32),
-        activity_id: Some(activity_id),
-        got_once_bonus: Some(got_once_bonus),
-    };
+        let (infos, login_count, got_once_bonus) =
+            activity101::get_activity101_info(session.db(), session.player_id()?, activity_id)
+                .await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::Get101InfosCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(Get101InfosReply {
+            infos: infos
+                .into_iter()
+                .map(|(id, state)| Act101Info {
+                    id: Some(id as u32),
+                    state: Some(state as u32),
+                })
+                .collect(),
+            sp_infos: vec![],
+            login_count: Some(login_count as u32),
+            activity_id: Some(activity_id),
+            got_once_bonus: Some(got_once_bonus),
+        })
+    }
 }
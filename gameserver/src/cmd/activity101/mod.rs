@@ -1,5 +1,5 @@
 mod get101_bonus;
 mod get101_infos;
 
-pub use get101_bonus::on_get101_bonus;
-pub use get101_infos::on_get101_infos;
+pub use get101_bonus::Get101Bonus;
+pub use get101_infos::Get101Infos;
@@ -1,36 +1,32 @@
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use crate::{error::AppError, utils::data_loader::GameDataLoader};
-use prost::Message;
 use sonettobuf::{CmdId, GetAct125InfosReply, GetAct125InfosRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_get_act125_infos(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = GetAct125InfosRequest::decode(&req.data[..])?;
-    let activity_id = request.activity_id.unwrap_or(0);
+pub struct GetAct125Infos;
 
-    tracing::info!("Requested activity_id: {}", activity_id);
+impl CmdHandler for GetAct125Infos {
+    const CMD: CmdId = CmdId::GetAct125InfosCmd;
+    type Request = GetAct125InfosRequest;
+    type Reply = GetAct125InfosReply;
 
-    let path = match activity_id {
-        13116 => "activity125/activity125_infos_13116.json",
-        13005 => "activity125/activity125_infos_13005.json",
-        _ => {
-            tracing::warn!("Unknown activity_id: {}, using default", activity_id);
-            "activity125/activity125_infos_13116.json"
-        }
-    };
+    async fn handle(
+        _session: &mut Session,
+        request: GetAct125InfosRequest,
+    ) -> Result<GetAct125InfosReply, AppError> {
+        let activity_id = request.activity_id.unwrap_or(0);
 
-    let reply: GetAct125InfosReply = GameDataLoader::load_struct(path)
-        .map_err(|e| AppError::Custom(format!("Failed to load: {}", e)))?;
+        tracing::info!("Requested activity_id: {}", activity_id);
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetAct125InfosCmd, reply, 0, req.up_tag)
-        .await?;
+        let path = match activity_id {
+            13116 => "activity125/activity125_infos_13116.json",
+            13005 => "activity125/activity125_infos_13005.json",
+            _ => {
+                tracing::warn!("Unknown activity_id: {}, using default", activity_id);
+                "activity125/activity125_infos_13116.json"
+            }
+        };
 
-    Ok(())
+        GameDataLoader::load_struct(path)
+            .map_err(|e| AppError::Custom(format!("Failed to load: {}", e)))
+    }
 }
@@ -1,3 +1,3 @@
 mod get_act125_infos;
 
-pub use get_act125_infos::on_get_act125_infos;
+pub use get_act125_infos::GetAct125Infos;
@@ -1,3 +1,3 @@
 mod act160_get_info;
 
-pub use act160_get_info::on_act160_get_info;
+pub use act160_get_info::Act160GetInfo;
@@ -1,21 +1,9 @@
-use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::send_reply;
-use crate::state::ConnectionContext;
-use sonettobuf::{Act165GetInfoReply, CmdId};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::static_reply;
+use sonettobuf::{Act165GetInfoReply, Act165GetInfoRequest, CmdId};
 
-pub async fn on_act165_get_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    send_reply!(
-        ctx,
-        req.up_tag,
-        CmdId::Act165GetInfoCmd,
-        Act165GetInfoReply,
-        "activity165/get_info.json"
-    );
-    Ok(())
-}
+static_reply!(
+    Act165GetInfo,
+    CmdId::Act165GetInfoCmd,
+    Act165GetInfoRequest => Act165GetInfoReply,
+    "activity165/get_info.json"
+);
@@ -1,3 +1,3 @@
 mod act165_get_info;
 
-pub use act165_get_info::on_act165_get_info;
+pub use act165_get_info::Act165GetInfo;
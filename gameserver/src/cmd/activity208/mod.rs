@@ -1,3 +1,3 @@
 mod get_act208_info;
 
-pub use get_act208_info::on_get_act208_info;
+pub use get_act208_info::GetAct208Info;
@@ -1,21 +1,9 @@
-use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::send_reply;
-use crate::state::ConnectionContext;
-use sonettobuf::{CmdId, GetAct209InfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::static_reply;
+use sonettobuf::{CmdId, GetAct209InfoReply, GetAct209InfoRequest};
 
-pub async fn on_get_act209_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    send_reply!(
-        ctx,
-        req.up_tag,
-        CmdId::GetAct209InfoCmd,
-        GetAct209InfoReply,
-        "activity209/get_info.json"
-    );
-    Ok(())
-}
+static_reply!(
+    GetAct209Info,
+    CmdId::GetAct209InfoCmd,
+    GetAct209InfoRequest => GetAct209InfoReply,
+    "activity209/get_info.json"
+);
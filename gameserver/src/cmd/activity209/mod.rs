@@ -1,3 +1,3 @@
 mod get_act209_info;
 
-pub use get_act209_info::on_get_act209_info;
+pub use get_act209_info::GetAct209Info;
@@ -1,30 +1,23 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::antiques;
-use sonettobuf::{CmdId, GetAntiqueInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetAntiqueInfoReply, GetAntiqueInfoRequest};
 
-pub async fn on_get_antique_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let antique_list = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetAntiqueInfo;
 
-        antiques::get_user_antiques(&ctx_guard.state.db, player_id).await?
-    };
+impl CmdHandler for GetAntiqueInfo {
+    const CMD: CmdId = CmdId::GetAntiqueInfoCmd;
+    type Request = GetAntiqueInfoRequest;
+    type Reply = GetAntiqueInfoReply;
 
-    let reply = GetAntiqueInfoReply {
-        antiques: antique_list.into_iter().map(Into::into).collect(),
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetAntiqueInfoRequest,
+    ) -> Result<GetAntiqueInfoReply, AppError> {
+        let antique_list = antiques::get_user_antiques(session.db(), session.player_id()?).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetAntiqueInfoCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetAntiqueInfoReply {
+            antiques: antique_list.into_iter().map(Into::into).collect(),
+        })
+    }
 }
@@ -1,3 +1,3 @@
 mod get_antique_info;
 
-pub use get_antique_info::on_get_antique_info;
+pub use get_antique_info::GetAntiqueInfo;
@@ -1,30 +1,24 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::bgm::load_user_bgm;
-use sonettobuf::{CmdId, GetBgmInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetBgmInfoReply, GetBgmInfoRequest};
 
-pub async fn on_get_bgm_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let (bgm_infos, use_bgm_id) = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetBgmInfo;
 
-        load_user_bgm(&ctx_guard.state.db, player_id).await?
-    };
+impl CmdHandler for GetBgmInfo {
+    const CMD: CmdId = CmdId::GetBgmInfoCmd;
+    type Request = GetBgmInfoRequest;
+    type Reply = GetBgmInfoReply;
 
-    let reply = GetBgmInfoReply {
-        bgm_infos,
-        use_bgm_id,
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetBgmInfoRequest,
+    ) -> Result<GetBgmInfoReply, AppError> {
+        let (bgm_infos, use_bgm_id) = load_user_bgm(session.db(), session.player_id()?).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetBgmInfoCmd, reply, 0, req.up_tag)
-        .await?;
-    Ok(())
+        Ok(GetBgmInfoReply {
+            bgm_infos,
+            use_bgm_id,
+        })
+    }
 }
@@ -2,6 +2,6 @@ mod get_bgm_info;
 mod set_favorite_bgm;
 mod set_use_bgm;
 
-pub use get_bgm_info::on_get_bgm_info;
-pub use set_favorite_bgm::on_set_favorite_bgm;
-pub use set_use_bgm::on_set_use_bgm;
+pub use get_bgm_info::GetBgmInfo;
+pub use set_favorite_bgm::SetFavoriteBgm;
+pub use set_use_bgm::SetUseBgm;
@@ -1,39 +1,31 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::bgm::set_bgm_favorite;
-use prost::Message;
 use sonettobuf::{CmdId, SetFavoriteBgmReply, SetFavoriteBgmRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_set_favorite_bgm(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = SetFavoriteBgmRequest::decode(&req.data[..])?;
+pub struct SetFavoriteBgm;
 
-    let mut ctx_guard = ctx.lock().await;
-    let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
-    let pool = &ctx_guard.state.db;
+impl CmdHandler for SetFavoriteBgm {
+    const CMD: CmdId = CmdId::SetFavoriteBgmCmd;
+    type Request = SetFavoriteBgmRequest;
+    type Reply = SetFavoriteBgmReply;
 
-    set_bgm_favorite(
-        pool,
-        player_id,
-        request.bgm_id.unwrap_or(2207),
-        request.favorite.unwrap_or(false),
-    )
-    .await
-    .map_err(AppError::from)?;
+    async fn handle(
+        session: &mut Session,
+        request: SetFavoriteBgmRequest,
+    ) -> Result<SetFavoriteBgmReply, AppError> {
+        set_bgm_favorite(
+            session.db(),
+            session.player_id()?,
+            request.bgm_id.unwrap_or(2207),
+            request.favorite.unwrap_or(false),
+        )
+        .await
+        .map_err(AppError::from)?;
 
-    let reply = SetFavoriteBgmReply {
-        bgm_id: request.bgm_id,
-        favorite: request.favorite,
-    };
-
-    ctx_guard
-        .send_reply(CmdId::SetFavoriteBgmCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(SetFavoriteBgmReply {
+            bgm_id: request.bgm_id,
+            favorite: request.favorite,
+        })
+    }
 }
@@ -1,21 +1,9 @@
-use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::send_reply;
-use crate::state::ConnectionContext;
-use sonettobuf::{CmdId, GetBpInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::static_reply;
+use sonettobuf::{CmdId, GetBpInfoReply, GetBpInfoRequest};
 
-pub async fn on_get_bp_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    send_reply!(
-        ctx,
-        req.up_tag,
-        CmdId::GetBpInfoCmd,
-        GetBpInfoReply,
-        "bp/bp_info.json"
-    );
-    Ok(())
-}
+static_reply!(
+    GetBpInfo,
+    CmdId::GetBpInfoCmd,
+    GetBpInfoRequest => GetBpInfoReply,
+    "bp/bp_info.json"
+);
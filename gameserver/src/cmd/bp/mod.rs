@@ -1,3 +1,3 @@
 mod get_bp_info;
 
-pub use get_bp_info::on_get_bp_info;
+pub use get_bp_info::GetBpInfo;
@@ -1,35 +1,28 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::charges;
-use sonettobuf::{CmdId, GetChargeInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetChargeInfoReply, GetChargeInfoRequest};
 
-pub async fn on_get_charge_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let (charge_infos, sandbox) = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetChargeInfo;
 
-        let infos = charges::get_charge_infos(&ctx_guard.state.db, player_id).await?;
-        let sandbox = charges::get_sandbox_settings(&ctx_guard.state.db, player_id).await?;
+impl CmdHandler for GetChargeInfo {
+    const CMD: CmdId = CmdId::GetChargeInfoCmd;
+    type Request = GetChargeInfoRequest;
+    type Reply = GetChargeInfoReply;
 
-        (infos, sandbox)
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetChargeInfoRequest,
+    ) -> Result<GetChargeInfoReply, AppError> {
+        let player_id = session.player_id()?;
 
-    let reply = GetChargeInfoReply {
-        infos: charge_infos.into_iter().map(Into::into).collect(),
-        sandbox_enable: Some(sandbox.sandbox_enable),
-        sandbox_balance: Some(sandbox.sandbox_balance),
-    };
+        let charge_infos = charges::get_charge_infos(session.db(), player_id).await?;
+        let sandbox = charges::get_sandbox_settings(session.db(), player_id).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetChargeInfoCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetChargeInfoReply {
+            infos: charge_infos.into_iter().map(Into::into).collect(),
+            sandbox_enable: Some(sandbox.sandbox_enable),
+            sandbox_balance: Some(sandbox.sandbox_balance),
+        })
+    }
 }
@@ -1,20 +1,18 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use sonettobuf::CmdId;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::handler::{CmdHandler, Session};
+use sonettobuf::{CmdId, GetChargePushInfoReply, GetChargePushInfoRequest};
 
-pub async fn on_get_charge_push_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard
-            .send_empty_reply(CmdId::GetChargePushInfoCmd, Vec::new(), 0, req.up_tag)
-            .await?;
-    }
+pub struct GetChargePushInfo;
+
+impl CmdHandler for GetChargePushInfo {
+    const CMD: CmdId = CmdId::GetChargePushInfoCmd;
+    type Request = GetChargePushInfoRequest;
+    type Reply = GetChargePushInfoReply;
 
-    Ok(())
+    async fn handle(
+        _session: &mut Session,
+        _request: GetChargePushInfoRequest,
+    ) -> Result<GetChargePushInfoReply, AppError> {
+        Ok(GetChargePushInfoReply::default())
+    }
 }
@@ -3,7 +3,7 @@ mod get_charge_push_info;
 mod get_month_card_info;
 mod read_charge_new;
 
-pub use get_charge_info::on_get_charge_info;
-pub use get_charge_push_info::on_get_charge_push_info;
-pub use get_month_card_info::on_get_month_card_info;
-pub use read_charge_new::on_read_charge_new;
+pub use get_charge_info::GetChargeInfo;
+pub use get_charge_push_info::GetChargePushInfo;
+pub use get_month_card_info::GetMonthCardInfo;
+pub use read_charge_new::ReadChargeNew;
@@ -1,29 +1,22 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use prost::Message;
+use crate::handler::{CmdHandler, Session};
 use sonettobuf::{CmdId, ReadChargeNewReply, ReadChargeNewRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_read_charge_new(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = ReadChargeNewRequest::decode(&req.data[..])?;
+pub struct ReadChargeNew;
 
-    tracing::info!("Received ReadChargeNewRequest: {:?}", request);
+impl CmdHandler for ReadChargeNew {
+    const CMD: CmdId = CmdId::ReadChargeNewCmd;
+    type Request = ReadChargeNewRequest;
+    type Reply = ReadChargeNewReply;
 
-    let data = ReadChargeNewReply {
-        goods_ids: request.goods_ids,
-    };
+    async fn handle(
+        _session: &mut Session,
+        request: ReadChargeNewRequest,
+    ) -> Result<ReadChargeNewReply, AppError> {
+        tracing::info!("Received ReadChargeNewRequest: {:?}", request);
 
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard
-            .send_reply(CmdId::ReadChargeNewCmd, data, 0, req.up_tag)
-            .await?;
+        Ok(ReadChargeNewReply {
+            goods_ids: request.goods_ids,
+        })
     }
-
-    Ok(())
 }
@@ -1,35 +1,29 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::command_post;
-use sonettobuf::{CmdId, GetCommandPostInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetCommandPostInfoReply, GetCommandPostInfoRequest};
 
-pub async fn on_get_command_post_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let (info, events, tasks, catch_tasks, gain_bonus) = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetCommandPostInfo;
 
-        command_post::get_command_post_info(&ctx_guard.state.db, player_id).await?
-    };
+impl CmdHandler for GetCommandPostInfo {
+    const CMD: CmdId = CmdId::GetCommandPostInfoCmd;
+    type Request = GetCommandPostInfoRequest;
+    type Reply = GetCommandPostInfoReply;
 
-    let reply = GetCommandPostInfoReply {
-        event_list: events.into_iter().map(Into::into).collect(),
-        tasks: tasks.into_iter().map(Into::into).collect(),
-        catch_tasks: catch_tasks.into_iter().map(Into::into).collect(),
-        gain_bonus,
-        paper: Some(info.paper),
-        catch_num: Some(info.catch_num),
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetCommandPostInfoRequest,
+    ) -> Result<GetCommandPostInfoReply, AppError> {
+        let (info, events, tasks, catch_tasks, gain_bonus) =
+            command_post::get_command_post_info(session.db(), session.player_id()?).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetCommandPostInfoCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetCommandPostInfoReply {
+            event_list: events.into_iter().map(Into::into).collect(),
+            tasks: tasks.into_iter().map(Into::into).collect(),
+            catch_tasks: catch_tasks.into_iter().map(Into::into).collect(),
+            gain_bonus,
+            paper: Some(info.paper),
+            catch_num: Some(info.catch_num),
+        })
+    }
 }
@@ -1,3 +1,3 @@
 mod get_command_post_info;
 
-pub use get_command_post_info::on_get_command_post_info;
+pub use get_command_post_info::GetCommandPostInfo;
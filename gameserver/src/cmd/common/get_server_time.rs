@@ -1,26 +1,24 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use common::time::ServerTime;
-use sonettobuf::{CmdId, GetServerTimeReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetServerTimeReply, GetServerTimeRequest};
 
-pub async fn on_get_server_time(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let data = GetServerTimeReply {
-        server_time: Some(ServerTime::now_ms() as u64),
-        offset_time: Some(-18000000),
-    };
+pub struct GetServerTime;
 
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard
-            .send_reply_fixed(CmdId::GetServerTimeCmd, data, 0, req.up_tag)
-            .await?;
-    }
+impl CmdHandler for GetServerTime {
+    const CMD: CmdId = CmdId::GetServerTimeCmd;
+    const REQUIRES_LOGIN: bool = false;
+    const FIXED_DOWN_TAG: bool = true;
+    type Request = GetServerTimeRequest;
+    type Reply = GetServerTimeReply;
 
-    Ok(())
+    async fn handle(
+        _session: &mut Session,
+        _request: GetServerTimeRequest,
+    ) -> Result<GetServerTimeReply, AppError> {
+        Ok(GetServerTimeReply {
+            server_time: Some(ServerTime::now_ms() as u64),
+            offset_time: Some(-18000000),
+        })
+    }
 }
@@ -1,3 +1,3 @@
 mod get_server_time;
 
-pub use get_server_time::on_get_server_time;
+pub use get_server_time::GetServerTime;
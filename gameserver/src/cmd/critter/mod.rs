@@ -1,3 +1,3 @@
 mod critter_get_info;
 
-pub use critter_get_info::on_critter_get_info;
+pub use critter_get_info::CritterGetInfo;
@@ -1,21 +1,20 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use sonettobuf::{CmdId, GetBuyPowerInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::handler::{CmdHandler, Session};
+use sonettobuf::{CmdId, GetBuyPowerInfoReply, GetBuyPowerInfoRequest};
 
-pub async fn on_get_buy_power_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let data = GetBuyPowerInfoReply {
-        can_buy_count: Some(8),
-    };
+pub struct GetBuyPowerInfo;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetBuyPowerInfoCmd, data, 0, req.up_tag)
-        .await?;
-    Ok(())
+impl CmdHandler for GetBuyPowerInfo {
+    const CMD: CmdId = CmdId::GetBuyPowerInfoCmd;
+    type Request = GetBuyPowerInfoRequest;
+    type Reply = GetBuyPowerInfoReply;
+
+    async fn handle(
+        _session: &mut Session,
+        _request: GetBuyPowerInfoRequest,
+    ) -> Result<GetBuyPowerInfoReply, AppError> {
+        Ok(GetBuyPowerInfoReply {
+            can_buy_count: Some(8),
+        })
+    }
 }
@@ -1,35 +1,27 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::currencies;
-use prost::Message;
 use sonettobuf::{CmdId, GetCurrencyListReply, GetCurrencyListRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_get_currency_list(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let req_msg = GetCurrencyListRequest::decode(&req.data[..])?;
+pub struct GetCurrencyList;
 
-    tracing::info!("Requested currency_ids: {:?}", req_msg.currency_ids);
+impl CmdHandler for GetCurrencyList {
+    const CMD: CmdId = CmdId::GetCurrencyListCmd;
+    type Request = GetCurrencyListRequest;
+    type Reply = GetCurrencyListReply;
 
-    let currency_list = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+    async fn handle(
+        session: &mut Session,
+        request: GetCurrencyListRequest,
+    ) -> Result<GetCurrencyListReply, AppError> {
+        tracing::info!("Requested currency_ids: {:?}", request.currency_ids);
 
-        currencies::get_currencies(&ctx_guard.state.db, player_id, &req_msg.currency_ids).await?
-    };
+        let currency_list =
+            currencies::get_currencies(session.db(), session.player_id()?, &request.currency_ids)
+                .await?;
 
-    let reply = GetCurrencyListReply {
-        currency_list: currency_list.into_iter().map(Into::into).collect(),
-    };
-
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetCurrencyListCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetCurrencyListReply {
+            currency_list: currency_list.into_iter().map(Into::into).collect(),
+        })
+    }
 }
@@ -1,5 +1,5 @@
 mod get_buy_power_info;
 mod get_currency_list;
 
-pub use get_buy_power_info::on_get_buy_power_info;
-pub use get_currency_list::on_get_currency_list;
+pub use get_buy_power_info::GetBuyPowerInfo;
+pub use get_currency_list::GetCurrencyList;
@@ -1,3 +1,3 @@
 mod destiny_stone_use;
 
-pub use destiny_stone_use::on_destiny_stone_use;
+pub use destiny_stone_use::DestinyStoneUse;
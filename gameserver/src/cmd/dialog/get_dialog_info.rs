@@ -1,28 +1,21 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::dialogs;
-use sonettobuf::{CmdId, GetDialogInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetDialogInfoReply, GetDialogInfoRequest};
 
-pub async fn on_get_dialog_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let dialog_ids = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetDialogInfo;
 
-        dialogs::get_dialog_ids(&ctx_guard.state.db, player_id).await?
-    };
+impl CmdHandler for GetDialogInfo {
+    const CMD: CmdId = CmdId::GetDialogInfoCmd;
+    type Request = GetDialogInfoRequest;
+    type Reply = GetDialogInfoReply;
 
-    let reply = GetDialogInfoReply { dialog_ids };
+    async fn handle(
+        session: &mut Session,
+        _request: GetDialogInfoRequest,
+    ) -> Result<GetDialogInfoReply, AppError> {
+        let dialog_ids = dialogs::get_dialog_ids(session.db(), session.player_id()?).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetDialogInfoCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetDialogInfoReply { dialog_ids })
+    }
 }
@@ -1,3 +1,3 @@
 mod get_dialog_info;
 
-pub use get_dialog_info::on_get_dialog_info;
+pub use get_dialog_info::GetDialogInfo;
@@ -1,3 +1,3 @@
 pub mod hero_get_info;
 
-pub use hero_get_info::on_dice_hero_get_info;
+pub use hero_get_info::DiceHeroGetInfo;
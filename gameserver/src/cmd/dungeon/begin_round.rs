@@ -1,211 +1,128 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::utils::push::{send_dungeon_update_push, send_end_dungeon_push, send_red_dot_push};
-
-use crate::send_push;
-use crate::state::{
-    BattleSimulator, ConnectionContext, generate_dungeon_rewards, send_end_fight_push,
-};
-use database::db::game::dungeons::{
-    get_user_dungeon, should_update_dungeon_record, update_dungeon_progress,
-};
-use database::db::game::{
-    battle::save_round_operations, dungeons::save_dungeon_record, equipment::build_equip_records,
-};
-use prost::Message;
-use sonettobuf::{BeginRoundReply, BeginRoundRequest, CmdId, InstructionDungeonInfoPush};
-use std::sync::Arc;
-use tokio::sync::Mutex;
-
-pub async fn on_begin_round(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = BeginRoundRequest::decode(&req.data[..])?;
-
-    tracing::info!(
-        "BeginRound: {} operations, auto={}",
-        request.opers.len(),
-        request.auto_oper.unwrap_or(false)
-    );
-
-    // Get active battle context
-    let (
-        fight,
-        current_deck,
-        fight_group,
-        chapter_id,
-        episode_id,
-        is_replay,
-        battle_id,
-        round_num,
-        multiplication,
-    ) = {
-        let ctx_guard = ctx.lock().await;
-        let battle = ctx_guard
-            .active_battle
-            .as_ref()
-            .ok_or(AppError::InvalidRequest)?;
-
-        (
-            battle.fight.clone().ok_or(AppError::InvalidRequest)?,
-            battle.current_deck.clone(),
-            battle.fight_group.clone(),
-            battle.chapter_id,
-            battle.episode_id,
-            battle.is_replay.unwrap_or(false),
-            battle.fight_id.unwrap_or_default(),
-            battle.current_round,
-            battle.multiplication.unwrap_or(1),
-        )
-    };
-
-    let (player_id, pool) = {
-        let ctx_guard = ctx.lock().await;
-        (
-            ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?,
-            ctx_guard.state.db.clone(),
-        )
-    };
-
-    // Process battle round
-    let mut simulator = BattleSimulator::new(fight);
-    let mut round = simulator
-        .process_round(request.opers.clone(), current_deck)
-        .await?;
-
-    // Auto-complete battle for now
-    round.is_finish = Some(true);
-    let record_round = round.cur_round.unwrap_or(1);
-
-    tracing::info!(
-        "Round result: {} steps, {} cards, round={}, finish={}",
-        round.fight_step.len(),
-        round.team_a_cards1.len(),
-        record_round,
-        round.is_finish.unwrap_or(false)
-    );
-
-    // Send reply first
-    let reply = BeginRoundReply {
-        round: Some(round.clone()),
-    };
-
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard
-            .send_reply(CmdId::BeginRoundCmd, reply, 0, req.up_tag)
-            .await?;
-    }
+use crate::handler::{CmdHandler, Session};
+use crate::state::{BattleSimulator, ExcelData, FightResult, finish_fight};
+use database::db::game::battle::save_round_operations;
+use sonettobuf::{BeginRoundReply, BeginRoundRequest, CmdId, FightWavePush};
+
+pub struct BeginRound;
+
+impl CmdHandler for BeginRound {
+    const CMD: CmdId = CmdId::BeginRoundCmd;
+    type Request = BeginRoundRequest;
+    type Reply = BeginRoundReply;
+
+    async fn handle(
+        session: &mut Session,
+        request: BeginRoundRequest,
+    ) -> Result<BeginRoundReply, AppError> {
+        tracing::info!(
+            "BeginRound: {} operations, auto={}",
+            request.opers.len(),
+            request.auto_oper.unwrap_or(false)
+        );
 
-    // Only save for real battles (not replays)
-    if !is_replay {
-        // Save operations for replay
-        save_round_operations(
-            &pool,
-            player_id,
+        // Get active battle context
+        let (
+            fight,
+            current_deck,
             episode_id,
+            is_replay,
             battle_id,
             round_num,
-            vec![], // TODO: Extract cloth_skill_opers from request
-            request.opers,
-        )
-        .await?;
+            act_point,
+            seed,
+            cloth,
+            move_opers,
+        ) = {
+            let mut ctx_guard = session.lock().await;
+            let battle = ctx_guard
+                .active_battle
+                .as_mut()
+                .ok_or(AppError::InvalidRequest)?;
+
+            (
+                battle.fight.clone().ok_or(AppError::InvalidRequest)?,
+                battle.current_deck.clone(),
+                battle.episode_id,
+                battle.is_replay.unwrap_or(false),
+                battle.fight_id.unwrap_or_default(),
+                battle.current_round,
+                battle.act_point,
+                battle.seed,
+                battle.cloth.clone(),
+                std::mem::take(&mut battle.move_opers),
+            )
+        };
+
+        let player_id = session.player_id()?;
+        let pool = session.db().clone();
+
+        // Process battle round
+        let wave = fight.cur_wave;
+        let mut simulator = BattleSimulator::new(ExcelData, fight, seed, cloth);
+        // Cards moved with MoveCard go first, they are part of this round
+        let opers: Vec<_> = move_opers.into_iter().chain(request.opers).collect();
+        let round = simulator.process_round(opers.clone(), current_deck, act_point)?;
+
+        let is_finish = round.is_finish.unwrap_or(false);
+        let record_round = round.cur_round.unwrap_or(1);
+
+        // Carry HP and the hand over to the next round
+        let cloth_opers = {
+            let mut ctx_guard = session.lock().await;
+            if let Some(battle) = ctx_guard.active_battle.as_mut() {
+                battle.fight = Some(simulator.fight().clone());
+                battle.current_deck = round.team_a_cards1.clone();
+                battle.current_round = record_round;
+                battle.cloth = simulator.cloth_state().clone();
+                std::mem::take(&mut battle.cloth_opers)
+            } else {
+                vec![]
+            }
+        };
 
-        // Update player's dungeon progress
-        let stars_earned = 2; // TODO: Calculate based on performance
-        update_dungeon_progress(&pool, player_id, chapter_id, episode_id, stars_earned).await?;
+        tracing::info!(
+            "Round result: {} steps, {} cards, round={}, finish={}",
+            round.fight_step.len(),
+            round.team_a_cards1.len(),
+            record_round,
+            is_finish
+        );
 
-        // Save dungeon record if it's a new best or different lineup
-        let should_save_record =
-            should_update_dungeon_record(&pool, player_id, episode_id, record_round, &fight_group)
-                .await?;
+        // A cleared wave hands the client the next monster group
+        if simulator.fight().cur_wave != wave {
+            let push = FightWavePush {
+                fight: Some(simulator.fight().clone()),
+            };
+            session.push_after_reply(CmdId::FightWavePushCmd, push)?;
+        }
 
-        if should_save_record {
-            let equips = build_equip_records(&pool, player_id, &fight_group).await?;
-            save_dungeon_record(
+        // Only save for real battles (not replays)
+        if !is_replay {
+            // Save operations for replay
+            save_round_operations(
                 &pool,
                 player_id,
                 episode_id,
-                record_round,
-                &fight_group.clone().unwrap_or_default(),
-                equips,
+                battle_id,
+                round_num,
+                cloth_opers,
+                opers,
             )
             .await?;
         }
 
-        tracing::info!(
-            "Battle completed: episode={}, round={}, record_saved={}",
-            episode_id,
-            record_round,
-            should_save_record
-        );
-    } else {
-        tracing::info!(
-            "Replay completed: episode={}, round={}",
-            episode_id,
-            record_round
-        );
-    }
-
-    // Send pushes regardless (client needs them to exit battle properly)
-    send_end_fight_push(
-        ctx.clone(),
-        battle_id,
-        1, // Win
-        fight_group.clone().unwrap_or_default(),
-        vec![],     // TODO: Actual battle stats
-        vec![],     // No defender stats
-        !is_replay, // is_record: only record real battles
-    )
-    .await?;
-
-    send_push!(
-        ctx,
-        CmdId::DungeonInstructionDungeonInfoPushCmd,
-        InstructionDungeonInfoPush,
-        "dungeon/instruction_dungeon_info.json"
-    );
-
-    // Update dungeon UI with current state (always, even for replays)
-    let updated_dungeon = get_user_dungeon(&pool, player_id, chapter_id, episode_id).await?;
-
-    // Get chapter type from Excel data
-    let game_data = data::exceldb::get();
-    let chapter_type = game_data
-        .chapter
-        .iter()
-        .find(|c| c.id == chapter_id)
-        .map(|c| c.r#type)
-        .unwrap_or(6);
-
-    send_dungeon_update_push(
-        ctx.clone(),
-        chapter_id,
-        episode_id,
-        updated_dungeon.star,
-        updated_dungeon.challenge_count,
-        updated_dungeon.has_record,
-        chapter_type, // From chapter Excel data
-        2,            // TODO: Calculate today's chapter completions
-        2,            // TODO: Calculate today's chapter attempts
-    )
-    .await?;
-
-    // Generate rewards based on episode data
-
-    let is_first_clear = updated_dungeon.challenge_count == 1;
-
-    let rewards = generate_dungeon_rewards(episode_id, is_first_clear, multiplication);
-
-    // Combine rewards for push
-    let mut all_rewards = rewards.normal_bonus.clone();
-    all_rewards.extend(rewards.first_bonus);
-    all_rewards.extend(rewards.free_bonus);
-
-    send_end_dungeon_push(ctx.clone(), chapter_id, episode_id, all_rewards).await?;
-
-    send_red_dot_push(Arc::clone(&ctx), player_id, Some(vec![1027, 1047])).await?;
+        if is_finish {
+            let result = simulator.result().unwrap_or(FightResult::Lose);
+            finish_fight(session, result).await?;
+        } else {
+            tracing::info!(
+                "Battle continues: episode={}, next round={}",
+                episode_id,
+                record_round
+            );
+        }
 
-    Ok(())
+        Ok(BeginRoundReply { round: Some(round) })
+    }
 }
@@ -1,30 +1,26 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use prost::Message;
+use crate::handler::{CmdHandler, Session};
 use sonettobuf::{ChangeHeroGroupSelectReply, ChangeHeroGroupSelectRequest, CmdId};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_change_hero_group_select(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = ChangeHeroGroupSelectRequest::decode(&req.data[..])?;
+pub struct ChangeHeroGroupSelect;
 
-    let id = request.id.ok_or(AppError::InvalidRequest)?;
-    let current_select = request.current_select.ok_or(AppError::InvalidRequest)?;
+impl CmdHandler for ChangeHeroGroupSelect {
+    const CMD: CmdId = CmdId::ChangeHeroGroupSelectCmd;
+    type Request = ChangeHeroGroupSelectRequest;
+    type Reply = ChangeHeroGroupSelectReply;
 
-    tracing::info!("Changing {} to {}", id, current_select);
+    async fn handle(
+        _session: &mut Session,
+        request: ChangeHeroGroupSelectRequest,
+    ) -> Result<ChangeHeroGroupSelectReply, AppError> {
+        let id = request.id.ok_or(AppError::InvalidRequest)?;
+        let current_select = request.current_select.ok_or(AppError::InvalidRequest)?;
 
-    let data = ChangeHeroGroupSelectReply {
-        id: Some(id),
-        current_select: Some(current_select),
-    };
+        tracing::info!("Changing {} to {}", id, current_select);
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::ChangeHeroGroupSelectCmd, data, 0, req.up_tag)
-        .await?;
-    Ok(())
+        Ok(ChangeHeroGroupSelectReply {
+            id: Some(id),
+            current_select: Some(current_select),
+        })
+    }
 }
@@ -1,32 +1,25 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use prost::Message;
+use crate::handler::{CmdHandler, Session};
 use sonettobuf::{CmdId, EndDungeonReply, EndDungeonRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_dungeon_end_dungeon(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = EndDungeonRequest::decode(&req.data[..])?;
+pub struct DungeonEndDungeon;
 
-    let is_abort = request.is_abort.ok_or(AppError::InvalidRequest)?;
+impl CmdHandler for DungeonEndDungeon {
+    const CMD: CmdId = CmdId::DungeonEndDungeonCmd;
+    type Request = EndDungeonRequest;
+    type Reply = EndDungeonReply;
 
-    tracing::info!("Dungeon ended with is_abort: {}", is_abort);
+    async fn handle(
+        session: &mut Session,
+        request: EndDungeonRequest,
+    ) -> Result<EndDungeonReply, AppError> {
+        let is_abort = request.is_abort.ok_or(AppError::InvalidRequest)?;
 
-    // Clear battle
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard.active_battle = None;
-    }
+        tracing::info!("Dungeon ended with is_abort: {}", is_abort);
 
-    let data = EndDungeonReply {};
+        // Clear battle
+        session.lock().await.active_battle = None;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::DungeonEndDungeonCmd, data, 0, req.up_tag)
-        .await?;
-    Ok(())
+        Ok(EndDungeonReply {})
+    }
 }
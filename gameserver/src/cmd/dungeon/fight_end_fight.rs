@@ -1,32 +1,25 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use prost::Message;
+use crate::handler::{CmdHandler, Session};
 use sonettobuf::{CmdId, EndFightReply, EndFightRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_fight_end_fight(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = EndFightRequest::decode(&req.data[..])?;
+pub struct FightEndFight;
 
-    let is_abort = request.is_abort.ok_or(AppError::InvalidRequest)?;
+impl CmdHandler for FightEndFight {
+    const CMD: CmdId = CmdId::FightEndFightCmd;
+    type Request = EndFightRequest;
+    type Reply = EndFightReply;
 
-    tracing::info!("Fight ended with is_abort: {}", is_abort);
+    async fn handle(
+        session: &mut Session,
+        request: EndFightRequest,
+    ) -> Result<EndFightReply, AppError> {
+        let is_abort = request.is_abort.ok_or(AppError::InvalidRequest)?;
 
-    // Clear battle
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard.active_battle = None;
-    }
+        tracing::info!("Fight ended with is_abort: {}", is_abort);
 
-    let data = EndFightReply {};
+        // Clear battle
+        session.lock().await.active_battle = None;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::FightEndFightCmd, data, 0, req.up_tag)
-        .await?;
-    Ok(())
+        Ok(EndFightReply {})
+    }
 }
@@ -1,47 +1,40 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::battle::load_battle_replay;
-use prost::Message;
 use sonettobuf::{CmdId, GetFightOperReply, GetFightOperRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_get_fight_oper(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let _ = GetFightOperRequest::decode(&req.data[..])?;
+pub struct GetFightOper;
 
-    let (player_id, pool, episode_id, is_replay) = {
-        let ctx_guard = ctx.lock().await;
-        let battle = ctx_guard
-            .active_battle
-            .as_ref()
-            .ok_or(AppError::InvalidRequest)?;
+impl CmdHandler for GetFightOper {
+    const CMD: CmdId = CmdId::GetFightOperCmd;
+    type Request = GetFightOperRequest;
+    type Reply = GetFightOperReply;
 
-        (
-            ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?,
-            ctx_guard.state.db.clone(),
-            battle.replay_episode_id.unwrap_or_default(),
-            battle.is_replay.unwrap_or(false),
-        )
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetFightOperRequest,
+    ) -> Result<GetFightOperReply, AppError> {
+        let (episode_id, is_replay) = {
+            let ctx_guard = session.lock().await;
+            let battle = ctx_guard
+                .active_battle
+                .as_ref()
+                .ok_or(AppError::InvalidRequest)?;
 
-    let oper_records = if is_replay {
-        // Load recorded operations from DB
-        load_battle_replay(&pool, player_id, episode_id).await?
-    } else {
-        // Not a replay, return empty
-        vec![]
-    };
+            (
+                battle.replay_episode_id.unwrap_or_default(),
+                battle.is_replay.unwrap_or(false),
+            )
+        };
 
-    let reply = GetFightOperReply { oper_records };
+        let oper_records = if is_replay {
+            // Load recorded operations from DB
+            load_battle_replay(session.db(), session.player_id()?, episode_id).await?
+        } else {
+            // Not a replay, return empty
+            vec![]
+        };
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetFightOperCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetFightOperReply { oper_records })
+    }
 }
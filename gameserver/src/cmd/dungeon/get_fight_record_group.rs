@@ -1,47 +1,36 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::dungeons::load_dungeon_record;
-use prost::Message;
 use sonettobuf::{CmdId, GetFightRecordGroupReply, GetFightRecordGroupRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_get_fight_record_group(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = GetFightRecordGroupRequest::decode(&req.data[..])?;
-    let episode_id = request.episode_id.unwrap_or(0);
+pub struct GetFightRecordGroup;
 
-    tracing::info!("GetFightRecordGroup for episode {}", episode_id);
+impl CmdHandler for GetFightRecordGroup {
+    const CMD: CmdId = CmdId::GetFightRecordGroupCmd;
+    type Request = GetFightRecordGroupRequest;
+    type Reply = GetFightRecordGroupReply;
 
-    let (pool, player_id) = {
-        let ctx_guard = ctx.lock().await;
-        (ctx_guard.state.db.clone(), ctx_guard.player_id)
-    };
+    async fn handle(
+        session: &mut Session,
+        request: GetFightRecordGroupRequest,
+    ) -> Result<GetFightRecordGroupReply, AppError> {
+        let episode_id = request.episode_id.unwrap_or(0);
 
-    let record = load_dungeon_record(&pool, player_id.unwrap_or(0), episode_id).await?;
+        tracing::info!("GetFightRecordGroup for episode {}", episode_id);
 
-    tracing::info!("Loaded record: {:?}", record.is_some());
-    if let Some(ref rec) = record {
-        tracing::info!(
-            "Record has {} heroes, {} trial heroes",
-            rec.hero_list.len(),
-            rec.trial_hero_list.len()
-        );
-    }
-
-    let reply = GetFightRecordGroupReply {
-        fight_group: record,
-    };
+        let record = load_dungeon_record(session.db(), session.player_id()?, episode_id).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetFightRecordGroupCmd, reply, 0, req.up_tag)
-        .await?;
+        tracing::info!("Loaded record: {:?}", record.is_some());
+        if let Some(ref rec) = record {
+            tracing::info!(
+                "Record has {} heroes, {} trial heroes",
+                rec.hero_list.len(),
+                rec.trial_hero_list.len()
+            );
+        }
 
-    tracing::info!("Sent GetFightRecordGroup reply");
-
-    Ok(())
+        Ok(GetFightRecordGroupReply {
+            fight_group: record,
+        })
+    }
 }
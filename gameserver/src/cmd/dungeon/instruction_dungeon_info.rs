@@ -1,21 +1,9 @@
-use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::send_reply;
-use crate::state::ConnectionContext;
-use sonettobuf::{CmdId, InstructionDungeonInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::static_reply;
+use sonettobuf::{CmdId, InstructionDungeonInfoReply, InstructionDungeonInfoRequest};
 
-pub async fn on_instruction_dungeon_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    send_reply!(
-        ctx,
-        req.up_tag,
-        CmdId::DungeonInstructionDungeonInfoCmd,
-        InstructionDungeonInfoReply,
-        "dungeon/instruction_dungeon_info.json"
-    );
-    Ok(())
-}
+static_reply!(
+    DungeonInstructionDungeonInfo,
+    CmdId::DungeonInstructionDungeonInfoCmd,
+    InstructionDungeonInfoRequest => InstructionDungeonInfoReply,
+    "dungeon/instruction_dungeon_info.json"
+);
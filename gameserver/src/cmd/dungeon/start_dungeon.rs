@@ -1,73 +1,120 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
+use crate::handler::{CmdHandler, Session};
 use crate::state::{
-    ActiveBattle, BattleContext, ConnectionContext, create_battle, default_max_ap,
-    generate_initial_deck,
+    ActiveBattle, BattleContext, ClothState, REPLAY_VERSION, cloth_level, create_battle,
+    default_max_ap, generate_initial_deck,
 };
 use data::exceldb;
+use database::db::game::battle::{load_battle_record, save_battle_record};
 use database::db::game::dungeons::get_user_dungeon;
-use prost::Message;
 use sonettobuf::{CmdId, DungeonUpdatePush, StartDungeonReply, StartDungeonRequest, UserDungeon};
-use std::sync::Arc;
-use tokio::sync::Mutex;
-
-pub async fn on_start_dungeon(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = StartDungeonRequest::decode(&req.data[..])?;
-    tracing::info!("Received start dungeon request {:?}", request);
-
-    let chapter_id = request.chapter_id.unwrap_or(0);
-    let episode_id = request.episode_id.unwrap_or(0);
-    let use_record = request.use_record.unwrap_or(false);
-    let multiplication = request.multiplication.unwrap_or(1);
-
-    let (player_id, pool) = {
-        let ctx_guard = ctx.lock().await;
-        (
-            ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?,
-            ctx_guard.state.db.clone(),
-        )
-    };
 
-    let game_data = exceldb::get();
+pub struct StartDungeon;
 
-    let episode_cfg = game_data
-        .episode
-        .iter()
-        .find(|e| e.id == episode_id)
-        .ok_or(AppError::InvalidRequest)?;
+impl CmdHandler for StartDungeon {
+    const CMD: CmdId = CmdId::StartDungeonCmd;
+    type Request = StartDungeonRequest;
+    type Reply = StartDungeonReply;
 
-    if episode_cfg.battle_id == 0 {
-        return handle_story_only_episode(ctx, req, chapter_id, episode_id).await;
-    }
+    async fn handle(
+        session: &mut Session,
+        request: StartDungeonRequest,
+    ) -> Result<StartDungeonReply, AppError> {
+        tracing::info!("Received start dungeon request {:?}", request);
 
-    let fight_group = request.fight_group.ok_or(AppError::InvalidRequest)?;
+        let chapter_id = request.chapter_id.unwrap_or(0);
+        let episode_id = request.episode_id.unwrap_or(0);
+        let use_record = request.use_record.unwrap_or(false);
+        let multiplication = request.multiplication.unwrap_or(1);
 
-    let hero_count = fight_group.hero_list.iter().filter(|&&u| u != 0).count();
+        let player_id = session.player_id()?;
+        let pool = session.db().clone();
 
-    let battle_id = episode_cfg.battle_id;
-    let max_ap = default_max_ap(episode_id, hero_count);
+        let game_data = exceldb::get();
 
-    let battle_ctx = BattleContext {
-        player_id,
-        chapter_id,
-        episode_id,
-        battle_id,
-    };
+        let episode_cfg = game_data
+            .episode
+            .iter()
+            .find(|e| e.id == episode_id)
+            .ok_or(AppError::InvalidRequest)?;
 
-    // Generate deck ONCE
-    let card_push = generate_initial_deck(&pool, player_id, &fight_group, max_ap).await?;
+        if episode_cfg.battle_id == 0 {
+            return handle_story_only_episode(session, chapter_id, episode_id).await;
+        }
 
-    let card_deck = card_push.card_group.clone();
+        let fight_group = request.fight_group.ok_or(AppError::InvalidRequest)?;
 
-    // Create battle using the SAME deck
-    let battle_data = create_battle(&pool, battle_ctx, &fight_group, card_deck.clone()).await?;
+        let hero_count = fight_group.hero_list.iter().filter(|&&u| u != 0).count();
 
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard.active_battle = Some(ActiveBattle {
+        let battle_id = episode_cfg.battle_id;
+        let max_ap = default_max_ap(episode_id, hero_count);
+        let mut cloth_level = cloth_level(&pool, player_id, &fight_group).await?;
+
+        let battle_ctx = BattleContext {
+            player_id,
+            chapter_id,
+            episode_id,
+            battle_id,
+            cloth_level,
+        };
+
+        // Generate deck ONCE
+        let mut card_push = generate_initial_deck(&pool, player_id, &fight_group, max_ap).await?;
+
+        let mut card_deck = card_push.card_group.clone();
+
+        // Create battle using the SAME deck
+        let mut battle_data =
+            create_battle(&pool, battle_ctx, &fight_group, card_deck.clone()).await?;
+
+        let fight_id = chrono::Utc::now().timestamp_millis();
+        let mut seed: u64 = rand::random();
+        let mut act_point = max_ap;
+
+        // A replay starts from the recorded fight so the same operations play out the same
+        let record = if use_record {
+            load_battle_record(&pool, player_id, episode_id)
+                .await?
+                .filter(|record| record.version == REPLAY_VERSION)
+        } else {
+            None
+        };
+
+        if let Some(record) = record {
+            tracing::info!(
+                "Replaying battle {} of episode {}",
+                record.battle_id,
+                episode_id
+            );
+
+            seed = record.seed;
+            act_point = record.act_point;
+            cloth_level = record.cloth_level;
+            card_deck = record.card_deck;
+            card_push.card_group = card_deck.clone();
+            card_push.deal_card_group = card_deck.clone();
+            card_push.act_point = Some(act_point);
+            if let Some(round) = battle_data.round.as_mut() {
+                round.team_a_cards1 = card_deck.clone();
+            }
+            battle_data.fight = Some(record.fight);
+        } else if !use_record && let Some(fight) = battle_data.fight.as_ref() {
+            save_battle_record(
+                &pool,
+                player_id,
+                episode_id,
+                fight_id,
+                seed,
+                act_point,
+                fight,
+                &card_deck,
+                REPLAY_VERSION,
+                cloth_level,
+            )
+            .await?;
+        }
+
+        session.lock().await.active_battle = Some(ActiveBattle {
             tower_type: None,
             tower_id: None,
             layer_id: None,
@@ -77,83 +124,70 @@ pub async fn on_start_dungeon(
             talent_plan_id: None,
             fight: battle_data.fight.clone(),
             current_round: 1,
-            act_point: max_ap,
+            act_point,
             power: 15,
             current_deck: card_deck,
+            cloth_opers: vec![],
+            move_opers: vec![],
+            cloth: ClothState::new(cloth_level),
             fight_group: Some(fight_group.clone()),
             is_replay: Some(use_record),
             replay_episode_id: Some(episode_id),
-            fight_id: Some(chrono::Utc::now().timestamp_millis()),
+            fight_id: Some(fight_id),
             multiplication: Some(multiplication),
+            seed,
         });
-    }
-
-    let updated_dungeon = get_user_dungeon(&pool, player_id, chapter_id, episode_id).await?;
-
-    let chapter_type = game_data
-        .chapter
-        .iter()
-        .find(|c| c.id == chapter_id)
-        .map(|c| c.r#type)
-        .unwrap_or(6);
-
-    let chapter_type_nums = vec![sonettobuf::UserChapterTypeNum {
-        chapter_type: Some(chapter_type),
-        today_pass_num: Some(1),
-        today_total_num: Some(2),
-    }];
-
-    let dungeon_push = DungeonUpdatePush {
-        dungeon_info: Some(UserDungeon {
-            chapter_id: Some(chapter_id),
-            episode_id: Some(episode_id),
-            star: Some(updated_dungeon.star),
-            challenge_count: Some(updated_dungeon.challenge_count),
-            has_record: Some(updated_dungeon.has_record),
-            left_return_all_num: Some(1),
-            today_pass_num: Some(0),
-            today_total_num: Some(0),
-        }),
-        chapter_type_nums,
-    };
-
-    let reply = StartDungeonReply {
-        fight: battle_data.fight,
-        round: battle_data.round,
-    };
-
-    let mut ctx_guard = ctx.lock().await;
-
-    ctx_guard
-        .send_push(CmdId::CardInfoPushCmd, card_push)
-        .await?;
-
-    ctx_guard
-        .send_push(CmdId::DungeonUpdatePushCmd, dungeon_push)
-        .await?;
-
-    ctx_guard
-        .send_reply(CmdId::StartDungeonCmd, reply, 0, req.up_tag)
-        .await?;
 
-    Ok(())
+        let updated_dungeon = get_user_dungeon(&pool, player_id, chapter_id, episode_id).await?;
+
+        let chapter_type = game_data
+            .chapter
+            .iter()
+            .find(|c| c.id == chapter_id)
+            .map(|c| c.r#type)
+            .unwrap_or(6);
+
+        let chapter_type_nums = vec![sonettobuf::UserChapterTypeNum {
+            chapter_type: Some(chapter_type),
+            today_pass_num: Some(1),
+            today_total_num: Some(2),
+        }];
+
+        let dungeon_push = DungeonUpdatePush {
+            dungeon_info: Some(UserDungeon {
+                chapter_id: Some(chapter_id),
+                episode_id: Some(episode_id),
+                star: Some(updated_dungeon.star),
+                challenge_count: Some(updated_dungeon.challenge_count),
+                has_record: Some(updated_dungeon.has_record),
+                left_return_all_num: Some(1),
+                today_pass_num: Some(0),
+                today_total_num: Some(0),
+            }),
+            chapter_type_nums,
+        };
+
+        session.send_push(CmdId::CardInfoPushCmd, card_push).await?;
+
+        session
+            .send_push(CmdId::DungeonUpdatePushCmd, dungeon_push)
+            .await?;
+
+        Ok(StartDungeonReply {
+            fight: battle_data.fight,
+            round: battle_data.round,
+        })
+    }
 }
 
 async fn handle_story_only_episode(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
+    session: &Session,
     chapter_id: i32,
     episode_id: i32,
-) -> Result<(), AppError> {
-    let (player_id, pool) = {
-        let ctx_guard = ctx.lock().await;
-        (
-            ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?,
-            ctx_guard.state.db.clone(),
-        )
-    };
+) -> Result<StartDungeonReply, AppError> {
     // Ensure dungeon row exists / update progress
-    let updated_dungeon = get_user_dungeon(&pool, player_id, chapter_id, episode_id).await?;
+    let updated_dungeon =
+        get_user_dungeon(session.db(), session.player_id()?, chapter_id, episode_id).await?;
 
     let dungeon_push = DungeonUpdatePush {
         dungeon_info: Some(UserDungeon {
@@ -169,20 +203,12 @@ async fn handle_story_only_episode(
         chapter_type_nums: vec![],
     };
 
-    let reply = StartDungeonReply {
-        fight: None,
-        round: None,
-    };
-
-    let mut ctx_guard = ctx.lock().await;
-
-    ctx_guard
+    session
         .send_push(CmdId::DungeonUpdatePushCmd, dungeon_push)
         .await?;
 
-    ctx_guard
-        .send_reply(CmdId::StartDungeonCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+    Ok(StartDungeonReply {
+        fight: None,
+        round: None,
+    })
 }
@@ -1,30 +1,24 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::equipment;
-use sonettobuf::{CmdId, GetEquipInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetEquipInfoReply, GetEquipInfoRequest};
 
-pub async fn on_get_equip_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let equipment_list = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetEquipInfo;
 
-        equipment::get_user_equipment(&ctx_guard.state.db, player_id).await?
-    };
+impl CmdHandler for GetEquipInfo {
+    const CMD: CmdId = CmdId::GetEquipInfoCmd;
+    type Request = GetEquipInfoRequest;
+    type Reply = GetEquipInfoReply;
 
-    let reply = GetEquipInfoReply {
-        equips: equipment_list.into_iter().map(Into::into).collect(),
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetEquipInfoRequest,
+    ) -> Result<GetEquipInfoReply, AppError> {
+        let equipment_list =
+            equipment::get_user_equipment(session.db(), session.player_id()?).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetEquipInfoCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetEquipInfoReply {
+            equips: equipment_list.into_iter().map(Into::into).collect(),
+        })
+    }
 }
@@ -1,3 +1,3 @@
 mod get_equip_info;
 
-pub use get_equip_info::on_get_equip_info;
+pub use get_equip_info::GetEquipInfo;
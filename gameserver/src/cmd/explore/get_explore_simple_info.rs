@@ -1,34 +1,28 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::explore;
-use sonettobuf::{CmdId, GetExploreSimpleInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetExploreSimpleInfoReply, GetExploreSimpleInfoRequest};
 
-pub async fn on_get_explore_simple_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let (info, chapters, maps, unlocked_maps) = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetExploreSimpleInfo;
 
-        explore::get_explore_info(&ctx_guard.state.db, player_id).await?
-    };
+impl CmdHandler for GetExploreSimpleInfo {
+    const CMD: CmdId = CmdId::GetExploreSimpleInfoCmd;
+    type Request = GetExploreSimpleInfoRequest;
+    type Reply = GetExploreSimpleInfoReply;
 
-    let reply = GetExploreSimpleInfoReply {
-        last_map_id: Some(info.last_map_id),
-        chapter_simple: chapters.into_iter().map(Into::into).collect(),
-        map_simple: maps.into_iter().map(Into::into).collect(),
-        unlock_map_ids: unlocked_maps,
-        is_show_bag: Some(info.is_show_bag),
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetExploreSimpleInfoRequest,
+    ) -> Result<GetExploreSimpleInfoReply, AppError> {
+        let (info, chapters, maps, unlocked_maps) =
+            explore::get_explore_info(session.db(), session.player_id()?).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetExploreSimpleInfoCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetExploreSimpleInfoReply {
+            last_map_id: Some(info.last_map_id),
+            chapter_simple: chapters.into_iter().map(Into::into).collect(),
+            map_simple: maps.into_iter().map(Into::into).collect(),
+            unlock_map_ids: unlocked_maps,
+            is_show_bag: Some(info.is_show_bag),
+        })
+    }
 }
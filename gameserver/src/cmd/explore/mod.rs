@@ -1,3 +1,3 @@
 mod get_explore_simple_info;
 
-pub use get_explore_simple_info::on_get_explore_simple_info;
+pub use get_explore_simple_info::GetExploreSimpleInfo;
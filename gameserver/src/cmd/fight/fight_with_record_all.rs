@@ -0,0 +1,115 @@
+use crate::error::AppError;
+use crate::handler::{CmdHandler, Session};
+use crate::state::{ExcelData, finish_fight, run_rounds};
+use database::db::game::battle::save_round_operations;
+use sonettobuf::{
+    CmdId, FightRoundOperRecord, FightWithRecordAllReply, FightWithRecordAllRequest,
+    UseClothSkillOperRecord,
+};
+
+pub struct FightWithRecordAll;
+
+impl CmdHandler for FightWithRecordAll {
+    const CMD: CmdId = CmdId::FightWithRecordAllCmd;
+    type Request = FightWithRecordAllRequest;
+    type Reply = FightWithRecordAllReply;
+
+    async fn handle(
+        session: &mut Session,
+        request: FightWithRecordAllRequest,
+    ) -> Result<FightWithRecordAllReply, AppError> {
+        let records = request.record_all.map(|r| r.records).unwrap_or_default();
+
+        let (fight, hand, seed, act_point, cloth, episode_id, battle_id, first_round, is_replay) = {
+            let ctx_guard = session.lock().await;
+            let battle = ctx_guard
+                .active_battle
+                .as_ref()
+                .ok_or(AppError::InvalidRequest)?;
+
+            (
+                battle.fight.clone().ok_or(AppError::InvalidRequest)?,
+                battle.current_deck.clone(),
+                battle.seed,
+                battle.act_point,
+                battle.cloth.clone(),
+                battle.episode_id,
+                battle.fight_id.unwrap_or_default(),
+                battle.current_round,
+                battle.is_replay.unwrap_or(false),
+            )
+        };
+
+        tracing::info!(
+            "FightWithRecordAll: {} recorded rounds from round {}",
+            records.len(),
+            first_round
+        );
+
+        // The client's rounds are only trusted for their operations, the
+        // outcome comes from running them on the server's own fight
+        let round_opers: Vec<FightRoundOperRecord> = records
+            .into_iter()
+            .map(|r| FightRoundOperRecord {
+                cloth_skill_opers: r
+                    .cloth_skills
+                    .into_iter()
+                    .map(|c| UseClothSkillOperRecord {
+                        skill_id: c.skill_id,
+                        from_id: c.from_id,
+                        to_id: c.to_id,
+                        r#type: c.r#type,
+                    })
+                    .collect(),
+                opers: r.opers,
+            })
+            .collect();
+        let replay = run_rounds(
+            &ExcelData,
+            fight,
+            hand,
+            seed,
+            act_point,
+            cloth,
+            round_opers.clone(),
+        )?;
+
+        if !is_replay {
+            let player_id = session.player_id()?;
+            for (round_num, record) in (first_round..).zip(round_opers).take(replay.rounds.len()) {
+                save_round_operations(
+                    session.db(),
+                    player_id,
+                    episode_id,
+                    battle_id,
+                    round_num,
+                    record.cloth_skill_opers,
+                    record.opers,
+                )
+                .await?;
+            }
+        }
+
+        let last_round = replay.rounds.last().cloned();
+
+        {
+            let mut ctx_guard = session.lock().await;
+            if let Some(battle) = ctx_guard.active_battle.as_mut() {
+                battle.fight = Some(replay.fight.clone());
+                battle.current_deck = replay.hand;
+                battle.cloth = replay.cloth;
+                battle.current_round = replay.fight.cur_round.unwrap_or(first_round);
+            }
+        }
+
+        // A decided fight ends here the same way a played round would
+        if let Some(result) = replay.result {
+            finish_fight(session, result).await?;
+        }
+
+        Ok(FightWithRecordAllReply {
+            fight: Some(replay.fight),
+            round: last_round,
+        })
+    }
+}
@@ -0,0 +1,56 @@
+use crate::error::AppError;
+use crate::handler::{CmdHandler, Session};
+use crate::state::{battle_rules, entity_detail};
+use sonettobuf::{CmdId, FightEntityInfo, GetEntityDetailInfosReply, GetEntityDetailInfosRequest};
+
+pub struct GetEntityDetailInfos;
+
+impl CmdHandler for GetEntityDetailInfos {
+    const CMD: CmdId = CmdId::GetEntityDetailInfosCmd;
+    type Request = GetEntityDetailInfosRequest;
+    type Reply = GetEntityDetailInfosReply;
+
+    async fn handle(
+        session: &mut Session,
+        _request: GetEntityDetailInfosRequest,
+    ) -> Result<GetEntityDetailInfosReply, AppError> {
+        let (fight, episode_id) = {
+            let ctx_guard = session.lock().await;
+            let battle = ctx_guard
+                .active_battle
+                .as_ref()
+                .ok_or(AppError::InvalidRequest)?;
+
+            (
+                battle.fight.clone().ok_or(AppError::InvalidRequest)?,
+                battle.episode_id,
+            )
+        };
+
+        let effectiveness = battle_rules(episode_id)
+            .map(|rules| rules.effectiveness)
+            .unwrap_or_default();
+
+        let player_id = session.player_id()?;
+        let pool = session.db();
+
+        let team = |team: Option<&sonettobuf::FightTeam>| -> Vec<FightEntityInfo> {
+            team.map(|t| t.entitys.iter().chain(&t.sub_entitys).cloned().collect())
+                .unwrap_or_default()
+        };
+
+        let mut reply = GetEntityDetailInfosReply::default();
+        for entity in team(fight.attacker.as_ref()) {
+            reply
+                .team_a_infos
+                .push(entity_detail(pool, player_id, &entity, &effectiveness).await);
+        }
+        for entity in team(fight.defender.as_ref()) {
+            reply
+                .team_b_infos
+                .push(entity_detail(pool, player_id, &entity, &effectiveness).await);
+        }
+
+        Ok(reply)
+    }
+}
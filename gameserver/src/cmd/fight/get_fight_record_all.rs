@@ -0,0 +1,112 @@
+use crate::error::AppError;
+use crate::handler::{CmdHandler, Session};
+use crate::state::{ExcelData, REPLAY_VERSION, build_initial_round, replay_battle};
+use database::db::game::battle::{load_battle_record, load_battle_replay};
+use database::db::game::dungeons::load_dungeon_record;
+use sonettobuf::{
+    CardInfoPush, CmdId, FightRoundRecord, FightRoundRecordAll, GetFightRecordAllReply,
+    GetFightRecordAllRequest,
+};
+
+pub struct GetFightRecordAll;
+
+impl CmdHandler for GetFightRecordAll {
+    const CMD: CmdId = CmdId::GetFightRecordAllCmd;
+    type Request = GetFightRecordAllRequest;
+    type Reply = GetFightRecordAllReply;
+
+    async fn handle(
+        session: &mut Session,
+        _request: GetFightRecordAllRequest,
+    ) -> Result<GetFightRecordAllReply, AppError> {
+        let episode_id = {
+            let ctx_guard = session.lock().await;
+            let battle = ctx_guard
+                .active_battle
+                .as_ref()
+                .ok_or(AppError::InvalidRequest)?;
+
+            battle.replay_episode_id.unwrap_or(battle.episode_id)
+        };
+
+        let player_id = session.player_id()?;
+        let pool = session.db();
+
+        let Some(record) = load_battle_record(pool, player_id, episode_id).await? else {
+            tracing::info!("No battle record for episode {}", episode_id);
+            return Ok(GetFightRecordAllReply::default());
+        };
+
+        // Played on another simulator, the operations may not lead to the same end
+        if record.version != REPLAY_VERSION {
+            tracing::info!(
+                "Battle {} of episode {} is from simulator version {}, not {}",
+                record.battle_id,
+                episode_id,
+                record.version,
+                REPLAY_VERSION
+            );
+            return Ok(GetFightRecordAllReply::default());
+        }
+
+        let round_opers = load_battle_replay(pool, player_id, episode_id).await?;
+        let replay = replay_battle(&ExcelData, &record, &round_opers)?;
+
+        // A record that no longer plays out the same would show a different fight
+        if !replay.matches(&record) {
+            tracing::warn!(
+                "Battle {} of episode {} no longer replays to its recorded end",
+                record.battle_id,
+                episode_id
+            );
+            return Ok(GetFightRecordAllReply::default());
+        }
+
+        let first_round = build_initial_round(&record.fight, record.card_deck.clone())?;
+        let total_round = replay.rounds.len() as i32;
+
+        let records = round_opers
+            .into_iter()
+            .zip(replay.rounds)
+            .zip(replay.cloth_skills)
+            .zip(replay.new_waves)
+            .map(
+                |(((opers, round), cloth_skills), new_wave)| FightRoundRecord {
+                    cloth_skills,
+                    opers: opers.opers,
+                    round: Some(round),
+                    new_wave: Some(new_wave),
+                },
+            )
+            .collect();
+
+        let push_info = CardInfoPush {
+            card_group: record.card_deck.clone(),
+            deal_card_group: record.card_deck.clone(),
+            act_point: Some(record.act_point),
+            move_num: Some(0),
+            before_cards: vec![],
+            extra_move_act: Some(0),
+            is_gm: Some(false),
+        };
+
+        let group = load_dungeon_record(pool, player_id, episode_id).await?;
+
+        Ok(GetFightRecordAllReply {
+            record_all: Some(FightRoundRecordAll {
+                fight: Some(record.fight),
+                round: Some(first_round),
+                records,
+                fight_record: None,
+                result: replay.result.map(|r| r.code()),
+                result_cause: Some(0),
+                total_round: Some(total_round),
+                kill_total: Some(0),
+                push_info: Some(push_info),
+                wave_push_fight: replay.wave_fights,
+                redeal_infos: vec![],
+            }),
+            group,
+        })
+    }
+}
@@ -1,3 +1,13 @@
+mod fight_with_record_all;
+mod get_entity_detail_infos;
+mod get_fight_record_all;
+mod move_card;
 mod reconnect_fight;
+mod use_cloth_skill;
 
-pub use reconnect_fight::on_reconnect_fight;
+pub use fight_with_record_all::FightWithRecordAll;
+pub use get_entity_detail_infos::GetEntityDetailInfos;
+pub use get_fight_record_all::GetFightRecordAll;
+pub use move_card::MoveCard;
+pub use reconnect_fight::ReconnectFight;
+pub use use_cloth_skill::UseClothSkill;
@@ -0,0 +1,56 @@
+use crate::error::AppError;
+use crate::handler::{CmdHandler, Session};
+use sonettobuf::{BeginRoundOper, CmdId, MoveCardReply, MoveCardRequest};
+
+pub struct MoveCard;
+
+impl CmdHandler for MoveCard {
+    const CMD: CmdId = CmdId::MoveCardCmd;
+    type Request = MoveCardRequest;
+    type Reply = MoveCardReply;
+
+    /// The move is queued as the round's first operations, so it costs an
+    /// action point, merges and earns power exactly like a move sent with
+    /// BeginRound, and replays see it
+    async fn handle(
+        session: &mut Session,
+        request: MoveCardRequest,
+    ) -> Result<MoveCardReply, AppError> {
+        let from = request.from_position.unwrap_or(0);
+        let to = request.to_position.unwrap_or(0);
+
+        let mut ctx_guard = session.lock().await;
+        let battle = ctx_guard
+            .active_battle
+            .as_mut()
+            .ok_or(AppError::InvalidRequest)?;
+
+        let hand = battle.current_deck.len() as i32;
+        if !(1..=hand).contains(&from) || !(1..=hand).contains(&to) {
+            tracing::warn!(
+                "MoveCard {} -> {} outside the hand ({} cards)",
+                from,
+                to,
+                hand
+            );
+            return Err(AppError::InvalidRequest);
+        }
+
+        battle.move_opers.push(BeginRoundOper {
+            oper_type: Some(2), // move card
+            param1: Some(from),
+            param2: Some(to),
+            to_id: None,
+            param3: None,
+        });
+
+        tracing::info!(
+            "MoveCard {} -> {} queued, {} moves this round",
+            from,
+            to,
+            battle.move_opers.len()
+        );
+
+        Ok(MoveCardReply {})
+    }
+}
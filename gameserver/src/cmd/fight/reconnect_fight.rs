@@ -1,17 +1,18 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use sonettobuf::CmdId;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::handler::{CmdHandler, Session};
+use sonettobuf::{CmdId, ReconnectFightReply, ReconnectFightRequest};
 
-pub async fn on_reconnect_fight(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_empty_reply(CmdId::ReconnectFightCmd, Vec::new(), 0, req.up_tag)
-        .await?;
-    Ok(())
+pub struct ReconnectFight;
+
+impl CmdHandler for ReconnectFight {
+    const CMD: CmdId = CmdId::ReconnectFightCmd;
+    type Request = ReconnectFightRequest;
+    type Reply = ReconnectFightReply;
+
+    async fn handle(
+        _session: &mut Session,
+        _request: ReconnectFightRequest,
+    ) -> Result<ReconnectFightReply, AppError> {
+        Ok(ReconnectFightReply::default())
+    }
 }
@@ -0,0 +1,55 @@
+use crate::error::AppError;
+use crate::handler::{CmdHandler, Session};
+use crate::state::{BattleSimulator, ExcelData};
+use sonettobuf::{CmdId, UseClothSkillOperRecord, UseClothSkillReply, UseClothSkillRequest};
+
+pub struct UseClothSkill;
+
+impl CmdHandler for UseClothSkill {
+    const CMD: CmdId = CmdId::UseClothSkillCmd;
+    type Request = UseClothSkillRequest;
+    type Reply = UseClothSkillReply;
+
+    async fn handle(
+        session: &mut Session,
+        request: UseClothSkillRequest,
+    ) -> Result<UseClothSkillReply, AppError> {
+        let skill_id = request.skill_id.unwrap_or(0);
+
+        let mut ctx_guard = session.lock().await;
+        let battle = ctx_guard
+            .active_battle
+            .as_mut()
+            .ok_or(AppError::InvalidRequest)?;
+        let fight = battle.fight.clone().ok_or(AppError::InvalidRequest)?;
+
+        let mut simulator =
+            BattleSimulator::new(ExcelData, fight, battle.seed, battle.cloth.clone());
+        let round =
+            simulator.use_cloth_skill(skill_id, battle.current_deck.clone(), battle.act_point)?;
+
+        // A refused skill changes nothing and isn't worth replaying
+        if round.fight_step.is_empty() {
+            return Ok(UseClothSkillReply { round: Some(round) });
+        }
+
+        battle.fight = Some(simulator.fight().clone());
+        battle.current_deck = round.team_a_cards1.clone();
+        battle.cloth = simulator.cloth_state().clone();
+        battle.cloth_opers.push(UseClothSkillOperRecord {
+            skill_id: request.skill_id,
+            from_id: request.from_id,
+            to_id: request.to_id,
+            r#type: request.r#type,
+        });
+
+        tracing::info!(
+            "UseClothSkill {}: power {:?}, {} cards in hand",
+            skill_id,
+            round.power,
+            battle.current_deck.len()
+        );
+
+        Ok(UseClothSkillReply { round: Some(round) })
+    }
+}
@@ -1,34 +1,27 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::friends;
-use sonettobuf::{CmdId, LoadFriendInfosReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, LoadFriendInfosReply, LoadFriendInfosRequest};
 
-pub async fn on_load_friend_infos(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let (friend_ids, blacklist_ids) = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct LoadFriendInfos;
 
-        let friends = friends::get_friend_ids(&ctx_guard.state.db, player_id).await?;
-        let blacklist = friends::get_blacklist_ids(&ctx_guard.state.db, player_id).await?;
+impl CmdHandler for LoadFriendInfos {
+    const CMD: CmdId = CmdId::LoadFriendInfosCmd;
+    type Request = LoadFriendInfosRequest;
+    type Reply = LoadFriendInfosReply;
 
-        (friends, blacklist)
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: LoadFriendInfosRequest,
+    ) -> Result<LoadFriendInfosReply, AppError> {
+        let player_id = session.player_id()?;
 
-    let reply = LoadFriendInfosReply {
-        friend_ids,
-        black_list_ids: blacklist_ids,
-    };
+        let friend_ids = friends::get_friend_ids(session.db(), player_id).await?;
+        let blacklist_ids = friends::get_blacklist_ids(session.db(), player_id).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::LoadFriendInfosCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(LoadFriendInfosReply {
+            friend_ids,
+            black_list_ids: blacklist_ids,
+        })
+    }
 }
@@ -1,3 +1,3 @@
 mod load_friend_infos;
 
-pub use load_friend_infos::on_load_friend_infos;
+pub use load_friend_infos::LoadFriendInfos;
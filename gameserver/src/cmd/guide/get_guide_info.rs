@@ -1,30 +1,24 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::guides;
-use sonettobuf::{CmdId, GetGuideInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetGuideInfoReply, GetGuideInfoRequest};
 
-pub async fn on_get_guide_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let guide_progress = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetGuideInfo;
 
-        guides::get_all_guide_progress(&ctx_guard.state.db, player_id).await?
-    };
+impl CmdHandler for GetGuideInfo {
+    const CMD: CmdId = CmdId::GetGuideInfoCmd;
+    type Request = GetGuideInfoRequest;
+    type Reply = GetGuideInfoReply;
 
-    let reply = GetGuideInfoReply {
-        guide_infos: guide_progress.into_iter().map(Into::into).collect(),
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetGuideInfoRequest,
+    ) -> Result<GetGuideInfoReply, AppError> {
+        let guide_progress =
+            guides::get_all_guide_progress(session.db(), session.player_id()?).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetGuideInfoCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetGuideInfoReply {
+            guide_infos: guide_progress.into_iter().map(Into::into).collect(),
+        })
+    }
 }
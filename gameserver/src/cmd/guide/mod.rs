@@ -1,3 +1,3 @@
 mod get_guide_info;
 
-pub use get_guide_info::on_get_guide_info;
+pub use get_guide_info::GetGuideInfo;
@@ -1,21 +1,9 @@
-use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::send_reply;
-use crate::state::ConnectionContext;
-use sonettobuf::{CmdId, GetHandbookInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::static_reply;
+use sonettobuf::{CmdId, GetHandbookInfoReply, GetHandbookInfoRequest};
 
-pub async fn on_get_handbook_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    send_reply!(
-        ctx,
-        req.up_tag,
-        CmdId::GetHandbookInfoCmd,
-        GetHandbookInfoReply,
-        "handbook/handbook_info.json"
-    );
-    Ok(())
-}
+static_reply!(
+    GetHandbookInfo,
+    CmdId::GetHandbookInfoCmd,
+    GetHandbookInfoRequest => GetHandbookInfoReply,
+    "handbook/handbook_info.json"
+);
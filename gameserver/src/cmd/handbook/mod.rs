@@ -1,3 +1,3 @@
 mod get_handbook_info;
 
-pub use get_handbook_info::on_get_handbook_info;
+pub use get_handbook_info::GetHandbookInfo;
@@ -1,36 +1,29 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::heroes;
-use prost::Message;
 use sonettobuf::{ChoiceHero3123WeaponReply, ChoiceHero3123WeaponRequest, CmdId, HeroUpdatePush};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_choice_hero_3123_weapon(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = ChoiceHero3123WeaponRequest::decode(&req.data[..])?;
+pub struct ChoiceHero3123Weapon;
 
-    tracing::info!("Received ChoiceHero3123WeaponRequest: {:?}", request);
+impl CmdHandler for ChoiceHero3123Weapon {
+    const CMD: CmdId = CmdId::ChoiceHero3123WeaponCmd;
+    type Request = ChoiceHero3123WeaponRequest;
+    type Reply = ChoiceHero3123WeaponReply;
 
-    let hero_id = request.hero_id.ok_or(AppError::InvalidRequest)?;
-    let main_id = request.main_id.ok_or(AppError::InvalidRequest)?;
-    let sub_id = request.sub_id.ok_or(AppError::InvalidRequest)?;
+    async fn handle(
+        session: &mut Session,
+        request: ChoiceHero3123WeaponRequest,
+    ) -> Result<ChoiceHero3123WeaponReply, AppError> {
+        tracing::info!("Received ChoiceHero3123WeaponRequest: {:?}", request);
 
-    let special_equip = format!("{}#{}", main_id, sub_id);
+        let hero_id = request.hero_id.ok_or(AppError::InvalidRequest)?;
+        let main_id = request.main_id.ok_or(AppError::InvalidRequest)?;
+        let sub_id = request.sub_id.ok_or(AppError::InvalidRequest)?;
 
-    let data = ChoiceHero3123WeaponReply {
-        hero_id: Some(hero_id),
-        main_id: Some(main_id),
-        sub_id: Some(sub_id),
-    };
+        let special_equip = format!("{}#{}", main_id, sub_id);
 
-    let updated_hero = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
-        let pool = &ctx_guard.state.db;
+        let player_id = session.player_id()?;
+        let pool = session.db();
 
         // Get hero
         let mut hero = heroes::get_hero_by_hero_id(pool, player_id, hero_id).await?;
@@ -46,20 +39,15 @@ pub async fn on_choice_hero_3123_weapon(
             hero_id
         );
 
-        hero
-    };
-
-    {
-        let mut ctx_guard = ctx.lock().await;
-
         // Send hero update push so client refreshes the UI
-        let hero_proto: sonettobuf::HeroInfo = updated_hero.into();
-        let push = HeroUpdatePush {
-            hero_updates: vec![hero_proto],
-        };
-
-        ctx_guard
-            .send_push(CmdId::HeroHeroUpdatePushCmd, push)
+        let hero_proto: sonettobuf::HeroInfo = hero.into();
+        session
+            .send_push(
+                CmdId::HeroHeroUpdatePushCmd,
+                HeroUpdatePush {
+                    hero_updates: vec![hero_proto],
+                },
+            )
             .await?;
 
         tracing::info!(
@@ -69,10 +57,10 @@ pub async fn on_choice_hero_3123_weapon(
             sub_id
         );
 
-        ctx_guard
-            .send_reply(CmdId::ChoiceHero3123WeaponCmd, data, 0, req.up_tag)
-            .await?;
+        Ok(ChoiceHero3123WeaponReply {
+            hero_id: Some(hero_id),
+            main_id: Some(main_id),
+            sub_id: Some(sub_id),
+        })
     }
-
-    Ok(())
 }
@@ -1,27 +1,20 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use prost::Message;
+use crate::handler::{CmdHandler, Session};
 use sonettobuf::{CmdId, GetHeroBirthdayReply, GetHeroBirthdayRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_get_hero_birthday(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = GetHeroBirthdayRequest::decode(&req.data[..])?;
+pub struct GetHeroBirthday;
 
-    let data = GetHeroBirthdayReply {
-        hero_id: Some(request.hero_id.unwrap_or(3080)),
-    };
+impl CmdHandler for GetHeroBirthday {
+    const CMD: CmdId = CmdId::GetHeroBirthdayCmd;
+    type Request = GetHeroBirthdayRequest;
+    type Reply = GetHeroBirthdayReply;
 
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard
-            .send_reply(CmdId::GetHeroBirthdayCmd, data, 0, req.up_tag)
-            .await?;
+    async fn handle(
+        _session: &mut Session,
+        request: GetHeroBirthdayRequest,
+    ) -> Result<GetHeroBirthdayReply, AppError> {
+        Ok(GetHeroBirthdayReply {
+            hero_id: Some(request.hero_id.unwrap_or(3080)),
+        })
     }
-
-    Ok(())
 }
@@ -1,26 +1,26 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::heroes;
-use prost::Message;
 use sonettobuf::{CmdId, HeroDefaultEquipReply, HeroDefaultEquipRequest, HeroUpdatePush};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_hero_default_equip(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = HeroDefaultEquipRequest::decode(&req.data[..])?;
-    tracing::info!("Received HeroDefaultEquipRequest: {:?}", request);
+pub struct HeroDefaultEquip;
 
-    let hero_id = request.hero_id.ok_or(AppError::InvalidRequest)?;
-    let equip_uid = request.default_equip_uid.ok_or(AppError::InvalidRequest)?;
+impl CmdHandler for HeroDefaultEquip {
+    const CMD: CmdId = CmdId::HeroDefaultEquipCmd;
+    type Request = HeroDefaultEquipRequest;
+    type Reply = HeroDefaultEquipReply;
 
-    let updated_hero = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
-        let pool = &ctx_guard.state.db;
+    async fn handle(
+        session: &mut Session,
+        request: HeroDefaultEquipRequest,
+    ) -> Result<HeroDefaultEquipReply, AppError> {
+        tracing::info!("Received HeroDefaultEquipRequest: {:?}", request);
+
+        let hero_id = request.hero_id.ok_or(AppError::InvalidRequest)?;
+        let equip_uid = request.default_equip_uid.ok_or(AppError::InvalidRequest)?;
+
+        let player_id = session.player_id()?;
+        let pool = session.db();
 
         // Get hero
         let mut hero = heroes::get_hero_by_hero_id(pool, player_id, hero_id).await?;
@@ -34,26 +34,16 @@ pub async fn on_hero_default_equip(
             equip_uid,
             hero_id
         );
-        hero
-    };
-
-    // Send main reply
-    let data = HeroDefaultEquipReply {
-        hero_id: Some(hero_id),
-        default_equip_uid: Some(equip_uid),
-    };
-
-    {
-        let mut ctx_guard = ctx.lock().await;
 
         // Send hero update push so client refreshes the UI
-        let hero_proto: sonettobuf::HeroInfo = updated_hero.into();
-        let push = HeroUpdatePush {
-            hero_updates: vec![hero_proto],
-        };
-
-        ctx_guard
-            .send_push(CmdId::HeroHeroUpdatePushCmd, push)
+        let hero_proto: sonettobuf::HeroInfo = hero.into();
+        session
+            .send_push(
+                CmdId::HeroHeroUpdatePushCmd,
+                HeroUpdatePush {
+                    hero_updates: vec![hero_proto],
+                },
+            )
             .await?;
 
         tracing::info!(
@@ -62,10 +52,9 @@ pub async fn on_hero_default_equip(
             equip_uid
         );
 
-        ctx_guard
-            .send_reply(CmdId::HeroDefaultEquipCmd, data, 0, req.up_tag)
-            .await?;
+        Ok(HeroDefaultEquipReply {
+            hero_id: Some(hero_id),
+            default_equip_uid: Some(equip_uid),
+        })
     }
-
-    Ok(())
 }
@@ -1,30 +1,23 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use prost::Message;
+use crate::handler::{CmdHandler, Session};
 use sonettobuf::{CmdId, HeroRedDotReadReply, HeroRedDotReadRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_hero_red_dot_read(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = HeroRedDotReadRequest::decode(&req.data[..])?;
+pub struct HeroRedDotRead;
 
-    tracing::info!("Received HeroRedDotReadRequest: {:?}", request);
+impl CmdHandler for HeroRedDotRead {
+    const CMD: CmdId = CmdId::HeroRedDotReadCmd;
+    type Request = HeroRedDotReadRequest;
+    type Reply = HeroRedDotReadReply;
 
-    let data = HeroRedDotReadReply {
-        hero_id: Some(request.hero_id.unwrap_or(3080)),
-        red_dot: Some(6),
-    };
+    async fn handle(
+        _session: &mut Session,
+        request: HeroRedDotReadRequest,
+    ) -> Result<HeroRedDotReadReply, AppError> {
+        tracing::info!("Received HeroRedDotReadRequest: {:?}", request);
 
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard
-            .send_reply(CmdId::HeroRedDotReadCmd, data, 0, req.up_tag)
-            .await?;
+        Ok(HeroRedDotReadReply {
+            hero_id: Some(request.hero_id.unwrap_or(3080)),
+            red_dot: Some(6),
+        })
     }
-
-    Ok(())
 }
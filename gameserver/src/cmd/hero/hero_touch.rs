@@ -1,26 +1,24 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::heroes;
-use prost::Message;
 use sonettobuf::{CmdId, HeroTouchReply, HeroTouchRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_hero_touch(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = HeroTouchRequest::decode(&req.data[..])?;
-    let hero_id = request.hero_id.ok_or(AppError::InvalidRequest)?;
+pub struct HeroTouch;
 
-    let (success, touch_count_left) = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
-        let pool = &ctx_guard.state.db;
+impl CmdHandler for HeroTouch {
+    const CMD: CmdId = CmdId::HeroTouchCmd;
+    type Request = HeroTouchRequest;
+    type Reply = HeroTouchReply;
+
+    async fn handle(
+        session: &mut Session,
+        request: HeroTouchRequest,
+    ) -> Result<HeroTouchReply, AppError> {
+        let hero_id = request.hero_id.ok_or(AppError::InvalidRequest)?;
+        let player_id = session.player_id()?;
 
         // Try to use a touch
-        match heroes::use_touch(pool, player_id).await? {
+        let (success, touch_count_left) = match heroes::use_touch(session.db(), player_id).await? {
             Some(new_count) => {
                 tracing::info!(
                     "User {} touched hero {}, {} touches remaining",
@@ -38,20 +36,11 @@ pub async fn on_hero_touch(
                 );
                 (false, 0)
             }
-        }
-    };
-
-    let data = HeroTouchReply {
-        touch_count_left: Some(touch_count_left),
-        success: Some(success),
-    };
+        };
 
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard
-            .send_reply(CmdId::HeroTouchCmd, data, 0, req.up_tag)
-            .await?;
+        Ok(HeroTouchReply {
+            touch_count_left: Some(touch_count_left),
+            success: Some(success),
+        })
     }
-
-    Ok(())
 }
@@ -1,26 +1,26 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::heroes;
-use prost::Message;
 use sonettobuf::{CmdId, HeroUpdatePush, MarkHeroFavorReply, MarkHeroFavorRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_mark_hero_favor(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = MarkHeroFavorRequest::decode(&req.data[..])?;
-    tracing::info!("Received MarkHeroFavorRequest: {:?}", request);
+pub struct MarkHeroFavor;
 
-    let hero_id = request.hero_id.ok_or(AppError::InvalidRequest)?;
-    let is_favor = request.is_favor.ok_or(AppError::InvalidRequest)?;
+impl CmdHandler for MarkHeroFavor {
+    const CMD: CmdId = CmdId::MarkHeroFavorCmd;
+    type Request = MarkHeroFavorRequest;
+    type Reply = MarkHeroFavorReply;
 
-    let updated_hero = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
-        let pool = &ctx_guard.state.db;
+    async fn handle(
+        session: &mut Session,
+        request: MarkHeroFavorRequest,
+    ) -> Result<MarkHeroFavorReply, AppError> {
+        tracing::info!("Received MarkHeroFavorRequest: {:?}", request);
+
+        let hero_id = request.hero_id.ok_or(AppError::InvalidRequest)?;
+        let is_favor = request.is_favor.ok_or(AppError::InvalidRequest)?;
+
+        let player_id = session.player_id()?;
+        let pool = session.db();
 
         // Get hero
         let mut hero = heroes::get_hero_by_hero_id(pool, player_id, hero_id).await?;
@@ -42,26 +42,16 @@ pub async fn on_mark_hero_favor(
             hero_id,
             is_favor
         );
-        hero
-    };
-
-    // Send main reply
-    let data = MarkHeroFavorReply {
-        hero_id: Some(hero_id),
-        is_favor: Some(is_favor),
-    };
-
-    {
-        let mut ctx_guard = ctx.lock().await;
 
         // Send hero update push so client refreshes the UI
-        let hero_proto: sonettobuf::HeroInfo = updated_hero.into();
-        let push = HeroUpdatePush {
-            hero_updates: vec![hero_proto],
-        };
-
-        ctx_guard
-            .send_push(CmdId::HeroHeroUpdatePushCmd, push)
+        let hero_proto: sonettobuf::HeroInfo = hero.into();
+        session
+            .send_push(
+                CmdId::HeroHeroUpdatePushCmd,
+                HeroUpdatePush {
+                    hero_updates: vec![hero_proto],
+                },
+            )
             .await?;
 
         tracing::info!(
@@ -70,10 +60,9 @@ pub async fn on_mark_hero_favor(
             is_favor
         );
 
-        ctx_guard
-            .send_reply(CmdId::MarkHeroFavorCmd, data, 0, req.up_tag)
-            .await?;
+        Ok(MarkHeroFavorReply {
+            hero_id: Some(hero_id),
+            is_favor: Some(is_favor),
+        })
     }
-
-    Ok(())
 }
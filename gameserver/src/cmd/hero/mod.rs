@@ -7,11 +7,11 @@ mod hero_touch;
 mod mark_hero_favor;
 mod set_show_hero_unique_ids;
 
-pub use choice_hero_3123_weapon::on_choice_hero_3123_weapon;
-pub use get_hero_birthday::on_get_hero_birthday;
-pub use hero_default_equip::on_hero_default_equip;
-pub use hero_info_list::on_hero_info_list;
-pub use hero_red_dot_read::on_hero_red_dot_read;
-pub use hero_touch::on_hero_touch;
-pub use mark_hero_favor::on_mark_hero_favor;
-pub use set_show_hero_unique_ids::on_set_show_hero_unique_ids;
+pub use choice_hero_3123_weapon::ChoiceHero3123Weapon;
+pub use get_hero_birthday::GetHeroBirthday;
+pub use hero_default_equip::HeroDefaultEquip;
+pub use hero_info_list::HeroInfoList;
+pub use hero_red_dot_read::HeroRedDotRead;
+pub use hero_touch::HeroTouch;
+pub use mark_hero_favor::MarkHeroFavor;
+pub use set_show_hero_unique_ids::SetShowHeroUniqueIds;
@@ -1,33 +1,28 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use sonettobuf::{CmdId, GetHeroGroupListReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::handler::{CmdHandler, Session};
+use database::db::game::hero_groups;
+use sonettobuf::{CmdId, GetHeroGroupListReply, GetHeroGroupListRequest};
 
-pub async fn on_get_hero_group_list(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let group_info = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetHeroGroupList;
 
-        database::db::game::hero_groups::get_current_hero_group(&ctx_guard.state.db, player_id)
-            .await?
-    };
+impl CmdHandler for GetHeroGroupList {
+    const CMD: CmdId = CmdId::GetHeroGroupListCmd;
+    type Request = GetHeroGroupListRequest;
+    type Reply = GetHeroGroupListReply;
 
-    let reply = GetHeroGroupListReply {
-        group_info_list: if let Some(info) = group_info {
-            vec![info.into()]
-        } else {
-            vec![]
-        },
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetHeroGroupListRequest,
+    ) -> Result<GetHeroGroupListReply, AppError> {
+        let group_info =
+            hero_groups::get_current_hero_group(session.db(), session.player_id()?).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetHeroGroupListCmd, reply, 0, req.up_tag)
-        .await?;
-    Ok(())
+        Ok(GetHeroGroupListReply {
+            group_info_list: if let Some(info) = group_info {
+                vec![info.into()]
+            } else {
+                vec![]
+            },
+        })
+    }
 }
@@ -1,56 +1,44 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::hero_group_snapshots;
-use prost::Message;
 use sonettobuf::{CmdId, GetHeroGroupSnapshotListReply, GetHeroGroupSnapshotListRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_get_hero_group_snapshot_list(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let req_msg = GetHeroGroupSnapshotListRequest::decode(&req.data[..])?;
+pub struct GetHeroGroupSnapshotList;
 
-    tracing::info!("Received GetHeroGroupSnapshotListRequest: {:?}", req_msg);
+impl CmdHandler for GetHeroGroupSnapshotList {
+    const CMD: CmdId = CmdId::GetHeroGroupSnapshotListCmd;
+    type Request = GetHeroGroupSnapshotListRequest;
+    type Reply = GetHeroGroupSnapshotListReply;
 
-    let snapshots = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+    async fn handle(
+        session: &mut Session,
+        request: GetHeroGroupSnapshotListRequest,
+    ) -> Result<GetHeroGroupSnapshotListReply, AppError> {
+        tracing::info!("Received GetHeroGroupSnapshotListRequest: {:?}", request);
 
-        let snapshot_id = req_msg.snapshot_id.unwrap_or(0);
+        let player_id = session.player_id()?;
+        let snapshot_id = request.snapshot_id.unwrap_or(0);
 
-        if snapshot_id == 0 {
+        let snapshots = if snapshot_id == 0 {
             // 0 means "get ALL snapshots"
-            hero_group_snapshots::get_hero_group_snapshots(&ctx_guard.state.db, player_id).await?
+            hero_group_snapshots::get_hero_group_snapshots(session.db(), player_id).await?
         } else {
             // Get specific snapshot
-            let snapshot = hero_group_snapshots::get_hero_group_snapshot(
-                &ctx_guard.state.db,
-                player_id,
-                snapshot_id,
-            )
-            .await?;
+            let snapshot =
+                hero_group_snapshots::get_hero_group_snapshot(session.db(), player_id, snapshot_id)
+                    .await?;
 
             if let Some(s) = snapshot {
                 vec![s]
             } else {
                 vec![]
             }
-        }
-    };
+        };
 
-    tracing::info!("Returning {} snapshot(s)", snapshots.len());
+        tracing::info!("Returning {} snapshot(s)", snapshots.len());
 
-    let reply = GetHeroGroupSnapshotListReply {
-        hero_group_snapshots: snapshots.into_iter().map(Into::into).collect(),
-    };
-
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetHeroGroupSnapshotListCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetHeroGroupSnapshotListReply {
+            hero_group_snapshots: snapshots.into_iter().map(Into::into).collect(),
+        })
+    }
 }
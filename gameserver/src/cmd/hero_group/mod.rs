@@ -4,8 +4,8 @@ mod get_hero_group_snapshot_list;
 mod set_hero_group_equip;
 mod set_hero_group_snapshot;
 
-pub use get_hero_group_common_list::on_get_hero_group_common_list;
-pub use get_hero_group_list::on_get_hero_group_list;
-pub use get_hero_group_snapshot_list::on_get_hero_group_snapshot_list;
-pub use set_hero_group_equip::on_set_hero_group_equip;
-pub use set_hero_group_snapshot::on_set_hero_group_snapshot;
+pub use get_hero_group_common_list::GetHeroGroupCommonList;
+pub use get_hero_group_list::GetHeroGroupList;
+pub use get_hero_group_snapshot_list::GetHeroGroupSnapshotList;
+pub use set_hero_group_equip::SetHeroGroupEquip;
+pub use set_hero_group_snapshot::SetHeroGroupSnapshot;
@@ -1,106 +1,97 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::{
     db::game::hero_group_snapshots::{self, sync_snapshot_to_common},
     models::game::hero_groups,
 };
-use prost::Message;
 use sonettobuf::{CmdId, SetHeroGroupSnapshotReply, SetHeroGroupSnapshotRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_set_hero_group_snapshot(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = SetHeroGroupSnapshotRequest::decode(&req.data[..])?;
-    tracing::info!("Received SetHeroGroupSnapshotRequest: {:?}", request);
+pub struct SetHeroGroupSnapshot;
 
-    let snapshot_id = request.snapshot_id.ok_or(AppError::InvalidRequest)?;
-    let snapshot_sub_id = request.snapshot_sub_id.unwrap_or(0);
-    let fight_group = request.fight_group.ok_or(AppError::InvalidRequest)?;
+impl CmdHandler for SetHeroGroupSnapshot {
+    const CMD: CmdId = CmdId::SetHeroGroupSnapshotCmd;
+    type Request = SetHeroGroupSnapshotRequest;
+    type Reply = SetHeroGroupSnapshotReply;
 
-    let (player_id, pool) = {
-        let ctx_guard = ctx.lock().await;
-        (
-            ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?,
-            ctx_guard.state.db.clone(),
-        )
-    };
+    async fn handle(
+        session: &mut Session,
+        request: SetHeroGroupSnapshotRequest,
+    ) -> Result<SetHeroGroupSnapshotReply, AppError> {
+        tracing::info!("Received SetHeroGroupSnapshotRequest: {:?}", request);
 
-    let hero_group = hero_groups::HeroGroupInfo {
-        group_id: snapshot_sub_id,
-        hero_list: {
-            let mut heroes: Vec<i64> = fight_group
-                .hero_list
-                .into_iter()
-                .filter(|&uid| uid != 0)
-                .collect();
-            heroes.extend(
-                fight_group
-                    .sub_hero_list
-                    .into_iter()
-                    .filter(|&uid| uid != 0),
-            );
-            heroes
-        },
-        name: String::new(),
-        cloth_id: fight_group.cloth_id.unwrap_or(1),
-        equips: fight_group
-            .equips
-            .into_iter()
-            .enumerate()
-            .filter(|(_, e)| e.hero_uid.unwrap_or(0) != 0)
-            .map(|(index, e)| hero_groups::HeroGroupEquip {
-                index: index as i32,
-                equip_uids: e.equip_uid.into_iter().filter(|&uid| uid != 0).collect(),
-            })
-            .collect(),
-        activity104_equips: fight_group
-            .activity104_equips
-            .into_iter()
-            .enumerate()
-            .filter(|(_, e)| {
-                let uid = e.hero_uid.unwrap_or(0);
-                uid != 0
-            })
-            .map(|(index, e)| hero_groups::HeroGroupEquip {
-                index: index as i32,
-                equip_uids: e.equip_uid.into_iter().filter(|&uid| uid != 0).collect(),
-            })
-            .collect(),
-        assist_boss_id: fight_group.assist_boss_id.unwrap_or(0),
-    };
+        let snapshot_id = request.snapshot_id.ok_or(AppError::InvalidRequest)?;
+        let snapshot_sub_id = request.snapshot_sub_id.unwrap_or(0);
+        let fight_group = request.fight_group.ok_or(AppError::InvalidRequest)?;
 
-    hero_group_snapshots::save_hero_group_snapshot(
-        &pool,
-        player_id,
-        snapshot_id,
-        vec![hero_group.clone()],
-        vec![snapshot_sub_id],
-    )
-    .await?;
+        let player_id = session.player_id()?;
+        let pool = session.db();
 
-    tracing::info!(
-        "Saved hero group snapshot {} (sub {}) for user {}",
-        snapshot_id,
-        snapshot_sub_id,
-        player_id
-    );
+        let hero_group = hero_groups::HeroGroupInfo {
+            group_id: snapshot_sub_id,
+            hero_list: {
+                let mut heroes: Vec<i64> = fight_group
+                    .hero_list
+                    .into_iter()
+                    .filter(|&uid| uid != 0)
+                    .collect();
+                heroes.extend(
+                    fight_group
+                        .sub_hero_list
+                        .into_iter()
+                        .filter(|&uid| uid != 0),
+                );
+                heroes
+            },
+            name: String::new(),
+            cloth_id: fight_group.cloth_id.unwrap_or(1),
+            equips: fight_group
+                .equips
+                .into_iter()
+                .enumerate()
+                .filter(|(_, e)| e.hero_uid.unwrap_or(0) != 0)
+                .map(|(index, e)| hero_groups::HeroGroupEquip {
+                    index: index as i32,
+                    equip_uids: e.equip_uid.into_iter().filter(|&uid| uid != 0).collect(),
+                })
+                .collect(),
+            activity104_equips: fight_group
+                .activity104_equips
+                .into_iter()
+                .enumerate()
+                .filter(|(_, e)| {
+                    let uid = e.hero_uid.unwrap_or(0);
+                    uid != 0
+                })
+                .map(|(index, e)| hero_groups::HeroGroupEquip {
+                    index: index as i32,
+                    equip_uids: e.equip_uid.into_iter().filter(|&uid| uid != 0).collect(),
+                })
+                .collect(),
+            assist_boss_id: fight_group.assist_boss_id.unwrap_or(0),
+        };
 
-    sync_snapshot_to_common(&pool, player_id, &hero_group).await?;
+        hero_group_snapshots::save_hero_group_snapshot(
+            pool,
+            player_id,
+            snapshot_id,
+            vec![hero_group.clone()],
+            vec![snapshot_sub_id],
+        )
+        .await?;
 
-    let data = SetHeroGroupSnapshotReply {
-        snapshot_id: Some(snapshot_id),
-        snapshot_sub_id: Some(snapshot_sub_id),
-        group_info: Some(hero_group.into()),
-    };
+        tracing::info!(
+            "Saved hero group snapshot {} (sub {}) for user {}",
+            snapshot_id,
+            snapshot_sub_id,
+            player_id
+        );
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::SetHeroGroupSnapshotCmd, data, 0, req.up_tag)
-        .await?;
+        sync_snapshot_to_common(pool, player_id, &hero_group).await?;
 
-    Ok(())
+        Ok(SetHeroGroupSnapshotReply {
+            snapshot_id: Some(snapshot_id),
+            snapshot_sub_id: Some(snapshot_sub_id),
+            group_info: Some(hero_group.into()),
+        })
+    }
 }
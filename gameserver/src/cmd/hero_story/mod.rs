@@ -1,3 +1,3 @@
 mod get_hero_story;
 
-pub use get_hero_story::on_get_hero_story;
+pub use get_hero_story::GetHeroStory;
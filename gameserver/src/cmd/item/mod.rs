@@ -1,5 +1,5 @@
 mod auto_use_expire_power_item;
 mod get_item_list;
 
-pub use auto_use_expire_power_item::on_auto_use_expire_power_item;
-pub use get_item_list::on_get_item_list;
+pub use auto_use_expire_power_item::AutoUseExpirePowerItem;
+pub use get_item_list::GetItemList;
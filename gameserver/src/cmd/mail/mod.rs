@@ -1,5 +1,5 @@
 mod get_all_mails;
 mod read_mail_batch;
 
-pub use get_all_mails::on_get_all_mails;
-pub use read_mail_batch::on_read_mail_batch;
+pub use get_all_mails::GetAllMails;
+pub use read_mail_batch::ReadMailBatch;
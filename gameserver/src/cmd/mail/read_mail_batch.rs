@@ -1,31 +1,24 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use prost::Message;
+use crate::handler::{CmdHandler, Session};
 use sonettobuf::{CmdId, ReadMailBatchReply, ReadMailBatchRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_read_mail_batch(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = ReadMailBatchRequest::decode(&req.data[..])?;
+pub struct ReadMailBatch;
 
-    let r#type = request.r#type.ok_or(AppError::InvalidRequest)?;
+impl CmdHandler for ReadMailBatch {
+    const CMD: CmdId = CmdId::ReadMailBatchCmd;
+    type Request = ReadMailBatchRequest;
+    type Reply = ReadMailBatchReply;
 
-    tracing::info!("Received ReadMailBatchRequest type {}", r#type);
+    async fn handle(
+        _session: &mut Session,
+        request: ReadMailBatchRequest,
+    ) -> Result<ReadMailBatchReply, AppError> {
+        let r#type = request.r#type.ok_or(AppError::InvalidRequest)?;
 
-    {
-        let mut ctx_guard = ctx.lock().await;
+        tracing::info!("Received ReadMailBatchRequest type {}", r#type);
 
-        let reply = ReadMailBatchReply {
+        Ok(ReadMailBatchReply {
             incr_ids: vec![279048737],
-        };
-        ctx_guard
-            .send_reply(CmdId::ReadMailBatchCmd, reply, 0, req.up_tag)
-            .await?;
+        })
     }
-
-    Ok(())
 }
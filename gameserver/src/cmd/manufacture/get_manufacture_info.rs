@@ -1,21 +1,9 @@
-use crate::packet::ClientPacket;
-use crate::send_reply;
-use crate::{error::AppError, state::ConnectionContext};
-use sonettobuf::{CmdId, GetManufactureInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::static_reply;
+use sonettobuf::{CmdId, GetManufactureInfoReply, GetManufactureInfoRequest};
 
-pub async fn on_get_manufacture_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    send_reply!(
-        ctx,
-        req.up_tag,
-        CmdId::GetManufactureInfoCmd,
-        GetManufactureInfoReply,
-        "manufacture/manufacture_info.json"
-    );
-
-    Ok(())
-}
+static_reply!(
+    GetManufactureInfo,
+    CmdId::GetManufactureInfoCmd,
+    GetManufactureInfoRequest => GetManufactureInfoReply,
+    "manufacture/manufacture_info.json"
+);
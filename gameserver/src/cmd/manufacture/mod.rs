@@ -1,3 +1,3 @@
 mod get_manufacture_info;
 
-pub use get_manufacture_info::on_get_manufacture_info;
+pub use get_manufacture_info::GetManufactureInfo;
@@ -1,3 +1,3 @@
 mod get_necrologist_story;
 
-pub use get_necrologist_story::on_get_necrologist_story;
+pub use get_necrologist_story::GetNecrologistStory;
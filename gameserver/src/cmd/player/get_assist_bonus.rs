@@ -1,64 +1,58 @@
-use crate::packet::ClientPacket;
-use crate::{error::AppError, send_push};
-
-use crate::state::ConnectionContext;
+use crate::error::AppError;
+use crate::handler::{CmdHandler, Session};
+use crate::send_push;
 use sonettobuf::{
-    CmdId, CurrencyChangePush, GetAssistBonusReply, ItemChangePush, MaterialChangePush,
+    CmdId, CurrencyChangePush, GetAssistBonusReply, GetAssistBonusRequest, ItemChangePush,
+    MaterialChangePush,
 };
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_get_assist_bonus(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let data = GetAssistBonusReply {
-        assist_bonus: Some(0),
-        has_receive_assist_bonus: Some(0),
-    };
+pub struct GetAssistBonus;
 
-    let should_push = {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard.check_and_mark_state_pushes().await?
-    };
+impl CmdHandler for GetAssistBonus {
+    const CMD: CmdId = CmdId::GetAssistBonusCmd;
+    type Request = GetAssistBonusRequest;
+    type Reply = GetAssistBonusReply;
 
-    if should_push {
-        tracing::info!("Sending state pushes from GetAssistBonus");
-        send_push!(
-            ctx,
-            CmdId::CurrencyChangePushCmd,
-            CurrencyChangePush,
-            "currency/currency_push_1.json"
-        );
-        send_push!(
-            ctx,
-            CmdId::ItemChangePushCmd,
-            ItemChangePush,
-            "item/item_push_1.json"
-        );
-        send_push!(
-            ctx,
-            CmdId::CurrencyChangePushCmd,
-            CurrencyChangePush,
-            "currency/currency_push_2.json"
-        );
-        send_push!(
-            ctx,
-            CmdId::MaterialChangePushCmd,
-            MaterialChangePush,
-            "material/material_push_1.json"
-        );
-    } else {
-        tracing::warn!("No state pushes from GetAssistBonus");
-    }
+    async fn handle(
+        session: &mut Session,
+        _request: GetAssistBonusRequest,
+    ) -> Result<GetAssistBonusReply, AppError> {
+        let should_push = session.lock().await.check_and_mark_state_pushes().await?;
 
-    {
-        let mut ctx_guard = ctx.lock().await;
+        if should_push {
+            let ctx = session.context();
+            tracing::info!("Sending state pushes from GetAssistBonus");
+            send_push!(
+                ctx,
+                CmdId::CurrencyChangePushCmd,
+                CurrencyChangePush,
+                "currency/currency_push_1.json"
+            );
+            send_push!(
+                ctx,
+                CmdId::ItemChangePushCmd,
+                ItemChangePush,
+                "item/item_push_1.json"
+            );
+            send_push!(
+                ctx,
+                CmdId::CurrencyChangePushCmd,
+                CurrencyChangePush,
+                "currency/currency_push_2.json"
+            );
+            send_push!(
+                ctx,
+                CmdId::MaterialChangePushCmd,
+                MaterialChangePush,
+                "material/material_push_1.json"
+            );
+        } else {
+            tracing::warn!("No state pushes from GetAssistBonus");
+        }
 
-        ctx_guard
-            .send_reply(CmdId::GetAssistBonusCmd, data, 0, req.up_tag)
-            .await?;
+        Ok(GetAssistBonusReply {
+            assist_bonus: Some(0),
+            has_receive_assist_bonus: Some(0),
+        })
     }
-
-    Ok(())
 }
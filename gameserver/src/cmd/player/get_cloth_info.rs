@@ -1,21 +1,23 @@
-use crate::packet::ClientPacket;
-use crate::send_reply;
-use crate::{error::AppError, state::ConnectionContext};
-use sonettobuf::{CmdId, GetClothInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::error::AppError;
+use crate::handler::{CmdHandler, Session};
+use database::db::game::cloths::load_cloths;
+use sonettobuf::{CmdId, GetClothInfoReply, GetClothInfoRequest, PlayerClothInfo};
 
-pub async fn on_get_cloth_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    send_reply!(
-        ctx,
-        req.up_tag,
-        CmdId::GetClothInfoCmd,
-        GetClothInfoReply,
-        "player/cloth_info.json"
-    );
+pub struct GetClothInfo;
 
-    Ok(())
+impl CmdHandler for GetClothInfo {
+    const CMD: CmdId = CmdId::GetClothInfoCmd;
+    type Request = GetClothInfoRequest;
+    type Reply = GetClothInfoReply;
+
+    async fn handle(
+        session: &mut Session,
+        _request: GetClothInfoRequest,
+    ) -> Result<GetClothInfoReply, AppError> {
+        let clothes = load_cloths(session.db(), session.player_id()?).await?;
+
+        Ok(GetClothInfoReply {
+            cloth_infos: Some(PlayerClothInfo { clothes }),
+        })
+    }
 }
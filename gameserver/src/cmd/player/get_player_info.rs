@@ -1,47 +1,41 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use data::exceldb;
 use database::db::game::player_infos;
-use sonettobuf::{CmdId, GetPlayerInfoReply, OpenInfo};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetPlayerInfoReply, GetPlayerInfoRequest, OpenInfo};
 
-pub async fn on_get_player_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let player_info_data = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetPlayerInfo;
 
-        player_infos::get_player_info_data(&ctx_guard.state.db, player_id)
-            .await?
-            .ok_or_else(|| AppError::Custom("Player info not found".to_string()))?
-    };
+impl CmdHandler for GetPlayerInfo {
+    const CMD: CmdId = CmdId::GetPlayerInfoCmd;
+    type Request = GetPlayerInfoRequest;
+    type Reply = GetPlayerInfoReply;
 
-    let game_data = exceldb::get();
-    let openinfos: Vec<OpenInfo> = game_data
-        .open
-        .iter()
-        .map(|open| OpenInfo {
-            id: open.id,
-            is_open: true, // TODO: Check actual unlock conditions per player
-        })
-        .collect();
-
-    let reply = GetPlayerInfoReply {
-        player_info: Some(player_info_data.into()),
-        openinfos,
-        can_rename: Some(true),
-        main_thumbnail: Some(false),
-        ext_rename: Some(0),
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetPlayerInfoRequest,
+    ) -> Result<GetPlayerInfoReply, AppError> {
+        let player_info_data =
+            player_infos::get_player_info_data(session.db(), session.player_id()?)
+                .await?
+                .ok_or_else(|| AppError::Custom("Player info not found".to_string()))?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetPlayerInfoCmd, reply, 0, req.up_tag)
-        .await?;
+        let game_data = exceldb::get();
+        let openinfos: Vec<OpenInfo> = game_data
+            .open
+            .iter()
+            .map(|open| OpenInfo {
+                id: open.id,
+                is_open: true, // TODO: Check actual unlock conditions per player
+            })
+            .collect();
 
-    Ok(())
+        Ok(GetPlayerInfoReply {
+            player_info: Some(player_info_data.into()),
+            openinfos,
+            can_rename: Some(true),
+            main_thumbnail: Some(false),
+            ext_rename: Some(0),
+        })
+    }
 }
@@ -1,18 +1,18 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use sonettobuf::CmdId;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::handler::{CmdHandler, Session};
+use sonettobuf::{CmdId, MarkMainThumbnailReply, MarkMainThumbnailRequest};
 
-pub async fn on_mark_main_thumbnail(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_empty_reply(CmdId::MarkMainThumbnailCmd, Vec::new(), 0, req.up_tag)
-        .await?;
+pub struct MarkMainThumbnail;
 
-    Ok(())
+impl CmdHandler for MarkMainThumbnail {
+    const CMD: CmdId = CmdId::MarkMainThumbnailCmd;
+    type Request = MarkMainThumbnailRequest;
+    type Reply = MarkMainThumbnailReply;
+
+    async fn handle(
+        _session: &mut Session,
+        _request: MarkMainThumbnailRequest,
+    ) -> Result<MarkMainThumbnailReply, AppError> {
+        Ok(MarkMainThumbnailReply {})
+    }
 }
@@ -4,8 +4,8 @@ mod get_player_info;
 
 mod mark_main_thumbnail;
 
-pub use get_assist_bonus::on_get_assist_bonus;
-pub use get_cloth_info::on_get_cloth_info;
-pub use get_player_info::on_get_player_info;
+pub use get_assist_bonus::GetAssistBonus;
+pub use get_cloth_info::GetClothInfo;
+pub use get_player_info::GetPlayerInfo;
 
-pub use mark_main_thumbnail::on_mark_main_thumbnail;
+pub use mark_main_thumbnail::MarkMainThumbnail;
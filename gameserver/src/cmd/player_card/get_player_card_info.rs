@@ -1,30 +1,24 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::player_card;
-use sonettobuf::{CmdId, GetPlayerCardInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetPlayerCardInfoReply, GetPlayerCardInfoRequest};
 
-pub async fn on_get_player_card_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let card_info = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetPlayerCardInfo;
 
-        player_card::get_player_card_info(&ctx_guard.state.db, player_id).await?
-    };
+impl CmdHandler for GetPlayerCardInfo {
+    const CMD: CmdId = CmdId::GetPlayerCardInfoCmd;
+    type Request = GetPlayerCardInfoRequest;
+    type Reply = GetPlayerCardInfoReply;
 
-    let reply = GetPlayerCardInfoReply {
-        player_card_info: Some(card_info.into()),
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetPlayerCardInfoRequest,
+    ) -> Result<GetPlayerCardInfoReply, AppError> {
+        let card_info =
+            player_card::get_player_card_info(session.db(), session.player_id()?).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetPlayerCardInfoCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetPlayerCardInfoReply {
+            player_card_info: Some(card_info.into()),
+        })
+    }
 }
@@ -1,3 +1,3 @@
 mod get_player_card_info;
 
-pub use get_player_card_info::on_get_player_card_info;
+pub use get_player_card_info::GetPlayerCardInfo;
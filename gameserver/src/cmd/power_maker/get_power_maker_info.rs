@@ -1,21 +1,9 @@
-use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::send_reply;
-use crate::state::ConnectionContext;
-use sonettobuf::{CmdId, GetPowerMakerInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::static_reply;
+use sonettobuf::{CmdId, GetPowerMakerInfoReply, GetPowerMakerInfoRequest};
 
-pub async fn on_get_power_maker_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    send_reply!(
-        ctx,
-        req.up_tag,
-        CmdId::GetPowerMakerInfoCmd,
-        GetPowerMakerInfoReply,
-        "power_maker/power_maker_info.json"
-    );
-    Ok(())
-}
+static_reply!(
+    GetPowerMakerInfo,
+    CmdId::GetPowerMakerInfoCmd,
+    GetPowerMakerInfoRequest => GetPowerMakerInfoReply,
+    "power_maker/power_maker_info.json"
+);
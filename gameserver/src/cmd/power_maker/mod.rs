@@ -1,3 +1,3 @@
 mod get_power_maker_info;
 
-pub use get_power_maker_info::on_get_power_maker_info;
+pub use get_power_maker_info::GetPowerMakerInfo;
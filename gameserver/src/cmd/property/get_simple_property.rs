@@ -1,30 +1,24 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::simple_property;
-use sonettobuf::{CmdId, GetSimplePropertyReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetSimplePropertyReply, GetSimplePropertyRequest};
 
-pub async fn on_get_simple_property(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let properties = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetSimpleProperty;
 
-        simple_property::get_simple_properties(&ctx_guard.state.db, player_id).await?
-    };
+impl CmdHandler for GetSimpleProperty {
+    const CMD: CmdId = CmdId::GetSimplePropertyCmd;
+    type Request = GetSimplePropertyRequest;
+    type Reply = GetSimplePropertyReply;
 
-    let reply = GetSimplePropertyReply {
-        simple_properties: properties.into_iter().map(Into::into).collect(),
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetSimplePropertyRequest,
+    ) -> Result<GetSimplePropertyReply, AppError> {
+        let properties =
+            simple_property::get_simple_properties(session.db(), session.player_id()?).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetSimplePropertyCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetSimplePropertyReply {
+            simple_properties: properties.into_iter().map(Into::into).collect(),
+        })
+    }
 }
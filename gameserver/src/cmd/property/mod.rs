@@ -1,5 +1,5 @@
 mod get_simple_property;
 mod set_simple_property;
 
-pub use get_simple_property::on_get_simple_property;
-pub use set_simple_property::on_set_simple_property;
+pub use get_simple_property::GetSimpleProperty;
+pub use set_simple_property::SetSimpleProperty;
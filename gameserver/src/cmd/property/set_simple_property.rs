@@ -1,30 +1,29 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::simple_property;
-use prost::Message;
 use sonettobuf::{
     CmdId, SetSimplePropertyReply, SetSimplePropertyRequest, SimpleProperty, SimplePropertyPush,
 };
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_set_simple_property(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = SetSimplePropertyRequest::decode(&req.data[..])?;
-    tracing::info!("Received SetSimplePropertyRequest: {:?}", request);
+pub struct SetSimpleProperty;
 
-    let property_id = request.id.ok_or(AppError::InvalidRequest)?;
-    let property_value = request.property.ok_or(AppError::InvalidRequest)?;
+impl CmdHandler for SetSimpleProperty {
+    const CMD: CmdId = CmdId::SetSimplePropertyCmd;
+    type Request = SetSimplePropertyRequest;
+    type Reply = SetSimplePropertyReply;
 
-    {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+    async fn handle(
+        session: &mut Session,
+        request: SetSimplePropertyRequest,
+    ) -> Result<SetSimplePropertyReply, AppError> {
+        tracing::info!("Received SetSimplePropertyRequest: {:?}", request);
+
+        let property_id = request.id.ok_or(AppError::InvalidRequest)?;
+        let property_value = request.property.ok_or(AppError::InvalidRequest)?;
+        let player_id = session.player_id()?;
 
         simple_property::set_simple_property(
-            &ctx_guard.state.db,
+            session.db(),
             player_id,
             property_id,
             property_value.clone(),
@@ -37,12 +36,6 @@ pub async fn on_set_simple_property(
             property_id,
             property_value
         );
-    }
-
-    let reply = SetSimplePropertyReply {};
-
-    {
-        let mut ctx_guard = ctx.lock().await;
 
         // Send push notification for property update
         let push = SimplePropertyPush {
@@ -51,15 +44,10 @@ pub async fn on_set_simple_property(
                 property: Some(property_value),
             }),
         };
-
-        ctx_guard
+        session
             .send_push(CmdId::SimplePropertyPushCmd, push)
             .await?;
 
-        ctx_guard
-            .send_reply(CmdId::SetSimplePropertyCmd, reply, 0, req.up_tag)
-            .await?;
+        Ok(SetSimplePropertyReply {})
     }
-
-    Ok(())
 }
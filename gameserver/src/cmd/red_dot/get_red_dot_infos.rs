@@ -1,70 +1,66 @@
 #![allow(unused_imports)]
-use crate::packet::ClientPacket;
+use crate::handler::{CmdHandler, Session};
 use crate::send_push;
-use crate::state::ConnectionContext;
 use crate::{error::AppError, utils::data_loader::GameDataLoader};
-use prost::Message;
 use sonettobuf::{CmdId, GetRedDotInfosReply, GetRedDotInfosRequest, SimplePropertyPush};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_get_red_dot_infos(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = GetRedDotInfosRequest::decode(&req.data[..])?;
-    let mut define_ids = request.ids;
-    tracing::info!("Requested define_ids: {:?}", define_ids);
+pub struct GetRedDotInfos;
 
-    // If ID 13 is requested, expand to include related IDs
-    const EXPANSION_IDS: [i32; 3] = [1042, 1013, 1902];
-    if define_ids.contains(&13) {
-        tracing::info!("ID 13 detected, expanding to include: {:?}", EXPANSION_IDS);
-        for id in EXPANSION_IDS {
-            if !define_ids.contains(&id) {
-                define_ids.push(id);
+impl CmdHandler for GetRedDotInfos {
+    const CMD: CmdId = CmdId::GetRedDotInfosCmd;
+    type Request = GetRedDotInfosRequest;
+    type Reply = GetRedDotInfosReply;
+
+    async fn handle(
+        _session: &mut Session,
+        request: GetRedDotInfosRequest,
+    ) -> Result<GetRedDotInfosReply, AppError> {
+        let mut define_ids = request.ids;
+        tracing::info!("Requested define_ids: {:?}", define_ids);
+
+        // If ID 13 is requested, expand to include related IDs
+        const EXPANSION_IDS: [i32; 3] = [1042, 1013, 1902];
+        if define_ids.contains(&13) {
+            tracing::info!("ID 13 detected, expanding to include: {:?}", EXPANSION_IDS);
+            for id in EXPANSION_IDS {
+                if !define_ids.contains(&id) {
+                    define_ids.push(id);
+                }
             }
+            tracing::info!("Expanded define_ids: {:?}", define_ids);
         }
-        tracing::info!("Expanded define_ids: {:?}", define_ids);
-    }
 
-    let response: GetRedDotInfosReply =
-        GameDataLoader::load_struct("red_dot/red_dot_infos.json")
-            .map_err(|e| AppError::Custom(format!("Failed to load: {}", e)))?;
+        let response: GetRedDotInfosReply =
+            GameDataLoader::load_struct("red_dot/red_dot_infos.json")
+                .map_err(|e| AppError::Custom(format!("Failed to load: {}", e)))?;
 
-    let reply = if define_ids.is_empty() {
-        response
-    } else {
-        GetRedDotInfosReply {
-            red_dot_infos: response
-                .red_dot_infos
-                .into_iter()
-                .filter(|info| define_ids.contains(&info.define_id))
-                .collect(),
-        }
-    };
+        let reply = if define_ids.is_empty() {
+            response
+        } else {
+            GetRedDotInfosReply {
+                red_dot_infos: response
+                    .red_dot_infos
+                    .into_iter()
+                    .filter(|info| define_ids.contains(&info.define_id))
+                    .collect(),
+            }
+        };
 
-    tracing::info!("Returning {} red dot infos", reply.red_dot_infos.len());
+        tracing::info!("Returning {} red dot infos", reply.red_dot_infos.len());
 
-    /*const TRIGGER_IDS: [i32; 3] = [1042, 1013, 1902];
-    let should_send_push = TRIGGER_IDS.iter().all(|id| define_ids.contains(id));
+        /*const TRIGGER_IDS: [i32; 3] = [1042, 1013, 1902];
+        let should_send_push = TRIGGER_IDS.iter().all(|id| define_ids.contains(id));
 
-    if should_send_push {
-        tracing::info!("All trigger IDs detected, sending property push");
-        send_push!(
-            ctx,
-            CmdId::SimplePropertyPushCmd,
-            SimplePropertyPush,
-            "property/property_push_1.json"
-        );
-    }*/
+        if should_send_push {
+            tracing::info!("All trigger IDs detected, sending property push");
+            send_push!(
+                session.context(),
+                CmdId::SimplePropertyPushCmd,
+                SimplePropertyPush,
+                "property/property_push_1.json"
+            );
+        }*/
 
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard
-            .send_reply(CmdId::GetRedDotInfosCmd, reply, 0, req.up_tag)
-            .await?;
+        Ok(reply)
     }
-
-    Ok(())
 }
@@ -1,3 +1,3 @@
 mod get_red_dot_infos;
 
-pub use get_red_dot_infos::on_get_red_dot_infos;
+pub use get_red_dot_infos::GetRedDotInfos;
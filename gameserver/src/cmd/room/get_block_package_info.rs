@@ -1,34 +1,27 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::block_packages;
-use sonettobuf::{CmdId, GetBlockPackageInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetBlockPackageInfoReply, GetBlockPackageInfoRequset};
 
-pub async fn on_get_block_package_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let (packages, special_blocks) = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetBlockPackageInfo;
 
-        let packages = block_packages::get_block_packages(&ctx_guard.state.db, player_id).await?;
-        let blocks = block_packages::get_special_blocks(&ctx_guard.state.db, player_id).await?;
+impl CmdHandler for GetBlockPackageInfo {
+    const CMD: CmdId = CmdId::GetBlockPackageInfoRequsetCmd;
+    type Request = GetBlockPackageInfoRequset;
+    type Reply = GetBlockPackageInfoReply;
 
-        (packages, blocks)
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetBlockPackageInfoRequset,
+    ) -> Result<GetBlockPackageInfoReply, AppError> {
+        let player_id = session.player_id()?;
 
-    let reply = GetBlockPackageInfoReply {
-        block_package_ids: packages,
-        special_blocks: special_blocks.into_iter().map(Into::into).collect(),
-    };
+        let packages = block_packages::get_block_packages(session.db(), player_id).await?;
+        let special_blocks = block_packages::get_special_blocks(session.db(), player_id).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetBlockPackageInfoRequsetCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetBlockPackageInfoReply {
+            block_package_ids: packages,
+            special_blocks: special_blocks.into_iter().map(Into::into).collect(),
+        })
+    }
 }
@@ -1,30 +1,24 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::buildings;
-use sonettobuf::{CmdId, GetBuildingInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetBuildingInfoReply, GetBuildingInfoRequest};
 
-pub async fn on_get_building_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let building_infos = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetBuildingInfo;
 
-        buildings::get_user_buildings(&ctx_guard.state.db, player_id).await?
-    };
+impl CmdHandler for GetBuildingInfo {
+    const CMD: CmdId = CmdId::GetBuildingInfoCmd;
+    type Request = GetBuildingInfoRequest;
+    type Reply = GetBuildingInfoReply;
 
-    let reply = GetBuildingInfoReply {
-        building_infos: building_infos.into_iter().map(Into::into).collect(),
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetBuildingInfoRequest,
+    ) -> Result<GetBuildingInfoReply, AppError> {
+        let building_infos =
+            buildings::get_user_buildings(session.db(), session.player_id()?).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetBuildingInfoCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetBuildingInfoReply {
+            building_infos: building_infos.into_iter().map(Into::into).collect(),
+        })
+    }
 }
@@ -1,37 +1,28 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::character_interactions;
-use sonettobuf::{CmdId, GetCharacterInteractionInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetCharacterInteractionInfoReply, GetCharacterInteractionInfoRequest};
 
-pub async fn on_get_character_interaction_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let (infos, count) = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetCharacterInteractionInfo;
 
-        let infos =
-            character_interactions::get_character_interactions(&ctx_guard.state.db, player_id)
-                .await?;
-        let count =
-            character_interactions::get_interaction_count(&ctx_guard.state.db, player_id).await?;
-
-        (infos, count)
-    };
+impl CmdHandler for GetCharacterInteractionInfo {
+    const CMD: CmdId = CmdId::GetCharacterInteractionInfoCmd;
+    type Request = GetCharacterInteractionInfoRequest;
+    type Reply = GetCharacterInteractionInfoReply;
 
-    let reply = GetCharacterInteractionInfoReply {
-        infos: infos.into_iter().map(Into::into).collect(),
-        interaction_count: Some(count),
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetCharacterInteractionInfoRequest,
+    ) -> Result<GetCharacterInteractionInfoReply, AppError> {
+        let player_id = session.player_id()?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetCharacterInteractionInfoCmd, reply, 0, req.up_tag)
-        .await?;
+        let infos =
+            character_interactions::get_character_interactions(session.db(), player_id).await?;
+        let count = character_interactions::get_interaction_count(session.db(), player_id).await?;
 
-    Ok(())
+        Ok(GetCharacterInteractionInfoReply {
+            infos: infos.into_iter().map(Into::into).collect(),
+            interaction_count: Some(count),
+        })
+    }
 }
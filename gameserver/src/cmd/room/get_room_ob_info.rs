@@ -1,21 +1,9 @@
-use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::send_reply;
-use crate::state::ConnectionContext;
-use sonettobuf::{CmdId, GetRoomObInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::static_reply;
+use sonettobuf::{CmdId, GetRoomObInfoReply, GetRoomObInfoRequest};
 
-pub async fn on_get_room_ob_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    send_reply!(
-        ctx,
-        req.up_tag,
-        CmdId::GetRoomObInfoCmd,
-        GetRoomObInfoReply,
-        "room/room_ob_info.json"
-    );
-    Ok(())
-}
+static_reply!(
+    GetRoomObInfo,
+    CmdId::GetRoomObInfoCmd,
+    GetRoomObInfoRequest => GetRoomObInfoReply,
+    "room/room_ob_info.json"
+);
@@ -1,21 +1,9 @@
-use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::send_reply;
-use crate::state::ConnectionContext;
-use sonettobuf::{CmdId, GetRoomPlanInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::static_reply;
+use sonettobuf::{CmdId, GetRoomPlanInfoReply, GetRoomPlanInfoRequest};
 
-pub async fn on_get_room_plan_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    send_reply!(
-        ctx,
-        req.up_tag,
-        CmdId::GetRoomPlanInfoCmd,
-        GetRoomPlanInfoReply,
-        "room/room_plan_info.json"
-    );
-    Ok(())
-}
+static_reply!(
+    GetRoomPlanInfo,
+    CmdId::GetRoomPlanInfoCmd,
+    GetRoomPlanInfoRequest => GetRoomPlanInfoReply,
+    "room/room_plan_info.json"
+);
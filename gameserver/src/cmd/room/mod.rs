@@ -5,9 +5,9 @@ mod get_room_log;
 mod get_room_ob_info;
 mod get_room_plan_info;
 
-pub use get_block_package_info::on_get_block_package_info;
-pub use get_building_info::on_get_building_info;
-pub use get_character_interaction_info::on_get_character_interaction_info;
-pub use get_room_log::on_get_room_log;
-pub use get_room_ob_info::on_get_room_ob_info;
-pub use get_room_plan_info::on_get_room_plan_info;
+pub use get_block_package_info::GetBlockPackageInfo;
+pub use get_building_info::GetBuildingInfo;
+pub use get_character_interaction_info::GetCharacterInteractionInfo;
+pub use get_room_log::GetRoomLog;
+pub use get_room_ob_info::GetRoomObInfo;
+pub use get_room_plan_info::GetRoomPlanInfo;
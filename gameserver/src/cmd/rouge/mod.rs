@@ -1,3 +1,3 @@
 mod get_rouge_outside;
 
-pub use get_rouge_outside::on_get_rouge_outside_info;
+pub use get_rouge_outside::GetRougeOutsideInfo;
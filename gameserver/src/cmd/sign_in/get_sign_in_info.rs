@@ -1,37 +1,31 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::sign_in;
-use sonettobuf::{CmdId, GetSignInInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetSignInInfoReply, GetSignInInfoRequest};
 
-pub async fn on_get_sign_in_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let (info, sign_in_days, addup_bonus, month_card_days, month_card_history, birthday_heroes) = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetSignInInfo;
 
-        sign_in::get_sign_in_info(&ctx_guard.state.db, player_id).await?
-    };
+impl CmdHandler for GetSignInInfo {
+    const CMD: CmdId = CmdId::GetSignInInfoCmd;
+    type Request = GetSignInInfoRequest;
+    type Reply = GetSignInInfoReply;
 
-    let reply = GetSignInInfoReply {
-        has_sign_in_days: sign_in_days,
-        addup_sign_in_day: Some(info.addup_sign_in_day),
-        has_get_addup_bonus: addup_bonus,
-        open_function_time: Some(info.open_function_time),
-        has_month_card_days: month_card_days,
-        month_card_history: month_card_history.into_iter().map(Into::into).collect(),
-        birthday_hero_ids: birthday_heroes,
-        reward_mark: Some(info.reward_mark),
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetSignInInfoRequest,
+    ) -> Result<GetSignInInfoReply, AppError> {
+        let (info, sign_in_days, addup_bonus, month_card_days, month_card_history, birthday_heroes) =
+            sign_in::get_sign_in_info(session.db(), session.player_id()?).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetSignInInfoCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetSignInInfoReply {
+            has_sign_in_days: sign_in_days,
+            addup_sign_in_day: Some(info.addup_sign_in_day),
+            has_get_addup_bonus: addup_bonus,
+            open_function_time: Some(info.open_function_time),
+            has_month_card_days: month_card_days,
+            month_card_history: month_card_history.into_iter().map(Into::into).collect(),
+            birthday_hero_ids: birthday_heroes,
+            reward_mark: Some(info.reward_mark),
+        })
+    }
 }
@@ -2,6 +2,6 @@ mod get_sign_in_info;
 mod sign_in;
 mod sign_in_total_reward_all;
 
-pub use get_sign_in_info::on_get_sign_in_info;
-pub use sign_in::on_sign_in;
-pub use sign_in_total_reward_all::on_sign_in_total_reward_all;
+pub use get_sign_in_info::GetSignInInfo;
+pub use sign_in::SignIn;
+pub use sign_in_total_reward_all::SignInTotalRewardAll;
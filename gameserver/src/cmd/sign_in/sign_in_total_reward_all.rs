@@ -1,24 +1,18 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-
-use prost::Message;
+use crate::handler::{CmdHandler, Session};
 use sonettobuf::{CmdId, SignInTotalRewardAllReply, SignInTotalRewardAllRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
-
-pub async fn on_sign_in_total_reward_all(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let _request = SignInTotalRewardAllRequest::decode(&req.data[..]);
 
-    let reply = SignInTotalRewardAllReply { mark: Some(1022) };
+pub struct SignInTotalRewardAll;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::SignInTotalRewardAllCmd, reply, 0, req.up_tag)
-        .await?;
+impl CmdHandler for SignInTotalRewardAll {
+    const CMD: CmdId = CmdId::SignInTotalRewardAllCmd;
+    type Request = SignInTotalRewardAllRequest;
+    type Reply = SignInTotalRewardAllReply;
 
-    Ok(())
+    async fn handle(
+        _session: &mut Session,
+        _request: SignInTotalRewardAllRequest,
+    ) -> Result<SignInTotalRewardAllReply, AppError> {
+        Ok(SignInTotalRewardAllReply { mark: Some(1022) })
+    }
 }
@@ -1,3 +1,3 @@
 mod use_skin;
 
-pub use use_skin::on_use_skin;
+pub use use_skin::UseSkin;
@@ -1,40 +1,30 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::user_stats;
-use sonettobuf::CmdId;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{ClientStatBaseInfoReply, ClientStatBaseInfoRequest, CmdId};
 
-pub async fn on_client_stat_base_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let stats = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct ClientStatBaseInfo;
 
-        user_stats::get_user_stats(&ctx_guard.state.db, player_id)
+impl CmdHandler for ClientStatBaseInfo {
+    const CMD: CmdId = CmdId::ClientStatBaseInfoCmd;
+    type Request = ClientStatBaseInfoRequest;
+    type Reply = ClientStatBaseInfoReply;
+
+    async fn handle(
+        session: &mut Session,
+        _request: ClientStatBaseInfoRequest,
+    ) -> Result<ClientStatBaseInfoReply, AppError> {
+        let stats = user_stats::get_user_stats(session.db(), session.player_id()?)
             .await?
-            .ok_or_else(|| AppError::Custom("User stats not found".to_string()))?
-    };
+            .ok_or_else(|| AppError::Custom("User stats not found".to_string()))?;
 
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard
+        session
             .send_push(
                 CmdId::StatInfoPushCmd,
                 <sonettobuf::StatInfoPush>::from(stats),
             )
             .await?;
-    }
 
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard
-            .send_empty_reply(CmdId::ClientStatBaseInfoCmd, Vec::new(), 0, req.up_tag)
-            .await?;
+        Ok(ClientStatBaseInfoReply {})
     }
-
-    Ok(())
 }
@@ -1,24 +1,20 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use sonettobuf::{CmdId, UpdateClientStatBaseInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::handler::{CmdHandler, Session};
+use sonettobuf::{CmdId, UpdateClientStatBaseInfoReply, UpdateClientStatBaseInfoRequest};
 
-pub async fn on_update_client_stat_base_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let data = UpdateClientStatBaseInfoReply {
-        account_bind_bonus: Some(0),
-    };
+pub struct UpdateClientStatBaseInfo;
 
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard
-            .send_reply(CmdId::UpdateClientStatBaseInfoCmd, data, 0, req.up_tag)
-            .await?;
-    }
+impl CmdHandler for UpdateClientStatBaseInfo {
+    const CMD: CmdId = CmdId::UpdateClientStatBaseInfoCmd;
+    type Request = UpdateClientStatBaseInfoRequest;
+    type Reply = UpdateClientStatBaseInfoReply;
 
-    Ok(())
+    async fn handle(
+        _session: &mut Session,
+        _request: UpdateClientStatBaseInfoRequest,
+    ) -> Result<UpdateClientStatBaseInfoReply, AppError> {
+        Ok(UpdateClientStatBaseInfoReply {
+            account_bind_bonus: Some(0),
+        })
+    }
 }
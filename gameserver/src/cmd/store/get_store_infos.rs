@@ -1,44 +1,42 @@
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use crate::{error::AppError, utils::data_loader::GameDataLoader};
-use prost::Message;
 use sonettobuf::{CmdId, GetStoreInfosReply, GetStoreInfosRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_get_store_infos(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = GetStoreInfosRequest::decode(&req.data[..])?;
-    let store_ids = request.store_ids;
-    tracing::info!("Requested store_ids: {:?}", store_ids);
+pub struct GetStoreInfos;
 
-    // Load master store file
-    let response: GetStoreInfosReply = GameDataLoader::load_struct("store/store_infos.json")
-        .map_err(|e| AppError::Custom(format!("Failed to load store infos: {}", e)))?;
+impl CmdHandler for GetStoreInfos {
+    const CMD: CmdId = CmdId::GetStoreInfosCmd;
+    type Request = GetStoreInfosRequest;
+    type Reply = GetStoreInfosReply;
 
-    let reply = if store_ids.is_empty() {
-        // Return all stores
-        tracing::info!("No specific store IDs requested, returning all stores");
-        response
-    } else {
-        // Filter to only requested store IDs (with all their goods)
-        GetStoreInfosReply {
-            store_infos: response
-                .store_infos
-                .into_iter()
-                .filter(|store| store_ids.contains(&store.id))
-                .collect(),
-        }
-    };
+    async fn handle(
+        _session: &mut Session,
+        request: GetStoreInfosRequest,
+    ) -> Result<GetStoreInfosReply, AppError> {
+        let store_ids = request.store_ids;
+        tracing::info!("Requested store_ids: {:?}", store_ids);
 
-    tracing::info!("Returning {} store(s)", reply.store_infos.len());
+        // Load master store file
+        let response: GetStoreInfosReply = GameDataLoader::load_struct("store/store_infos.json")
+            .map_err(|e| AppError::Custom(format!("Failed to load store infos: {}", e)))?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetStoreInfosCmd, reply, 0, req.up_tag)
-        .await?;
+        let reply = if store_ids.is_empty() {
+            // Return all stores
+            tracing::info!("No specific store IDs requested, returning all stores");
+            response
+        } else {
+            // Filter to only requested store IDs (with all their goods)
+            GetStoreInfosReply {
+                store_infos: response
+                    .store_infos
+                    .into_iter()
+                    .filter(|store| store_ids.contains(&store.id))
+                    .collect(),
+            }
+        };
 
-    Ok(())
+        tracing::info!("Returning {} store(s)", reply.store_infos.len());
+
+        Ok(reply)
+    }
 }
@@ -1,3 +1,3 @@
 mod get_store_infos;
 
-pub use get_store_infos::on_get_store_infos;
+pub use get_store_infos::GetStoreInfos;
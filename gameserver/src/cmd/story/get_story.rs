@@ -1,34 +1,27 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::stories;
-use sonettobuf::{CmdId, GetStoryReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetStoryReply, GetStoryRequest};
 
-pub async fn on_get_story(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let (finished, processing) = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetStory;
 
-        let finished = stories::get_finished_stories(&ctx_guard.state.db, player_id).await?;
-        let processing = stories::get_processing_stories(&ctx_guard.state.db, player_id).await?;
+impl CmdHandler for GetStory {
+    const CMD: CmdId = CmdId::GetStoryCmd;
+    type Request = GetStoryRequest;
+    type Reply = GetStoryReply;
 
-        (finished, processing)
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetStoryRequest,
+    ) -> Result<GetStoryReply, AppError> {
+        let player_id = session.player_id()?;
 
-    let reply = GetStoryReply {
-        finish_list: finished,
-        processing_list: processing.into_iter().map(Into::into).collect(),
-    };
+        let finished = stories::get_finished_stories(session.db(), player_id).await?;
+        let processing = stories::get_processing_stories(session.db(), player_id).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetStoryCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetStoryReply {
+            finish_list: finished,
+            processing_list: processing.into_iter().map(Into::into).collect(),
+        })
+    }
 }
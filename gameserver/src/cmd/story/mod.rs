@@ -1,5 +1,5 @@
 mod get_story;
 mod update_story;
 
-pub use get_story::on_get_story;
-pub use update_story::on_update_story;
+pub use get_story::GetStory;
+pub use update_story::UpdateStory;
@@ -1,25 +1,20 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use prost::Message;
+use crate::handler::{CmdHandler, Session};
 use sonettobuf::{CmdId, UpdateStoryReply, UpdateStoryRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_update_story(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = UpdateStoryRequest::decode(&req.data[..])?;
+pub struct UpdateStory;
 
-    tracing::info!("Received update story request: {:?}", request);
+impl CmdHandler for UpdateStory {
+    const CMD: CmdId = CmdId::UpdateStoryCmd;
+    type Request = UpdateStoryRequest;
+    type Reply = UpdateStoryReply;
 
-    let reply = UpdateStoryReply {};
+    async fn handle(
+        _session: &mut Session,
+        request: UpdateStoryRequest,
+    ) -> Result<UpdateStoryReply, AppError> {
+        tracing::info!("Received update story request: {:?}", request);
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::UpdateStoryCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(UpdateStoryReply {})
+    }
 }
@@ -0,0 +1,52 @@
+use crate::error::AppError;
+use crate::handler::{CmdHandler, Session};
+use crate::state::{BannerType, six_star_heroes};
+use database::db::game::summon::{get_sp_pool_info, update_sp_pool_up_heroes};
+use sonettobuf::{ChooseMultiUpHeroReply, ChooseMultiUpHeroRequest, CmdId};
+
+pub struct ChooseMultiUpHero;
+
+impl CmdHandler for ChooseMultiUpHero {
+    const CMD: CmdId = CmdId::ChooseMultiUpHeroCmd;
+    type Request = ChooseMultiUpHeroRequest;
+    type Reply = ChooseMultiUpHeroReply;
+
+    async fn handle(
+        session: &mut Session,
+        request: ChooseMultiUpHeroRequest,
+    ) -> Result<ChooseMultiUpHeroReply, AppError> {
+        let pool_id = request.pool_id.ok_or(AppError::InvalidRequest)?;
+        let hero_ids = request.hero_ids;
+        let user_id = session.player_id()?;
+
+        let sp = get_sp_pool_info(session.db(), user_id, pool_id)
+            .await?
+            .ok_or(AppError::InvalidRequest)?;
+        if !matches!(BannerType::from(sp.sp_type), BannerType::MultiUp) {
+            return Err(AppError::InvalidRequest);
+        }
+
+        // only the pool's own 6*, each picked once
+        let candidates = six_star_heroes(pool_id);
+        let distinct = hero_ids
+            .iter()
+            .enumerate()
+            .all(|(i, id)| !hero_ids[..i].contains(id));
+        if hero_ids.is_empty() || !distinct || !hero_ids.iter().all(|id| candidates.contains(id)) {
+            tracing::info!(
+                "User {} picked invalid UP heroes {:?} on pool {}",
+                user_id,
+                hero_ids,
+                pool_id
+            );
+            return Err(AppError::InvalidRequest);
+        }
+
+        update_sp_pool_up_heroes(session.db(), user_id, pool_id, &hero_ids).await?;
+
+        Ok(ChooseMultiUpHeroReply {
+            pool_id: Some(pool_id),
+            hero_ids,
+        })
+    }
+}
@@ -1,36 +1,49 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
+use crate::state::{PityGroup, load_gacha_state};
 use database::db::game::summon;
-use sonettobuf::{CmdId, GetSummonInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetSummonInfoReply, GetSummonInfoRequest};
 
-pub async fn on_get_summon_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let (stats, pool_infos) = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetSummonInfo;
 
-        let stats = summon::get_summon_stats(&ctx_guard.state.db, player_id).await?;
-        let pools = summon::get_summon_pool_infos(&ctx_guard.state.db, player_id).await?;
+impl CmdHandler for GetSummonInfo {
+    const CMD: CmdId = CmdId::GetSummonInfoCmd;
+    type Request = GetSummonInfoRequest;
+    type Reply = GetSummonInfoReply;
 
-        (stats, pools)
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetSummonInfoRequest,
+    ) -> Result<GetSummonInfoReply, AppError> {
+        let player_id = session.player_id()?;
 
-    let reply = GetSummonInfoReply {
-        free_equip_summon: Some(stats.free_equip_summon),
-        is_show_new_summon: Some(stats.is_show_new_summon),
-        new_summon_count: Some(stats.new_summon_count),
-        pool_infos: pool_infos.into_iter().map(Into::into).collect(),
-        total_summon_count: Some(stats.total_summon_count),
-    };
+        let stats = summon::get_summon_stats(session.db(), player_id).await?;
+        let mut pool_infos = summon::get_summon_pool_infos(session.db(), player_id).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetSummonInfoCmd, reply, 0, req.up_tag)
-        .await?;
-    Ok(())
+        // pools show the pull count of their whole pity group
+        let mut group_counts: Vec<(i32, i32)> = Vec::new();
+        for info in &mut pool_infos {
+            let Some(group) = PityGroup::of(info.pool.pool_id) else {
+                continue;
+            };
+
+            let summon_count = match group_counts.iter().find(|(id, _)| *id == group.id) {
+                Some((_, count)) => *count,
+                None => {
+                    let state = load_gacha_state(session.db(), player_id, &group).await?;
+                    group_counts.push((group.id, state.summon_count));
+                    state.summon_count
+                }
+            };
+            info.pool.summon_count = summon_count;
+        }
+
+        Ok(GetSummonInfoReply {
+            free_equip_summon: Some(stats.free_equip_summon),
+            is_show_new_summon: Some(stats.is_show_new_summon),
+            new_summon_count: Some(stats.new_summon_count),
+            pool_infos: pool_infos.into_iter().map(Into::into).collect(),
+            total_summon_count: Some(stats.total_summon_count),
+        })
+    }
 }
@@ -0,0 +1,62 @@
+use super::grants::send_material_pushes;
+use crate::error::AppError;
+use crate::handler::{CmdHandler, Session};
+use crate::state::{grant_material, parse_progress_rewards};
+use data::exceldb;
+use database::db::game::summon::{
+    add_reward_progress, get_pool_summon_count, get_reward_progresses,
+};
+use sonettobuf::{CmdId, GetSummonProgressRewardsReply, GetSummonProgressRewardsRequest};
+
+pub struct GetSummonProgressRewards;
+
+impl CmdHandler for GetSummonProgressRewards {
+    const CMD: CmdId = CmdId::GetSummonProgressRewardsCmd;
+    type Request = GetSummonProgressRewardsRequest;
+    type Reply = GetSummonProgressRewardsReply;
+
+    async fn handle(
+        session: &mut Session,
+        request: GetSummonProgressRewardsRequest,
+    ) -> Result<GetSummonProgressRewardsReply, AppError> {
+        let pool_id = request.pool_id.ok_or(AppError::InvalidRequest)?;
+        let user_id = session.player_id()?;
+        let db = session.db();
+
+        let summon_pool = exceldb::get()
+            .summon_pool
+            .get(pool_id)
+            .ok_or(AppError::InvalidRequest)?;
+        let rewards = parse_progress_rewards(&summon_pool.progress_rewards);
+
+        let summon_count = get_pool_summon_count(db, user_id, pool_id).await?;
+        let mut claimed = get_reward_progresses(db, user_id, pool_id).await?;
+
+        // every milestone reached so far is claimed in one go
+        let due: Vec<_> = rewards
+            .into_iter()
+            .filter(|r| r.is_due(summon_count, &claimed))
+            .collect();
+
+        let mut granted = Vec::new();
+        let mut tx = db.begin().await?;
+        for reward in &due {
+            for material in &reward.materials {
+                if grant_material(&mut tx, user_id, material).await? {
+                    granted.push(*material);
+                }
+            }
+            add_reward_progress(&mut tx, user_id, pool_id, reward.progress).await?;
+            claimed.push(reward.progress);
+        }
+        tx.commit().await?;
+
+        send_material_pushes(session, user_id, &granted).await?;
+
+        claimed.sort_unstable();
+        Ok(GetSummonProgressRewardsReply {
+            pool_id: Some(pool_id),
+            has_get_reward_progresses: claimed,
+        })
+    }
+}
@@ -0,0 +1,147 @@
+use crate::error::AppError;
+use crate::handler::Session;
+use crate::state::{
+    MATERIAL_CURRENCY, MATERIAL_ITEM, Material, changed_ids, duplicate_materials, grant_material,
+};
+use crate::utils::push::{send_currency_change_push, send_item_change_push};
+use data::exceldb;
+use database::db::game::currencies::{get_currency, remove_currency};
+use database::db::game::heroes::{add_hero_duplicate, create_hero, get_hero_by_hero_id, has_hero};
+use database::db::game::items::{get_item, remove_item_quantity};
+use sonettobuf::{CmdId, HeroUpdatePush};
+use sqlx::{Sqlite, SqlitePool, Transaction};
+
+/// How much of each material the player holds
+pub(super) async fn owned_materials(
+    db: &SqlitePool,
+    user_id: i64,
+    materials: Vec<Material>,
+) -> Result<Vec<(Material, i32)>, AppError> {
+    let mut owned = Vec::with_capacity(materials.len());
+
+    for material in materials {
+        let quantity = match material.material_type {
+            MATERIAL_ITEM => get_item(db, user_id, material.id)
+                .await?
+                .map_or(0, |i| i.quantity),
+            MATERIAL_CURRENCY => get_currency(db, user_id, material.id as i32)
+                .await?
+                .map_or(0, |c| c.quantity),
+            other => {
+                tracing::warn!("Summon cost of material type {} can't be paid", other);
+                0
+            }
+        };
+        owned.push((material, quantity));
+    }
+
+    Ok(owned)
+}
+
+/// Takes the material away, false when the player ran short
+pub(super) async fn spend(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: i64,
+    material: &Material,
+) -> Result<bool, AppError> {
+    let spent = match material.material_type {
+        MATERIAL_ITEM => remove_item_quantity(tx, user_id, material.id, material.quantity).await?,
+        MATERIAL_CURRENCY => {
+            remove_currency(tx, user_id, material.id as i32, material.quantity).await?
+        }
+        _ => false,
+    };
+
+    Ok(spent)
+}
+
+/// A hero won from a pull or a lucky bag
+pub(super) struct ObtainedHero {
+    pub is_new: bool,
+    pub duplicate_count: i32,
+    /// What a duplicate turned into, already granted
+    pub returned: Vec<Material>,
+}
+
+/// Adds the hero, or counts the duplicate and grants what it converts into
+pub(super) async fn obtain_hero(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: i64,
+    hero_id: i32,
+) -> Result<ObtainedHero, AppError> {
+    if !has_hero(tx, user_id, hero_id).await? {
+        create_hero(tx, user_id, hero_id).await?;
+        return Ok(ObtainedHero {
+            is_new: true,
+            duplicate_count: 0,
+            returned: Vec::new(),
+        });
+    }
+
+    let duplicate_count = add_hero_duplicate(tx, user_id, hero_id).await?;
+
+    let materials = match exceldb::get().character.get(hero_id) {
+        Some(character) => duplicate_materials(character, duplicate_count),
+        None => {
+            tracing::warn!("Character {} not found for duplicate", hero_id);
+            Vec::new()
+        }
+    };
+
+    let mut returned = Vec::with_capacity(materials.len());
+    for material in materials {
+        if grant_material(tx, user_id, &material).await? {
+            returned.push(material);
+        }
+    }
+
+    Ok(ObtainedHero {
+        is_new: false,
+        duplicate_count,
+        returned,
+    })
+}
+
+/// Currency and item change pushes for every material that moved
+pub(super) async fn send_material_pushes(
+    session: &Session,
+    user_id: i64,
+    materials: &[Material],
+) -> Result<(), AppError> {
+    let ctx = session.context();
+    send_currency_change_push(
+        ctx.clone(),
+        user_id,
+        changed_ids(materials, MATERIAL_CURRENCY)
+            .into_iter()
+            .map(|id| id as i32)
+            .collect(),
+    )
+    .await?;
+    send_item_change_push(ctx.clone(), user_id, changed_ids(materials, MATERIAL_ITEM)).await?;
+
+    Ok(())
+}
+
+pub(super) async fn send_hero_updates(
+    session: &Session,
+    user_id: i64,
+    hero_ids: &[i32],
+) -> Result<(), AppError> {
+    if hero_ids.is_empty() {
+        return Ok(());
+    }
+
+    let mut hero_updates = Vec::with_capacity(hero_ids.len());
+    for hero_id in hero_ids {
+        let hero = get_hero_by_hero_id(session.db(), user_id, *hero_id).await?;
+        hero_updates.push(hero.into());
+    }
+
+    session
+        .send_push(
+            CmdId::HeroHeroUpdatePushCmd,
+            HeroUpdatePush { hero_updates },
+        )
+        .await
+}
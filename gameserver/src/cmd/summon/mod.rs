@@ -1,9 +1,16 @@
 mod choose_enhanced_pool_hero;
+mod choose_multi_up_hero;
 mod get_summon_info;
+mod get_summon_progress_rewards;
+mod grants;
+mod open_lucky_bag;
 mod summon;
 mod summon_query_token;
 
-pub use choose_enhanced_pool_hero::on_choose_enhanced_pool_hero;
-pub use get_summon_info::on_get_summon_info;
-pub use summon::on_summon;
-pub use summon_query_token::on_summon_query_token;
+pub use choose_enhanced_pool_hero::ChooseEnhancedPoolHero;
+pub use choose_multi_up_hero::ChooseMultiUpHero;
+pub use get_summon_info::GetSummonInfo;
+pub use get_summon_progress_rewards::GetSummonProgressRewards;
+pub use open_lucky_bag::OpenLuckyBag;
+pub use summon::Summon;
+pub use summon_query_token::SummonQueryToken;
@@ -0,0 +1,68 @@
+use super::grants::{obtain_hero, send_hero_updates, send_material_pushes};
+use crate::error::AppError;
+use crate::handler::{CmdHandler, Session};
+use crate::state::six_star_heroes;
+use data::exceldb;
+use database::db::game::summon::{get_closed_lucky_bag_pool, open_lucky_bag};
+use sonettobuf::{CmdId, LuckyBagResult, OpenLuckyBagReply, OpenLuckyBagRequest};
+
+pub struct OpenLuckyBag;
+
+impl CmdHandler for OpenLuckyBag {
+    const CMD: CmdId = CmdId::OpenLuckyBagCmd;
+    type Request = OpenLuckyBagRequest;
+    type Reply = OpenLuckyBagReply;
+
+    async fn handle(
+        session: &mut Session,
+        request: OpenLuckyBagRequest,
+    ) -> Result<OpenLuckyBagReply, AppError> {
+        let bag_id = request.lucky_bag_id.ok_or(AppError::InvalidRequest)?;
+        let hero_id = request.hero_id.ok_or(AppError::InvalidRequest)?;
+        let user_id = session.player_id()?;
+        let db = session.db();
+
+        let pool_id = get_closed_lucky_bag_pool(db, user_id, bag_id)
+            .await?
+            .ok_or(AppError::InvalidRequest)?;
+
+        // a bag holds any of its pool's 6*, or any 6* when the pool lists none
+        let candidates = six_star_heroes(pool_id);
+        let allowed = if candidates.is_empty() {
+            exceldb::get()
+                .character
+                .get(hero_id)
+                .is_some_and(|c| c.rare == 5)
+        } else {
+            candidates.contains(&hero_id)
+        };
+        if !allowed {
+            tracing::info!(
+                "User {} can't pick hero {} from lucky bag {}",
+                user_id,
+                hero_id,
+                bag_id
+            );
+            return Err(AppError::InvalidRequest);
+        }
+
+        let mut tx = db.begin().await?;
+        if !open_lucky_bag(&mut tx, user_id, pool_id, bag_id, hero_id).await? {
+            return Err(AppError::InvalidRequest);
+        }
+        let hero = obtain_hero(&mut tx, user_id, hero_id).await?;
+        tx.commit().await?;
+
+        send_hero_updates(session, user_id, &[hero_id]).await?;
+        send_material_pushes(session, user_id, &hero.returned).await?;
+
+        Ok(OpenLuckyBagReply {
+            lucky_bag_results: vec![LuckyBagResult {
+                hero_id: Some(hero_id),
+                is_new: Some(hero.is_new),
+                cur_count: Some(hero.duplicate_count),
+                return_materials: hero.returned.into_iter().map(Into::into).collect(),
+            }],
+        })
+    }
+}
@@ -1,132 +1,215 @@
-use crate::error::AppError;
-use crate::packet::ClientPacket;
+use super::grants::{obtain_hero, owned_materials, send_hero_updates, send_material_pushes, spend};
+use crate::error::{AppError, CmdError};
+use crate::handler::{CmdHandler, Session};
 use crate::state::{
-    BannerType, ConnectionContext, GachaResult, GachaState, build_gacha, load_gacha_state,
-    save_gacha_state,
+    BannerType, GachaResult, GachaState, Material, PityGroup, SummonCost, build_gacha,
+    load_gacha_state, save_gacha_state,
 };
+use common::summon_config;
 use data::exceldb;
-use database::db::game::heroes::{add_hero_duplicate, create_hero, has_hero};
-use database::db::game::summon::{add_summon_history, get_sp_pool_info};
-use prost::Message;
+use database::db::game::summon::{
+    add_summon_history, get_discount_time, get_lucky_bag_ids, get_sp_pool_info,
+    record_lucky_bag_pulls, record_pool_pulls,
+};
 use rand::thread_rng;
 
 use sonettobuf::{CmdId, SummonReply, SummonRequest, SummonResult};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_summon(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = SummonRequest::decode(&req.data[..])?;
+/// Result code for a pull the player can't pay for
+const NOT_ENOUGH_COST: i16 = 2;
 
-    let pool_id = request.pool_id.unwrap_or(0);
-    let count = request.count.unwrap_or(1).clamp(1, 10);
+pub struct Summon;
 
-    tracing::info!("Summon request received: Pool {} Count {}", pool_id, count);
+impl CmdHandler for Summon {
+    const CMD: CmdId = CmdId::SummonCmd;
+    type Request = SummonRequest;
+    type Reply = SummonReply;
 
-    let (user_id, db) = {
-        let ctx = ctx.lock().await;
-        (
-            ctx.player_id.ok_or(AppError::NotLoggedIn)?,
-            ctx.state.db.clone(),
-        )
-    };
+    async fn handle(
+        session: &mut Session,
+        request: SummonRequest,
+    ) -> Result<SummonReply, AppError> {
+        let pool_id = request.pool_id.unwrap_or(0);
+        // a single or a ten-pull, nothing in between
+        let count = if request.count == Some(10) { 10 } else { 1 };
+
+        tracing::info!("Summon request received: Pool {} Count {}", pool_id, count);
+
+        let user_id = session.player_id()?;
+        let db = session.db();
+
+        let game_data = exceldb::get();
+        let summon_pool = game_data
+            .summon_pool
+            .iter()
+            .find(|p| p.id == pool_id)
+            .ok_or(AppError::InvalidRequest)?;
 
-    let sp_pool_info = get_sp_pool_info(&db, user_id, pool_id).await?;
+        let sp_pool_info = get_sp_pool_info(db, user_id, pool_id).await?;
 
-    let banner_type = match &sp_pool_info {
-        Some(sp) => BannerType::from(sp.sp_type),
-        None => BannerType::RateUp,
-    };
+        let banner_type = match &sp_pool_info {
+            Some(sp) => BannerType::from(sp.sp_type),
+            None => BannerType::RateUp,
+        };
 
-    let pool = build_gacha(pool_id, sp_pool_info.as_ref()).await?;
+        let mut pool = build_gacha(pool_id, sp_pool_info.as_ref(), summon_config()).await?;
 
-    let state = load_gacha_state(&db, user_id, pool_id).await?;
-    let mut gacha = GachaState {
-        pity_6: state.pity_6,
-        up_guaranteed: state.up_guaranteed,
-    };
+        // each bag comes out of its pool once
+        let lucky_bag_pool = !pool.lucky_bags.is_empty();
+        if lucky_bag_pool {
+            let owned_bags = get_lucky_bag_ids(db, user_id, pool_id).await?;
+            pool.lucky_bags.retain(|bag| !owned_bags.contains(bag));
+        }
 
-    let gacha_results = {
-        let mut rng = thread_rng();
-        if count == 10 {
-            gacha.ten_pull(banner_type, &pool, &mut rng)
-        } else {
-            vec![gacha.single_pull(banner_type, &pool, &mut rng, false)]
+        let pity_group = PityGroup::of(pool_id).ok_or(AppError::InvalidRequest)?;
+        let state = load_gacha_state(db, user_id, &pity_group).await?;
+        let mut gacha = GachaState {
+            pity_6: state.pity_6,
+            up_guaranteed: state.up_guaranteed,
+        };
+
+        let cost = SummonCost::from_pool(summon_pool);
+        let owned = owned_materials(db, user_id, cost.materials()).await?;
+
+        // the charge, the heroes and the pity all land together or not at all
+        let mut tx = db.begin().await?;
+
+        let discount_time =
+            get_discount_time(&mut tx, user_id, pool_id, summon_pool.discount_time10).await?;
+        let owned_of = |m: &Material| {
+            owned
+                .iter()
+                .find(|(o, _)| o.same_kind(m))
+                .map_or(0, |(_, quantity)| *quantity)
+        };
+        let Some(payment) = cost.payment(count, discount_time > 0, owned_of) else {
+            tracing::info!(
+                "User {} can't pay for {} pulls on pool {}",
+                user_id,
+                count,
+                pool_id
+            );
+            return Err(CmdError::ResultCode(NOT_ENOUGH_COST).into());
+        };
+
+        for material in &payment.materials {
+            if !spend(&mut tx, user_id, material).await? {
+                return Err(CmdError::ResultCode(NOT_ENOUGH_COST).into());
+            }
         }
-    };
-
-    let mut reply_results = Vec::with_capacity(gacha_results.len());
-
-    for result in gacha_results {
-        match result {
-            GachaResult::Hero {
-                hero_id,
-                rare,
-                is_up,
-            } => {
-                let (is_new, duplicate_count) = if has_hero(&db, user_id, hero_id).await? {
-                    let dup = add_hero_duplicate(&db, user_id, hero_id).await?;
-                    (false, dup)
-                } else {
-                    create_hero(&db, user_id, hero_id).await?;
-                    (true, 0)
-                };
-
-                reply_results.push(SummonResult {
-                    hero_id: Some(hero_id),
-                    is_new: Some(is_new),
-                    duplicate_count: Some(duplicate_count),
-                    equip_id: Some(0),
-                    return_materials: Vec::new(),
-                    lucky_bag_id: Some(0),
-                    limited_ticket_id: Some(0),
-                });
-
-                tracing::info!(
-                    "User {} pulled hero {} (rarity: {}, is_up: {}, is_new: {})",
-                    user_id,
+
+        let gacha_results = {
+            let mut rng = thread_rng();
+            if count == 10 {
+                gacha.ten_pull(banner_type, &pool, &mut rng)
+            } else {
+                gacha
+                    .single_pull(banner_type, &pool, &mut rng, None)
+                    .map(|result| vec![result])
+            }
+        };
+        // the charge is rolled back with the transaction
+        let Some(gacha_results) = gacha_results else {
+            tracing::warn!("Pool {} has no hero for a rolled star", pool_id);
+            return Err(AppError::InvalidRequest);
+        };
+
+        let mut reply_results = Vec::with_capacity(gacha_results.len());
+        let mut pulled_heroes = Vec::new();
+        let mut granted = Vec::new();
+        let mut bag_misses = 0;
+
+        for result in gacha_results {
+            match result {
+                GachaResult::Hero {
                     hero_id,
                     rare,
                     is_up,
-                    is_new
-                );
+                } => {
+                    let hero = obtain_hero(&mut tx, user_id, hero_id).await?;
+
+                    if !pulled_heroes.contains(&hero_id) {
+                        pulled_heroes.push(hero_id);
+                    }
+                    granted.extend(hero.returned.iter().copied());
+                    bag_misses += 1;
+
+                    reply_results.push(SummonResult {
+                        hero_id: Some(hero_id),
+                        is_new: Some(hero.is_new),
+                        duplicate_count: Some(hero.duplicate_count),
+                        equip_id: Some(0),
+                        return_materials: hero.returned.into_iter().map(Into::into).collect(),
+                        lucky_bag_id: Some(0),
+                        limited_ticket_id: Some(0),
+                    });
+
+                    tracing::info!(
+                        "User {} pulled hero {} (rarity: {}, is_up: {}, is_new: {})",
+                        user_id,
+                        hero_id,
+                        rare,
+                        is_up,
+                        hero.is_new
+                    );
+                }
+
+                GachaResult::LuckyBag { bag_id } => {
+                    record_lucky_bag_pulls(&mut tx, user_id, pool_id, 0, Some(bag_id)).await?;
+                    bag_misses = 0;
+
+                    reply_results.push(SummonResult {
+                        hero_id: None,
+                        is_new: Some(false),
+                        duplicate_count: Some(0),
+                        equip_id: Some(0),
+                        return_materials: Vec::new(),
+                        lucky_bag_id: Some(bag_id),
+                        limited_ticket_id: Some(0),
+                    });
+
+                    tracing::info!("User {} pulled lucky bag {}", user_id, bag_id);
+                }
             }
         }
-    }
 
-    save_gacha_state(&db, user_id, pool_id, &gacha).await?;
-
-    let game_data = exceldb::get();
-    let summon_pool = game_data
-        .summon_pool
-        .iter()
-        .find(|p| p.id == pool_id)
-        .ok_or(AppError::InvalidRequest)?;
-
-    let summon_type = if count == 10 { 2 } else { 1 };
-
-    add_summon_history(
-        &db,
-        user_id,
-        pool_id,
-        summon_pool.name_en.as_str(),
-        summon_pool.r#type,
-        summon_type,
-        &reply_results,
-    )
-    .await?;
-
-    let reply = SummonReply {
-        summon_result: reply_results,
-    };
-
-    {
-        let mut ctx = ctx.lock().await;
-        ctx.send_reply(CmdId::SummonCmd, reply, 0, req.up_tag)
-            .await?;
-    }
+        if lucky_bag_pool && bag_misses > 0 {
+            record_lucky_bag_pulls(&mut tx, user_id, pool_id, bag_misses, None).await?;
+        }
+
+        save_gacha_state(
+            &mut tx,
+            user_id,
+            &pity_group,
+            &gacha,
+            state.summon_count + count,
+        )
+        .await?;
+
+        let summon_type = if count == 10 { 2 } else { 1 };
+
+        add_summon_history(
+            &mut tx,
+            user_id,
+            pool_id,
+            summon_pool.name_en.as_str(),
+            summon_pool.r#type,
+            summon_type,
+            &reply_results,
+        )
+        .await?;
 
-    Ok(())
+        let discount_left = discount_time - i32::from(payment.discounted);
+        record_pool_pulls(&mut tx, user_id, pool_id, count, discount_left).await?;
+
+        tx.commit().await?;
+
+        let moved: Vec<Material> = payment.materials.iter().chain(&granted).copied().collect();
+        send_material_pushes(session, user_id, &moved).await?;
+        send_hero_updates(session, user_id, &pulled_heroes).await?;
+
+        Ok(SummonReply {
+            summon_result: reply_results,
+        })
+    }
 }
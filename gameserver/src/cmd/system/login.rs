@@ -1,7 +1,7 @@
 use crate::cmd::system::util::*;
 use crate::error::AppError;
 use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::state::{ConnectionContext, SessionState};
 use crate::utils::push::send_red_dot_push;
 
 use common::time::ServerTime;
@@ -23,35 +23,14 @@ pub async fn on_login(
 
     tracing::info!("→ Login attempt user_id={}", user_id);
 
-    let (stored_token, token_expires_at) = {
-        let db = {
-            let ctx = ctx.lock().await;
-            ctx.state.db.clone()
-        };
-
-        let row = sqlx::query("SELECT token, token_expires_at FROM users WHERE id = ?")
-            .bind(user_id)
-            .fetch_optional(&db)
-            .await?
-            .ok_or_else(|| AppError::Custom("User not found".into()))?;
-
-        (
-            row.try_get::<String, _>("token")?,
-            row.try_get::<Option<i64>, _>("token_expires_at")?,
-        )
-    };
-
-    if stored_token != login.token {
-        return login_error(&ctx, "Invalid token", req.up_tag).await;
-    }
-
-    let now = ServerTime::now_ms() as i64;
-    if token_expires_at.is_some_and(|exp| now > exp) {
-        return login_error(&ctx, "Token expired", req.up_tag).await;
+    if let Some(msg) = token_error(&ctx, user_id, &login.token).await? {
+        return login_error(&ctx, msg, req.up_tag).await;
     }
 
     tracing::info!("✓ Token validated for user_id={}", user_id);
 
+    take_over_previous_session(&ctx, user_id).await?;
+
     {
         let mut ctx_guard = ctx.lock().await;
         ctx_guard.load_player_state(user_id).await?;
@@ -118,3 +97,115 @@ pub async fn on_login(
     tracing::info!("✓ Login successful for user_id={}", user_id);
     Ok(())
 }
+
+/// Why `token` can't log `user_id` in, None when it checks out
+pub(super) async fn token_error(
+    ctx: &Arc<Mutex<ConnectionContext>>,
+    user_id: i64,
+    token: &str,
+) -> Result<Option<&'static str>, AppError> {
+    let db = {
+        let ctx = ctx.lock().await;
+        ctx.state.db.clone()
+    };
+
+    let row = sqlx::query("SELECT token, token_expires_at FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(&db)
+        .await?
+        .ok_or_else(|| AppError::Custom("User not found".into()))?;
+
+    let stored_token = row.try_get::<String, _>("token")?;
+    let token_expires_at = row.try_get::<Option<i64>, _>("token_expires_at")?;
+
+    if stored_token != token {
+        return Ok(Some("Invalid token"));
+    }
+
+    let now = ServerTime::now_ms() as i64;
+    if token_expires_at.is_some_and(|exp| now > exp) {
+        return Ok(Some("Token expired"));
+    }
+
+    Ok(None)
+}
+
+/// Binds a fresh socket to the player's previous session, live or parked, for
+/// a ReconnectRequestCmd sent without logging in again. Without a previous
+/// session to pick up the socket is left as it was, still without a player
+pub(super) async fn resume_player(
+    ctx: &Arc<Mutex<ConnectionContext>>,
+    user_id: i64,
+) -> Result<(), AppError> {
+    if !take_over_previous_session(ctx, user_id).await? {
+        tracing::info!("Reconnect: no previous session for user_id={}", user_id);
+        return Ok(());
+    }
+
+    ctx.lock().await.load_player_state(user_id).await?;
+    ConnectionContext::register(Arc::clone(ctx)).await;
+
+    tracing::info!("✓ Reconnect resumed user_id={}", user_id);
+    Ok(())
+}
+
+/// Single session per account: the previous connection is kicked and its state
+/// flushed before ours is loaded. Its down_tag / resend state is carried over so a
+/// ReconnectRequestCmd after a network blip can replay what the client missed
+/// before the takeover, without the packets this connection sent since.
+/// Returns whether there was a previous session to carry over.
+async fn take_over_previous_session(
+    ctx: &Arc<Mutex<ConnectionContext>>,
+    user_id: i64,
+) -> Result<bool, AppError> {
+    let state = {
+        let ctx_guard = ctx.lock().await;
+        ctx_guard.state.clone()
+    };
+
+    let parked = state.take_parked_session(user_id);
+    let previous = match state.get_connection_context(user_id) {
+        Some(old_ctx) if !Arc::ptr_eq(&old_ctx, ctx) => Some(kick_session(&old_ctx, user_id).await),
+        _ => parked,
+    };
+
+    let Some(previous) = previous else {
+        return Ok(false);
+    };
+
+    let mut ctx_guard = ctx.lock().await;
+    ctx_guard.session.resume_from(&previous);
+    tracing::info!("Resumed previous session state for user_id={}", user_id);
+
+    Ok(true)
+}
+
+async fn kick_session(old_ctx: &Arc<Mutex<ConnectionContext>>, user_id: i64) -> SessionState {
+    let mut old = old_ctx.lock().await;
+    tracing::warn!(
+        "user_id={} logged in again, kicking previous session",
+        user_id
+    );
+
+    // taken before the kick push so it never gets replayed to the new connection
+    let session = old.session.clone();
+
+    if let Err(e) = old.save_current_player_state().await {
+        tracing::error!("Failed to save player state for {}: {}", user_id, e);
+    }
+    // detach it from the account so its disconnect cleanup leaves our session alone
+    old.player_id = None;
+    old.player_state = None;
+    old.active_battle = None;
+
+    let kick = build_force_logout("Logged in elsewhere");
+    if let Err(e) = old.send_raw_push(CmdId::ForceLogoutPushCmd, kick).await {
+        tracing::debug!("Could not queue kick for previous session: {}", e);
+    }
+    if let Err(e) = old.flush_send_queue().await {
+        tracing::debug!("Could not deliver kick to previous session: {}", e);
+    }
+
+    old.close();
+    session
+}
@@ -4,5 +4,5 @@ mod rename;
 mod util;
 
 pub use login::on_login;
-pub use reconnect::on_reconnect;
-pub use rename::on_rename;
+pub use reconnect::{on_get_reconnect_start_tag, on_reconnect};
+pub use rename::Rename;
@@ -1,18 +1,103 @@
+use super::login::{resume_player, token_error};
+use super::util::{extract_user_id, parse_login_request};
 use crate::error::AppError;
 use crate::packet::ClientPacket;
 use crate::state::ConnectionContext;
+use common::command_config;
 use sonettobuf::CmdId;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+/// GetLostCmdRespRequest: body starts with the last down_tag the client received,
+/// everything after it still in the resend buffer is replayed before the reply.
+/// On a socket that hasn't logged in, the tag is followed by the account and
+/// token as in LoginRequest and the player's previous session is picked up.
+/// A tag we can't resume from fails the reconnect so the client logs in again
 pub async fn on_reconnect(
     ctx: Arc<Mutex<ConnectionContext>>,
     req: ClientPacket,
 ) -> Result<(), AppError> {
+    let last_received = req.data.first().copied();
+
+    let logged_in = ctx.lock().await.player_id.is_some();
+    if !logged_in && let Some(user_id) = reconnecting_player(&ctx, &req.data).await? {
+        resume_player(&ctx, user_id).await?;
+    }
+
     let mut ctx_guard = ctx.lock().await;
+    // still nobody on this socket: its fresh session would take the client's
+    // tag for a clean start, so send it back to a full login instead
+    let missed = if ctx_guard.player_id.is_some() {
+        ctx_guard.session.packets_after(last_received)
+    } else {
+        None
+    };
+    let Some(missed) = missed else {
+        tracing::info!(
+            "Reconnect: client last down_tag {:?} can't be resumed, refusing",
+            last_received
+        );
+        ctx_guard
+            .send_empty_reply(
+                CmdId::ReconnectRequestCmd,
+                Vec::new(),
+                command_config().error_result_code,
+                req.up_tag,
+            )
+            .await?;
+        return Ok(());
+    };
+
+    tracing::info!(
+        "Reconnect: client last down_tag {:?}, replaying {} packets",
+        last_received,
+        missed.len()
+    );
+
+    ctx_guard.replay_packets(missed).await?;
+
     ctx_guard
         .send_empty_reply(CmdId::ReconnectRequestCmd, vec![0x01], 0, req.up_tag)
         .await?;
 
     Ok(())
 }
+
+/// The player a reconnect on a fresh socket names, once its token checks out
+async fn reconnecting_player(
+    ctx: &Arc<Mutex<ConnectionContext>>,
+    data: &[u8],
+) -> Result<Option<i64>, AppError> {
+    let Some(login) = data.get(1..).and_then(|d| parse_login_request(d).ok()) else {
+        tracing::info!("Reconnect: fresh socket without account, nothing to resume");
+        return Ok(None);
+    };
+    let user_id = extract_user_id(&login.account_id)?;
+
+    if let Some(msg) = token_error(ctx, user_id, &login.token).await? {
+        tracing::warn!("Reconnect: user_id={} refused, {}", user_id, msg);
+        return Ok(None);
+    }
+
+    Ok(Some(user_id))
+}
+
+/// GetLostCmdRespResponseStartTag: oldest down_tag we can still replay
+pub async fn on_get_reconnect_start_tag(
+    ctx: Arc<Mutex<ConnectionContext>>,
+    req: ClientPacket,
+) -> Result<(), AppError> {
+    let mut ctx_guard = ctx.lock().await;
+    let start_tag = ctx_guard.session.resend_start_tag();
+
+    ctx_guard
+        .send_raw_reply_fixed(
+            CmdId::GetReconnectStartTagRequestCmd,
+            vec![start_tag],
+            0,
+            req.up_tag,
+        )
+        .await?;
+
+    Ok(())
+}
@@ -124,6 +124,19 @@ pub fn build_login_error(reason: &str) -> Vec<u8> {
     payload
 }
 
+/// ForceLogoutResponse body, same u16 length prefixed reason string as login
+pub fn build_force_logout(reason: &str) -> Vec<u8> {
+    let mut payload = Vec::new();
+
+    let reason_bytes = reason.as_bytes();
+    let reason_len = reason_bytes.len() as u16;
+
+    payload.extend_from_slice(&reason_len.to_be_bytes());
+    payload.extend_from_slice(reason_bytes);
+
+    payload
+}
+
 /// Load critters from database and send push
 pub async fn send_critter_push(
     ctx: Arc<Mutex<ConnectionContext>>,
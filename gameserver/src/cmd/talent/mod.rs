@@ -1,3 +1,3 @@
 mod talent_style_read;
 
-pub use talent_style_read::on_talent_style_read;
+pub use talent_style_read::TalentStyleRead;
@@ -1,27 +1,20 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use prost::Message;
+use crate::handler::{CmdHandler, Session};
 use sonettobuf::{CmdId, TalentStyleReadReply, TalentStyleReadRequest};
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
-pub async fn on_talent_style_read(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = TalentStyleReadRequest::decode(&req.data[..])?;
+pub struct TalentStyleRead;
 
-    let data = TalentStyleReadReply {
-        hero_id: Some(request.hero_id.unwrap_or(3080)),
-    };
+impl CmdHandler for TalentStyleRead {
+    const CMD: CmdId = CmdId::TalentStyleReadCmd;
+    type Request = TalentStyleReadRequest;
+    type Reply = TalentStyleReadReply;
 
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard
-            .send_reply(CmdId::TalentStyleReadCmd, data, 0, req.up_tag)
-            .await?;
+    async fn handle(
+        _session: &mut Session,
+        request: TalentStyleReadRequest,
+    ) -> Result<TalentStyleReadReply, AppError> {
+        Ok(TalentStyleReadReply {
+            hero_id: Some(request.hero_id.unwrap_or(3080)),
+        })
     }
-
-    Ok(())
 }
@@ -1,21 +1,9 @@
-use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::send_reply;
-use crate::state::ConnectionContext;
-use sonettobuf::{CmdId, GetTaskInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::static_reply;
+use sonettobuf::{CmdId, GetTaskInfoReply, GetTaskInfoRequest};
 
-pub async fn on_get_task_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    send_reply!(
-        ctx,
-        req.up_tag,
-        CmdId::GetTaskInfoCmd,
-        GetTaskInfoReply,
-        "task/task_info.json"
-    );
-    Ok(())
-}
+static_reply!(
+    GetTaskInfo,
+    CmdId::GetTaskInfoCmd,
+    GetTaskInfoRequest => GetTaskInfoReply,
+    "task/task_info.json"
+);
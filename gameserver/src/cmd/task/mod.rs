@@ -1,3 +1,3 @@
 mod get_task_info;
 
-pub use get_task_info::on_get_task_info;
+pub use get_task_info::GetTaskInfo;
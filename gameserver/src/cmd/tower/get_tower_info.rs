@@ -1,34 +1,28 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::tower;
-use sonettobuf::{CmdId, GetTowerInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetTowerInfoReply, GetTowerInfoRequest};
 
-pub async fn on_get_tower_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let (info, tower_opens, towers, assist_bosses) = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct GetTowerInfo;
 
-        tower::get_tower_info(&ctx_guard.state.db, player_id).await?
-    };
+impl CmdHandler for GetTowerInfo {
+    const CMD: CmdId = CmdId::GetTowerInfoCmd;
+    type Request = GetTowerInfoRequest;
+    type Reply = GetTowerInfoReply;
 
-    let reply = GetTowerInfoReply {
-        tower_opens: tower_opens.into_iter().map(Into::into).collect(),
-        towers,
-        assist_bosses: assist_bosses.into_iter().map(Into::into).collect(),
-        mop_up_times: Some(info.mop_up_times),
-        trial_hero_season: Some(info.trial_hero_season),
-    };
+    async fn handle(
+        session: &mut Session,
+        _request: GetTowerInfoRequest,
+    ) -> Result<GetTowerInfoReply, AppError> {
+        let (info, tower_opens, towers, assist_bosses) =
+            tower::get_tower_info(session.db(), session.player_id()?).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetTowerInfoCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(GetTowerInfoReply {
+            tower_opens: tower_opens.into_iter().map(Into::into).collect(),
+            towers,
+            assist_bosses: assist_bosses.into_iter().map(Into::into).collect(),
+            mop_up_times: Some(info.mop_up_times),
+            trial_hero_season: Some(info.trial_hero_season),
+        })
+    }
 }
@@ -1,5 +1,5 @@
 mod get_tower_info;
 mod start_tower_battle;
 
-pub use get_tower_info::on_get_tower_info;
-pub use start_tower_battle::on_start_tower_battle;
+pub use get_tower_info::GetTowerInfo;
+pub use start_tower_battle::StartTowerBattle;
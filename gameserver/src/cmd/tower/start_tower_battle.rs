@@ -1,91 +1,108 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
+use crate::handler::{CmdHandler, Session};
 use crate::state::{
-    ActiveBattle, BattleContext, ConnectionContext, create_battle, default_max_ap,
-    generate_initial_deck,
+    ActiveBattle, BattleContext, ClothState, REPLAY_VERSION, cloth_level, create_battle,
+    default_max_ap, generate_initial_deck,
 };
 use data::exceldb;
-use prost::Message;
+use database::db::game::battle::save_battle_record;
 use sonettobuf::{
     CmdId, DungeonUpdatePush, StartDungeonReply, StartTowerBattleReply, StartTowerBattleRequest,
     UserDungeon,
 };
-use std::sync::Arc;
-use tokio::sync::Mutex;
-
-pub async fn on_start_tower_battle(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let request = StartTowerBattleRequest::decode(&req.data[..])?;
-
-    let start_req = request
-        .start_dungeon_request
-        .ok_or(AppError::InvalidRequest)?;
-    let fight_group = start_req.fight_group.ok_or(AppError::InvalidRequest)?;
-
-    let dungeon_type = request.r#type.ok_or(AppError::InvalidRequest)?;
-    let tower_id = request.tower_id.ok_or(AppError::InvalidRequest)?;
-    let layer_id = request.layer_id.ok_or(AppError::InvalidRequest)?;
-    let difficulty = request.difficulty.ok_or(AppError::InvalidRequest)?;
-    let talent_plan_id = request.talent_plan_id.unwrap_or(0);
-
-    let chapter_id = start_req.chapter_id.unwrap_or(0);
-    let episode_id = start_req.episode_id.unwrap_or(0);
-
-    tracing::info!(
-        "Start tower battle: type={}, tower={}, layer={}, episode={}, diff={}, talent={}",
-        dungeon_type,
-        tower_id,
-        layer_id,
-        episode_id,
-        difficulty,
-        talent_plan_id
-    );
-
-    tracing::info!(
-        "Fight group: heroes={:?}, cloth={}, assist_boss={}",
-        fight_group.hero_list,
-        fight_group.cloth_id.unwrap_or(1),
-        fight_group.assist_boss_id.unwrap_or(0)
-    );
-
-    let (player_id, pool) = {
-        let ctx_guard = ctx.lock().await;
-        (
-            ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?,
-            ctx_guard.state.db.clone(),
-        )
-    };
-
-    let hero_count = fight_group.hero_list.iter().filter(|&&u| u != 0).count();
-
-    let game_data = exceldb::get();
-    let battle_id = game_data
-        .episode
-        .iter()
-        .find(|e| e.id == episode_id)
-        .ok_or(AppError::InvalidRequest)?
-        .battle_id;
-
-    let max_ap = default_max_ap(episode_id, hero_count);
-
-    let battle_ctx = BattleContext {
-        player_id,
-        chapter_id,
-        episode_id,
-        battle_id,
-    };
-
-    let card_push = generate_initial_deck(&pool, player_id, &fight_group, max_ap).await?;
-
-    let card_deck = card_push.card_group.clone();
-
-    let battle_data = create_battle(&pool, battle_ctx, &fight_group, card_deck.clone()).await?;
-
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard.active_battle = Some(ActiveBattle {
+
+pub struct StartTowerBattle;
+
+impl CmdHandler for StartTowerBattle {
+    const CMD: CmdId = CmdId::StartTowerBattleCmd;
+    type Request = StartTowerBattleRequest;
+    type Reply = StartTowerBattleReply;
+
+    async fn handle(
+        session: &mut Session,
+        request: StartTowerBattleRequest,
+    ) -> Result<StartTowerBattleReply, AppError> {
+        let start_req = request
+            .start_dungeon_request
+            .ok_or(AppError::InvalidRequest)?;
+        let fight_group = start_req.fight_group.ok_or(AppError::InvalidRequest)?;
+
+        let dungeon_type = request.r#type.ok_or(AppError::InvalidRequest)?;
+        let tower_id = request.tower_id.ok_or(AppError::InvalidRequest)?;
+        let layer_id = request.layer_id.ok_or(AppError::InvalidRequest)?;
+        let difficulty = request.difficulty.ok_or(AppError::InvalidRequest)?;
+        let talent_plan_id = request.talent_plan_id.unwrap_or(0);
+
+        let chapter_id = start_req.chapter_id.unwrap_or(0);
+        let episode_id = start_req.episode_id.unwrap_or(0);
+
+        tracing::info!(
+            "Start tower battle: type={}, tower={}, layer={}, episode={}, diff={}, talent={}",
+            dungeon_type,
+            tower_id,
+            layer_id,
+            episode_id,
+            difficulty,
+            talent_plan_id
+        );
+
+        tracing::info!(
+            "Fight group: heroes={:?}, cloth={}, assist_boss={}",
+            fight_group.hero_list,
+            fight_group.cloth_id.unwrap_or(1),
+            fight_group.assist_boss_id.unwrap_or(0)
+        );
+
+        let player_id = session.player_id()?;
+        let pool = session.db();
+
+        let hero_count = fight_group.hero_list.iter().filter(|&&u| u != 0).count();
+
+        let game_data = exceldb::get();
+        let battle_id = game_data
+            .episode
+            .iter()
+            .find(|e| e.id == episode_id)
+            .ok_or(AppError::InvalidRequest)?
+            .battle_id;
+
+        let max_ap = default_max_ap(episode_id, hero_count);
+        let cloth_level = cloth_level(pool, player_id, &fight_group).await?;
+
+        let battle_ctx = BattleContext {
+            player_id,
+            chapter_id,
+            episode_id,
+            battle_id,
+            cloth_level,
+        };
+
+        let card_push = generate_initial_deck(pool, player_id, &fight_group, max_ap).await?;
+
+        let card_deck = card_push.card_group.clone();
+
+        let battle_data = create_battle(pool, battle_ctx, &fight_group, card_deck.clone()).await?;
+
+        let fight_id = chrono::Utc::now().timestamp_millis();
+        let seed: u64 = rand::random();
+
+        if let Some(fight) = battle_data.fight.as_ref() {
+            save_battle_record(
+                pool,
+                player_id,
+                episode_id,
+                fight_id,
+                seed,
+                max_ap,
+                fight,
+                &card_deck,
+                REPLAY_VERSION,
+                cloth_level,
+            )
+            .await?;
+        }
+
+        session.lock().await.active_battle = Some(ActiveBattle {
             tower_type: Some(dungeon_type),
             tower_id: Some(tower_id),
             layer_id: Some(layer_id),
@@ -98,56 +115,49 @@ pub async fn on_start_tower_battle(
             act_point: max_ap,
             power: 15,
             current_deck: card_deck,
+            cloth_opers: vec![],
+            move_opers: vec![],
+            cloth: ClothState::new(cloth_level),
             fight_group: Some(fight_group.clone()),
             is_replay: None,
             replay_episode_id: None,
-            fight_id: Some(chrono::Utc::now().timestamp_millis()),
+            fight_id: Some(fight_id),
             multiplication: None,
+            seed,
         });
-    }
 
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard
-            .send_push(CmdId::CardInfoPushCmd, card_push)
+        session.send_push(CmdId::CardInfoPushCmd, card_push).await?;
+
+        let start_reply = StartTowerBattleReply {
+            start_dungeon_reply: Some(StartDungeonReply {
+                fight: battle_data.fight,
+                round: battle_data.round,
+            }),
+            r#type: Some(dungeon_type),
+            tower_id: Some(tower_id),
+            layer_id: Some(layer_id),
+            difficulty: Some(difficulty),
+            talent_plan_id: Some(talent_plan_id),
+        };
+
+        let dungeon_push = DungeonUpdatePush {
+            dungeon_info: Some(UserDungeon {
+                chapter_id: Some(chapter_id),
+                episode_id: Some(episode_id),
+                star: Some(0),
+                challenge_count: Some(0),
+                has_record: Some(false),
+                left_return_all_num: Some(1),
+                today_pass_num: Some(0),
+                today_total_num: Some(0),
+            }),
+            chapter_type_nums: vec![],
+        };
+
+        session
+            .send_push(CmdId::DungeonUpdatePushCmd, dungeon_push)
             .await?;
-    }
 
-    let start_reply = StartTowerBattleReply {
-        start_dungeon_reply: Some(StartDungeonReply {
-            fight: battle_data.fight,
-            round: battle_data.round,
-        }),
-        r#type: Some(dungeon_type),
-        tower_id: Some(tower_id),
-        layer_id: Some(layer_id),
-        difficulty: Some(difficulty),
-        talent_plan_id: Some(talent_plan_id),
-    };
-
-    let dungeon_push = DungeonUpdatePush {
-        dungeon_info: Some(UserDungeon {
-            chapter_id: Some(chapter_id),
-            episode_id: Some(episode_id),
-            star: Some(0),
-            challenge_count: Some(0),
-            has_record: Some(false),
-            left_return_all_num: Some(1),
-            today_pass_num: Some(0),
-            today_total_num: Some(0),
-        }),
-        chapter_type_nums: vec![],
-    };
-
-    let mut ctx_guard = ctx.lock().await;
-
-    ctx_guard
-        .send_push(CmdId::DungeonUpdatePushCmd, dungeon_push)
-        .await?;
-
-    ctx_guard
-        .send_reply(CmdId::StartTowerBattleCmd, start_reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(start_reply)
+    }
 }
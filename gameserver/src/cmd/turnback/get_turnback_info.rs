@@ -1,18 +1,18 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
-use sonettobuf::CmdId;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::handler::{CmdHandler, Session};
+use sonettobuf::{CmdId, GetTurnbackInfoReply, GetTurnbackInfoRequest};
 
-pub async fn on_get_turnback_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_empty_reply(CmdId::GetTurnbackInfoCmd, Vec::new(), 0, req.up_tag)
-        .await?;
+pub struct GetTurnbackInfo;
 
-    Ok(())
+impl CmdHandler for GetTurnbackInfo {
+    const CMD: CmdId = CmdId::GetTurnbackInfoCmd;
+    type Request = GetTurnbackInfoRequest;
+    type Reply = GetTurnbackInfoReply;
+
+    async fn handle(
+        _session: &mut Session,
+        _request: GetTurnbackInfoRequest,
+    ) -> Result<GetTurnbackInfoReply, AppError> {
+        Ok(GetTurnbackInfoReply::default())
+    }
 }
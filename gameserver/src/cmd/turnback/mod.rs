@@ -1,3 +1,3 @@
 mod get_turnback_info;
 
-pub use get_turnback_info::on_get_turnback_info;
+pub use get_turnback_info::GetTurnbackInfo;
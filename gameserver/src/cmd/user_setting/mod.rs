@@ -1,3 +1,3 @@
 mod get_setting_infos;
 
-pub use get_setting_infos::on_get_setting_infos;
+pub use get_setting_infos::GetSettingInfos;
@@ -1,21 +1,18 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
+use sonettobuf::{CmdId, GetUnlockVoucherInfoReply, GetUnlockVoucherInfoRequest};
 
-use sonettobuf::CmdId;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+pub struct GetUnlockVoucherInfo;
 
-pub async fn on_get_unlock_voucher_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    {
-        let mut ctx_guard = ctx.lock().await;
-        ctx_guard
-            .send_empty_reply(CmdId::GetUnlockVoucherInfoCmd, Vec::new(), 0, req.up_tag)
-            .await?;
-    }
+impl CmdHandler for GetUnlockVoucherInfo {
+    const CMD: CmdId = CmdId::GetUnlockVoucherInfoCmd;
+    type Request = GetUnlockVoucherInfoRequest;
+    type Reply = GetUnlockVoucherInfoReply;
 
-    Ok(())
+    async fn handle(
+        _session: &mut Session,
+        _request: GetUnlockVoucherInfoRequest,
+    ) -> Result<GetUnlockVoucherInfoReply, AppError> {
+        Ok(GetUnlockVoucherInfoReply::default())
+    }
 }
@@ -1,3 +1,3 @@
 mod get_unlock_voucher_info;
 
-pub use get_unlock_voucher_info::on_get_unlock_voucher_info;
+pub use get_unlock_voucher_info::GetUnlockVoucherInfo;
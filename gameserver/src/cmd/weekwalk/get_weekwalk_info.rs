@@ -1,40 +1,36 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::weekwalk;
-use sonettobuf::{CmdId, GetWeekwalkInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, GetWeekwalkInfoReply, GetWeekwalkInfoRequest};
 
-pub async fn on_get_weekwalk_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let (info, map_infos) = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
-        weekwalk::get_weekwalk_info(&ctx_guard.state.db, player_id).await?
-    };
+pub struct GetWeekwalkInfo;
 
-    let reply = GetWeekwalkInfoReply {
-        info: Some(sonettobuf::WeekwalkInfo {
-            time: Some(info.time),
-            end_time: Some(info.end_time),
-            map_info: map_infos,
-            max_layer: Some(info.max_layer),
-            issue_id: Some(info.issue_id),
-            is_pop_deep_rule: Some(info.is_pop_deep_rule),
-            is_open_deep: Some(info.is_open_deep),
-            is_pop_shallow_settle: Some(info.is_pop_shallow_settle),
-            is_pop_deep_settle: Some(info.is_pop_deep_settle),
-            deep_progress: Some(info.deep_progress),
-        }),
-        time_this_week: Some(info.time_this_week),
-    };
+impl CmdHandler for GetWeekwalkInfo {
+    const CMD: CmdId = CmdId::GetWeekwalkInfoCmd;
+    type Request = GetWeekwalkInfoRequest;
+    type Reply = GetWeekwalkInfoReply;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::GetWeekwalkInfoCmd, reply, 0, req.up_tag)
-        .await?;
-    Ok(())
+    async fn handle(
+        session: &mut Session,
+        _request: GetWeekwalkInfoRequest,
+    ) -> Result<GetWeekwalkInfoReply, AppError> {
+        let (info, map_infos) =
+            weekwalk::get_weekwalk_info(session.db(), session.player_id()?).await?;
+
+        Ok(GetWeekwalkInfoReply {
+            info: Some(sonettobuf::WeekwalkInfo {
+                time: Some(info.time),
+                end_time: Some(info.end_time),
+                map_info: map_infos,
+                max_layer: Some(info.max_layer),
+                issue_id: Some(info.issue_id),
+                is_pop_deep_rule: Some(info.is_pop_deep_rule),
+                is_open_deep: Some(info.is_open_deep),
+                is_pop_shallow_settle: Some(info.is_pop_shallow_settle),
+                is_pop_deep_settle: Some(info.is_pop_deep_settle),
+                deep_progress: Some(info.deep_progress),
+            }),
+            time_this_week: Some(info.time_this_week),
+        })
+    }
 }
@@ -1,5 +1,5 @@
 mod get_weekwalk_info;
 mod weekwalk_ver2_get_info;
 
-pub use get_weekwalk_info::on_get_weekwalk_info;
-pub use weekwalk_ver2_get_info::on_weekwalk_ver2_get_info;
+pub use get_weekwalk_info::GetWeekwalkInfo;
+pub use weekwalk_ver2_get_info::WeekwalkVer2GetInfo;
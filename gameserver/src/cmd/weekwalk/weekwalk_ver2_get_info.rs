@@ -1,28 +1,21 @@
 use crate::error::AppError;
-use crate::packet::ClientPacket;
-use crate::state::ConnectionContext;
+use crate::handler::{CmdHandler, Session};
 use database::db::game::weekwalk_v2;
-use sonettobuf::{CmdId, WeekwalkVer2GetInfoReply};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use sonettobuf::{CmdId, WeekwalkVer2GetInfoReply, WeekwalkVer2GetInfoRequest};
 
-pub async fn on_weekwalk_ver2_get_info(
-    ctx: Arc<Mutex<ConnectionContext>>,
-    req: ClientPacket,
-) -> Result<(), AppError> {
-    let info = {
-        let ctx_guard = ctx.lock().await;
-        let player_id = ctx_guard.player_id.ok_or(AppError::NotLoggedIn)?;
+pub struct WeekwalkVer2GetInfo;
 
-        weekwalk_v2::get_weekwalk_v2_info(&ctx_guard.state.db, player_id).await?
-    };
+impl CmdHandler for WeekwalkVer2GetInfo {
+    const CMD: CmdId = CmdId::WeekwalkVer2GetInfoCmd;
+    type Request = WeekwalkVer2GetInfoRequest;
+    type Reply = WeekwalkVer2GetInfoReply;
 
-    let reply = WeekwalkVer2GetInfoReply { info: Some(info) };
+    async fn handle(
+        session: &mut Session,
+        _request: WeekwalkVer2GetInfoRequest,
+    ) -> Result<WeekwalkVer2GetInfoReply, AppError> {
+        let info = weekwalk_v2::get_weekwalk_v2_info(session.db(), session.player_id()?).await?;
 
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_reply(CmdId::WeekwalkVer2GetInfoCmd, reply, 0, req.up_tag)
-        .await?;
-
-    Ok(())
+        Ok(WeekwalkVer2GetInfoReply { info: Some(info) })
+    }
 }
@@ -0,0 +1,424 @@
+//! tokio_util codecs for the game port framing.
+//!
+//! Every frame starts with a 4 byte big endian length that doesn't count itself.
+//! Client -> server: len i32 | sequence i32 | cmd_id i16 | up_tag u8 | body
+//! Server -> client: len u32 | cmd_id i16 | result_code u16 | up_tag u8 | down_tag u8 | body
+//!
+//! The server decodes with `ClientFrameCodec` and encodes with `ServerFrameCodec`,
+//! a test client does the opposite.
+
+use crate::error::{AppError, PacketError};
+use crate::packet::{ClientPacket, ServerPacket};
+use byteorder::{BE, ByteOrder};
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+const LENGTH_PREFIX: usize = 4;
+
+/// Default cap on decoded frames, matches `[network] max_frame_size`
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ClientFrameCodec {
+    max_frame_size: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ServerFrameCodec {
+    max_frame_size: usize,
+}
+
+impl ClientFrameCodec {
+    /// `max_frame_size` counts the length prefix and only applies to decoding
+    pub fn new(max_frame_size: usize) -> Self {
+        Self { max_frame_size }
+    }
+}
+
+impl Default for ClientFrameCodec {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_FRAME_SIZE)
+    }
+}
+
+impl ServerFrameCodec {
+    /// `max_frame_size` counts the length prefix and only applies to decoding
+    pub fn new(max_frame_size: usize) -> Self {
+        Self { max_frame_size }
+    }
+}
+
+impl Default for ServerFrameCodec {
+    fn default() -> Self {
+        // server replies (player info, hero lists...) are a lot bigger than requests
+        Self::new(16 * DEFAULT_MAX_FRAME_SIZE)
+    }
+}
+
+/// Size of the next complete frame in `src` (prefix included), None if more bytes are needed
+fn next_frame_len(
+    src: &mut BytesMut,
+    header: usize,
+    max_frame_size: usize,
+) -> Result<Option<usize>, AppError> {
+    if src.len() < LENGTH_PREFIX {
+        return Ok(None);
+    }
+
+    let raw_len = BE::read_i32(&src[..LENGTH_PREFIX]);
+    let body_len = usize::try_from(raw_len).map_err(|_| PacketError::NegativeLength(raw_len))?;
+    let frame_len = body_len + LENGTH_PREFIX;
+
+    if frame_len < header {
+        return Err(PacketError::LengthLessThanHeader(header, frame_len).into());
+    }
+    if frame_len > max_frame_size {
+        return Err(PacketError::FrameTooLarge(frame_len, max_frame_size).into());
+    }
+
+    if src.len() < frame_len {
+        src.reserve(frame_len - src.len());
+        return Ok(None);
+    }
+
+    Ok(Some(frame_len))
+}
+
+/// Body length has to fit the signed prefix the client side uses
+fn check_encodable(header: usize, body_len: usize) -> Result<(), AppError> {
+    let frame_len = header + body_len;
+    if frame_len - LENGTH_PREFIX > i32::MAX as usize {
+        return Err(PacketError::FrameTooLarge(frame_len, i32::MAX as usize).into());
+    }
+    Ok(())
+}
+
+impl Decoder for ClientFrameCodec {
+    type Item = ClientPacket;
+    type Error = AppError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(frame_len) =
+            next_frame_len(src, ClientPacket::PACKET_HEADER, self.max_frame_size)?
+        else {
+            return Ok(None);
+        };
+
+        let frame = src.split_to(frame_len);
+        ClientPacket::decode(&frame).map(Some)
+    }
+}
+
+impl Encoder<ClientPacket> for ClientFrameCodec {
+    type Error = AppError;
+
+    fn encode(&mut self, item: ClientPacket, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        check_encodable(ClientPacket::PACKET_HEADER, item.data.len())?;
+        dst.extend_from_slice(&item.encode());
+        Ok(())
+    }
+}
+
+impl Decoder for ServerFrameCodec {
+    type Item = ServerPacket;
+    type Error = AppError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let Some(frame_len) =
+            next_frame_len(src, ServerPacket::PACKET_HEADER, self.max_frame_size)?
+        else {
+            return Ok(None);
+        };
+
+        let frame = src.split_to(frame_len);
+        ServerPacket::decode(&frame).map(Some)
+    }
+}
+
+impl Encoder<ServerPacket> for ServerFrameCodec {
+    type Error = AppError;
+
+    fn encode(&mut self, item: ServerPacket, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        check_encodable(ServerPacket::PACKET_HEADER, item.data.len())?;
+        dst.extend_from_slice(&item.encode());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_packet(sequence: i32, cmd_id: i16, up_tag: u8, data: &[u8]) -> ClientPacket {
+        ClientPacket {
+            sequence,
+            cmd_id,
+            up_tag,
+            data: data.to_vec(),
+        }
+    }
+
+    fn server_packet(
+        cmd_id: i16,
+        result_code: u16,
+        up_tag: u8,
+        down_tag: u8,
+        data: &[u8],
+    ) -> ServerPacket {
+        ServerPacket {
+            cmd_id,
+            result_code,
+            up_tag,
+            down_tag,
+            data: data.to_vec(),
+        }
+    }
+
+    fn encode_client(packet: ClientPacket) -> BytesMut {
+        let mut buf = BytesMut::new();
+        ClientFrameCodec::default()
+            .encode(packet, &mut buf)
+            .unwrap();
+        buf
+    }
+
+    fn encode_server(packet: ServerPacket) -> BytesMut {
+        let mut buf = BytesMut::new();
+        ServerFrameCodec::default()
+            .encode(packet, &mut buf)
+            .unwrap();
+        buf
+    }
+
+    fn roundtrip_client(packet: ClientPacket) -> ClientPacket {
+        let mut buf = encode_client(packet);
+        let decoded = ClientFrameCodec::default()
+            .decode(&mut buf)
+            .unwrap()
+            .unwrap();
+        assert!(buf.is_empty());
+        decoded
+    }
+
+    fn roundtrip_server(packet: ServerPacket) -> ServerPacket {
+        let mut buf = encode_server(packet);
+        let decoded = ServerFrameCodec::default()
+            .decode(&mut buf)
+            .unwrap()
+            .unwrap();
+        assert!(buf.is_empty());
+        decoded
+    }
+
+    fn frame_with_len(len: i32, rest: &[u8]) -> BytesMut {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&len.to_be_bytes());
+        buf.extend_from_slice(rest);
+        buf
+    }
+
+    #[test]
+    fn client_frame_layout() {
+        let buf = encode_client(client_packet(0x01020304, 0x0506, 0x07, &[0xAA, 0xBB]));
+        assert_eq!(&buf[..], &[0, 0, 0, 9, 1, 2, 3, 4, 5, 6, 7, 0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn server_frame_layout() {
+        let buf = encode_server(server_packet(0x0102, 0x0304, 0x05, 0x06, &[0xAA]));
+        assert_eq!(&buf[..], &[0, 0, 0, 7, 1, 2, 3, 4, 5, 6, 0xAA]);
+    }
+
+    #[test]
+    fn client_sequence_roundtrip() {
+        for sequence in [0, 1, -1, i32::MIN, i32::MAX] {
+            let decoded = roundtrip_client(client_packet(sequence, 1, 0, &[]));
+            assert_eq!(decoded.sequence, sequence);
+        }
+    }
+
+    #[test]
+    fn client_cmd_id_roundtrip() {
+        // unknown ids still decode, the dispatcher decides what to do with them
+        for cmd_id in [0, 1, -1, i16::MIN, i16::MAX] {
+            let decoded = roundtrip_client(client_packet(1, cmd_id, 0, &[]));
+            assert_eq!(decoded.cmd_id, cmd_id);
+        }
+    }
+
+    #[test]
+    fn client_up_tag_roundtrip() {
+        for up_tag in [0, 1, 127, 128, 255] {
+            let decoded = roundtrip_client(client_packet(1, 1, up_tag, &[]));
+            assert_eq!(decoded.up_tag, up_tag);
+        }
+    }
+
+    #[test]
+    fn client_body_roundtrip() {
+        let body: Vec<u8> = (0..=255).collect();
+        let decoded = roundtrip_client(client_packet(7, 42, 3, &body));
+        assert_eq!(decoded.data, body);
+        assert_eq!(
+            roundtrip_client(client_packet(7, 42, 3, &[])).data,
+            Vec::<u8>::new()
+        );
+    }
+
+    #[test]
+    fn server_cmd_id_roundtrip() {
+        for cmd_id in [0, 1, -1, i16::MIN, i16::MAX] {
+            let decoded = roundtrip_server(server_packet(cmd_id, 0, 0, 0, &[]));
+            assert_eq!(decoded.cmd_id, cmd_id);
+        }
+    }
+
+    #[test]
+    fn server_result_code_roundtrip() {
+        for result_code in [0, 1, 0x7FFF, 0x8000, u16::MAX] {
+            let decoded = roundtrip_server(server_packet(1, result_code, 0, 0, &[]));
+            assert_eq!(decoded.result_code, result_code);
+        }
+    }
+
+    #[test]
+    fn server_tags_roundtrip() {
+        for tag in [0, 1, 127, 128, 255] {
+            let decoded = roundtrip_server(server_packet(1, 0, tag, 255 - tag, &[]));
+            assert_eq!(decoded.up_tag, tag);
+            assert_eq!(decoded.down_tag, 255 - tag);
+        }
+    }
+
+    #[test]
+    fn server_body_roundtrip() {
+        let body: Vec<u8> = (0..=255).rev().collect();
+        let decoded = roundtrip_server(server_packet(9, 0, 1, 2, &body));
+        assert_eq!(decoded.data, body);
+    }
+
+    #[test]
+    fn decodes_byte_by_byte() {
+        let encoded = encode_client(client_packet(5, 6, 7, &[1, 2, 3]));
+        let mut codec = ClientFrameCodec::default();
+        let mut buf = BytesMut::new();
+
+        for (i, byte) in encoded.iter().enumerate() {
+            buf.extend_from_slice(&[*byte]);
+            let decoded = codec.decode(&mut buf).unwrap();
+            if i + 1 < encoded.len() {
+                assert!(decoded.is_none(), "frame completed early at byte {}", i);
+            } else {
+                assert_eq!(decoded.unwrap().data, vec![1, 2, 3]);
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_back_to_back_frames() {
+        let mut buf = encode_server(server_packet(1, 0, 1, 1, &[1]));
+        buf.extend_from_slice(&encode_server(server_packet(2, 0, 2, 2, &[2, 2])));
+        buf.extend_from_slice(&[0, 0]); // start of a third frame
+
+        let mut codec = ServerFrameCodec::default();
+        assert_eq!(codec.decode(&mut buf).unwrap().unwrap().cmd_id, 1);
+        assert_eq!(codec.decode(&mut buf).unwrap().unwrap().data, vec![2, 2]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        assert_eq!(buf.len(), 2);
+    }
+
+    #[test]
+    fn rejects_negative_length() {
+        let mut buf = frame_with_len(-1, &[0; 16]);
+        let err = ClientFrameCodec::default().decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            err,
+            AppError::Packet(PacketError::NegativeLength(-1))
+        ));
+
+        let mut buf = frame_with_len(i32::MIN, &[]);
+        assert!(ClientFrameCodec::default().decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn rejects_length_shorter_than_header() {
+        // 6 bytes after the prefix, client header needs 7
+        let mut buf = frame_with_len(6, &[0; 6]);
+        let err = ClientFrameCodec::default().decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            err,
+            AppError::Packet(PacketError::LengthLessThanHeader(11, 10))
+        ));
+
+        let mut buf = frame_with_len(5, &[0; 5]);
+        let err = ServerFrameCodec::default().decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            err,
+            AppError::Packet(PacketError::LengthLessThanHeader(10, 9))
+        ));
+
+        let mut buf = frame_with_len(0, &[]);
+        assert!(ClientFrameCodec::default().decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn accepts_header_only_frames() {
+        let mut buf = frame_with_len(7, &[0; 7]);
+        let decoded = ClientFrameCodec::default()
+            .decode(&mut buf)
+            .unwrap()
+            .unwrap();
+        assert!(decoded.data.is_empty());
+
+        let mut buf = frame_with_len(6, &[0; 6]);
+        let decoded = ServerFrameCodec::default()
+            .decode(&mut buf)
+            .unwrap()
+            .unwrap();
+        assert!(decoded.data.is_empty());
+    }
+
+    #[test]
+    fn rejects_oversized_frame_before_buffering_it() {
+        let mut codec = ClientFrameCodec::new(64);
+
+        // exactly at the limit is fine
+        let mut buf = frame_with_len(60, &[0; 60]);
+        assert!(codec.decode(&mut buf).unwrap().is_some());
+
+        // only the prefix has arrived, still rejected right away
+        let mut buf = frame_with_len(61, &[]);
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert!(matches!(
+            err,
+            AppError::Packet(PacketError::FrameTooLarge(65, 64))
+        ));
+
+        let mut buf = frame_with_len(i32::MAX, &[]);
+        assert!(ServerFrameCodec::default().decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn waits_for_truncated_frames() {
+        let mut codec = ClientFrameCodec::default();
+
+        let mut buf = BytesMut::from(&[0u8, 0, 0][..]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+
+        let mut encoded = encode_client(client_packet(1, 2, 3, &[9; 32]));
+        let full_len = encoded.len();
+        let mut buf = encoded.split_to(full_len - 1);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        assert_eq!(buf.len(), full_len - 1);
+
+        buf.extend_from_slice(&encoded);
+        assert_eq!(codec.decode(&mut buf).unwrap().unwrap().data, vec![9; 32]);
+    }
+
+    #[test]
+    fn server_frames_do_not_parse_as_client_frames() {
+        // the headers differ by one byte, a 10 byte server header is too short for a client
+        let mut buf = encode_server(server_packet(1, 0, 0, 0, &[]));
+        assert!(ClientFrameCodec::default().decode(&mut buf).is_err());
+    }
+}
@@ -63,6 +63,12 @@ pub enum PacketError {
     #[error("Packet length mismatch (expected: {0}, actual: {1})")]
     LengthMismatch(usize, usize),
 
+    #[error("Negative packet length: {0}")]
+    NegativeLength(i32),
+
+    #[error("Frame too large (size: {0}, max: {1})")]
+    FrameTooLarge(usize, usize),
+
     #[error("Client packet data decode failed: {0}")]
     ClientPacketDataDecodeFail(#[from] prost::DecodeError),
 
@@ -84,4 +90,8 @@ pub enum CmdError {
 
     #[error("Received server packet as client request")]
     ServerPacketReceivedAsClient,
+
+    /// Not a failure, the handler answers with this result code and an empty body
+    #[error("Rejected with result code {0}")]
+    ResultCode(i16),
 }
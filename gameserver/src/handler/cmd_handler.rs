@@ -0,0 +1,69 @@
+use super::Session;
+use crate::error::AppError;
+use crate::packet::ClientPacket;
+use crate::state::ConnectionContext;
+use futures::future::BoxFuture;
+use prost::Message;
+use sonettobuf::CmdId;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// A cmd with a protobuf request and reply.
+///
+/// The dispatcher rejects it before login when `REQUIRES_LOGIN` is set, decodes
+/// the request and sends the returned reply under `CMD`. Return
+/// `CmdError::ResultCode` to answer with an error code instead.
+pub trait CmdHandler: 'static {
+    const CMD: CmdId;
+    const REQUIRES_LOGIN: bool = true;
+    /// Reply with the fixed down_tag instead of reserving the next one
+    const FIXED_DOWN_TAG: bool = false;
+
+    type Request: Message + Default;
+    type Reply: Message;
+
+    fn handle(
+        session: &mut Session,
+        request: Self::Request,
+    ) -> impl Future<Output = Result<Self::Reply, AppError>> + Send;
+}
+
+pub(super) type RouteFn =
+    fn(Arc<Mutex<ConnectionContext>>, ClientPacket) -> BoxFuture<'static, Result<(), AppError>>;
+
+/// One registered cmd, see `routes!` in handler.rs
+#[derive(Clone, Copy)]
+pub(super) struct Route {
+    pub requires_login: bool,
+    pub call: RouteFn,
+}
+
+impl Route {
+    pub fn typed<H: CmdHandler>() -> Self {
+        Self {
+            requires_login: H::REQUIRES_LOGIN,
+            call: |ctx, req| Box::pin(run::<H>(ctx, req)),
+        }
+    }
+
+    /// Handlers that parse their own body and send their own reply
+    pub fn raw(requires_login: bool, call: RouteFn) -> Self {
+        Self {
+            requires_login,
+            call,
+        }
+    }
+}
+
+async fn run<H: CmdHandler>(
+    ctx: Arc<Mutex<ConnectionContext>>,
+    req: ClientPacket,
+) -> Result<(), AppError> {
+    let request = H::Request::decode(&req.data[..])?;
+
+    let mut session = Session::new(ctx, req.up_tag).await;
+    let reply = H::handle(&mut session, request).await?;
+
+    session.finish(H::CMD, reply, H::FIXED_DOWN_TAG).await
+}
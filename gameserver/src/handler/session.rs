@@ -0,0 +1,96 @@
+use crate::error::AppError;
+use crate::state::{AppState, ConnectionContext};
+use crate::utils::common::encode_message;
+use prost::Message;
+use sonettobuf::CmdId;
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use tokio::sync::{Mutex, MutexGuard};
+
+/// What a `CmdHandler` gets instead of the raw connection context.
+/// The context is only locked when the handler asks for it.
+pub struct Session {
+    ctx: Arc<Mutex<ConnectionContext>>,
+    state: Arc<AppState>,
+    player_id: Option<i64>,
+    up_tag: u8,
+    /// Pushes the client expects after the reply, queued by `finish`
+    after_reply: Vec<(CmdId, Vec<u8>)>,
+}
+
+impl Session {
+    pub(super) async fn new(ctx: Arc<Mutex<ConnectionContext>>, up_tag: u8) -> Self {
+        let (state, player_id) = {
+            let ctx_guard = ctx.lock().await;
+            (ctx_guard.state.clone(), ctx_guard.player_id)
+        };
+
+        Self {
+            ctx,
+            state,
+            player_id,
+            up_tag,
+            after_reply: Vec::new(),
+        }
+    }
+
+    /// Always Ok for handlers with `REQUIRES_LOGIN`, the dispatcher checks it first
+    pub fn player_id(&self) -> Result<i64, AppError> {
+        self.player_id.ok_or(AppError::NotLoggedIn)
+    }
+
+    pub fn db(&self) -> &SqlitePool {
+        &self.state.db
+    }
+
+    pub fn state(&self) -> &Arc<AppState> {
+        &self.state
+    }
+
+    pub fn up_tag(&self) -> u8 {
+        self.up_tag
+    }
+
+    /// The shared context, for helpers that still take it directly
+    pub fn context(&self) -> &Arc<Mutex<ConnectionContext>> {
+        &self.ctx
+    }
+
+    pub async fn lock(&self) -> MutexGuard<'_, ConnectionContext> {
+        self.ctx.lock().await
+    }
+
+    /// Queue a push, it goes out before the reply
+    pub async fn send_push<T: Message>(&self, cmd_id: CmdId, msg: T) -> Result<(), AppError> {
+        self.ctx.lock().await.send_push(cmd_id, msg).await
+    }
+
+    /// Queue a push that goes out right after the reply
+    pub fn push_after_reply<T: Message>(&mut self, cmd_id: CmdId, msg: T) -> Result<(), AppError> {
+        self.after_reply.push((cmd_id, encode_message(&msg)?));
+        Ok(())
+    }
+
+    pub(super) async fn finish<T: Message>(
+        self,
+        cmd_id: CmdId,
+        reply: T,
+        fixed_down_tag: bool,
+    ) -> Result<(), AppError> {
+        let mut ctx_guard = self.ctx.lock().await;
+
+        if fixed_down_tag {
+            ctx_guard
+                .send_reply_fixed(cmd_id, reply, 0, self.up_tag)
+                .await?;
+        } else {
+            ctx_guard.send_reply(cmd_id, reply, 0, self.up_tag).await?;
+        }
+
+        for (push_cmd, body) in self.after_reply {
+            ctx_guard.send_raw_push(push_cmd, body).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,4 +1,9 @@
+pub mod client;
+pub mod cmd;
+pub mod codec;
 pub mod error;
+pub mod handler;
 pub mod packet;
+pub mod server;
 pub mod state;
 pub mod utils;
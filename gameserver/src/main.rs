@@ -1,24 +1,12 @@
-use crate::{
-    client::handle_client,
-    state::{AppState, ConnectionContext},
-};
 use common::{config, excel_data_directory, game_port, host, init_config, init_tracing};
 use database::{DatabaseSettings, connect_to, run_migrations};
+use gameserver::{server, state::AppState};
 use std::path::PathBuf;
 use std::sync::Arc;
 
 use tokio::net::TcpListener;
-use tokio::sync::Mutex;
 use tracing::info;
 
-mod client;
-mod cmd;
-mod error;
-mod handler;
-mod packet;
-mod state;
-mod utils;
-
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     init_tracing();
@@ -60,34 +48,5 @@ async fn main() -> anyhow::Result<()> {
     let listener = TcpListener::bind(&addr).await?;
     info!("Listening on tcp://{}", &addr);
 
-    loop {
-        let (raw_socket, client) = listener.accept().await?;
-        tracing::info!("New client connected: {:?}", client);
-
-        let state = state.clone();
-        let socket = Arc::new(Mutex::new(raw_socket));
-
-        tokio::spawn(async move {
-            let ctx = Arc::new(Mutex::new(ConnectionContext::new(
-                socket.clone(),
-                state.clone(),
-            )));
-
-            let result = handle_client(ctx.clone()).await;
-
-            let ctx_guard = ctx.lock().await;
-            if let Some(player_id) = ctx_guard.player_id {
-                if let Err(e) = ctx_guard.save_current_player_state().await {
-                    tracing::error!("Failed to save player state for {}: {}", player_id, e);
-                }
-
-                tracing::warn!("Player {} disconnected and saved progress", player_id);
-                ctx_guard.state.unregister_session(player_id);
-            }
-
-            if let Err(e) = result {
-                tracing::error!("Client handler error: {e}");
-            }
-        });
-    }
+    server::run(listener, state).await
 }
@@ -0,0 +1,87 @@
+use crate::utils::capture::CaptureRecorder;
+use crate::{
+    client::{handle_client, write_packets},
+    state::{AppState, ConnectionContext, OUTBOUND_QUEUE_SIZE},
+};
+use common::{capture_config, network_config};
+use socket2::{SockRef, TcpKeepalive};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::{Mutex, mpsc};
+
+/// Accept loop of the game port, every client gets a reader and a writer task.
+/// Only returns if the listener fails.
+pub async fn run(listener: TcpListener, state: Arc<AppState>) -> anyhow::Result<()> {
+    loop {
+        let (raw_socket, client) = listener.accept().await?;
+
+        let net = network_config();
+        let Some(slot) = state.try_acquire_connection(client.ip(), net.max_connections_per_ip)
+        else {
+            tracing::warn!(
+                "Refusing {:?}: already {} connections from this IP",
+                client,
+                net.max_connections_per_ip
+            );
+            continue;
+        };
+        tracing::info!("New client connected: {:?}", client);
+
+        if let Some(idle) = net.tcp_keepalive() {
+            let keepalive = TcpKeepalive::new().with_time(idle).with_interval(idle);
+            if let Err(e) = SockRef::from(&raw_socket).set_tcp_keepalive(&keepalive) {
+                tracing::warn!("Failed to enable TCP keepalive for {:?}: {e}", client);
+            }
+        }
+
+        let state = state.clone();
+        let (reader, writer) = raw_socket.into_split();
+        let (outbound_tx, outbound_rx) = mpsc::channel(OUTBOUND_QUEUE_SIZE);
+
+        tokio::spawn(async move {
+            if let Err(e) = write_packets(writer, outbound_rx).await {
+                tracing::debug!("Client writer closed: {e}");
+            }
+        });
+
+        tokio::spawn(async move {
+            let mut conn = ConnectionContext::new(outbound_tx, state.clone());
+            let capture = capture_config();
+            if capture.enabled {
+                match CaptureRecorder::create(&capture.directory, client) {
+                    Ok(recorder) => {
+                        tracing::info!("Capturing {:?} to {}", client, recorder.path().display());
+                        conn.capture = Some(recorder);
+                    }
+                    Err(e) => tracing::warn!("Failed to start capture for {:?}: {e}", client),
+                }
+            }
+            let ctx = Arc::new(Mutex::new(conn));
+
+            let result = handle_client(ctx.clone(), reader).await;
+
+            let ctx_guard = ctx.lock().await;
+            if let Some(player_id) = ctx_guard.player_id {
+                // a kicked session already handed its state over, saving again would clobber it
+                if ctx_guard.state.unregister_session(player_id, &ctx) {
+                    if let Err(e) = ctx_guard.save_current_player_state().await {
+                        tracing::error!("Failed to save player state for {}: {}", player_id, e);
+                    }
+
+                    tracing::warn!("Player {} disconnected and saved progress", player_id);
+                    ctx_guard
+                        .state
+                        .park_session(player_id, ctx_guard.session.clone());
+                } else {
+                    tracing::warn!("Player {} old session closed", player_id);
+                }
+            }
+
+            if let Err(e) = result {
+                tracing::error!("Client handler error: {e}");
+            }
+
+            drop(slot);
+        });
+    }
+}
@@ -1,33 +1,39 @@
+use common::network_config;
+use prost::Message;
+use sonettobuf::CmdId;
 use sqlx::SqlitePool;
+use std::net::IpAddr;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::Mutex;
 
-use super::ConnectionContext;
+use super::{ConnectionContext, SessionState};
+use crate::error::AppError;
 
 /// App-level shared state
 pub struct AppState {
-    next_down_tag: Mutex<u8>,
     pub db: SqlitePool,
     sessions: dashmap::DashMap<i64, Arc<Mutex<ConnectionContext>>>,
+    unhandled_cmds: dashmap::DashMap<CmdId, u64>,
+    /// Session state of players whose socket closed and when it was parked,
+    /// picked up again on relogin within `[network] reconnect_window_secs`
+    parked_sessions: dashmap::DashMap<i64, (Instant, SessionState)>,
+    /// Open sockets per client IP, see `[network] max_connections_per_ip`
+    connections_per_ip: dashmap::DashMap<IpAddr, usize>,
 }
 
 #[allow(dead_code)]
 impl AppState {
     pub fn new(db: SqlitePool) -> Self {
         Self {
-            next_down_tag: Mutex::new(0),
             db,
             sessions: dashmap::DashMap::new(),
+            unhandled_cmds: dashmap::DashMap::new(),
+            parked_sessions: dashmap::DashMap::new(),
+            connections_per_ip: dashmap::DashMap::new(),
         }
     }
 
-    pub async fn reserve_down_tag(&self) -> u8 {
-        let mut tag = self.next_down_tag.lock().await;
-        let current = *tag & 0x7F;
-        *tag = (*tag + 1) & 0x7F;
-        current
-    }
-
     pub fn get_connection_context(&self, player_id: i64) -> Option<Arc<Mutex<ConnectionContext>>> {
         self.sessions.get(&player_id).map(|v| Arc::clone(v.value()))
     }
@@ -36,7 +42,105 @@ impl AppState {
         self.sessions.insert(player_id, ctx);
     }
 
-    pub fn unregister_session(&self, player_id: i64) {
-        self.sessions.remove(&player_id);
+    /// Removes the session only if it still belongs to `ctx`, a newer login of
+    /// the same player may already have replaced it. Returns true if removed.
+    pub fn unregister_session(&self, player_id: i64, ctx: &Arc<Mutex<ConnectionContext>>) -> bool {
+        self.sessions
+            .remove_if(&player_id, |_, current| Arc::ptr_eq(current, ctx))
+            .is_some()
+    }
+
+    /// Keeps the session for a reconnect, and lets go of the ones whose
+    /// window has passed so every player who ever left isn't held forever
+    pub fn park_session(&self, player_id: i64, session: SessionState) {
+        let Some(window) = network_config().reconnect_window() else {
+            return;
+        };
+
+        self.parked_sessions
+            .retain(|_, (parked_at, _)| parked_at.elapsed() < window);
+        self.parked_sessions
+            .insert(player_id, (Instant::now(), session));
+    }
+
+    /// The player's parked session, if it's still within the reconnect window
+    pub fn take_parked_session(&self, player_id: i64) -> Option<SessionState> {
+        let window = network_config().reconnect_window()?;
+        let (_, (parked_at, session)) = self.parked_sessions.remove(&player_id)?;
+
+        (parked_at.elapsed() < window).then_some(session)
+    }
+
+    /// Deliver a push to an online player from any task (mail, resets, GM grants...).
+    /// Returns false if the player has no session.
+    pub async fn push_to_player<T: Message>(
+        &self,
+        player_id: i64,
+        cmd_id: CmdId,
+        msg: T,
+    ) -> Result<bool, AppError> {
+        let Some(ctx) = self.get_connection_context(player_id) else {
+            return Ok(false);
+        };
+
+        let mut ctx_guard = ctx.lock().await;
+        ctx_guard.push_now(cmd_id, msg).await?;
+        Ok(true)
+    }
+
+    /// Bumps the counter for a cmd with no handler and returns the new count
+    pub fn record_unhandled_cmd(&self, cmd_id: CmdId) -> u64 {
+        let mut count = self.unhandled_cmds.entry(cmd_id).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    /// Unhandled cmds seen since startup, most requested first
+    pub fn unhandled_cmd_counts(&self) -> Vec<(CmdId, u64)> {
+        let mut counts: Vec<_> = self
+            .unhandled_cmds
+            .iter()
+            .map(|e| (*e.key(), *e.value()))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// Claims a connection slot for `ip`, None if it already has `limit` open (0 = no limit).
+    /// The slot is given back when the returned guard is dropped.
+    pub fn try_acquire_connection(
+        self: &Arc<Self>,
+        ip: IpAddr,
+        limit: usize,
+    ) -> Option<ConnectionSlot> {
+        let mut count = self.connections_per_ip.entry(ip).or_insert(0);
+        if limit > 0 && *count >= limit {
+            return None;
+        }
+        *count += 1;
+
+        Some(ConnectionSlot {
+            state: Arc::clone(self),
+            ip,
+        })
+    }
+
+    fn release_connection(&self, ip: IpAddr) {
+        self.connections_per_ip.remove_if_mut(&ip, |_, count| {
+            *count = count.saturating_sub(1);
+            *count == 0
+        });
+    }
+}
+
+/// Held for as long as a client socket is open
+pub struct ConnectionSlot {
+    state: Arc<AppState>,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.state.release_connection(self.ip);
     }
 }
@@ -0,0 +1,90 @@
+use sonettobuf::{CardInfo, FightEntityInfo};
+
+use super::buff::{self, ControlKind};
+use super::data::CombatData;
+
+/// How monsters pick their skills, selected by `battle.aiLink`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AiBehaviour {
+    /// Cycle through the active skills, unique skill once ex points are full
+    Rotation,
+}
+
+impl AiBehaviour {
+    pub fn from_ai_link(ai_link: i32) -> Self {
+        if ai_link != 0 {
+            tracing::debug!("No behaviour for ai_link {}, using rotation", ai_link);
+        }
+
+        AiBehaviour::Rotation
+    }
+
+    pub fn choose_skill(
+        self,
+        data: &impl CombatData,
+        entity: &FightEntityInfo,
+        round: i32,
+    ) -> Option<MonsterAction> {
+        match self {
+            AiBehaviour::Rotation => rotation(data, entity, round),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MonsterAction {
+    pub skill_id: i32,
+    pub is_unique: bool,
+}
+
+fn rotation(data: &impl CombatData, entity: &FightEntityInfo, round: i32) -> Option<MonsterAction> {
+    let unique_skill = entity.ex_skill.unwrap_or(0);
+    // 0 when the monster has no unique skill point set
+    let unique_point = data
+        .monster(entity.model_id.unwrap_or(0))
+        .map(|m| m.unique_skill_point)
+        .unwrap_or(0);
+
+    let sealed = buff::has_control(data, entity, ControlKind::Seal);
+
+    if !sealed
+        && unique_skill != 0
+        && unique_point > 0
+        && entity.ex_point.unwrap_or(0) >= unique_point
+    {
+        return Some(MonsterAction {
+            skill_id: unique_skill,
+            is_unique: true,
+        });
+    }
+
+    let skills = if entity.skill_group1.is_empty() {
+        &entity.skill_group2
+    } else {
+        &entity.skill_group1
+    };
+
+    let idx = (round - 1).max(0) as usize % skills.len().max(1);
+
+    skills.get(idx).map(|&skill_id| MonsterAction {
+        skill_id,
+        is_unique: false,
+    })
+}
+
+/// What the monster shows as its intent for the round
+pub fn intent_card(entity: &FightEntityInfo, action: MonsterAction) -> CardInfo {
+    CardInfo {
+        uid: entity.uid,
+        hero_id: entity.model_id,
+        skill_id: Some(action.skill_id),
+        card_type: Some(0),
+        status: Some(0),
+        temp_card: Some(false),
+        target_uid: Some(0),
+        energy: Some(0),
+        area_red_or_blue: Some(0),
+        heat_id: Some(0),
+        ..Default::default()
+    }
+}
@@ -1,13 +1,12 @@
 use sonettobuf::{BeginRoundOper, CardInfo};
 
-
-
 pub fn generate_auto_opers(deck: &[CardInfo]) -> Vec<BeginRoundOper> {
     let mut opers = Vec::new();
 
-    // Pick first 3 cards (simple baseline)
-    for (idx, _card) in deck.iter().take(3).enumerate() {
-        let card_index = idx as i32;
+    // Play the first 3 cards (simple baseline), the hand shrinks
+    // with every play so the next card is always at the front
+    for _card in deck.iter().take(3) {
+        let card_index = 1;
 
         // 1) Select card
         opers.push(BeginRoundOper {
@@ -0,0 +1,463 @@
+use serde::Deserialize;
+use sonettobuf::{BuffInfo, FightEntityInfo, HeroAttribute};
+use std::collections::HashMap;
+
+use super::data::CombatData;
+
+/// When a buff's effect fires, passive ones only modify stats or block actions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum BuffTrigger {
+    Passive,
+    RoundStart,
+    OnHit,
+    OnCardPlay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum BuffAttr {
+    Attack,
+    Defense,
+    MDefense,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ControlKind {
+    Stun, // no actions at all
+    Seal, // no ex or unique skills
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum BuffEffect {
+    /// Stat change per layer, negative for debuffs
+    Attr {
+        attr: BuffAttr,
+        ratio: f32,
+    },
+    /// Share of the holder's max hp lost per layer each time it fires
+    DamageOverTime {
+        ratio: f32,
+    },
+    /// Absorbs the caster's attack times this, the amount left is kept in `BuffInfo.count`
+    Shield {
+        ratio: f32,
+    },
+    Control(ControlKind),
+}
+
+/// How the server runs a buff, looked up by the buff id the client knows
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct BuffTemplate {
+    pub buff_id: i32,
+    pub effect: BuffEffect,
+    pub trigger: BuffTrigger,
+    /// Rounds it lasts, 0 for until removed
+    pub duration: i32,
+    pub max_layer: i32,
+}
+
+/// A buff a skill puts on its caster or its target when it lands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct SkillBuff {
+    pub skill_id: i32,
+    pub buff_id: i32,
+    pub on_caster: bool,
+}
+
+/// What happened to a buff, mapped to BUFFADD / BUFFUPDATE / BUFFDEL effects
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuffChange {
+    Added(BuffInfo),
+    Updated(BuffInfo),
+    Removed(BuffInfo),
+}
+
+/// Next free buff uid, buffs live on the entities so this survives between rounds
+pub fn next_buff_uid(entities: &HashMap<i64, FightEntityInfo>) -> i64 {
+    entities
+        .values()
+        .flat_map(|e| e.buffs.iter())
+        .filter_map(|b| b.uid)
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+/// Adds a layer to an existing buff and refreshes it, or puts a new one on
+pub fn add_buff(
+    entity: &mut FightEntityInfo,
+    template: &BuffTemplate,
+    uid: i64,
+    from_uid: i64,
+    shield: i32,
+) -> BuffChange {
+    if let Some(buff) = entity
+        .buffs
+        .iter_mut()
+        .find(|b| b.buff_id == Some(template.buff_id))
+    {
+        buff.layer = Some((buff.layer.unwrap_or(1) + 1).min(template.max_layer));
+        buff.duration = Some(template.duration);
+        buff.from_uid = Some(from_uid);
+        if shield > 0 {
+            buff.count = Some(buff.count.unwrap_or(0).max(shield));
+        }
+        return BuffChange::Updated(buff.clone());
+    }
+
+    let buff = BuffInfo {
+        buff_id: Some(template.buff_id),
+        duration: Some(template.duration),
+        uid: Some(uid),
+        ex_info: Some(0),
+        from_uid: Some(from_uid),
+        count: Some(shield),
+        layer: Some(1),
+        r#type: Some(0),
+        act_common_params: Some(String::new()),
+        act_info: vec![],
+    };
+
+    entity.buffs.push(buff.clone());
+    BuffChange::Added(buff)
+}
+
+/// Buffs the server knows, with their templates
+fn known_buffs<'a>(
+    data: &'a impl CombatData,
+    entity: &'a FightEntityInfo,
+) -> impl Iterator<Item = (&'a BuffInfo, BuffTemplate)> {
+    entity
+        .buffs
+        .iter()
+        .filter_map(|b| data.buff(b.buff_id.unwrap_or(0)).map(|t| (b, t)))
+}
+
+/// The entity's attributes with every stat buff applied
+pub fn modified_attr(data: &impl CombatData, entity: &FightEntityInfo) -> HeroAttribute {
+    let mut attr = entity.attr.unwrap_or_default();
+    let mut ratios = [1.0f32; 3];
+
+    for (buff, template) in known_buffs(data, entity) {
+        if let BuffEffect::Attr { attr, ratio } = template.effect {
+            ratios[attr as usize] += ratio * buff.layer.unwrap_or(1) as f32;
+        }
+    }
+
+    let scale = |value: Option<i32>, ratio: f32| {
+        Some((value.unwrap_or(0) as f32 * ratio.max(0.0)).round() as i32)
+    };
+    attr.attack = scale(attr.attack, ratios[BuffAttr::Attack as usize]);
+    attr.defense = scale(attr.defense, ratios[BuffAttr::Defense as usize]);
+    attr.mdefense = scale(attr.mdefense, ratios[BuffAttr::MDefense as usize]);
+    attr
+}
+
+pub fn has_control(data: &impl CombatData, entity: &FightEntityInfo, kind: ControlKind) -> bool {
+    known_buffs(data, entity).any(|(_, t)| t.effect == BuffEffect::Control(kind))
+}
+
+/// Lets shields soak up the hit, returns the damage that gets through
+pub fn absorb_damage(
+    data: &impl CombatData,
+    entity: &mut FightEntityInfo,
+    damage: i32,
+) -> (i32, Vec<BuffChange>) {
+    let mut remaining = damage;
+    let mut changes = Vec::new();
+
+    for buff in entity.buffs.iter_mut() {
+        if remaining == 0 {
+            break;
+        }
+
+        let is_shield = data
+            .buff(buff.buff_id.unwrap_or(0))
+            .is_some_and(|t| matches!(t.effect, BuffEffect::Shield { .. }));
+        if !is_shield {
+            continue;
+        }
+
+        let absorbed = remaining.min(buff.count.unwrap_or(0));
+        remaining -= absorbed;
+        buff.count = Some(buff.count.unwrap_or(0) - absorbed);
+
+        if buff.count == Some(0) {
+            changes.push(BuffChange::Removed(buff.clone()));
+        } else {
+            changes.push(BuffChange::Updated(buff.clone()));
+        }
+    }
+
+    entity.buffs.retain(|b| {
+        !changes
+            .iter()
+            .any(|c| matches!(c, BuffChange::Removed(r) if r.uid == b.uid))
+    });
+
+    (remaining, changes)
+}
+
+/// Buffs that fire on this trigger, cloned so the caller can mutate the entity
+pub fn triggered(
+    data: &impl CombatData,
+    entity: &FightEntityInfo,
+    trigger: BuffTrigger,
+) -> Vec<(BuffInfo, BuffTemplate)> {
+    known_buffs(data, entity)
+        .filter(|(_, t)| t.trigger == trigger)
+        .map(|(b, t)| (b.clone(), t))
+        .collect()
+}
+
+/// One more layer for a stacking buff, `None` once it is capped
+pub fn stack_buff(
+    data: &impl CombatData,
+    entity: &mut FightEntityInfo,
+    buff_uid: i64,
+) -> Option<BuffChange> {
+    let buff = entity.buffs.iter_mut().find(|b| b.uid == Some(buff_uid))?;
+    let template = data.buff(buff.buff_id.unwrap_or(0))?;
+    let layer = buff.layer.unwrap_or(1);
+
+    if layer >= template.max_layer {
+        return None;
+    }
+
+    buff.layer = Some(layer + 1);
+    Some(BuffChange::Updated(buff.clone()))
+}
+
+/// Counts timed buffs down and drops the expired ones, permanent ones are left alone
+pub fn tick_round_end(entity: &mut FightEntityInfo) -> Vec<BuffChange> {
+    let mut changes = Vec::new();
+
+    entity.buffs.retain_mut(|buff| {
+        let duration = buff.duration.unwrap_or(0);
+        if duration <= 0 {
+            return true;
+        }
+
+        buff.duration = Some(duration - 1);
+        if duration == 1 {
+            changes.push(BuffChange::Removed(buff.clone()));
+            false
+        } else {
+            changes.push(BuffChange::Updated(buff.clone()));
+            true
+        }
+    });
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::battle::cloth::ClothRules;
+    use crate::state::battle::data::{BattleRules, HeroCombat, MonsterCombat};
+    use crate::state::battle::fixtures::Fixture;
+    use crate::state::battle::skill::SkillEffect;
+
+    const SHIELD: i32 = 1;
+    const STUN: i32 = 2;
+    const ATTACK_UP: i32 = 3;
+    const FOCUS: i32 = 4;
+
+    /// Only buff rows, enough for the buff engine
+    struct Buffs(Vec<BuffTemplate>);
+
+    impl CombatData for Buffs {
+        fn battle(&self, _: i32) -> Option<BattleRules> {
+            None
+        }
+        fn skill_rank(&self, _: i32) -> Option<i32> {
+            None
+        }
+        fn skill_effect(&self, _: i32) -> Option<SkillEffect> {
+            None
+        }
+        fn is_ex_skill(&self, _: i32, _: i32) -> bool {
+            false
+        }
+        fn hero(&self, _: i32) -> Option<HeroCombat> {
+            None
+        }
+        fn monster(&self, _: i32) -> Option<MonsterCombat> {
+            None
+        }
+        fn cloth(&self, _: i32, _: i32) -> Option<ClothRules> {
+            None
+        }
+        fn buff(&self, buff_id: i32) -> Option<BuffTemplate> {
+            self.0.iter().find(|t| t.buff_id == buff_id).copied()
+        }
+        fn skill_buffs(&self, _: i32) -> Vec<SkillBuff> {
+            Vec::new()
+        }
+    }
+
+    fn buffs() -> Buffs {
+        let template = |buff_id, effect, trigger, duration, max_layer| BuffTemplate {
+            buff_id,
+            effect,
+            trigger,
+            duration,
+            max_layer,
+        };
+
+        Buffs(vec![
+            template(
+                SHIELD,
+                BuffEffect::Shield { ratio: 1.0 },
+                BuffTrigger::Passive,
+                2,
+                1,
+            ),
+            template(
+                STUN,
+                BuffEffect::Control(ControlKind::Stun),
+                BuffTrigger::Passive,
+                1,
+                1,
+            ),
+            template(
+                ATTACK_UP,
+                BuffEffect::Attr {
+                    attr: BuffAttr::Attack,
+                    ratio: 0.15,
+                },
+                BuffTrigger::Passive,
+                2,
+                3,
+            ),
+            template(
+                FOCUS,
+                BuffEffect::Attr {
+                    attr: BuffAttr::Attack,
+                    ratio: 0.05,
+                },
+                BuffTrigger::OnCardPlay,
+                0,
+                5,
+            ),
+        ])
+    }
+
+    fn entity() -> FightEntityInfo {
+        FightEntityInfo {
+            uid: Some(1),
+            attr: Some(HeroAttribute {
+                hp: Some(1000),
+                attack: Some(100),
+                defense: Some(50),
+                mdefense: Some(50),
+                technic: Some(0),
+                multi_hp_idx: Some(0),
+                multi_hp_num: Some(0),
+            }),
+            current_hp: Some(1000),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stacking_caps_at_max_layer_and_refreshes_duration() {
+        let data = buffs();
+        let mut e = entity();
+        let t = &data.buff(ATTACK_UP).unwrap();
+
+        assert!(matches!(add_buff(&mut e, t, 1, 1, 0), BuffChange::Added(_)));
+        tick_round_end(&mut e);
+        for _ in 0..5 {
+            add_buff(&mut e, t, 2, 1, 0);
+        }
+
+        assert_eq!(e.buffs.len(), 1);
+        assert_eq!(e.buffs[0].layer, Some(t.max_layer));
+        assert_eq!(e.buffs[0].duration, Some(t.duration));
+        assert_eq!(modified_attr(&data, &e).attack, Some(145));
+    }
+
+    #[test]
+    fn shields_soak_damage_until_broken() {
+        let data = buffs();
+        let mut e = entity();
+        add_buff(&mut e, &data.buff(SHIELD).unwrap(), 1, 1, 300);
+
+        let (through, changes) = absorb_damage(&data, &mut e, 200);
+        assert_eq!(through, 0);
+        assert!(matches!(&changes[0], BuffChange::Updated(b) if b.count == Some(100)));
+
+        let (through, changes) = absorb_damage(&data, &mut e, 250);
+        assert_eq!(through, 150);
+        assert!(matches!(changes[0], BuffChange::Removed(_)));
+        assert!(e.buffs.is_empty());
+    }
+
+    #[test]
+    fn round_end_expires_timed_buffs_only() {
+        let data = buffs();
+        let mut e = entity();
+        add_buff(&mut e, &data.buff(STUN).unwrap(), 1, 1, 0);
+        add_buff(&mut e, &data.buff(FOCUS).unwrap(), 2, 1, 0);
+        assert!(has_control(&data, &e, ControlKind::Stun));
+
+        let changes = tick_round_end(&mut e);
+
+        assert_eq!(changes.len(), 1);
+        assert!(matches!(changes[0], BuffChange::Removed(_)));
+        assert!(!has_control(&data, &e, ControlKind::Stun));
+        assert_eq!(e.buffs.len(), 1);
+        assert_eq!(next_buff_uid(&[(1, e)].into_iter().collect()), 3);
+    }
+
+    #[test]
+    fn unknown_buffs_do_nothing() {
+        let mut e = entity();
+        add_buff(&mut e, &buffs().buff(SHIELD).unwrap(), 1, 1, 300);
+
+        let (through, changes) = absorb_damage(&Buffs(Vec::new()), &mut e, 200);
+        assert_eq!(through, 200);
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn skill_buffs_land_on_the_target() {
+        let mut fixture = Fixture::sweep();
+        let hand = fixture.hand();
+        fixture.data.buffs.push(BuffTemplate {
+            buff_id: 30002,
+            effect: BuffEffect::Attr {
+                attr: BuffAttr::Defense,
+                ratio: -0.1,
+            },
+            trigger: BuffTrigger::Passive,
+            duration: 3,
+            max_layer: 1,
+        });
+        fixture.data.skill_buffs.push(SkillBuff {
+            skill_id: hand[0].skill_id.unwrap(),
+            buff_id: 30002,
+            on_caster: false,
+        });
+
+        let fight = fixture.fight();
+        let target = fight.defender.as_ref().unwrap().entitys[0].uid;
+        let play = vec![sonettobuf::BeginRoundOper {
+            oper_type: Some(1),
+            param1: Some(1),
+            param2: None,
+            to_id: target,
+            param3: None,
+        }];
+
+        let mut simulator = fixture.simulator(fight);
+        simulator
+            .process_round(play, hand, fixture.input.act_point)
+            .unwrap();
+
+        let defender = simulator.fight().defender.as_ref().unwrap();
+        let target = defender.entitys.iter().find(|e| e.uid == target).unwrap();
+        assert!(target.buffs.iter().any(|b| b.buff_id == Some(30002)));
+    }
+}
@@ -1,18 +1,24 @@
-use rand::{seq::SliceRandom, thread_rng};
+use rand::{Rng, seq::SliceRandom, thread_rng};
 use sqlx::SqlitePool;
 
+use super::data::{CombatData, DEFAULT_MAX_MOXIE, ExcelData};
 use crate::error::AppError;
 use data::exceldb;
 use database::db::game::heroes;
-use sonettobuf::{CardInfo, CardInfoPush, FightGroup};
+use sonettobuf::{CardInfo, CardInfoPush, FightEntityInfo, FightGroup};
 
-// Core deck generation
-pub async fn generate_card_deck(
+// Moxie a hero gets for each card played and each merge of their cards
+pub const MOXIE_PER_CARD: i32 = 1;
+pub const MOXIE_PER_MERGE: i32 = 1;
+const MAX_CARD_RANK: usize = 3;
+
+//  creates CardInfoPush for handlers
+pub async fn generate_initial_deck(
     pool: &SqlitePool,
     user_id: i64,
     fight_group: &FightGroup,
-    max_cards: usize,
-) -> Result<Vec<CardInfo>, AppError> {
+    act_point: i32,
+) -> Result<CardInfoPush, AppError> {
     let active_heroes: Vec<i64> = fight_group
         .hero_list
         .iter()
@@ -20,23 +26,8 @@ pub async fn generate_card_deck(
         .filter(|&u| u != 0)
         .collect();
 
-    let candidates = build_candidate_pool(pool, user_id, &active_heroes).await?;
-    let deck = draw_cards_with_merge(candidates, max_cards);
-
-    Ok(deck)
-}
-
-//  creates CardInfoPush for handlers
-pub async fn generate_initial_deck(
-    pool: &SqlitePool,
-    user_id: i64,
-    fight_group: &FightGroup,
-    act_point: i32,
-) -> Result<CardInfoPush, AppError> {
-    let hero_count = fight_group.hero_list.iter().filter(|&&u| u != 0).count();
-    let max_cards = compute_max_cards(hero_count);
-
-    let deck = generate_card_deck(pool, user_id, fight_group, max_cards).await?;
+    let heroes = resolve_hero_ids(pool, user_id, &active_heroes).await?;
+    let deck = deal_hand(&ExcelData, &heroes, &mut thread_rng());
 
     Ok(CardInfoPush {
         card_group: deck.clone(),
@@ -49,14 +40,56 @@ pub async fn generate_initial_deck(
     })
 }
 
-#[allow(dead_code)]
-fn draw_cards_no_merge(candidates: Vec<CardInfo>, max_cards: usize) -> Vec<CardInfo> {
-    let mut rng = thread_rng();
+/// Opening hand for `(hero_uid, hero_id)` pairs, already merged
+pub fn deal_hand(
+    data: &impl CombatData,
+    heroes: &[(i64, i32)],
+    rng: &mut impl Rng,
+) -> Vec<CardInfo> {
+    let candidates: Vec<CardInfo> = heroes
+        .iter()
+        .flat_map(|&(hero_uid, hero_id)| hero_cards(data, hero_uid, hero_id))
+        .collect();
+
+    if candidates.is_empty() {
+        return Vec::new();
+    }
+
+    let mut deck = draw_cards(&candidates, compute_max_cards(heroes.len()), rng);
+    merge_hand(data, &mut deck);
+
+    deck
+}
+
+// tops the hand back up after a round from the heroes still standing, keeps the
+// cards that weren't played and merges whatever lines up with the new ones.
+// Takes the fight's RNG so a replay deals the same cards
+pub fn refill_hand(
+    data: &impl CombatData,
+    heroes: &[FightEntityInfo],
+    mut hand: Vec<CardInfo>,
+    rng: &mut impl Rng,
+) -> Vec<CardInfo> {
+    let missing = compute_max_cards(heroes.len()).saturating_sub(hand.len());
+    let candidates: Vec<CardInfo> = heroes
+        .iter()
+        .flat_map(|h| hero_cards(data, h.uid.unwrap_or(0), h.model_id.unwrap_or(0)))
+        .collect();
+
+    if missing > 0 && !candidates.is_empty() {
+        hand.extend(draw_cards(&candidates, missing, rng));
+        merge_hand(data, &mut hand);
+    }
+
+    hand
+}
+
+fn draw_cards(candidates: &[CardInfo], max_cards: usize, rng: &mut impl Rng) -> Vec<CardInfo> {
     let mut deck: Vec<CardInfo> = Vec::with_capacity(max_cards);
 
     for _ in 0..max_cards {
         let card = candidates
-            .choose(&mut rng)
+            .choose(rng)
             .expect("candidate pool empty")
             .clone();
         deck.push(card);
@@ -69,12 +102,12 @@ fn compute_max_cards(hero_count: usize) -> usize {
     (hero_count * 3).min(9)
 }
 
-async fn build_candidate_pool(
+async fn resolve_hero_ids(
     pool: &SqlitePool,
     user_id: i64,
     hero_uids: &[i64],
-) -> Result<Vec<CardInfo>, AppError> {
-    let mut pool_cards = Vec::new();
+) -> Result<Vec<(i64, i32)>, AppError> {
+    let mut hero_ids = Vec::new();
     let game_data = exceldb::get();
 
     for &hero_uid in hero_uids {
@@ -105,110 +138,199 @@ async fn build_candidate_pool(
             hero.record.hero_id
         };
 
-        for skill_id in get_hero_skills(hero_id) {
-            pool_cards.push(CardInfo {
-                uid: Some(hero_uid),
-                hero_id: Some(hero_id),
-                skill_id: Some(skill_id),
-                card_type: Some(0), // rank 1
-                status: Some(0),
-                temp_card: Some(hero_uid < 0), // Mark trial hero cards as temp
-                enchants: vec![],
-                target_uid: Some(0),
-                energy: Some(0),
-                extra_infos: vec![],
-                area_red_or_blue: Some(0),
-                heat_id: Some(0),
-                card_effect: None,
-                extra_info: None,
-            });
-        }
+        hero_ids.push((hero_uid, hero_id));
     }
 
-    Ok(pool_cards)
+    Ok(hero_ids)
 }
 
-fn draw_cards_with_merge(candidates: Vec<CardInfo>, max_cards: usize) -> Vec<CardInfo> {
-    let mut rng = thread_rng();
-    let mut deck: Vec<CardInfo> = Vec::with_capacity(max_cards);
+// rank 1 card for each of the hero's skills
+fn hero_cards(data: &impl CombatData, hero_uid: i64, hero_id: i32) -> Vec<CardInfo> {
+    get_hero_skills(data, hero_id)
+        .into_iter()
+        .map(|skill_id| CardInfo {
+            uid: Some(hero_uid),
+            hero_id: Some(hero_id),
+            skill_id: Some(skill_id),
+            card_type: Some(0), // rank 1
+            status: Some(0),
+            temp_card: Some(hero_uid < 0), // Mark trial hero cards as temp
+            enchants: vec![],
+            target_uid: Some(0),
+            energy: Some(0),
+            extra_infos: vec![],
+            area_red_or_blue: Some(0),
+            heat_id: Some(0),
+            card_effect: None,
+            extra_info: None,
+        })
+        .collect()
+}
 
-    while deck.len() < max_cards {
-        let card = candidates
-            .choose(&mut rng)
-            .expect("candidate pool empty")
-            .clone();
+/// A pair of adjacent cards that became one card of the next rank
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CardMerge {
+    pub hero_uid: i64,
+    pub skill_id: i32,
+}
+
+/// Merges adjacent identical cards until nothing lines up any more
+pub fn merge_hand(data: &impl CombatData, hand: &mut Vec<CardInfo>) -> Vec<CardMerge> {
+    merge_hand_with(hand, |hero_id, skill_id| {
+        upgraded_skill(data, hero_id, skill_id)
+    })
+}
 
-        if let Some(last) = deck.last_mut() {
-            // Check if same hero and same base skill (differ only in rank)
-            if last.hero_id == card.hero_id
-                && last.skill_id.unwrap_or(0) / 10 == card.skill_id.unwrap_or(0) / 10
-                && last.card_type == card.card_type
-            {
-                let new_rank = last.card_type.unwrap_or(1) + 1;
-
-                let game_data = exceldb::get();
-                if let Some(upgraded_skill) = game_data.skill.iter().find(|s| {
-                    s.hero_id == last.hero_id.unwrap_or(0)
-                        && s.skill_rank == new_rank
-                        && s.id / 10 == last.skill_id.unwrap_or(0) / 10
-                }) {
-                    last.card_type = Some(new_rank);
-                    last.skill_id = Some(upgraded_skill.id);
-
-                    tracing::debug!(
-                        "Merged: skill {} -> {}, rank {}",
-                        card.skill_id.unwrap_or(0),
-                        upgraded_skill.id,
-                        new_rank
-                    );
-
-                    continue;
-                }
+fn merge_hand_with(
+    hand: &mut Vec<CardInfo>,
+    upgrade: impl Fn(i32, i32) -> Option<i32>,
+) -> Vec<CardMerge> {
+    let mut merges = Vec::new();
+
+    'scan: loop {
+        for i in 1..hand.len() {
+            let (left, right) = (&hand[i - 1], &hand[i]);
+            if left.uid != right.uid || left.skill_id != right.skill_id {
+                continue;
             }
+
+            let hero_id = left.hero_id.unwrap_or(0);
+            let skill_id = left.skill_id.unwrap_or(0);
+            let Some(next_skill) = upgrade(hero_id, skill_id) else {
+                continue;
+            };
+
+            tracing::debug!("Merged: skill {} -> {}", skill_id, next_skill);
+
+            merges.push(CardMerge {
+                hero_uid: left.uid.unwrap_or(0),
+                skill_id: next_skill,
+            });
+            hand.remove(i);
+            hand[i - 1].skill_id = Some(next_skill);
+            continue 'scan;
         }
 
-        deck.push(card);
+        return merges;
     }
-
-    deck
 }
 
-fn get_hero_skills(hero_id: i32) -> Vec<i32> {
-    let game_data = exceldb::get();
+/// `from` and `to` are 1-based like card indices, false if either is out of the hand
+pub fn move_card(hand: &mut Vec<CardInfo>, from: i32, to: i32) -> bool {
+    let position = |p: i32| {
+        usize::try_from(p)
+            .ok()
+            .and_then(|p| p.checked_sub(1))
+            .filter(|&p| p < hand.len())
+    };
 
+    let (Some(from), Some(to)) = (position(from), position(to)) else {
+        return false;
+    };
 
-    let character = game_data.character.iter().find(|c| c.id == hero_id);
+    let card = hand.remove(from);
+    hand.insert(to, card);
+    true
+}
 
-    let Some(character) = character else {
-        tracing::warn!("Character {} not found in character table", hero_id);
-        return Vec::new();
-    };
+/// The same skill one rank up, `None` for ultimates and rank 3 cards
+fn upgraded_skill(data: &impl CombatData, hero_id: i32, skill_id: i32) -> Option<i32> {
+    data.hero(hero_id)?.skill_groups.iter().find_map(|ranks| {
+        let rank = ranks.iter().position(|&id| id == skill_id)?;
 
-    // Parse skill string: "1#31240111#31240112#31240113|2#31240121#31240122#31240123"
-    let mut skills = Vec::new();
+        (rank + 1 < MAX_CARD_RANK)
+            .then(|| ranks.get(rank + 1).copied())
+            .flatten()
+    })
+}
+
+/// Moxie a hero needs before their ultimate shows up, from `character.uniqueSkill_point`
+pub fn max_moxie(data: &impl CombatData, hero_id: i32) -> i32 {
+    data.hero(hero_id)
+        .map(|h| h.max_moxie)
+        .unwrap_or(DEFAULT_MAX_MOXIE)
+}
 
-    for skill_group in character.skill.split('|') {
-        let parts: Vec<&str> = skill_group.split('#').collect();
+pub fn is_ultimate(data: &impl CombatData, card: &CardInfo) -> bool {
+    data.hero(card.hero_id.unwrap_or(0))
+        .is_some_and(|h| h.ex_skill != 0 && Some(h.ex_skill) == card.skill_id)
+}
 
-        if parts.is_empty() {
+/// Fills the hero's moxie up to their max, stored in `ex_point` like the client reads it
+pub fn add_moxie(data: &impl CombatData, hero: &mut FightEntityInfo, amount: i32) {
+    let max = max_moxie(data, hero.model_id.unwrap_or(0));
+    hero.ex_point = Some((hero.ex_point.unwrap_or(0) + amount).min(max));
+}
+
+/// Gives every living hero at full moxie their ultimate, one per hero
+pub fn deal_ultimates<'a>(
+    data: &impl CombatData,
+    hand: &mut Vec<CardInfo>,
+    heroes: impl IntoIterator<Item = &'a FightEntityInfo>,
+) {
+    for hero in heroes {
+        let hero_id = hero.model_id.unwrap_or(0);
+        let full = hero.ex_point.unwrap_or(0) >= max_moxie(data, hero_id);
+        let alive = hero.current_hp.unwrap_or(0) > 0;
+        let holding = hand
+            .iter()
+            .any(|c| c.uid == hero.uid && is_ultimate(data, c));
+
+        if hero.team_type != Some(1) || !full || !alive || holding {
             continue;
         }
 
-        // Skip the first part (skill group number like "1" or "2")
-        // Take only the first skill ID from each group (rank 1)
-        if parts.len() > 1 {
-            if let Ok(skill_id) = parts[1].parse::<i32>() {
-                skills.push(skill_id);
-            }
+        if let Some(card) = ultimate_card(data, hero.uid.unwrap_or(0), hero_id) {
+            tracing::info!(
+                "Ultimate {} dealt to {}",
+                card.skill_id.unwrap_or(0),
+                hero_id
+            );
+            hand.push(card);
         }
     }
+}
+
+/// The hero's ultimate from `character.exSkill`, put at the end of the hand at full moxie
+pub fn ultimate_card(data: &impl CombatData, hero_uid: i64, hero_id: i32) -> Option<CardInfo> {
+    let ex_skill = data.hero(hero_id)?.ex_skill;
+    if ex_skill == 0 {
+        return None;
+    }
+
+    Some(CardInfo {
+        uid: Some(hero_uid),
+        hero_id: Some(hero_id),
+        skill_id: Some(ex_skill),
+        card_type: Some(0),
+        status: Some(0),
+        temp_card: Some(hero_uid < 0),
+        enchants: vec![],
+        target_uid: Some(0),
+        energy: Some(0),
+        extra_infos: vec![],
+        area_red_or_blue: Some(0),
+        heat_id: Some(0),
+        card_effect: None,
+        extra_info: None,
+    })
+}
+
+// rank 1 skill of each skill group
+fn get_hero_skills(data: &impl CombatData, hero_id: i32) -> Vec<i32> {
+    let Some(hero) = data.hero(hero_id) else {
+        tracing::warn!("Character {} not found in character table", hero_id);
+        return Vec::new();
+    };
+
+    let mut skills: Vec<i32> = hero
+        .skill_groups
+        .iter()
+        .filter_map(|ranks| ranks.first().copied())
+        .collect();
 
     if skills.is_empty() {
-        tracing::warn!(
-            "No skills parsed for hero {} from skill string: {}",
-            hero_id,
-            character.skill
-        );
+        tracing::warn!("No skills parsed for hero {}", hero_id);
     } else {
         tracing::debug!("Hero {} skills: {:?}", hero_id, skills);
     }
@@ -242,3 +364,58 @@ pub fn default_max_ap(episode_id: i32, hero_count: usize) -> i32 {
     // Use the smaller of config vs hero-based cap
     base_ap.min(hero_ap)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(uid: i64, skill_id: i32) -> CardInfo {
+        CardInfo {
+            uid: Some(uid),
+            hero_id: Some(3001),
+            skill_id: Some(skill_id),
+            ..Default::default()
+        }
+    }
+
+    // 11 -> 12 -> 13, 21 -> 22 -> 23
+    fn upgrade(_hero_id: i32, skill_id: i32) -> Option<i32> {
+        (skill_id % 10 < 3).then_some(skill_id + 1)
+    }
+
+    fn skills(hand: &[CardInfo]) -> Vec<i32> {
+        hand.iter().map(|c| c.skill_id.unwrap_or(0)).collect()
+    }
+
+    #[test]
+    fn adjacent_cards_merge_up_to_rank_three() {
+        let mut hand = vec![card(1, 11), card(1, 11), card(1, 12), card(1, 21)];
+
+        let merges = merge_hand_with(&mut hand, upgrade);
+
+        assert_eq!(skills(&hand), vec![13, 21]);
+        assert_eq!(merges.len(), 2);
+        assert!(merges.iter().all(|m| m.hero_uid == 1));
+    }
+
+    #[test]
+    fn different_heroes_and_top_ranks_do_not_merge() {
+        let mut hand = vec![card(1, 11), card(2, 11), card(1, 13), card(1, 13)];
+
+        assert!(merge_hand_with(&mut hand, upgrade).is_empty());
+        assert_eq!(hand.len(), 4);
+    }
+
+    #[test]
+    fn moving_a_card_lines_up_a_merge() {
+        let mut hand = vec![card(1, 11), card(1, 21), card(1, 11)];
+
+        assert!(move_card(&mut hand, 3, 2));
+        assert_eq!(skills(&hand), vec![11, 11, 21]);
+        assert_eq!(merge_hand_with(&mut hand, upgrade).len(), 1);
+        assert_eq!(skills(&hand), vec![12, 21]);
+
+        assert!(!move_card(&mut hand, 0, 1));
+        assert!(!move_card(&mut hand, 1, 3));
+    }
+}
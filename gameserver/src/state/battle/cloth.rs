@@ -0,0 +1,300 @@
+use serde::Deserialize;
+use sonettobuf::PlayerSkillInfo;
+use std::collections::HashMap;
+
+use super::skill::battle_tags;
+
+/// What a cloth skill does once paid for
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum ClothEffect {
+    /// Every living hero gets this share of their max hp back
+    Heal { ratio: f32 },
+    /// The hand is dealt again, ultimates stay
+    Reroll,
+    /// Every living hero gets the buff
+    Buff { buff_id: i32 },
+}
+
+impl ClothEffect {
+    /// From the skill's `skill.battleTag`: heal tags heal and redeal tags
+    /// redeal. The table has no rates, so heals take `heal_ratio`. None for
+    /// skills the server doesn't run
+    pub fn from_battle_tag(battle_tag: &str, heal_ratio: f32) -> Option<Self> {
+        let tags = battle_tags(battle_tag);
+        let has = |names: &[&str]| tags.iter().any(|t| names.iter().any(|n| t.contains(n)));
+
+        if has(&["heal", "restore", "regen"]) {
+            Some(Self::Heal { ratio: heal_ratio })
+        } else if has(&["redeal", "reroll", "shuffle", "refresh"]) {
+            Some(Self::Reroll)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ClothSkill {
+    pub skill_id: i32,
+    /// Rounds before it can be used again
+    pub cd: i32,
+    /// Power for the first, second, ... use, the last one repeats
+    pub costs: Vec<i32>,
+    /// Uses per fight, 0 for no limit
+    pub limit: i32,
+    pub effect: ClothEffect,
+}
+
+impl ClothSkill {
+    pub fn cost(&self, uses: i32) -> i32 {
+        let idx = usize::try_from(uses).unwrap_or(0);
+        self.costs
+            .get(idx)
+            .or(self.costs.last())
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn used_up(&self, uses: i32) -> bool {
+        self.limit > 0 && uses >= self.limit
+    }
+}
+
+/// Power the player earns during a fight, from `cloth_level`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct PowerGains {
+    pub play: i32,
+    pub move_card: i32,
+    pub merge: i32,
+    pub hero_death: i32,
+    pub defeat: i32,
+    pub round: i32,
+}
+
+/// A cloth at the level the player fights with
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ClothRules {
+    pub initial_power: i32,
+    pub max_power: i32,
+    #[serde(default)]
+    pub gains: PowerGains,
+    pub skills: Vec<ClothSkill>,
+}
+
+impl ClothRules {
+    pub fn skill(&self, skill_id: i32) -> Option<&ClothSkill> {
+        self.skills.iter().find(|s| s.skill_id == skill_id)
+    }
+
+    /// `FightTeam.skill_infos` at the start of a fight, nothing cooling down
+    pub fn skill_infos(&self) -> Vec<PlayerSkillInfo> {
+        self.skills
+            .iter()
+            .map(|s| PlayerSkillInfo {
+                skill_id: Some(s.skill_id),
+                cd: Some(0),
+                need_power: Some(s.cost(0)),
+                r#type: Some(0),
+            })
+            .collect()
+    }
+}
+
+/// The player's cloth over one fight. Kept by the server next to the fight,
+/// the client only sees the cooldowns and costs it leads to
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClothState {
+    /// The player's level of the cloth when the fight started
+    pub level: i32,
+    /// Times each skill was used this fight
+    uses: HashMap<i32, i32>,
+}
+
+impl ClothState {
+    pub fn new(level: i32) -> Self {
+        Self {
+            level,
+            uses: HashMap::new(),
+        }
+    }
+
+    pub fn uses(&self, skill_id: i32) -> i32 {
+        self.uses.get(&skill_id).copied().unwrap_or(0)
+    }
+
+    pub fn record_use(&mut self, skill_id: i32) -> i32 {
+        let count = self.uses.entry(skill_id).or_insert(0);
+        *count += 1;
+        *count
+    }
+}
+
+/// One round closer to using each skill again
+pub fn tick_cooldowns(skill_infos: &mut [PlayerSkillInfo]) {
+    for info in skill_infos {
+        info.cd = Some((info.cd.unwrap_or(0) - 1).max(0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::battle::buff::{BuffAttr, BuffEffect, BuffTemplate, BuffTrigger};
+    use crate::state::battle::fixtures::Fixture;
+
+    fn skill() -> ClothSkill {
+        ClothSkill {
+            skill_id: 1001,
+            cd: 2,
+            costs: vec![3, 5],
+            limit: 3,
+            effect: ClothEffect::Reroll,
+        }
+    }
+
+    #[test]
+    fn costs_climb_and_uses_run_out() {
+        let skill = skill();
+        assert_eq!(skill.cost(0), 3);
+        assert_eq!(skill.cost(1), 5);
+        assert_eq!(skill.cost(4), 5);
+        assert!(!skill.used_up(2));
+        assert!(skill.used_up(3));
+
+        let unlimited = ClothSkill { limit: 0, ..skill };
+        assert!(!unlimited.used_up(100));
+    }
+
+    #[test]
+    fn battle_tags_pick_the_cloth_effect() {
+        assert_eq!(
+            ClothEffect::from_battle_tag("Heal", 0.3),
+            Some(ClothEffect::Heal { ratio: 0.3 })
+        );
+        assert_eq!(
+            ClothEffect::from_battle_tag("redeal", 0.3),
+            Some(ClothEffect::Reroll)
+        );
+        assert_eq!(ClothEffect::from_battle_tag("attack", 0.3), None);
+        assert_eq!(ClothEffect::from_battle_tag("", 0.3), None);
+    }
+
+    #[test]
+    fn uses_are_counted_per_skill() {
+        let mut state = ClothState::new(1);
+        assert_eq!(state.uses(1001), 0);
+
+        state.record_use(1001);
+        state.record_use(1001);
+        state.record_use(1002);
+
+        assert_eq!(state.uses(1001), 2);
+        assert_eq!(state.uses(1002), 1);
+    }
+
+    #[test]
+    fn cooldowns_stop_at_zero() {
+        let mut infos = ClothRules {
+            initial_power: 0,
+            max_power: 10,
+            gains: PowerGains::default(),
+            skills: vec![skill()],
+        }
+        .skill_infos();
+        infos[0].cd = Some(1);
+
+        tick_cooldowns(&mut infos);
+        assert_eq!(infos[0].cd, Some(0));
+        tick_cooldowns(&mut infos);
+        assert_eq!(infos[0].cd, Some(0));
+    }
+
+    #[test]
+    fn cloth_skills_spend_power_and_cool_down() {
+        let mut fixture = Fixture::sweep();
+        fixture.data.cloths.insert(
+            1,
+            ClothRules {
+                initial_power: 6,
+                max_power: 10,
+                gains: PowerGains::default(),
+                skills: vec![ClothSkill {
+                    skill_id: 1001,
+                    cd: 2,
+                    costs: vec![5, 8],
+                    limit: 0,
+                    effect: ClothEffect::Heal { ratio: 0.5 },
+                }],
+            },
+        );
+
+        fixture.input.cloth_id = 1;
+        let mut fight = fixture.fight();
+        let hero = &mut fight.attacker.as_mut().unwrap().entitys[0];
+        hero.current_hp = Some(1);
+        let hero_uid = hero.uid;
+
+        let mut simulator = fixture.simulator(fight);
+        let round = simulator
+            .use_cloth_skill(1001, vec![], fixture.input.act_point)
+            .unwrap();
+
+        assert_eq!(round.power, Some(1));
+        assert_eq!(round.skill_infos[0].cd, Some(2));
+        assert_eq!(round.skill_infos[0].need_power, Some(8));
+        let hero = round.ex_point_info.iter().find(|e| e.uid == hero_uid);
+        assert!(hero.and_then(|h| h.current_hp).unwrap() > 1);
+
+        // still cooling down and short of power
+        let again = simulator
+            .use_cloth_skill(1001, vec![], fixture.input.act_point)
+            .unwrap();
+        assert!(again.fight_step.is_empty());
+        assert_eq!(again.power, Some(1));
+    }
+
+    #[test]
+    fn cloth_buffs_land_on_every_hero() {
+        let mut fixture = Fixture::sweep();
+        fixture.data.buffs.push(BuffTemplate {
+            buff_id: 30001,
+            effect: BuffEffect::Attr {
+                attr: BuffAttr::Attack,
+                ratio: 0.1,
+            },
+            trigger: BuffTrigger::Passive,
+            duration: 2,
+            max_layer: 1,
+        });
+        fixture.data.cloths.insert(
+            1,
+            ClothRules {
+                initial_power: 5,
+                max_power: 10,
+                gains: PowerGains::default(),
+                skills: vec![ClothSkill {
+                    skill_id: 1003,
+                    cd: 0,
+                    costs: vec![5],
+                    limit: 1,
+                    effect: ClothEffect::Buff { buff_id: 30001 },
+                }],
+            },
+        );
+
+        fixture.input.cloth_id = 1;
+        let mut simulator = fixture.simulator(fixture.fight());
+        simulator
+            .use_cloth_skill(1003, vec![], fixture.input.act_point)
+            .unwrap();
+
+        let heroes = &simulator.fight().attacker.as_ref().unwrap().entitys;
+        assert!(!heroes.is_empty());
+        for hero in heroes {
+            assert!(hero.buffs.iter().any(|b| b.buff_id == Some(30001)));
+        }
+        assert_eq!(simulator.cloth_state().uses(1003), 1);
+        assert!(simulator.fight().param.is_empty());
+    }
+}
@@ -0,0 +1,478 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use sonettobuf::{EquipRecord, Fight, FightEntityInfo, FightTeam, HeroAttribute};
+
+use super::data::{BattleRules, CombatData, MonsterCombat};
+use super::entity_builder::build_player_entity;
+
+/// A hero as it walks into the fight, levels, gear and passives already applied
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeroSnapshot {
+    pub uid: i64,
+    pub hero_id: i32,
+    #[serde(default)]
+    pub user_id: i64,
+    pub skin: i32,
+    pub level: i32,
+    pub attr: HeroAttribute,
+    pub career: i32,
+    pub ex_skill: i32,
+    #[serde(default)]
+    pub ex_skill_level: i32,
+    #[serde(default)]
+    pub passive_skill: Vec<i32>,
+    #[serde(default)]
+    pub equip_uid: i64,
+    #[serde(default)]
+    pub destiny_rank: i32,
+    #[serde(default)]
+    pub destiny_stone: i32,
+}
+
+/// The player's side as it was lined up: `heroes` by slot with `None` for an
+/// empty one, `subs` on the bench from the start
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Lineup {
+    pub heroes: Vec<Option<HeroSnapshot>>,
+    #[serde(default)]
+    pub subs: Vec<HeroSnapshot>,
+    #[serde(default)]
+    pub cloth_id: i32,
+    /// The player's level of the cloth
+    #[serde(default)]
+    pub cloth_level: i32,
+}
+
+/// The lineup against the battle's monsters. Heroes keep their slot as their
+/// position, past `battle.playerMax` they sit on the bench
+pub fn build_fight(data: &impl CombatData, battle_id: i32, lineup: &Lineup) -> Result<Fight> {
+    let rules = data
+        .battle(battle_id)
+        .ok_or_else(|| anyhow!("Battle {} not found", battle_id))?;
+
+    let (main, bench) = lineup
+        .heroes
+        .split_at(on_field(lineup.heroes.len(), rules.player_max));
+    let entitys = main
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, hero)| {
+            let hero = hero.as_ref()?;
+            Some(hero_entity(data, hero, (idx + 1) as i32, 1))
+        })
+        .collect();
+    let sub_entitys = bench
+        .iter()
+        .flatten()
+        .chain(&lineup.subs)
+        .map(|hero| hero_entity(data, hero, -1, 1))
+        .collect();
+    let user_id = lineup
+        .heroes
+        .iter()
+        .flatten()
+        .chain(&lineup.subs)
+        .next()
+        .map(|h| h.user_id)
+        .unwrap_or(0);
+    let (power, skill_infos) = cloth_loadout(data, lineup.cloth_id, lineup.cloth_level);
+    let attacker = fight_team(
+        entitys,
+        sub_entitys,
+        build_player_entity(user_id, 1),
+        Some(power),
+        Some(lineup.cloth_id),
+        skill_infos,
+    );
+
+    let defender = defender_team(data, &rules)?;
+
+    Ok(new_fight(battle_id, 0, rules.max_round, attacker, defender))
+}
+
+pub fn new_fight(
+    battle_id: i32,
+    episode_id: i32,
+    max_round: i32,
+    attacker: FightTeam,
+    defender: FightTeam,
+) -> Fight {
+    Fight {
+        attacker: Some(attacker),
+        defender: Some(defender),
+        cur_round: Some(1),
+        max_round: Some(max_round),
+        is_finish: Some(false), // determines if fight is over
+        cur_wave: Some(1),
+        battle_id: Some(battle_id),
+        magic_circle: None,
+        version: Some(5),
+        is_record: Some(false), // enables sweep feature
+        episode_id: Some(episode_id),
+        fight_act_type: Some(sonettobuf::fight::FightActType::Normal.into()),
+        last_change_hero_uid: Some(0),
+        progress: Some(0),
+        progress_max: Some(0),
+        param: vec![],
+        custom_data: vec![],
+        fight_task_box: Some(sonettobuf::FightTaskBox { tasks: vec![] }),
+        progress_list: vec![],
+    }
+}
+
+/// How many of a side go on the field, `battle.playerMax` or `battle.monsterMax` caps it
+pub fn on_field(count: usize, max: i32) -> usize {
+    match usize::try_from(max) {
+        Ok(max) if max > 0 => count.min(max),
+        _ => count,
+    }
+}
+
+/// The first wave of `battle.monsterGroupIds`, the rest come in as it falls
+pub fn defender_team(data: &impl CombatData, rules: &BattleRules) -> Result<FightTeam> {
+    let (entitys, sub_entitys) = wave_entities(data, &rules.waves, 0, rules.monster_max)?;
+
+    Ok(fight_team(
+        entitys,
+        sub_entitys,
+        build_player_entity(0, 2),
+        Some(0),
+        Some(0),
+        vec![],
+    ))
+}
+
+/// The monsters of a wave (0-based), front to back: the first `monster_max` on
+/// the field, the rest held back to fill in. Uids keep counting down across
+/// waves so a new wave never reuses a fallen monster's uid
+pub fn wave_entities(
+    data: &impl CombatData,
+    waves: &[Vec<i32>],
+    wave: usize,
+    monster_max: i32,
+) -> Result<(Vec<FightEntityInfo>, Vec<FightEntityInfo>)> {
+    let Some(monster_ids) = waves.get(wave) else {
+        return Ok((vec![], vec![]));
+    };
+    let first_idx: usize = waves[..wave].iter().map(Vec::len).sum();
+    let field = on_field(monster_ids.len(), monster_max);
+
+    let mut entitys = Vec::new();
+    let mut sub_entitys = Vec::new();
+    for (slot, &monster_id) in monster_ids.iter().enumerate() {
+        let monster = data
+            .monster(monster_id)
+            .ok_or_else(|| anyhow!("Monster {} not found", monster_id))?;
+        let position = if slot < field { (slot + 1) as i32 } else { -1 };
+        let entity = monster_entity(&monster, first_idx + slot, position, 2);
+
+        tracing::debug!(
+            "Enemy entity: wave={}, monster_id={}, position={}, uid={:?}",
+            wave + 1,
+            monster_id,
+            position,
+            entity.uid
+        );
+
+        if slot < field {
+            entitys.push(entity);
+        } else {
+            sub_entitys.push(entity);
+        }
+    }
+
+    Ok((entitys, sub_entitys))
+}
+
+// power the player had before cloths were wired up, kept for clothless fights
+const DEFAULT_POWER: i32 = 15;
+
+/// Opening power and cloth skills of the player's team
+pub fn cloth_loadout(
+    data: &impl CombatData,
+    cloth_id: i32,
+    cloth_level: i32,
+) -> (i32, Vec<sonettobuf::PlayerSkillInfo>) {
+    match data.cloth(cloth_id, cloth_level) {
+        Some(cloth) => (cloth.initial_power, cloth.skill_infos()),
+        None => (DEFAULT_POWER, vec![]),
+    }
+}
+
+pub fn fight_team(
+    entitys: Vec<FightEntityInfo>,
+    sub_entitys: Vec<FightEntityInfo>,
+    player_entity: FightEntityInfo,
+    power: Option<i32>,
+    cloth_id: Option<i32>,
+    skill_infos: Vec<sonettobuf::PlayerSkillInfo>,
+) -> FightTeam {
+    FightTeam {
+        entitys,
+        sub_entitys,
+        power,
+        cloth_id,
+        skill_infos,
+        sp_entitys: vec![],
+        indicators: vec![],
+        ex_team_str: Some(String::new()),
+        assist_boss: None,
+        assist_boss_info: None,
+        emitter: None,
+        emitter_info: None,
+        player_entity: Some(player_entity),
+        player_finisher_info: None,
+        energy: Some(0),
+        card_heat: Some(sonettobuf::CardHeatInfo { values: vec![] }),
+        card_deck_size: Some(0),
+        blood_pool: None,
+        vorpalith: None,
+        item_skill_group: None,
+        sp_fight_entities: vec![],
+    }
+}
+
+pub fn hero_entity(
+    data: &impl CombatData,
+    hero: &HeroSnapshot,
+    position: i32,
+    team_type: i32,
+) -> FightEntityInfo {
+    let skill_groups = data
+        .hero(hero.hero_id)
+        .map(|h| h.skill_groups)
+        .unwrap_or_default();
+    let group = |n: usize| skill_groups.get(n).cloned().unwrap_or_default();
+
+    FightEntityInfo {
+        uid: Some(hero.uid),
+        model_id: Some(hero.hero_id),
+        skin: Some(hero.skin),
+        position: Some(position),
+        entity_type: Some(1), // 1 = Hero
+        user_id: Some(hero.user_id),
+        ex_point: Some(0),
+        level: Some(hero.level),
+        current_hp: hero.attr.hp,
+        attr: Some(hero.attr),
+        buffs: vec![], // Filled in round_builder
+        skill_group1: group(0),
+        skill_group2: group(1),
+        passive_skill: hero.passive_skill.clone(),
+        ex_skill: Some(hero.ex_skill),
+        shield_value: Some(0),
+        no_effect_buffs: vec![],
+        expoint_max_add: Some(0),
+        buff_harm_statistic: Some(0),
+        equip_uid: Some(hero.equip_uid),
+        trial_equip: Some(empty_equip()),
+        ex_skill_level: Some(hero.ex_skill_level),
+        power_infos: vec![],
+        act104_equip_uids: vec![],
+        trial_act104_equips: vec![],
+        summoned_list: vec![],
+        base_attr: Some(hero.attr),
+        ex_skill_point_change: Some(0),
+        team_type: Some(team_type),
+        enhance_info_box: Some(sonettobuf::EnhanceInfoBox {
+            uid: Some(hero.uid),
+            can_upgrade_ids: vec![],
+            upgraded_options: vec![],
+        }),
+        trial_id: Some(0),
+        career: Some(hero.career),
+        status: Some(0),
+        guard: Some(-1),
+        sub_cd: Some(0),
+        ex_point_type: Some(0),
+        equips: vec![], // game has this empty???
+        destiny_stone: Some(hero.destiny_stone),
+        destiny_rank: Some(hero.destiny_rank),
+        custom_unit_id: Some(0),
+    }
+}
+
+/// Enemies get negative uids in spawn order (-1, -2, -3, ...)
+pub fn monster_entity(
+    monster: &MonsterCombat,
+    idx: usize,
+    position: i32,
+    team_type: i32,
+) -> FightEntityInfo {
+    let uid = -((idx + 1) as i64);
+    let attr = HeroAttribute {
+        hp: Some(monster.hp),
+        attack: Some(monster.attack),
+        defense: Some(monster.defense),
+        mdefense: Some(monster.mdefense),
+        technic: Some(monster.technic),
+        multi_hp_idx: Some(0),
+        multi_hp_num: Some(0),
+    };
+
+    FightEntityInfo {
+        uid: Some(uid),
+        model_id: Some(monster.monster_id),
+        skin: Some(monster.skin_id),
+        position: Some(position),
+        entity_type: Some(2), // 2 = Enemy
+        user_id: Some(0),
+        ex_point: Some(0),
+        level: Some(monster.level),
+        current_hp: Some(monster.hp),
+        attr: Some(attr),
+        buffs: vec![],
+        skill_group1: monster.skill_group1.clone(),
+        skill_group2: monster.skill_group2.clone(),
+        passive_skill: monster.passive_skill.clone(),
+        ex_skill: Some(monster.unique_skill),
+        shield_value: Some(0),
+        no_effect_buffs: vec![],
+        expoint_max_add: Some(0),
+        buff_harm_statistic: Some(0),
+        equip_uid: Some(0),
+        trial_equip: Some(empty_equip()),
+        ex_skill_level: Some(0),
+        power_infos: vec![],
+        act104_equip_uids: vec![],
+        trial_act104_equips: vec![],
+        summoned_list: vec![],
+        base_attr: Some(attr),
+        ex_skill_point_change: Some(0),
+        team_type: Some(team_type),
+        enhance_info_box: Some(sonettobuf::EnhanceInfoBox {
+            uid: Some(uid),
+            can_upgrade_ids: vec![],
+            upgraded_options: vec![],
+        }),
+        trial_id: Some(0),
+        career: Some(monster.career),
+        status: Some(0),
+        guard: Some(-1),
+        sub_cd: Some(0),
+        ex_point_type: Some(0),
+        equips: vec![],
+        destiny_stone: Some(0),
+        destiny_rank: Some(0),
+        custom_unit_id: Some(0),
+    }
+}
+
+fn empty_equip() -> EquipRecord {
+    EquipRecord {
+        equip_uid: Some(0),
+        equip_id: Some(0),
+        equip_lv: Some(0),
+        refine_lv: Some(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::state::battle::fixtures::{CombatInput, FIXTURES, Fixture, load, simulate};
+    use crate::state::battle::replay::Replay;
+    use serde_json::{Value, json};
+
+    fn skill_ids(cards: &[sonettobuf::CardInfo]) -> Vec<i32> {
+        cards.iter().map(|c| c.skill_id.unwrap_or(0)).collect()
+    }
+
+    // what the golden files pin down: hp, hands, intents and the result per round
+    fn summarize(replay: &Replay) -> Value {
+        let rounds: Vec<Value> = replay
+            .rounds
+            .iter()
+            .map(|round| {
+                let hp: Vec<Value> = round
+                    .ex_point_info
+                    .iter()
+                    .map(|e| json!([e.uid, e.current_hp, e.ex_point]))
+                    .collect();
+
+                json!({
+                    "round": round.cur_round,
+                    "hp": hp,
+                    "used": round.use_card_list,
+                    "hand": skill_ids(&round.team_a_cards1),
+                    "intents": skill_ids(&round.ai_use_cards),
+                    "finished": round.is_finish,
+                })
+            })
+            .collect();
+
+        json!({
+            "result": replay.result.map(|r| format!("{:?}", r)),
+            "rounds": rounds,
+        })
+    }
+
+    #[test]
+    fn synthetic_fights_match_golden_files() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src/state/battle/golden");
+
+        for (name, raw) in FIXTURES {
+            let fixture = load(raw);
+            let replay = simulate(&fixture.data, &fixture.input).unwrap();
+            let summary = summarize(&replay);
+
+            let path = format!("{}/{}.golden.json", dir, name);
+            if std::env::var_os("UPDATE_GOLDEN").is_some() {
+                let pretty = serde_json::to_string_pretty(&summary).unwrap();
+                std::fs::write(&path, pretty + "\n").unwrap();
+                continue;
+            }
+
+            let golden = std::fs::read_to_string(&path)
+                .unwrap_or_else(|_| panic!("{} missing, run with UPDATE_GOLDEN=1", path));
+            let golden: Value = serde_json::from_str(&golden).unwrap();
+
+            assert_eq!(summary, golden, "{} drifted from its golden file", name);
+        }
+    }
+
+    #[test]
+    fn same_seed_same_fight() {
+        let fixture = Fixture::sweep();
+
+        let first = simulate(&fixture.data, &fixture.input).unwrap();
+        let second = simulate(&fixture.data, &fixture.input).unwrap();
+
+        assert_eq!(first.rounds, second.rounds);
+        assert_eq!(first.result, second.result);
+    }
+
+    #[test]
+    fn runs_thousands_of_fights() {
+        let fixtures: Vec<Fixture> = FIXTURES.iter().map(|(_, raw)| load(raw)).collect();
+        let mut finished = 0;
+
+        for seed in 0..2000u64 {
+            let fixture = &fixtures[seed as usize % fixtures.len()];
+            let input = CombatInput {
+                seed,
+                ..fixture.input.clone()
+            };
+
+            let replay = simulate(&fixture.data, &input).unwrap();
+            assert!(replay.rounds.len() <= input.rounds.len());
+            if replay.result.is_some() {
+                finished += 1;
+            }
+        }
+
+        assert!(finished > 0);
+    }
+
+    #[test]
+    fn heroes_past_player_max_sit_on_the_bench() {
+        let mut fixture = Fixture::sweep();
+        let battle_id = fixture.input.battle_id;
+        fixture.data.battles.get_mut(&battle_id).unwrap().player_max = 2;
+
+        let attacker = fixture.fight().attacker.unwrap();
+
+        assert_eq!(attacker.entitys.len(), 2);
+        assert_eq!(attacker.sub_entitys.len(), fixture.input.heroes.len() - 2);
+        assert_eq!(attacker.sub_entitys[0].position, Some(-1));
+    }
+}
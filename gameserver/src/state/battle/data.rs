@@ -0,0 +1,401 @@
+use common::combat_config;
+use data::exceldb;
+use serde::Deserialize;
+
+use super::buff::{BuffTemplate, SkillBuff};
+use super::cloth::{ClothEffect, ClothRules, ClothSkill, PowerGains};
+use super::outcome::WinCondition;
+use super::skill::{SkillEffect, SkillKind};
+use super::stats::Effectiveness;
+
+// Moxie needed for the ultimate when the character row doesn't say
+pub const DEFAULT_MAX_MOXIE: i32 = 5;
+
+/// The static game data a fight reads. The server runs on `ExcelData`, tests
+/// bring their own tables so the combat core never needs the excel files
+pub trait CombatData {
+    fn battle(&self, battle_id: i32) -> Option<BattleRules>;
+    fn skill_rank(&self, skill_id: i32) -> Option<i32>;
+    /// None for skills missing from the table or with an effect the server can't run
+    fn skill_effect(&self, skill_id: i32) -> Option<SkillEffect>;
+    fn is_ex_skill(&self, hero_id: i32, skill_id: i32) -> bool;
+    fn hero(&self, hero_id: i32) -> Option<HeroCombat>;
+    fn monster(&self, monster_id: i32) -> Option<MonsterCombat>;
+    /// The cloth at the player's level of it
+    fn cloth(&self, cloth_id: i32, level: i32) -> Option<ClothRules>;
+    /// None for buffs the server can't run, they stay off the entities
+    fn buff(&self, buff_id: i32) -> Option<BuffTemplate>;
+    fn skill_buffs(&self, skill_id: i32) -> Vec<SkillBuff>;
+}
+
+impl<T: CombatData + ?Sized> CombatData for &T {
+    fn battle(&self, battle_id: i32) -> Option<BattleRules> {
+        (**self).battle(battle_id)
+    }
+
+    fn skill_rank(&self, skill_id: i32) -> Option<i32> {
+        (**self).skill_rank(skill_id)
+    }
+
+    fn skill_effect(&self, skill_id: i32) -> Option<SkillEffect> {
+        (**self).skill_effect(skill_id)
+    }
+
+    fn is_ex_skill(&self, hero_id: i32, skill_id: i32) -> bool {
+        (**self).is_ex_skill(hero_id, skill_id)
+    }
+
+    fn hero(&self, hero_id: i32) -> Option<HeroCombat> {
+        (**self).hero(hero_id)
+    }
+
+    fn monster(&self, monster_id: i32) -> Option<MonsterCombat> {
+        (**self).monster(monster_id)
+    }
+
+    fn cloth(&self, cloth_id: i32, level: i32) -> Option<ClothRules> {
+        (**self).cloth(cloth_id, level)
+    }
+
+    fn buff(&self, buff_id: i32) -> Option<BuffTemplate> {
+        (**self).buff(buff_id)
+    }
+
+    fn skill_buffs(&self, skill_id: i32) -> Vec<SkillBuff> {
+        (**self).skill_buffs(skill_id)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BattleRules {
+    pub ai_link: i32,
+    pub win_condition: WinCondition,
+    pub max_round: i32,
+    /// `battle.monsterGroupIds`, one monster group per wave, each front to back
+    pub waves: Vec<Vec<i32>>,
+    /// Heroes on the field, the rest wait on the bench. 0 for no cap
+    pub player_max: i32,
+    /// Monsters of a wave on the field, the rest fill in as they fall. 0 for no cap
+    pub monster_max: i32,
+    pub effectiveness: Effectiveness,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct HeroCombat {
+    pub dmg_type: i32,
+    /// Skill ids by rank for each skill group, from `character.skill`
+    pub skill_groups: Vec<Vec<i32>>,
+    pub ex_skill: i32,
+    pub max_moxie: i32,
+}
+
+/// A monster with its stats already grown to its level
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MonsterCombat {
+    pub monster_id: i32,
+    pub skin_id: i32,
+    pub level: i32,
+    pub career: i32,
+    pub dmg_type: i32,
+    pub hp: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub mdefense: i32,
+    pub technic: i32,
+    pub skill_group1: Vec<i32>,
+    pub skill_group2: Vec<i32>,
+    pub passive_skill: Vec<i32>,
+    pub unique_skill: i32,
+    pub unique_skill_point: i32,
+}
+
+/// `CombatData` straight from the loaded excel tables
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExcelData;
+
+impl CombatData for ExcelData {
+    fn battle(&self, battle_id: i32) -> Option<BattleRules> {
+        let battle = exceldb::get().battle.get(battle_id)?;
+
+        Some(BattleRules {
+            ai_link: battle.ai_link,
+            win_condition: WinCondition::parse(&battle.win_condition),
+            max_round: battle.max_round,
+            waves: parse_waves(&battle.monster_group_ids),
+            player_max: battle.player_max,
+            monster_max: battle.monster_max,
+            effectiveness: Effectiveness::from_battle(
+                battle.hero_effectiveness,
+                battle.equip_effectiveness,
+                battle.talent_effectiveness,
+            ),
+        })
+    }
+
+    fn skill_rank(&self, skill_id: i32) -> Option<i32> {
+        exceldb::get().skill.get(skill_id).map(|s| s.skill_rank)
+    }
+
+    fn skill_effect(&self, skill_id: i32) -> Option<SkillEffect> {
+        let game_data = exceldb::get();
+        let skill = game_data.skill.get(skill_id)?;
+        // TODO: kind and rate from the `skill_effect` rows once the table is
+        // exported, until then the battle tags pick the kind and `[combat]`
+        // in the config rates skills by rank
+        let kind = SkillKind::from_battle_tag(&skill.battle_tag)?;
+
+        let is_ex = game_data
+            .skill_ex_level
+            .iter()
+            .any(|s| s.skill_ex == skill_id);
+        let combat = combat_config();
+        let rate = combat.rank_rate(skill.skill_rank, is_ex);
+
+        Some(SkillEffect {
+            kind,
+            rate: match kind {
+                SkillKind::Heal => rate * combat.heal_ratio,
+                _ => rate,
+            },
+        })
+    }
+
+    fn is_ex_skill(&self, hero_id: i32, skill_id: i32) -> bool {
+        exceldb::get()
+            .skill_ex_level
+            .iter()
+            .any(|s| s.hero_id == hero_id && s.skill_ex == skill_id)
+    }
+
+    fn hero(&self, hero_id: i32) -> Option<HeroCombat> {
+        let character = exceldb::get().character.get(hero_id)?;
+
+        Some(HeroCombat {
+            dmg_type: character.dmg_type,
+            skill_groups: parse_skill_groups(&character.skill),
+            ex_skill: character.ex_skill,
+            max_moxie: character
+                .unique_skill_point
+                .split('#')
+                .next()
+                .and_then(|p| p.parse().ok())
+                .filter(|&p: &i32| p > 0)
+                .unwrap_or(DEFAULT_MAX_MOXIE),
+        })
+    }
+
+    fn monster(&self, monster_id: i32) -> Option<MonsterCombat> {
+        let game_data = exceldb::get();
+        let monster = game_data.monster.get(monster_id)?;
+
+        let template_id = if monster.template != 0 {
+            monster.template
+        } else {
+            monster.skill_template
+        };
+
+        let Some(template) = game_data
+            .monster_template
+            .iter()
+            .find(|t| t.template == template_id)
+        else {
+            tracing::warn!(
+                "Monster template {} not found (monster {})",
+                template_id,
+                monster_id
+            );
+            return None;
+        };
+
+        let Some(skill_template) = game_data.monster_skill_template.get(monster.skill_template)
+        else {
+            tracing::warn!(
+                "Monster skill template {} not found (monster {})",
+                monster.skill_template,
+                monster_id
+            );
+            return None;
+        };
+
+        let level = if monster.level_true != 0 {
+            monster.level_true
+        } else {
+            monster.level
+        };
+
+        // "1#40212511#40212512|2#40212521#40212522"
+        let groups = parse_skill_groups(&skill_template.active_skill);
+        let group = |n: usize| groups.get(n).cloned().unwrap_or_default();
+
+        Some(MonsterCombat {
+            monster_id,
+            skin_id: monster.skin_id,
+            level,
+            career: skill_template.career,
+            dmg_type: skill_template.dmg_type,
+            hp: template.life + (template.life_grow * level),
+            attack: template.attack + (template.attack_grow * level),
+            defense: template.defense + (template.defense_grow * level),
+            mdefense: template.mdefense + (template.mdefense_grow * level),
+            technic: template.technic + (template.technic_grow * level),
+            skill_group1: group(0),
+            skill_group2: group(1),
+            passive_skill: parse_ids(&skill_template.passive_skill),
+            unique_skill: parse_ids(&skill_template.unique_skill)
+                .first()
+                .copied()
+                .unwrap_or(0),
+            unique_skill_point: skill_template.unique_skill_point,
+        })
+    }
+
+    fn cloth(&self, cloth_id: i32, level: i32) -> Option<ClothRules> {
+        let cloth = exceldb::get()
+            .cloth_level
+            .iter()
+            .find(|c| c.id == cloth_id && c.level == level)?;
+
+        let use_power3: Vec<i32> = cloth
+            .use_power3
+            .iter()
+            .filter_map(|v| v.as_i64())
+            .map(|v| v as i32)
+            .collect();
+        let slots = [
+            (
+                cloth.skill1,
+                cloth.cd1,
+                cloth.use_power1.clone(),
+                cloth.all_limit1,
+            ),
+            (
+                cloth.skill2,
+                cloth.cd2,
+                cloth.use_power2.clone(),
+                cloth.all_limit2,
+            ),
+            (cloth.skill3, cloth.cd3, use_power3, cloth.all_limit3),
+        ];
+
+        // A slot whose skill has no effect the server runs isn't offered
+        let skills = slots
+            .into_iter()
+            .filter(|(skill_id, ..)| *skill_id != 0)
+            .filter_map(|(skill_id, cd, costs, limit)| {
+                let Some(effect) = cloth_effect(skill_id) else {
+                    tracing::warn!("Cloth skill {} has no effect the server runs", skill_id);
+                    return None;
+                };
+
+                Some(ClothSkill {
+                    skill_id,
+                    cd,
+                    costs,
+                    limit,
+                    effect,
+                })
+            })
+            .collect();
+
+        Some(ClothRules {
+            initial_power: cloth.initial,
+            max_power: cloth.max_power,
+            gains: PowerGains {
+                play: cloth.r#use,
+                move_card: cloth.r#move,
+                merge: cloth.compose,
+                hero_death: cloth.death,
+                defeat: cloth.defeat,
+                // "amount" or "amount#..." per round
+                round: parse_ids(&cloth.recover).first().copied().unwrap_or(0),
+            },
+            skills,
+        })
+    }
+
+    // TODO: `skill_buff` rows, the table is listed in data/codegen/tables.rs but
+    // the excel export this tree was generated from doesn't carry it
+    fn buff(&self, _buff_id: i32) -> Option<BuffTemplate> {
+        None
+    }
+
+    // TODO: the buffs of the `skill_effect` row `skill.skillEffect` points at,
+    // once that table is in the export too
+    fn skill_buffs(&self, _skill_id: i32) -> Vec<SkillBuff> {
+        Vec::new()
+    }
+}
+
+fn cloth_effect(skill_id: i32) -> Option<ClothEffect> {
+    let skill = exceldb::get().skill.get(skill_id)?;
+
+    ClothEffect::from_battle_tag(&skill.battle_tag, combat_config().cloth_heal_ratio)
+}
+
+/// "1#2#3" -> [1, 2, 3]
+pub fn parse_ids(raw: &str) -> Vec<i32> {
+    raw.split('#')
+        .filter_map(|s| s.trim().parse().ok())
+        .collect()
+}
+
+/// "a1#a2|b1#b2#b3" -> [[a1, a2], [b1, b2, b3]], one wave per group
+pub fn parse_waves(raw: &str) -> Vec<Vec<i32>> {
+    raw.split('|')
+        .map(parse_ids)
+        .filter(|ids| !ids.is_empty())
+        .collect()
+}
+
+/// "1#a1#a2#a3|2#b1#b2#b3" -> [[a1, a2, a3], [b1, b2, b3]], ordered by group number
+pub fn parse_skill_groups(raw: &str) -> Vec<Vec<i32>> {
+    let mut groups: Vec<(i32, Vec<i32>)> = raw
+        .split('|')
+        .filter_map(|group| {
+            let mut ids = parse_ids(group).into_iter();
+            let number = ids.next()?;
+            Some((number, ids.collect()))
+        })
+        .filter(|(_, ids): &(i32, Vec<i32>)| !ids.is_empty())
+        .collect();
+
+    groups.sort_by_key(|(number, _)| *number);
+    groups.into_iter().map(|(_, ids)| ids).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::config::CombatConfig;
+
+    #[test]
+    fn higher_ranks_hit_harder() {
+        let combat = CombatConfig::default();
+        assert!(combat.rank_rate(2, false) > combat.rank_rate(1, false));
+        assert!(combat.rank_rate(3, false) > combat.rank_rate(2, false));
+        assert!(combat.rank_rate(1, true) > combat.rank_rate(3, false));
+        assert_eq!(combat.rank_rate(9, false), combat.rank_rate(3, false));
+    }
+
+    #[test]
+    fn skill_groups_parse_in_group_order() {
+        assert_eq!(
+            parse_skill_groups("2#21#22#23|1#11#12#13"),
+            vec![vec![11, 12, 13], vec![21, 22, 23]]
+        );
+        assert_eq!(parse_skill_groups("1#11|2"), vec![vec![11]]);
+        assert!(parse_skill_groups("").is_empty());
+    }
+
+    #[test]
+    fn waves_split_on_groups_only() {
+        assert_eq!(parse_waves("1#2#3"), vec![vec![1, 2, 3]]);
+        assert_eq!(parse_waves("1#2|3"), vec![vec![1, 2], vec![3]]);
+        assert_eq!(
+            parse_waves("1#2#3#4#5|6"),
+            vec![vec![1, 2, 3, 4, 5], vec![6]]
+        );
+        assert_eq!(parse_waves("1||2"), vec![vec![1], vec![2]]);
+        assert!(parse_waves("").is_empty());
+    }
+}
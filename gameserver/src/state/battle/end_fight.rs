@@ -1,12 +1,5 @@
-use crate::error::AppError;
-use crate::state::ConnectionContext;
-use anyhow::Result;
-use sonettobuf::{
-    CmdId, EndFightPush, FightGroup, FightRecord, FightStatistics, UseCardStatistics,
-};
+use sonettobuf::{EndFightPush, FightGroup, FightRecord, FightStatistics, UseCardStatistics};
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::Mutex;
 
 pub struct BattleStats {
     pub hero_uid: i64,
@@ -17,15 +10,14 @@ pub struct BattleStats {
     pub buffs_received: Vec<i32>,      // buff_ids
 }
 
-pub async fn send_end_fight_push(
-    ctx: Arc<Mutex<ConnectionContext>>,
+pub fn end_fight_push(
     fight_id: i64,
     fight_result: i32, // 1 = win, 2 = lose
     fight_group: FightGroup,
     attacker_stats: Vec<BattleStats>,
     defender_stats: Vec<BattleStats>,
     is_record: bool, // ADD THIS PARAMETER
-) -> Result<(), AppError> {
+) -> EndFightPush {
     let fight_time = chrono::Utc::now().timestamp_millis();
 
     // Build attacker statistics
@@ -49,18 +41,11 @@ pub async fn send_end_fight_push(
         defense_statistics,
     };
 
-    let push = EndFightPush {
+    EndFightPush {
         record: Some(record),
         fight_group_a: Some(fight_group),
         is_record: Some(is_record), // Use the parameter
-    };
-
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_push(CmdId::FightEndFightPushCmd, push)
-        .await?;
-
-    Ok(())
+    }
 }
 
 fn build_fight_statistics(stats: BattleStats) -> FightStatistics {
@@ -1,90 +1,245 @@
+use super::combat::HeroSnapshot;
+use super::stats::{self, Effectiveness, PsychubeStats, StatSources, Stats};
 use data::exceldb;
-use database::db::game::{equipment, heroes::HeroData};
-use sonettobuf::{EquipRecord, FightEntityInfo, HeroAttribute};
+use database::db::game::equipment::{self, Equipment};
+use database::db::game::heroes::{self, HeroData};
+use sonettobuf::{
+    EquipRecord, FightEntityDetailInfo, FightEntityInfo, HeroAttribute, HeroSpAttribute,
+};
 use sqlx::SqlitePool;
 
-pub async fn build_hero_entity(
+/// The hero as the combat core sees it, with passives, ex skill and final stats resolved
+pub async fn hero_snapshot(
     pool: &SqlitePool,
     hero_data: &HeroData,
-    position: i32,
-    team_type: i32,
-) -> FightEntityInfo {
+    effectiveness: &Effectiveness,
+) -> HeroSnapshot {
     let record = &hero_data.record;
+    let psychube = hero_psychube(pool, hero_data).await;
+    let stats = hero_stats(hero_data, psychube.as_ref(), effectiveness);
 
-    let equip_id = equipment::get_hero_default_equip_id(pool, record.uid, record.user_id)
+    HeroSnapshot {
+        uid: record.uid,
+        hero_id: record.hero_id,
+        user_id: record.user_id,
+        skin: record.skin,
+        level: record.level,
+        attr: HeroAttribute {
+            multi_hp_idx: Some(record.base_multi_hp_idx),
+            multi_hp_num: Some(record.base_multi_hp_num),
+            ..stats.attr()
+        },
+        career: get_hero_career(hero_data),
+        ex_skill: get_hero_ex_skill(hero_data),
+        ex_skill_level: record.ex_skill_level,
+        passive_skill: get_hero_passive_skills(hero_data, psychube.as_ref()),
+        equip_uid: record.default_equip_uid,
+        destiny_rank: record.destiny_rank,
+        destiny_stone: record.destiny_stone,
+    }
+}
+
+/// The psychube the hero has equipped, if any
+pub async fn hero_psychube(pool: &SqlitePool, hero_data: &HeroData) -> Option<Equipment> {
+    let record = &hero_data.record;
+    if record.default_equip_uid == 0 {
+        return None;
+    }
+
+    equipment::get_equipment_by_uid(pool, record.user_id, record.default_equip_uid)
         .await
+        .inspect_err(|e| {
+            tracing::warn!(
+                "Psychube {} of hero {} not loaded: {}",
+                record.default_equip_uid,
+                record.uid,
+                e
+            )
+        })
         .ok()
-        .flatten();
+}
 
-    FightEntityInfo {
-        uid: Some(record.uid),
-        model_id: Some(record.hero_id),
-        skin: Some(record.skin),
-        position: Some(position),
-        entity_type: Some(1), // 1 = Hero
-        user_id: Some(record.user_id),
-        ex_point: Some(0),
-        level: Some(record.level),
-        current_hp: Some(record.base_hp),
-        attr: Some(HeroAttribute {
-            hp: Some(record.base_hp),
-            attack: Some(record.base_attack),
-            defense: Some(record.base_defense),
-            mdefense: Some(record.base_mdefense),
-            technic: Some(record.base_technic),
-            multi_hp_idx: Some(record.base_multi_hp_idx),
-            multi_hp_num: Some(record.base_multi_hp_num),
-        }),
-        buffs: vec![], // Filled in round_builder
-        skill_group1: get_hero_skill_group(record.hero_id, 1),
-        skill_group2: get_hero_skill_group(record.hero_id, 2),
-        passive_skill: get_hero_passive_skills(&hero_data, equip_id),
-        ex_skill: Some(get_hero_ex_skill(&hero_data)),
-        shield_value: Some(0),
-        no_effect_buffs: vec![],
-        expoint_max_add: Some(0),
-        buff_harm_statistic: Some(0),
-        equip_uid: Some(record.default_equip_uid), // TODO: Load from hero equipment
-        trial_equip: Some(EquipRecord {
-            equip_uid: Some(0),
-            equip_id: Some(0),
-            equip_lv: Some(0),
-            refine_lv: Some(0),
-        }),
-        ex_skill_level: Some(record.ex_skill_level),
-        power_infos: vec![],
-        act104_equip_uids: vec![],
-        trial_act104_equips: vec![],
-        summoned_list: vec![],
-        base_attr: Some(HeroAttribute {
-            hp: Some(record.base_hp),
-            attack: Some(record.base_attack),
-            defense: Some(record.base_defense),
-            mdefense: Some(record.base_mdefense),
-            technic: Some(record.base_technic),
-            multi_hp_idx: Some(record.base_multi_hp_idx),
-            multi_hp_num: Some(record.base_multi_hp_num),
-        }),
-        ex_skill_point_change: Some(0),
-        team_type: Some(team_type),
-        enhance_info_box: Some(sonettobuf::EnhanceInfoBox {
-            uid: Some(record.uid),
-            can_upgrade_ids: vec![],
-            upgraded_options: vec![],
-        }),
-        trial_id: Some(0),
-        career: Some(get_hero_career(&hero_data)),
-        status: Some(0),
-        guard: Some(-1),
-        sub_cd: Some(0),
-        ex_point_type: Some(0),
-        equips: vec![], // game has this empty???
-        destiny_stone: Some(record.destiny_stone),
-        destiny_rank: Some(record.destiny_rank),
-        custom_unit_id: Some(0),
+/// The hero's final stats in a battle with these scalars
+pub fn hero_stats(
+    hero_data: &HeroData,
+    psychube: Option<&Equipment>,
+    effectiveness: &Effectiveness,
+) -> Stats {
+    stats::aggregate(&stat_sources(hero_data, psychube), effectiveness)
+}
+
+/// What `GetEntityDetailInfosCmd` shows for a fighter: its live state plus the
+/// ex stats the fight entity doesn't carry. Only heroes have any
+pub async fn entity_detail(
+    pool: &SqlitePool,
+    user_id: i64,
+    entity: &FightEntityInfo,
+    effectiveness: &Effectiveness,
+) -> FightEntityDetailInfo {
+    let uid = entity.uid.unwrap_or(0);
+
+    let ex_attr = if entity.entity_type == Some(1) && uid > 0 {
+        match heroes::get_hero_by_hero_uid(pool, user_id, uid as i32).await {
+            Ok(hero_data) => {
+                let psychube = hero_psychube(pool, &hero_data).await;
+                hero_stats(&hero_data, psychube.as_ref(), effectiveness).ex_attr()
+            }
+            Err(e) => {
+                tracing::warn!("Hero {} not loaded for detail info: {}", uid, e);
+                Stats::default().ex_attr()
+            }
+        }
+    } else {
+        Stats::default().ex_attr()
+    };
+
+    FightEntityDetailInfo {
+        info: Some(entity.clone()),
+        ex_attr: Some(ex_attr),
+        sp_attr: Some(HeroSpAttribute::default()),
+        final_attr_base: entity.attr,
+        final_ex_attr: Some(ex_attr),
+        ..Default::default()
     }
 }
 
+fn stat_sources(hero_data: &HeroData, psychube: Option<&Equipment>) -> StatSources {
+    let game_data = exceldb::get();
+    let record = &hero_data.record;
+
+    // Stored base stats are only a fallback, they may already carry a psychube
+    let level = game_data
+        .character_level
+        .iter()
+        .find(|l| l.hero_id == record.hero_id && l.level == record.level)
+        .map(|l| Stats {
+            hp: l.hp,
+            attack: l.atk,
+            defense: l.def,
+            mdefense: l.mdef,
+            technic: l.technic,
+            cri: l.cri,
+            recri: l.recri,
+            cri_dmg: l.cri_dmg,
+            cri_def: l.cri_def,
+            add_dmg: l.add_dmg,
+            drop_dmg: l.drop_dmg,
+        })
+        .unwrap_or(Stats {
+            hp: record.base_hp,
+            attack: record.base_attack,
+            defense: record.base_defense,
+            mdefense: record.base_mdefense,
+            technic: record.base_technic,
+            cri: record.ex_cri,
+            recri: record.ex_recri,
+            cri_dmg: record.ex_cri_dmg,
+            cri_def: record.ex_cri_def,
+            add_dmg: record.ex_add_dmg,
+            drop_dmg: record.ex_drop_dmg,
+        });
+
+    let insight_permille = game_data
+        .character_rank
+        .iter()
+        .filter(|r| r.hero_id == record.hero_id && r.rank <= record.rank)
+        .map(|r| stats::insight_permille(&r.effect))
+        .sum();
+
+    StatSources {
+        level,
+        insight_permille,
+        psychube: psychube.map(psychube_stats),
+        cubes: board_cubes(hero_data)
+            .into_iter()
+            .filter_map(cube_stats)
+            .collect(),
+        // TODO: the stone's attributes per destiny rank, `character_destiny` only
+        // maps slots to facets and the facet attribute table isn't exported yet
+        destiny_stone: Stats::default(),
+    }
+}
+
+/// The cube ids on the hero's resonance board. A board never saved falls back
+/// to the `talent_scheme` layout of the hero's resonance level
+fn board_cubes(hero_data: &HeroData) -> Vec<i32> {
+    if !hero_data.talent_cubes.is_empty() {
+        return hero_data.talent_cubes.iter().map(|c| c.cube_id).collect();
+    }
+
+    let game_data = exceldb::get();
+    let record = &hero_data.record;
+
+    game_data
+        .character_talent
+        .iter()
+        .find(|t| t.hero_id == record.hero_id && t.talent_id == record.talent)
+        .and_then(|talent| {
+            game_data
+                .talent_scheme
+                .iter()
+                .find(|s| s.talent_id == talent.talent_id && s.talent_mould == talent.talent_mould)
+        })
+        .map(|scheme| stats::scheme_cube_ids(&scheme.talen_scheme))
+        .unwrap_or_default()
+}
+
+/// A resonance cube's attributes
+fn cube_stats(_cube_id: i32) -> Option<Stats> {
+    // TODO: the cube attribute table isn't in the excel export yet
+    None
+}
+
+fn psychube_stats(psychube: &Equipment) -> PsychubeStats {
+    let game_data = exceldb::get();
+
+    let strengthen = game_data
+        .equip
+        .get(psychube.equip_id)
+        .and_then(|equip| {
+            game_data
+                .equip_strengthen
+                .iter()
+                .find(|s| s.strength_type == equip.strength_type)
+        })
+        .map(|s| Stats {
+            hp: s.hp,
+            attack: s.atk,
+            defense: s.def,
+            mdefense: s.mdef,
+            ..Stats::default()
+        })
+        .unwrap_or_default();
+
+    let skill = equip_skill(psychube.equip_id, psychube.refine_lv);
+
+    PsychubeStats {
+        strengthen: PsychubeStats::at_level(strengthen, psychube.level),
+        attack_permille: skill.map(|s| s.attack).unwrap_or(0),
+        hp_permille: skill.map(|s| s.hp).unwrap_or(0),
+        ex: skill
+            .map(|s| Stats {
+                cri: s.cri,
+                recri: s.recri,
+                cri_dmg: s.cri_dmg,
+                cri_def: s.cri_def,
+                add_dmg: s.add_dmg,
+                drop_dmg: s.drop_dmg,
+                ..Stats::default()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+/// The `equip_skill` row for the psychube's refine level, else its first row
+fn equip_skill(equip_id: i32, refine_lv: i32) -> Option<&'static exceldb::equip_skill::EquipSkill> {
+    let rows = &exceldb::get().equip_skill;
+
+    rows.iter()
+        .find(|s| s.id == equip_id && s.skill_lv == refine_lv)
+        .or_else(|| rows.iter().find(|s| s.id == equip_id))
+}
+
 pub fn build_player_entity(user_id: i64, team_type: i32) -> FightEntityInfo {
     let uid = if team_type == 1 { 0 } else { -99999 };
 
@@ -157,36 +312,7 @@ pub fn build_player_entity(user_id: i64, team_type: i32) -> FightEntityInfo {
     }
 }
 
-pub fn get_hero_skill_group(hero_id: i32, group: i32) -> Vec<i32> {
-    let game_data = exceldb::get();
-
-    let character = game_data.character.iter().find(|c| c.id == hero_id);
-
-    if let Some(character) = character {
-        // Parse skill string: "1#30860111#30860112#30860113|2#30860121#30860122#30860123"
-        for group_str in character.skill.split('|') {
-            let parts: Vec<&str> = group_str.split('#').collect();
-
-            // First part is group number, check if it matches
-            if let Some(first) = parts.first() {
-                if let Ok(group_num) = first.parse::<i32>() {
-                    if group_num == group {
-                        // Return the skill IDs (skip first element which is the group number)
-                        return parts[1..]
-                            .iter()
-                            .filter_map(|s| s.parse::<i32>().ok())
-                            .collect();
-                    }
-                }
-            }
-        }
-    }
-
-    vec![]
-}
-
-// Make it non-async, pass equip_id from outside
-fn get_hero_passive_skills(hero_data: &HeroData, equip_id: Option<i32>) -> Vec<i32> {
+fn get_hero_passive_skills(hero_data: &HeroData, psychube: Option<&Equipment>) -> Vec<i32> {
     let game_data = exceldb::get();
     let mut passives = Vec::new();
     let hero_id = hero_data.record.hero_id;
@@ -225,14 +351,14 @@ fn get_hero_passive_skills(hero_data: &HeroData, equip_id: Option<i32>) -> Vec<i
     }
 
     // Equipment passive skills
-    if let Some(equip_id) = equip_id {
-        if let Some(equip_skill) = game_data.equip_skill.iter().find(|e| e.id == equip_id) {
-            if equip_skill.skill != 0 {
-                passives.push(equip_skill.skill);
-            }
-            if equip_skill.skill2 != 0 {
-                passives.push(equip_skill.skill2);
-            }
+    if let Some(psychube) = psychube
+        && let Some(equip_skill) = equip_skill(psychube.equip_id, psychube.refine_lv)
+    {
+        if equip_skill.skill != 0 {
+            passives.push(equip_skill.skill);
+        }
+        if equip_skill.skill2 != 0 {
+            passives.push(equip_skill.skill2);
         }
     }
 
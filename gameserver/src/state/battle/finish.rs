@@ -0,0 +1,202 @@
+use crate::error::AppError;
+use crate::handler::Session;
+use crate::load_message;
+use crate::state::{MATERIAL_CURRENCY, MATERIAL_ITEM, Material, changed_ids, grant_material};
+use crate::utils::push::{
+    currency_change_push, dungeon_update_push, end_dungeon_push, item_change_push, red_dot_push,
+};
+use database::db::game::battle::finish_battle_record;
+use database::db::game::dungeons::{
+    get_user_dungeon, save_dungeon_record, should_update_dungeon_record, update_dungeon_progress,
+};
+use database::db::game::equipment::build_equip_records;
+use sonettobuf::{CmdId, InstructionDungeonInfoPush};
+
+use super::end_fight::end_fight_push;
+use super::outcome::{FightResult, MAX_STARS};
+use super::rewards::{clear_stars, generate_dungeon_rewards};
+use super::tower::{TowerBattle, tower_score, update_tower_progress};
+
+/// Everything that follows a decided fight: every real fight goes to the dungeon
+/// and tower progress with its result, a win may become the episode's record
+/// and pays its rewards, then the end pushes let the client leave the fight.
+/// They are queued after the round's reply.
+/// The battle is taken off the connection, so a fight is only ever finished once.
+pub async fn finish_fight(session: &mut Session, result: FightResult) -> Result<(), AppError> {
+    let player_id = session.player_id()?;
+    let pool = session.db().clone();
+    let battle = session
+        .lock()
+        .await
+        .active_battle
+        .take()
+        .ok_or(AppError::InvalidRequest)?;
+
+    let fight = battle.fight.clone().unwrap_or_default();
+    let fight_group = battle.fight_group.clone();
+    let chapter_id = battle.chapter_id;
+    let episode_id = battle.episode_id;
+    let battle_id = battle.fight_id.unwrap_or_default();
+    let record_round = battle.current_round;
+    let is_replay = battle.is_replay.unwrap_or(false);
+    let won = result == FightResult::Win;
+
+    tracing::info!("Fight over: episode={}, result={:?}", episode_id, result);
+
+    // Only a win is rated
+    let stars = if won { clear_stars(&fight) } else { 0 };
+
+    let tower = battle
+        .tower_type
+        .zip(battle.tower_id)
+        .zip(battle.layer_id)
+        .map(|((tower_type, tower_id), layer_id)| TowerBattle {
+            tower_type,
+            tower_id,
+            layer_id,
+        });
+    if !is_replay && let Some(tower) = tower {
+        let score = tower_score(result, fight.max_round.unwrap_or(0), record_round);
+        update_tower_progress(&pool, player_id, tower, episode_id, result, score).await?;
+    }
+
+    if !is_replay && won {
+        // A new best or a different lineup becomes the record
+        let should_save_record =
+            should_update_dungeon_record(&pool, player_id, episode_id, record_round, &fight_group)
+                .await?;
+
+        if should_save_record {
+            let equips = build_equip_records(&pool, player_id, &fight_group).await?;
+            save_dungeon_record(
+                &pool,
+                player_id,
+                episode_id,
+                record_round,
+                &fight_group.clone().unwrap_or_default(),
+                equips,
+            )
+            .await?;
+
+            // The recorded lineup's fight becomes the one replays run again
+            finish_battle_record(&pool, player_id, episode_id, battle_id, &fight).await?;
+        }
+
+        tracing::info!(
+            "Battle completed: episode={}, round={}, record_saved={}",
+            episode_id,
+            record_round,
+            should_save_record
+        );
+    } else if is_replay {
+        tracing::info!(
+            "Replay completed: episode={}, round={}",
+            episode_id,
+            record_round
+        );
+    }
+
+    // The attempt, a clear and what it pays land together
+    let mut tx = pool.begin().await?;
+
+    // A replay runs an episode that is already cleared
+    let mut is_first_clear = false;
+    let mut advanced_earned = false;
+    if !is_replay {
+        let previous =
+            update_dungeon_progress(&mut tx, player_id, chapter_id, episode_id, won, stars).await?;
+        is_first_clear = won && previous.challenge_count == 0;
+        // Full stars pay the advanced bonus once
+        advanced_earned = stars == MAX_STARS && previous.star < MAX_STARS;
+    }
+
+    // Nothing for a lost fight
+    let mut all_rewards = Vec::new();
+    let mut advanced_bonus = Vec::new();
+    let mut granted = Vec::new();
+    if won {
+        let rewards = generate_dungeon_rewards(
+            episode_id,
+            is_first_clear,
+            advanced_earned,
+            battle.multiplication.unwrap_or(1),
+        );
+        advanced_bonus = rewards.advanced_bonus;
+
+        all_rewards.extend(rewards.normal_bonus);
+        all_rewards.extend(rewards.first_bonus);
+        all_rewards.extend(rewards.free_bonus);
+
+        for &(material_type, id, quantity) in all_rewards.iter().chain(&advanced_bonus) {
+            let material = Material {
+                material_type,
+                id,
+                quantity,
+            };
+            if grant_material(&mut tx, player_id, &material).await? {
+                granted.push(material);
+            }
+        }
+    }
+
+    tx.commit().await?;
+
+    // Sent for replays and losses too, the client needs them to leave the fight
+    let push = end_fight_push(
+        battle_id,
+        result.code(),
+        fight_group.unwrap_or_default(),
+        vec![],     // TODO: Actual battle stats
+        vec![],     // No defender stats
+        !is_replay, // is_record: only record real battles
+    );
+    session.push_after_reply(CmdId::FightEndFightPushCmd, push)?;
+
+    let push = load_message!(
+        InstructionDungeonInfoPush,
+        "dungeon/instruction_dungeon_info.json"
+    )?;
+    session.push_after_reply(CmdId::DungeonInstructionDungeonInfoPushCmd, push)?;
+
+    let updated_dungeon = get_user_dungeon(&pool, player_id, chapter_id, episode_id).await?;
+
+    let chapter_type = data::exceldb::get()
+        .chapter
+        .iter()
+        .find(|c| c.id == chapter_id)
+        .map(|c| c.r#type)
+        .unwrap_or(6);
+
+    let push = dungeon_update_push(
+        chapter_id,
+        episode_id,
+        updated_dungeon.star,
+        updated_dungeon.challenge_count,
+        updated_dungeon.has_record,
+        chapter_type,
+        2, // TODO: Calculate today's chapter completions
+        2, // TODO: Calculate today's chapter attempts
+    );
+    session.push_after_reply(CmdId::DungeonUpdatePushCmd, push)?;
+
+    let push = end_dungeon_push(chapter_id, episode_id, stars, all_rewards, advanced_bonus);
+    session.push_after_reply(CmdId::DungeonEndDungeonPushCmd, push)?;
+
+    let currency_ids = changed_ids(&granted, MATERIAL_CURRENCY)
+        .into_iter()
+        .map(|id| id as i32)
+        .collect();
+    if let Some(push) = currency_change_push(&pool, player_id, currency_ids).await? {
+        session.push_after_reply(CmdId::CurrencyChangePushCmd, push)?;
+    }
+    let item_ids = changed_ids(&granted, MATERIAL_ITEM);
+    if let Some(push) = item_change_push(&pool, player_id, item_ids).await? {
+        session.push_after_reply(CmdId::ItemChangePushCmd, push)?;
+    }
+
+    if let Some(push) = red_dot_push(&pool, player_id, Some(vec![1027, 1047])).await? {
+        session.push_after_reply(CmdId::UpdateRedDotPushCmd, push)?;
+    }
+
+    Ok(())
+}
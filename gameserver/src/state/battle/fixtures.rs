@@ -0,0 +1,209 @@
+//! The synthetic fights the combat tests play, with the tables they carry
+
+use anyhow::Result;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use serde::Deserialize;
+use sonettobuf::{BeginRoundOper, CardInfo, Fight, FightRoundOperRecord};
+use std::collections::HashMap;
+
+use super::buff::{BuffTemplate, SkillBuff};
+use super::cards;
+use super::cloth::{ClothRules, ClothState};
+use super::combat::{HeroSnapshot, Lineup, build_fight};
+use super::data::{BattleRules, CombatData, HeroCombat, MonsterCombat};
+use super::outcome::WinCondition;
+use super::replay::{Replay, run_rounds};
+use super::simulator::BattleSimulator;
+use super::skill::SkillEffect;
+use super::stats::Effectiveness;
+
+/// Everything a fight needs besides the static data
+#[derive(Debug, Clone, Deserialize)]
+pub struct CombatInput {
+    pub battle_id: i32,
+    pub heroes: Vec<HeroSnapshot>,
+    pub seed: u64,
+    pub act_point: i32,
+    #[serde(default)]
+    pub cloth_id: i32,
+    pub rounds: Vec<Vec<BeginRoundOper>>,
+}
+
+impl CombatInput {
+    /// Every hero in a slot of its own, in order
+    pub fn lineup(&self) -> Lineup {
+        Lineup {
+            heroes: self.heroes.iter().cloned().map(Some).collect(),
+            subs: vec![],
+            cloth_id: self.cloth_id,
+            cloth_level: 1,
+        }
+    }
+}
+
+/// Plays a whole fight without touching the database: builds both teams, deals
+/// the opening hand from the seed and runs the rounds until the fight is over
+pub fn simulate(data: &impl CombatData, input: &CombatInput) -> Result<Replay> {
+    let lineup = input.lineup();
+    let fight = build_fight(data, input.battle_id, &lineup)?;
+
+    let heroes: Vec<(i64, i32)> = input.heroes.iter().map(|h| (h.uid, h.hero_id)).collect();
+    // round 0 of the fight's RNG, the simulator starts at round 1
+    let hand = cards::deal_hand(data, &heroes, &mut StdRng::seed_from_u64(input.seed));
+
+    run_rounds(
+        data,
+        fight,
+        hand,
+        input.seed,
+        input.act_point,
+        ClothState::new(lineup.cloth_level),
+        input.rounds.iter().map(|opers| FightRoundOperRecord {
+            cloth_skill_opers: vec![],
+            opers: opers.clone(),
+        }),
+    )
+}
+
+// Synthetic fights, not real episodes. The excel export isn't checked in,
+// so each fixture carries hand-written rows for the battle it plays
+pub const FIXTURES: [(&str, &str); 3] = [
+    (
+        "synthetic_sweep",
+        include_str!("golden/synthetic_sweep.json"),
+    ),
+    ("synthetic_boss", include_str!("golden/synthetic_boss.json")),
+    (
+        "synthetic_overrun",
+        include_str!("golden/synthetic_overrun.json"),
+    ),
+];
+
+#[derive(Deserialize)]
+pub struct Fixture {
+    pub data: FixtureData,
+    pub input: CombatInput,
+}
+
+#[derive(Deserialize)]
+pub struct FixtureBattle {
+    pub ai_link: i32,
+    pub win_condition: String,
+    pub max_round: i32,
+    pub monster_ids: Vec<i32>,
+    /// Later waves, `monster_ids` is the first
+    #[serde(default)]
+    pub more_waves: Vec<Vec<i32>>,
+    #[serde(default)]
+    pub player_max: i32,
+    #[serde(default)]
+    pub monster_max: i32,
+}
+
+#[derive(Deserialize)]
+pub struct FixtureData {
+    pub battles: HashMap<i32, FixtureBattle>,
+    pub skill_ranks: HashMap<i32, i32>,
+    #[serde(default)]
+    pub skill_effects: HashMap<i32, SkillEffect>,
+    pub heroes: HashMap<i32, HeroCombat>,
+    pub monsters: HashMap<i32, MonsterCombat>,
+    #[serde(default)]
+    pub cloths: HashMap<i32, ClothRules>,
+    #[serde(default)]
+    pub buffs: Vec<BuffTemplate>,
+    #[serde(default)]
+    pub skill_buffs: Vec<SkillBuff>,
+}
+
+impl CombatData for FixtureData {
+    fn battle(&self, battle_id: i32) -> Option<BattleRules> {
+        self.battles.get(&battle_id).map(|b| BattleRules {
+            ai_link: b.ai_link,
+            win_condition: WinCondition::parse(&b.win_condition),
+            max_round: b.max_round,
+            waves: std::iter::once(b.monster_ids.clone())
+                .chain(b.more_waves.iter().cloned())
+                .collect(),
+            player_max: b.player_max,
+            monster_max: b.monster_max,
+            effectiveness: Effectiveness::default(),
+        })
+    }
+
+    fn skill_rank(&self, skill_id: i32) -> Option<i32> {
+        self.skill_ranks.get(&skill_id).copied()
+    }
+
+    fn skill_effect(&self, skill_id: i32) -> Option<SkillEffect> {
+        self.skill_effects.get(&skill_id).copied()
+    }
+
+    fn is_ex_skill(&self, hero_id: i32, skill_id: i32) -> bool {
+        self.heroes
+            .get(&hero_id)
+            .is_some_and(|h| h.ex_skill == skill_id)
+    }
+
+    fn hero(&self, hero_id: i32) -> Option<HeroCombat> {
+        self.heroes.get(&hero_id).cloned()
+    }
+
+    fn monster(&self, monster_id: i32) -> Option<MonsterCombat> {
+        self.monsters.get(&monster_id).cloned()
+    }
+
+    // one level per cloth is enough for the tests
+    fn cloth(&self, cloth_id: i32, _: i32) -> Option<ClothRules> {
+        self.cloths.get(&cloth_id).cloned()
+    }
+
+    fn buff(&self, buff_id: i32) -> Option<BuffTemplate> {
+        self.buffs.iter().find(|b| b.buff_id == buff_id).copied()
+    }
+
+    fn skill_buffs(&self, skill_id: i32) -> Vec<SkillBuff> {
+        self.skill_buffs
+            .iter()
+            .filter(|b| b.skill_id == skill_id)
+            .copied()
+            .collect()
+    }
+}
+
+pub fn load(raw: &str) -> Fixture {
+    serde_json::from_str(raw).expect("bad fixture")
+}
+
+impl Fixture {
+    /// The first synthetic fight, the one most tests bend to their case
+    pub fn sweep() -> Self {
+        load(FIXTURES[0].1)
+    }
+
+    /// The fixture's fight as its lineup builds it
+    pub fn fight(&self) -> Fight {
+        build_fight(&self.data, self.input.battle_id, &self.input.lineup()).unwrap()
+    }
+
+    /// The opening hand `simulate` deals
+    pub fn hand(&self) -> Vec<CardInfo> {
+        let heroes: Vec<(i64, i32)> = self
+            .input
+            .heroes
+            .iter()
+            .map(|h| (h.uid, h.hero_id))
+            .collect();
+        cards::deal_hand(
+            &self.data,
+            &heroes,
+            &mut StdRng::seed_from_u64(self.input.seed),
+        )
+    }
+
+    /// A simulator on `fight` with the fixture's seed and a level 1 cloth
+    pub fn simulator(&self, fight: Fight) -> BattleSimulator<&FixtureData> {
+        BattleSimulator::new(&self.data, fight, self.input.seed, ClothState::new(1))
+    }
+}
@@ -1,14 +1,27 @@
 // src/battle/mod.rs
 
+mod ai;
 mod auto;
+mod buff;
 mod cards;
+pub mod cloth;
+pub mod combat;
+pub mod data;
 pub mod end_fight;
 pub mod entity_builder;
 pub mod fight_builder;
+pub mod finish;
+#[cfg(test)]
+mod fixtures;
+pub mod outcome;
+pub mod replay;
 pub mod rewards;
 pub mod round_builder;
 pub mod simulator;
+mod skill;
+pub mod stats;
 pub mod step_builder;
+pub mod tower;
 
 use anyhow::Result;
 use sonettobuf::StartDungeonReply;
@@ -24,6 +37,8 @@ pub struct BattleContext {
     pub chapter_id: i32,
     pub episode_id: i32,
     pub battle_id: i32,
+    /// The player's level of the fight group's cloth
+    pub cloth_level: i32,
 }
 
 pub async fn create_battle(
@@ -34,7 +49,7 @@ pub async fn create_battle(
 ) -> Result<StartDungeonReply> {
     let fight = fight_builder::build_fight(pool, &ctx, fight_group).await?;
 
-    let round = round_builder::build_initial_round(&fight, card_deck).await?;
+    let round = round_builder::build_initial_round(&fight, card_deck)?;
 
     Ok(StartDungeonReply {
         fight: Some(fight),
@@ -0,0 +1,278 @@
+use sonettobuf::{Fight, FightEntityInfo};
+use std::collections::HashMap;
+
+pub const MAX_STARS: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FightResult {
+    Win,
+    Lose,
+    Timeout,
+}
+
+impl FightResult {
+    /// `FightRecord.fight_result`
+    pub fn code(self) -> i32 {
+        match self {
+            FightResult::Win => 1,
+            FightResult::Lose => 2,
+            FightResult::Timeout => 3,
+        }
+    }
+}
+
+/// `battle.winCondition`: "" or "1" = defeat everyone, "2#monsterId#..." = defeat
+/// those monsters, "3#round" = survive until that round
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WinCondition {
+    DefeatAll,
+    DefeatMonsters(Vec<i32>),
+    Survive(i32),
+}
+
+impl WinCondition {
+    pub fn parse(raw: &str) -> Self {
+        let mut parts = raw.split('#').filter_map(|s| s.trim().parse::<i32>().ok());
+
+        match parts.next() {
+            Some(2) => {
+                let monsters: Vec<i32> = parts.collect();
+                if monsters.is_empty() {
+                    WinCondition::DefeatAll
+                } else {
+                    WinCondition::DefeatMonsters(monsters)
+                }
+            }
+            Some(3) => match parts.next() {
+                Some(round) if round > 0 => WinCondition::Survive(round),
+                _ => WinCondition::DefeatAll,
+            },
+            Some(1) | None => WinCondition::DefeatAll,
+            Some(other) => {
+                tracing::debug!("Unknown win condition {} ({}), defeat all", other, raw);
+                WinCondition::DefeatAll
+            }
+        }
+    }
+
+    /// Wiping the enemy team always wins, whatever the condition
+    pub fn is_met(&self, entities: &HashMap<i64, FightEntityInfo>, round: i32) -> bool {
+        if !team_alive(entities, 2) {
+            return true;
+        }
+
+        match self {
+            WinCondition::DefeatAll => false,
+            WinCondition::DefeatMonsters(monsters) => !entities.values().any(|e| {
+                e.team_type == Some(2) && is_alive(e) && monsters.contains(&e.model_id.unwrap_or(0))
+            }),
+            WinCondition::Survive(target) => round >= *target,
+        }
+    }
+
+    /// Whether the fight is won before the `upcoming` monsters, the wave's
+    /// reinforcements and the later waves, come in
+    pub fn met_before_waves(&self, upcoming: &[Vec<i32>], round: i32) -> bool {
+        match self {
+            WinCondition::DefeatAll => false,
+            WinCondition::DefeatMonsters(monsters) => !upcoming
+                .iter()
+                .flatten()
+                .any(|monster_id| monsters.contains(monster_id)),
+            WinCondition::Survive(target) => round >= *target,
+        }
+    }
+}
+
+/// One entry of `battle.advancedCondition`, "|" separated. The server only
+/// rates "1#round" = win by that round and "2#count" = lose at most that many
+/// heroes, anything else is kept as `Unknown` and never met
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdvancedCondition {
+    WithinRounds(i32),
+    MaxDeaths(i32),
+    Unknown(String),
+}
+
+impl AdvancedCondition {
+    pub fn parse(raw: &str) -> Vec<Self> {
+        raw.split('|')
+            .filter(|part| !part.trim().is_empty())
+            .map(|part| {
+                let mut parts = part.split('#').filter_map(|s| s.trim().parse::<i32>().ok());
+
+                match (parts.next(), parts.next()) {
+                    (Some(1), Some(round)) => AdvancedCondition::WithinRounds(round),
+                    (Some(2), deaths) => AdvancedCondition::MaxDeaths(deaths.unwrap_or(0)),
+                    _ => {
+                        tracing::warn!(
+                            "Unknown advanced condition {}, no advanced star for it",
+                            part
+                        );
+                        AdvancedCondition::Unknown(part.trim().to_string())
+                    }
+                }
+            })
+            .collect()
+    }
+
+    pub fn is_met(&self, clear: &ClearStats) -> bool {
+        match *self {
+            AdvancedCondition::WithinRounds(round) => clear.rounds <= round,
+            AdvancedCondition::MaxDeaths(deaths) => clear.deaths <= deaths,
+            AdvancedCondition::Unknown(_) => false,
+        }
+    }
+}
+
+/// How a won fight went, what the star rating looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClearStats {
+    pub rounds: i32,
+    pub deaths: i32,
+}
+
+impl ClearStats {
+    pub fn from_fight(fight: &Fight) -> Self {
+        let deaths = fight
+            .attacker
+            .iter()
+            .flat_map(|team| team.entitys.iter())
+            .filter(|e| !is_alive(e))
+            .count();
+
+        Self {
+            rounds: fight.cur_round.unwrap_or(1),
+            deaths: deaths as i32,
+        }
+    }
+
+    /// One star for the win, two when nobody went down, three when the
+    /// battle's advanced conditions were met on top of that
+    pub fn stars(&self, conditions: &[AdvancedCondition]) -> i32 {
+        if self.deaths > 0 {
+            1
+        } else if conditions.iter().all(|c| c.is_met(self)) {
+            MAX_STARS
+        } else {
+            2
+        }
+    }
+}
+
+pub fn is_alive(entity: &FightEntityInfo) -> bool {
+    entity.current_hp.unwrap_or(0) > 0
+}
+
+pub fn team_alive(entities: &HashMap<i64, FightEntityInfo>, team_type: i32) -> bool {
+    entities
+        .values()
+        .any(|e| e.team_type == Some(team_type) && is_alive(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(uid: i64, model_id: i32, team_type: i32, hp: i32) -> (i64, FightEntityInfo) {
+        (
+            uid,
+            FightEntityInfo {
+                uid: Some(uid),
+                model_id: Some(model_id),
+                team_type: Some(team_type),
+                current_hp: Some(hp),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn parses_win_conditions() {
+        assert_eq!(WinCondition::parse(""), WinCondition::DefeatAll);
+        assert_eq!(WinCondition::parse("1"), WinCondition::DefeatAll);
+        assert_eq!(
+            WinCondition::parse("2#100101#100102"),
+            WinCondition::DefeatMonsters(vec![100101, 100102])
+        );
+        assert_eq!(WinCondition::parse("3#5"), WinCondition::Survive(5));
+        assert_eq!(WinCondition::parse("3"), WinCondition::DefeatAll);
+        assert_eq!(WinCondition::parse("9#1"), WinCondition::DefeatAll);
+    }
+
+    #[test]
+    fn defeating_the_listed_monsters_wins() {
+        let entities: HashMap<_, _> = [
+            entity(1, 3001, 1, 500),
+            entity(-1, 100101, 2, 0),
+            entity(-2, 100102, 2, 800),
+        ]
+        .into_iter()
+        .collect();
+
+        assert!(WinCondition::DefeatMonsters(vec![100101]).is_met(&entities, 1));
+        assert!(!WinCondition::DefeatMonsters(vec![100102]).is_met(&entities, 1));
+        assert!(!WinCondition::DefeatAll.is_met(&entities, 1));
+        assert!(WinCondition::Survive(3).is_met(&entities, 3));
+    }
+
+    #[test]
+    fn wiping_the_enemy_team_always_wins() {
+        let entities: HashMap<_, _> = [entity(1, 3001, 1, 500), entity(-1, 100101, 2, 0)]
+            .into_iter()
+            .collect();
+
+        assert!(WinCondition::Survive(10).is_met(&entities, 1));
+    }
+
+    #[test]
+    fn waves_left_hold_off_the_win() {
+        let upcoming = [vec![100103], vec![100104, 100105]];
+
+        assert!(!WinCondition::DefeatAll.met_before_waves(&upcoming, 1));
+        assert!(WinCondition::DefeatMonsters(vec![100101]).met_before_waves(&upcoming, 1));
+        assert!(!WinCondition::DefeatMonsters(vec![100105]).met_before_waves(&upcoming, 1));
+        assert!(!WinCondition::Survive(3).met_before_waves(&upcoming, 2));
+        assert!(WinCondition::Survive(3).met_before_waves(&upcoming, 3));
+    }
+
+    #[test]
+    fn parses_advanced_conditions() {
+        assert_eq!(
+            AdvancedCondition::parse("1#8|2"),
+            vec![
+                AdvancedCondition::WithinRounds(8),
+                AdvancedCondition::MaxDeaths(0)
+            ]
+        );
+        assert_eq!(
+            AdvancedCondition::parse("2#1"),
+            vec![AdvancedCondition::MaxDeaths(1)]
+        );
+        assert!(AdvancedCondition::parse("").is_empty());
+        assert_eq!(
+            AdvancedCondition::parse("9#3|1"),
+            vec![
+                AdvancedCondition::Unknown("9#3".into()),
+                AdvancedCondition::Unknown("1".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn stars_follow_deaths_and_conditions() {
+        let conditions = [AdvancedCondition::WithinRounds(5)];
+        let clear = |rounds, deaths| ClearStats { rounds, deaths };
+
+        assert_eq!(clear(4, 0).stars(&conditions), 3);
+        assert_eq!(clear(6, 0).stars(&conditions), 2);
+        assert_eq!(clear(4, 1).stars(&conditions), 1);
+        assert_eq!(clear(20, 0).stars(&[]), 3);
+
+        let unknown = [
+            AdvancedCondition::WithinRounds(5),
+            AdvancedCondition::Unknown("9#3".into()),
+        ];
+        assert_eq!(clear(4, 0).stars(&unknown), 2);
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use database::db::game::battle::BattleRecord;
+use sonettobuf::{BuffInfo, CardInfo, Fight, FightRound, FightRoundOperRecord, UseClothSkillRound};
+
+use super::cloth::ClothState;
+use super::data::CombatData;
+use super::outcome::FightResult;
+use super::simulator::BattleSimulator;
+
+/// Stored with every battle record. Bump it when the simulator or the combat
+/// data it reads would play the same operations out differently, older records
+/// are then no longer replayed.
+pub const REPLAY_VERSION: i32 = 4;
+
+/// A fight run from a known state and seed
+pub struct Replay {
+    pub fight: Fight,
+    pub hand: Vec<CardInfo>,
+    pub rounds: Vec<FightRound>,
+    /// The cloth skills used before each round, same order as `rounds`
+    pub cloth_skills: Vec<Vec<UseClothSkillRound>>,
+    /// Whether a new wave came in during each round, same order as `rounds`
+    pub new_waves: Vec<bool>,
+    /// The fight as each new wave came in
+    pub wave_fights: Vec<Fight>,
+    /// The cloth after the last round, its skill uses counted
+    pub cloth: ClothState,
+    pub result: Option<FightResult>,
+}
+
+impl Replay {
+    /// Whether it ended exactly where the recorded fight did
+    pub fn matches(&self, record: &BattleRecord) -> bool {
+        record
+            .final_fight
+            .as_ref()
+            .is_some_and(|expected| fight_state(&self.fight) == fight_state(expected))
+    }
+}
+
+/// Plays the rounds one after another, cloth skills first, stopping once the
+/// fight is over
+pub fn run_rounds(
+    data: &impl CombatData,
+    mut fight: Fight,
+    mut hand: Vec<CardInfo>,
+    seed: u64,
+    act_point: i32,
+    mut cloth: ClothState,
+    round_opers: impl IntoIterator<Item = FightRoundOperRecord>,
+) -> Result<Replay> {
+    let mut rounds = Vec::new();
+    let mut cloth_skills = Vec::new();
+    let mut new_waves = Vec::new();
+    let mut wave_fights = Vec::new();
+    let mut result = None;
+
+    for record in round_opers {
+        if result.is_some() {
+            tracing::warn!("Operations left over after the fight ended");
+            break;
+        }
+
+        let mut used = Vec::new();
+        for oper in record.cloth_skill_opers {
+            let mut simulator = BattleSimulator::new(data, fight, seed, cloth);
+            let round = simulator.use_cloth_skill(oper.skill_id.unwrap_or(0), hand, act_point)?;
+
+            hand = round.team_a_cards1.clone();
+            fight = simulator.fight().clone();
+            cloth = simulator.cloth_state().clone();
+            used.push(UseClothSkillRound {
+                skill_id: oper.skill_id,
+                from_id: oper.from_id,
+                to_id: oper.to_id,
+                round: Some(round),
+                r#type: oper.r#type,
+            });
+        }
+        cloth_skills.push(used);
+
+        let wave = fight.cur_wave;
+        let mut simulator = BattleSimulator::new(data, fight, seed, cloth);
+        let round = simulator.process_round(record.opers, hand, act_point)?;
+
+        hand = round.team_a_cards1.clone();
+        result = simulator.result();
+        fight = simulator.fight().clone();
+        cloth = simulator.cloth_state().clone();
+        rounds.push(round);
+
+        let new_wave = fight.cur_wave != wave;
+        if new_wave {
+            wave_fights.push(fight.clone());
+        }
+        new_waves.push(new_wave);
+    }
+
+    Ok(Replay {
+        fight,
+        hand,
+        rounds,
+        cloth_skills,
+        new_waves,
+        wave_fights,
+        cloth,
+        result,
+    })
+}
+
+/// The stored fight run again from its seed and opening hand
+pub fn replay_battle(
+    data: &impl CombatData,
+    record: &BattleRecord,
+    round_opers: &[FightRoundOperRecord],
+) -> Result<Replay> {
+    run_rounds(
+        data,
+        record.fight.clone(),
+        record.card_deck.clone(),
+        record.seed,
+        record.act_point,
+        ClothState::new(record.cloth_level),
+        round_opers.iter().cloned(),
+    )
+}
+
+type EntityState = (Option<i64>, Option<i32>, Option<i32>, Vec<BuffInfo>);
+
+// what a fight's outcome depends on: round, wave, finish flag, hp, moxie and buffs
+fn fight_state(fight: &Fight) -> (Option<i32>, Option<i32>, Option<bool>, Vec<EntityState>) {
+    let mut entities: Vec<EntityState> = [fight.attacker.as_ref(), fight.defender.as_ref()]
+        .into_iter()
+        .flatten()
+        .flat_map(|team| team.entitys.iter())
+        .map(|e| (e.uid, e.current_hp, e.ex_point, e.buffs.clone()))
+        .collect();
+    entities.sort_by_key(|e| e.0);
+
+    (fight.cur_round, fight.cur_wave, fight.is_finish, entities)
+}
@@ -1,16 +1,45 @@
 use data::exceldb;
+use sonettobuf::Fight;
+
+use super::outcome::{AdvancedCondition, ClearStats};
 
 #[derive(Debug, Clone)]
 pub struct DungeonRewards {
     pub normal_bonus: Vec<(u32, u32, i32)>, // (type, id, amount)
     pub first_bonus: Vec<(u32, u32, i32)>,
     pub free_bonus: Vec<(u32, u32, i32)>,
+    pub advanced_bonus: Vec<(u32, u32, i32)>,
+}
+
+/// Star rating of a won fight against its battle's advanced conditions
+pub fn clear_stars(fight: &Fight) -> i32 {
+    let battle_id = fight.battle_id.unwrap_or(0);
+    let conditions = exceldb::get()
+        .battle
+        .get(battle_id)
+        .map(|b| AdvancedCondition::parse(&b.advanced_condition))
+        .unwrap_or_default();
+
+    let clear = ClearStats::from_fight(fight);
+    let stars = clear.stars(&conditions);
+
+    tracing::info!(
+        "Battle {} cleared in {} rounds with {} down: {} stars",
+        battle_id,
+        clear.rounds,
+        clear.deaths,
+        stars
+    );
+
+    stars
 }
 
-/// Generate dungeon rewards from episode data
+/// Generate dungeon rewards from episode data, `advanced_earned` when this
+/// clear reached full stars for the first time
 pub fn generate_dungeon_rewards(
     episode_id: i32,
     is_first_clear: bool,
+    advanced_earned: bool,
     multiplication: i32, // From StartDungeonRequest
 ) -> DungeonRewards {
     let game_data = exceldb::get();
@@ -22,6 +51,7 @@ pub fn generate_dungeon_rewards(
             normal_bonus: vec![],
             first_bonus: vec![],
             free_bonus: vec![],
+            advanced_bonus: vec![],
         };
     };
 
@@ -45,10 +75,18 @@ pub fn generate_dungeon_rewards(
         vec![]
     };
 
+    // Full stars pay out once, not on every flawless clear
+    let advanced_bonus = if advanced_earned && episode.advanced_bonus != 0 {
+        parse_bonus_rewards(episode.advanced_bonus, 1)
+    } else {
+        vec![]
+    };
+
     DungeonRewards {
         normal_bonus,
         first_bonus,
         free_bonus,
+        advanced_bonus,
     }
 }
 
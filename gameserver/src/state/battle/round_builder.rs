@@ -3,11 +3,17 @@ use sonettobuf::{Fight, FightExPointInfo, FightRound};
 
 use crate::state::battle::step_builder::FightStepBuilder;
 
-pub async fn build_initial_round(
+pub fn build_initial_round(
     fight: &Fight,
     card_deck: Vec<sonettobuf::CardInfo>,
 ) -> Result<FightRound> {
     let mut steps = vec![];
+    let power = fight.attacker.as_ref().and_then(|t| t.power).unwrap_or(0);
+    let skill_infos = fight
+        .attacker
+        .as_ref()
+        .map(|t| t.skill_infos.clone())
+        .unwrap_or_default();
 
     // Add ENTERFIGHTDEAL with all fields initialized
     steps.push(
@@ -19,7 +25,7 @@ pub async fn build_initial_round(
     // Power generation (already working)
     steps.push(
         FightStepBuilder::new_effect()
-            .add_power_generation(15, 1)
+            .add_power_generation(power, 1)
             .build(),
     );
 
@@ -32,8 +38,8 @@ pub async fn build_initial_round(
         move_num: Some(0),
         ex_point_info,
         ai_use_cards: vec![],
-        power: Some(15),
-        skill_infos: vec![],
+        power: Some(power),
+        skill_infos,
         before_cards1: vec![],
         team_a_cards1: card_deck,
         before_cards2: vec![],
@@ -1,46 +1,123 @@
 use anyhow::Result;
-use rand::SeedableRng;
 use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use sonettobuf::{
     ActEffect, BeginRoundOper, CardInfo, Fight, FightEntityInfo, FightRound, FightStep, fight_step,
 };
 use std::collections::HashMap;
 
-#[allow(dead_code)]
-pub struct BattleSimulator {
+use super::ai::{self, AiBehaviour};
+use super::buff::{self, BuffEffect, BuffTrigger, ControlKind};
+use super::cards;
+use super::cloth::{self, ClothEffect, ClothRules, ClothState, PowerGains};
+use super::combat;
+use super::data::CombatData;
+use super::outcome::{FightResult, WinCondition, is_alive, team_alive};
+use super::skill::{self, SkillKind, SkillProfile};
+use super::step_builder::FightStepBuilder;
+
+pub struct BattleSimulator<D: CombatData> {
+    data: D,
     fight: Fight,
     rng: StdRng,
+    behaviour: AiBehaviour,
+    win_condition: WinCondition,
+    max_round: i32,
+    /// `battle.monsterGroupIds`, the fight's `cur_wave` says which one is up
+    waves: Vec<Vec<i32>>,
+    monster_max: i32,
+    cloth: Option<ClothRules>,
+    cloth_state: ClothState,
+    result: Option<FightResult>,
 }
 
-#[allow(dead_code)]
-#[allow(unused_variables)]
-impl BattleSimulator {
-    pub fn new(fight: Fight) -> Self {
-        let seed = chrono::Utc::now().timestamp_millis() as u64;
+impl<D: CombatData> BattleSimulator<D> {
+    /// Same data, seed, fight, cloth state and operations always give the same round
+    pub fn new(data: D, fight: Fight, seed: u64, cloth_state: ClothState) -> Self {
+        let rng = round_rng(seed, fight.cur_round.unwrap_or(1));
+        let battle = data.battle(fight.battle_id.unwrap_or(0));
+
+        let behaviour = AiBehaviour::from_ai_link(battle.as_ref().map(|b| b.ai_link).unwrap_or(0));
+        let max_round = fight
+            .max_round
+            .or(battle.as_ref().map(|b| b.max_round))
+            .unwrap_or(0);
+        let waves = battle.as_ref().map(|b| b.waves.clone()).unwrap_or_default();
+        let monster_max = battle.as_ref().map(|b| b.monster_max).unwrap_or(0);
+        let win_condition = battle
+            .map(|b| b.win_condition)
+            .unwrap_or(WinCondition::DefeatAll);
+        let cloth = fight
+            .attacker
+            .as_ref()
+            .and_then(|t| t.cloth_id)
+            .and_then(|id| data.cloth(id, cloth_state.level));
+
         Self {
+            data,
             fight,
-            rng: StdRng::seed_from_u64(seed),
+            rng,
+            behaviour,
+            win_condition,
+            max_round,
+            waves,
+            monster_max,
+            cloth,
+            cloth_state,
+            result: None,
         }
     }
 
-    pub async fn process_round(
+    /// Set once the fight is over
+    pub fn result(&self) -> Option<FightResult> {
+        self.result
+    }
+
+    /// The cloth with this round's skill uses counted, for the next round
+    pub fn cloth_state(&self) -> &ClothState {
+        &self.cloth_state
+    }
+
+    pub fn process_round(
         &mut self,
         operations: Vec<BeginRoundOper>,
         current_deck: Vec<CardInfo>,
+        act_point: i32,
     ) -> Result<FightRound> {
-        let mut state = RoundState::from_fight(&self.fight)?;
-        state.player_deck = current_deck;
+        let mut state = self.round_state(current_deck, act_point)?;
 
         let mut steps = Vec::new();
 
         // Process player operations
         for oper in operations {
-            let step = self.execute_operation(&mut state, oper).await?;
+            let step = self.execute_operation(&mut state, oper)?;
             if step.act_type.is_some() && step.act_type.unwrap() != 0 {
                 steps.push(step);
             }
         }
 
+        self.result = self.check_battle_end(&state);
+
+        if self.result.is_none() {
+            let ai_steps = self.execute_ai_turn(&mut state)?;
+            steps.extend(ai_steps);
+            self.result = self.check_battle_end(&state);
+        }
+
+        if let Some(step) = state.tick_buffs() {
+            steps.push(step);
+        }
+
+        if self.result.is_none() {
+            steps.extend(self.reinforce(&mut state));
+        }
+
+        if self.result.is_none()
+            && let Some(step) = self.change_wave(&mut state)?
+        {
+            steps.push(step);
+        }
+
         // Add round end effect
         steps.push(FightStep {
             act_type: Some(fight_step::ActType::Effect.into()),
@@ -82,10 +159,74 @@ impl BattleSimulator {
         // Increment move counter
         state.move_num += 1;
 
-        Ok(self.build_round_response(steps, state))
+        if self.result.is_none() && self.max_round > 0 && state.round_num >= self.max_round {
+            tracing::info!("Fight ran out of rounds ({})", self.max_round);
+            self.result = Some(FightResult::Timeout);
+        }
+
+        let mut begin_steps = Vec::new();
+        if self.result.is_none() {
+            state.round_num += 1;
+            state.gain_power(state.gains.round);
+            if let Some(attacker) = self.fight.attacker.as_mut() {
+                cloth::tick_cooldowns(&mut attacker.skill_infos);
+            }
+
+            if let Some(step) = state.round_start_buffs(&self.data) {
+                begin_steps.push(step);
+                self.result = self.check_battle_end(&state);
+            }
+        }
+
+        state.is_finish = self.result.is_some();
+        if !state.is_finish {
+            state.ai_cards = self.plan_ai_cards(&state);
+
+            let heroes = state.living(1);
+            let hand = std::mem::take(&mut state.player_deck);
+            state.player_deck = cards::refill_hand(&self.data, &heroes, hand, &mut self.rng);
+        }
+
+        self.sync_fight(&state);
+
+        Ok(self.build_round_response(steps, begin_steps, state))
     }
 
-    async fn execute_operation(
+    /// A cloth skill used before the round's cards, applied to the fight right away
+    pub fn use_cloth_skill(
+        &mut self,
+        skill_id: i32,
+        current_deck: Vec<CardInfo>,
+        act_point: i32,
+    ) -> Result<FightRound> {
+        let mut state = self.round_state(current_deck, act_point)?;
+
+        let step = self.cast_cloth_skill(&mut state, skill_id);
+        let steps = if step.act_type.is_some() {
+            vec![step]
+        } else {
+            vec![]
+        };
+
+        self.sync_fight(&state);
+
+        Ok(self.build_round_response(steps, vec![], state))
+    }
+
+    fn round_state(&self, current_deck: Vec<CardInfo>, act_point: i32) -> Result<RoundState> {
+        let mut state = RoundState::from_fight(&self.fight)?;
+        state.player_deck = current_deck;
+        state.act_point = act_point;
+
+        if let Some(cloth) = &self.cloth {
+            state.gains = cloth.gains;
+            state.max_power = cloth.max_power;
+        }
+
+        Ok(state)
+    }
+
+    fn execute_operation(
         &mut self,
         state: &mut RoundState,
         oper: BeginRoundOper,
@@ -93,140 +234,449 @@ impl BattleSimulator {
         let oper_type = oper.oper_type.unwrap_or(0);
 
         match oper_type {
-            1 => self.play_card(state, oper).await,
-            2 => {
-                // Check if this is card selection or actual player skill
-                let param1 = oper.param1.unwrap_or(0);
-                if param1 < 100 {
-                    // Low param1 = card selection (0, 1, 2, etc)
-                    self.select_card(state, oper).await
-                } else {
-                    // High param1 = cloth skill ID
-                    self.use_player_skill(state, oper).await
-                }
+            1 => self.play_card(state, oper),
+            // Moving a card, param1 from and param2 to. Cloth skills come
+            // through `use_cloth_skill`, never as a round operation
+            2 => Ok(self.move_card(state, oper.param1.unwrap_or(0), oper.param2.unwrap_or(0))),
+            3 => {
+                tracing::info!("Changing hero to {}", oper.to_id.unwrap_or(0));
+                Ok(FightStep::default())
+            }
+            4 => {
+                tracing::info!("Ending turn");
+                Ok(FightStep::default())
             }
-            3 => self.change_hero(state, oper).await,
-            4 => self.end_turn(state).await,
             _ => Ok(FightStep::default()),
         }
     }
 
-    async fn select_card(
-        &mut self,
-        state: &mut RoundState,
-        oper: BeginRoundOper,
-    ) -> Result<FightStep> {
+    /// Spends an action point to move a card, merging what lands next to its
+    /// twin. Refused without action points or outside the hand
+    fn move_card(&mut self, state: &mut RoundState, from: i32, to: i32) -> FightStep {
+        if state.act_point <= 0 {
+            tracing::warn!("Card moved without action points left");
+            return FightStep::default();
+        }
+
+        if !cards::move_card(&mut state.player_deck, from, to) {
+            tracing::warn!(
+                "Can't move card {} -> {} (hand size {})",
+                from,
+                to,
+                state.player_deck.len()
+            );
+            return FightStep::default();
+        }
+
+        tracing::info!("Moving card {} -> {}", from, to);
+
+        state.act_point -= 1;
+        state.gain_power(state.gains.move_card);
+        state.settle_hand(&self.data);
+
+        FightStep::default()
+    }
+
+    fn play_card(&mut self, state: &mut RoundState, oper: BeginRoundOper) -> Result<FightStep> {
         let card_index = oper.param1.unwrap_or(0);
 
-        tracing::info!("Selecting card at index {}", card_index);
+        if state.act_point <= 0 {
+            tracing::warn!("Card {} played without action points left", card_index);
+            return Ok(FightStep::default());
+        }
 
-        // Return a step indicating card was selected
-        Ok(FightStep {
-            act_type: Some(fight_step::ActType::Effect.into()),
-            from_id: Some(0),
-            to_id: Some(0),
-            act_id: Some(0),
-            act_effect: vec![ActEffect {
-                target_id: Some(0),
-                effect_type: Some(149), // ADDHANDCARD
-                effect_num: Some(card_index),
-                buff: None,
-                entity: None,
-                config_effect: Some(0),
-                buff_act_id: Some(0),
-                reserve_id: Some(0),
-                reserve_str: Some(String::new()),
-                summoned: None,
-                magic_circle: None,
-                card_info: None,
-                card_info_list: vec![],
-                team_type: Some(1),
-                fight_step: None,
-                assist_boss_info: None,
-                effect_num1: Some(0),
-                emitter_info: None,
-                player_finisher_info: None,
-                power_info: None,
-                card_heat_value: None,
-                fight_tasks: vec![],
-                fight: None,
-                buff_act_info: None,
-                hurt_info: None,
-            }],
-            card_index: Some(card_index),
-            support_hero_id: Some(0),
-            fake_timeline: Some(false),
-        })
+        let Some(card) = state.take_card(card_index) else {
+            tracing::warn!(
+                "No card at index {} (hand size {})",
+                card_index,
+                state.player_deck.len()
+            );
+            return Ok(FightStep::default());
+        };
+
+        state.act_point -= 1;
+        state.gain_power(state.gains.play);
+
+        let caster_uid = card.uid.unwrap_or(0);
+        let skill_id = card.skill_id.unwrap_or(0);
+        let mut profile = skill::resolve_skill(&self.data, card.hero_id.unwrap_or(0), skill_id);
+        state.used_cards.push(skill_id);
+
+        // Spending the ultimate empties the moxie, anything else adds to it
+        if cards::is_ultimate(&self.data, &card) {
+            profile.is_ex = true;
+            if let Some(caster) = state.get_entity_mut(caster_uid) {
+                caster.ex_point = Some(0);
+            }
+        } else if let Some(caster) = state.get_entity_mut(caster_uid).filter(|e| is_alive(e)) {
+            cards::add_moxie(&self.data, caster, cards::MOXIE_PER_CARD);
+        }
+
+        // Playing a card can close the gap between two identical ones
+        state.settle_hand(&self.data);
+
+        let Some(caster) = state
+            .get_entity(caster_uid)
+            .filter(|e| is_alive(e))
+            .cloned()
+        else {
+            tracing::warn!(
+                "Card {} cast by missing or dead entity {}",
+                skill_id,
+                caster_uid
+            );
+            return Ok(FightStepBuilder::new_skill(caster_uid, 0, skill_id)
+                .card_index(card_index)
+                .build());
+        };
+
+        if buff::has_control(&self.data, &caster, ControlKind::Stun)
+            || (profile.is_ex && buff::has_control(&self.data, &caster, ControlKind::Seal))
+        {
+            tracing::info!("Card {} blocked, {} is controlled", skill_id, caster_uid);
+            return Ok(FightStepBuilder::new_skill(caster_uid, 0, skill_id)
+                .card_index(card_index)
+                .build());
+        }
+
+        let Some(target_uid) = state.resolve_target(&caster, oper.to_id.unwrap_or(0), profile.kind)
+        else {
+            return Ok(FightStepBuilder::new_skill(caster_uid, 0, skill_id)
+                .card_index(card_index)
+                .build());
+        };
+
+        tracing::info!(
+            "Playing card {} (skill {}, rank {}, ex={}) from {} targeting {}",
+            card_index,
+            skill_id,
+            profile.rank,
+            profile.is_ex,
+            caster_uid,
+            target_uid
+        );
+
+        let builder =
+            FightStepBuilder::new_skill(caster_uid, target_uid, skill_id).card_index(card_index);
+        let builder = state.trigger_buffs(&self.data, caster_uid, BuffTrigger::OnCardPlay, builder);
+
+        let builder = state.apply_effect(&self.data, &caster, target_uid, &profile, builder);
+        let builder = state.apply_skill_buffs(&self.data, &caster, target_uid, skill_id, builder);
+
+        Ok(builder.build())
     }
 
-    async fn play_card(
-        &mut self,
-        state: &mut RoundState,
-        oper: BeginRoundOper,
-    ) -> Result<FightStep> {
-        let card_index = oper.param1.unwrap_or(0) as usize;
-        let target_uid = oper.to_id.unwrap_or(0);
+    /// Pays for a cloth skill and applies it. Refused while it cools down, once
+    /// its uses run out or without enough power
+    fn cast_cloth_skill(&mut self, state: &mut RoundState, skill_id: i32) -> FightStep {
+        let Some(skill) = self.cloth.as_ref().and_then(|c| c.skill(skill_id)).cloned() else {
+            tracing::warn!("Cloth skill {} not on the player's cloth", skill_id);
+            return FightStep::default();
+        };
 
-        // TODO: Get card from player deck
-        // TODO: Load skill config from excel
-        // TODO: Execute skill effects
-        // TODO: Update state (HP, buffs, etc)
-        // TODO: Consume action points
+        let uses = self.cloth_state.uses(skill_id);
+        let cd = self
+            .skill_info_mut(skill_id)
+            .and_then(|i| i.cd)
+            .unwrap_or(0);
+        let cost = skill.cost(uses);
 
-        tracing::info!("Playing card {} targeting {}", card_index, target_uid);
+        if cd > 0 || skill.used_up(uses) || state.power < cost {
+            tracing::warn!(
+                "Cloth skill {} refused: cd={}, uses={}, power={}/{}",
+                skill_id,
+                cd,
+                uses,
+                state.power,
+                cost
+            );
+            return FightStep::default();
+        }
 
-        // Placeholder: return empty step for now
-        Ok(FightStep {
-            act_type: Some(fight_step::ActType::Skill.into()),
-            from_id: Some(0),
-            to_id: Some(target_uid),
-            act_id: Some(0),
-            act_effect: vec![],
-            card_index: Some(card_index as i32),
-            support_hero_id: Some(0),
-            fake_timeline: Some(false),
-        })
+        state.power -= cost;
+        let uses = self.cloth_state.record_use(skill_id);
+        if let Some(info) = self.skill_info_mut(skill_id) {
+            info.cd = Some(skill.cd);
+            info.need_power = Some(skill.cost(uses));
+        }
+
+        let player = self
+            .fight
+            .attacker
+            .as_ref()
+            .and_then(|t| t.player_entity.clone())
+            .unwrap_or_default();
+
+        tracing::info!(
+            "Cloth skill {} ({:?}) for {} power, {} left",
+            skill_id,
+            skill.effect,
+            cost,
+            state.power
+        );
+
+        let mut builder = FightStepBuilder::new_skill(player.uid.unwrap_or(0), 0, skill_id)
+            .add_power_change(-cost);
+
+        match skill.effect {
+            ClothEffect::Heal { ratio } => {
+                for hero in state.living(1) {
+                    let max_hp = hero.attr.and_then(|a| a.hp).unwrap_or(0);
+                    let amount = (max_hp as f32 * ratio).round() as i32;
+                    builder = state.restore_hp(hero.uid.unwrap_or(0), amount, builder);
+                }
+            }
+            ClothEffect::Reroll => {
+                let heroes = state.living(1);
+                let mut kept = std::mem::take(&mut state.player_deck);
+                kept.retain(|card| cards::is_ultimate(&self.data, card));
+
+                state.player_deck = cards::refill_hand(&self.data, &heroes, kept, &mut self.rng);
+                builder = builder.add_card_distribution(state.player_deck.clone(), 54); // REDEALCARD
+            }
+            ClothEffect::Buff { buff_id } => {
+                for hero in state.living(1) {
+                    builder = state.add_buff(
+                        &self.data,
+                        &player,
+                        hero.uid.unwrap_or(0),
+                        buff_id,
+                        builder,
+                    );
+                }
+            }
+        }
+
+        builder.build()
     }
 
-    async fn use_player_skill(
-        &mut self,
-        state: &mut RoundState,
-        oper: BeginRoundOper,
-    ) -> Result<FightStep> {
-        tracing::info!("Using player skill");
-        Ok(FightStep::default())
+    fn skill_info_mut(&mut self, skill_id: i32) -> Option<&mut sonettobuf::PlayerSkillInfo> {
+        self.fight
+            .attacker
+            .as_mut()?
+            .skill_infos
+            .iter_mut()
+            .find(|i| i.skill_id == Some(skill_id))
     }
 
-    async fn change_hero(
-        &mut self,
-        state: &mut RoundState,
-        oper: BeginRoundOper,
-    ) -> Result<FightStep> {
-        tracing::info!("Changing hero");
-        Ok(FightStep::default())
+    fn execute_ai_turn(&mut self, state: &mut RoundState) -> Result<Vec<FightStep>> {
+        let mut steps = Vec::new();
+
+        for monster in state.living(2) {
+            if buff::has_control(&self.data, &monster, ControlKind::Stun) {
+                tracing::info!("Monster {} is stunned", monster.uid.unwrap_or(0));
+                continue;
+            }
+
+            let Some(action) = self
+                .behaviour
+                .choose_skill(&self.data, &monster, state.round_num)
+            else {
+                continue;
+            };
+
+            let heroes = state.living(1);
+            if heroes.is_empty() {
+                break;
+            }
+
+            let monster_uid = monster.uid.unwrap_or(0);
+            let profile =
+                skill::resolve_monster_skill(&self.data, action.skill_id, action.is_unique);
+
+            // attacks pick a random hero, heals the most wounded monster and
+            // support skills land on the caster
+            let target_uid = match profile.kind {
+                SkillKind::Damage => heroes[self.rng.gen_range(0..heroes.len())].uid.unwrap_or(0),
+                SkillKind::Heal => state.most_wounded(2).unwrap_or(monster_uid),
+                SkillKind::Support => monster_uid,
+            };
+
+            tracing::info!(
+                "Monster {} casts {} (unique={}) on {}",
+                monster_uid,
+                action.skill_id,
+                action.is_unique,
+                target_uid
+            );
+
+            let builder = FightStepBuilder::new_skill(monster_uid, target_uid, action.skill_id);
+            let builder = state.apply_effect(&self.data, &monster, target_uid, &profile, builder);
+            let builder =
+                state.apply_skill_buffs(&self.data, &monster, target_uid, action.skill_id, builder);
+
+            if let Some(entity) = state.get_entity_mut(monster_uid) {
+                entity.ex_point = Some(if action.is_unique {
+                    0
+                } else {
+                    entity.ex_point.unwrap_or(0) + 1
+                });
+            }
+
+            steps.push(builder.build());
+        }
+
+        Ok(steps)
+    }
+
+    /// The skills each monster will use next round, shown to the player as intents
+    fn plan_ai_cards(&self, state: &RoundState) -> Vec<CardInfo> {
+        state
+            .living(2)
+            .iter()
+            .filter_map(|monster| {
+                self.behaviour
+                    .choose_skill(&self.data, monster, state.round_num)
+                    .map(|action| ai::intent_card(monster, action))
+            })
+            .collect()
+    }
+
+    fn check_battle_end(&self, state: &RoundState) -> Option<FightResult> {
+        let upcoming = self.upcoming_monsters();
+        let won = self.win_condition.is_met(&state.entities, state.round_num)
+            && (upcoming.is_empty()
+                || self
+                    .win_condition
+                    .met_before_waves(&upcoming, state.round_num));
+
+        if won {
+            Some(FightResult::Win)
+        } else if !team_alive(&state.entities, 1) {
+            Some(FightResult::Lose)
+        } else {
+            None
+        }
+    }
+
+    /// Waves still to come after the current one
+    fn upcoming_waves(&self) -> &[Vec<i32>] {
+        let current = usize::try_from(self.fight.cur_wave.unwrap_or(1)).unwrap_or(1);
+        self.waves.get(current..).unwrap_or_default()
     }
 
-    async fn end_turn(&mut self, state: &mut RoundState) -> Result<FightStep> {
-        tracing::info!("Ending turn");
-        Ok(FightStep::default())
+    /// Monsters still to come: the current wave's reinforcements, then the later waves
+    fn upcoming_monsters(&self) -> Vec<Vec<i32>> {
+        let reserve: Vec<i32> = self
+            .fight
+            .defender
+            .iter()
+            .flat_map(|t| &t.sub_entitys)
+            .filter_map(|e| e.model_id)
+            .collect();
+
+        std::iter::once(reserve)
+            .filter(|ids| !ids.is_empty())
+            .chain(self.upcoming_waves().iter().cloned())
+            .collect()
     }
 
-    async fn execute_ai_turn(&mut self, state: &mut RoundState) -> Result<Vec<FightStep>> {
-        // TODO: AI logic
-        Ok(vec![])
+    /// Fills the slots of fallen monsters from the wave's reinforcements, front
+    /// first, so at most `battle.monsterMax` stand at once
+    fn reinforce(&mut self, state: &mut RoundState) -> Vec<FightStep> {
+        let Some(defender) = self.fight.defender.as_mut() else {
+            return vec![];
+        };
+
+        let mut steps = Vec::new();
+        for slot in 0..defender.entitys.len() {
+            if defender.sub_entitys.is_empty() {
+                break;
+            }
+
+            let fallen = &defender.entitys[slot];
+            let fallen_uid = fallen.uid.unwrap_or(0);
+            if state.get_entity(fallen_uid).is_none_or(is_alive) {
+                continue;
+            }
+
+            let mut monster = defender.sub_entitys.remove(0);
+            monster.position = fallen.position;
+            let uid = monster.uid.unwrap_or(0);
+
+            tracing::info!("Monster {} takes the slot of {}", uid, fallen_uid);
+
+            state.entities.remove(&fallen_uid);
+            state.entities.insert(uid, monster.clone());
+            defender.entitys[slot] = monster;
+            steps.push(FightStepBuilder::new_change_hero(fallen_uid, uid).build());
+        }
+
+        if !steps.is_empty() {
+            self.sync_fight(state);
+        }
+        steps
     }
 
-    async fn process_round_end(&mut self, state: &mut RoundState) -> Result<Vec<FightStep>> {
-        // TODO: End of round processing
-        Ok(vec![])
+    /// Brings in the next monster group once the current one is wiped out.
+    /// The heroes keep their hp, moxie and buffs, the fallen monsters leave
+    fn change_wave(&mut self, state: &mut RoundState) -> Result<Option<FightStep>> {
+        if team_alive(&state.entities, 2) || self.upcoming_waves().is_empty() {
+            return Ok(None);
+        }
+
+        let wave = self.fight.cur_wave.unwrap_or(1) + 1;
+        let (entitys, sub_entitys) = combat::wave_entities(
+            &self.data,
+            &self.waves,
+            (wave - 1) as usize,
+            self.monster_max,
+        )?;
+
+        state.entities.retain(|_, e| e.team_type != Some(2));
+        for entity in &entitys {
+            if let Some(uid) = entity.uid {
+                state.entities.insert(uid, entity.clone());
+            }
+        }
+        if let Some(defender) = self.fight.defender.as_mut() {
+            defender.entitys = entitys;
+            defender.sub_entitys = sub_entitys;
+        }
+        self.fight.cur_wave = Some(wave);
+
+        tracing::info!("Wave {} of {} comes in", wave, self.waves.len());
+
+        self.sync_fight(state);
+        Ok(Some(
+            FightStepBuilder::new_change_wave(wave)
+                .add_new_wave(wave, self.fight.clone())
+                .build(),
+        ))
     }
-    fn check_battle_end(&self, state: &RoundState) -> bool {
-        // TODO: Check if all enemies dead (win)
-        // TODO: Check if all heroes dead (lose)
-        false
+
+    /// Fight with the HP left after the last processed round
+    pub fn fight(&self) -> &Fight {
+        &self.fight
+    }
+
+    fn sync_fight(&mut self, state: &RoundState) {
+        let teams = [self.fight.attacker.as_mut(), self.fight.defender.as_mut()];
+
+        for team in teams.into_iter().flatten() {
+            for entity in team.entitys.iter_mut() {
+                if let Some(updated) = entity.uid.and_then(|uid| state.get_entity(uid)) {
+                    entity.current_hp = updated.current_hp;
+                    entity.ex_point = updated.ex_point;
+                    entity.buffs = updated.buffs.clone();
+                }
+            }
+        }
+
+        if let Some(attacker) = self.fight.attacker.as_mut() {
+            attacker.power = Some(state.power);
+        }
+
+        self.fight.cur_round = Some(state.round_num);
+        self.fight.is_finish = Some(state.is_finish);
     }
 
-    fn build_round_response(&self, steps: Vec<FightStep>, state: RoundState) -> FightRound {
+    fn build_round_response(
+        &self,
+        steps: Vec<FightStep>,
+        begin_steps: Vec<FightStep>,
+        state: RoundState,
+    ) -> FightRound {
         // Build ex_point_info from current entity states
         let ex_point_info = state.build_ex_point_info();
 
@@ -238,12 +688,17 @@ impl BattleSimulator {
             ex_point_info,
             ai_use_cards: state.ai_cards,
             power: Some(state.power),
-            skill_infos: vec![],
+            skill_infos: self
+                .fight
+                .attacker
+                .as_ref()
+                .map(|t| t.skill_infos.clone())
+                .unwrap_or_default(),
             before_cards1: vec![],
             team_a_cards1: state.player_deck, // Updated deck
             before_cards2: vec![],
             team_a_cards2: vec![],
-            next_round_begin_step: vec![],
+            next_round_begin_step: begin_steps,
             use_card_list: state.used_cards,
             cur_round: Some(state.round_num),
             hero_sp_attributes: vec![],
@@ -252,9 +707,16 @@ impl BattleSimulator {
     }
 }
 
+/// Each round gets its own stream, the simulator is rebuilt for every round
+fn round_rng(seed: u64, round: i32) -> StdRng {
+    StdRng::seed_from_u64(seed ^ (round as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+}
+
 struct RoundState {
     act_point: i32,
     power: i32,
+    max_power: i32,
+    gains: PowerGains,
     player_deck: Vec<CardInfo>,
     ai_cards: Vec<CardInfo>,
     entities: HashMap<i64, FightEntityInfo>,
@@ -262,9 +724,9 @@ struct RoundState {
     round_num: i32,
     move_num: i32,
     is_finish: bool,
+    next_buff_uid: i64,
 }
 
-#[allow(dead_code)]
 impl RoundState {
     fn from_fight(fight: &Fight) -> Result<Self> {
         let mut entities = HashMap::new();
@@ -287,9 +749,14 @@ impl RoundState {
             }
         }
 
+        let next_buff_uid = buff::next_buff_uid(&entities);
+        let power = fight.attacker.as_ref().and_then(|t| t.power).unwrap_or(0);
+
         Ok(Self {
             act_point: 4,
-            power: 15,
+            power,
+            max_power: 0,
+            gains: PowerGains::default(),
             player_deck: vec![], // Will be set from active_battle
             ai_cards: vec![],
             entities,
@@ -297,6 +764,7 @@ impl RoundState {
             round_num: fight.cur_round.unwrap_or(1),
             move_num: 0,
             is_finish: false,
+            next_buff_uid,
         })
     }
 
@@ -304,13 +772,350 @@ impl RoundState {
         self.entities.get(&uid)
     }
 
+    /// Adds power up to the cloth's cap, a cloth without one has no cap
+    fn gain_power(&mut self, amount: i32) {
+        if amount <= 0 {
+            return;
+        }
+
+        let cap = if self.max_power > 0 {
+            self.max_power.max(self.power)
+        } else {
+            i32::MAX
+        };
+        self.power = self.power.saturating_add(amount).min(cap);
+    }
+
     fn get_entity_mut(&mut self, uid: i64) -> Option<&mut FightEntityInfo> {
         self.entities.get_mut(&uid)
     }
 
-    fn build_ex_point_info(&self) -> Vec<sonettobuf::FightExPointInfo> {
+    /// `card_index` is 1-based like the client's
+    fn take_card(&mut self, card_index: i32) -> Option<CardInfo> {
+        let idx = usize::try_from(card_index).ok()?.checked_sub(1)?;
+        (idx < self.player_deck.len()).then(|| self.player_deck.remove(idx))
+    }
+
+    /// Merges what lines up in the hand, pays moxie for it and deals ultimates
+    fn settle_hand(&mut self, data: &impl CombatData) {
+        for merge in cards::merge_hand(data, &mut self.player_deck) {
+            self.gain_power(self.gains.merge);
+            if let Some(hero) = self.get_entity_mut(merge.hero_uid) {
+                cards::add_moxie(data, hero, cards::MOXIE_PER_MERGE);
+            }
+        }
+
+        let mut heroes: Vec<&FightEntityInfo> = self.entities.values().collect();
+        heroes.sort_by_key(|e| (e.position.unwrap_or(i32::MAX), e.uid));
+        cards::deal_ultimates(data, &mut self.player_deck, heroes);
+    }
+
+    /// Living member of a team missing the most hp, the front-most on a tie
+    fn most_wounded(&self, team_type: i32) -> Option<i64> {
+        self.living(team_type)
+            .into_iter()
+            .min_by_key(|e| e.current_hp.unwrap_or(0) - e.attr.and_then(|a| a.hp).unwrap_or(0))
+            .and_then(|e| e.uid)
+    }
+
+    /// Living entities of a team, front to back
+    fn living(&self, team_type: i32) -> Vec<FightEntityInfo> {
+        let mut entities: Vec<FightEntityInfo> = self
+            .entities
+            .values()
+            .filter(|e| e.team_type == Some(team_type) && is_alive(e))
+            .cloned()
+            .collect();
+
+        entities.sort_by_key(|e| (e.position.unwrap_or(i32::MAX), e.uid));
+        entities
+    }
+
+    /// What the skill itself does to its target, before the buffs it lays
+    fn apply_effect(
+        &mut self,
+        data: &impl CombatData,
+        caster: &FightEntityInfo,
+        target_uid: i64,
+        profile: &SkillProfile,
+        builder: FightStepBuilder,
+    ) -> FightStepBuilder {
+        match profile.kind {
+            SkillKind::Damage => {
+                self.apply_damage(data, caster, target_uid, profile.multiplier, builder)
+            }
+            SkillKind::Heal => {
+                self.apply_heal(data, caster, target_uid, profile.multiplier, builder)
+            }
+            SkillKind::Support => builder,
+        }
+    }
+
+    fn apply_damage(
+        &mut self,
+        data: &impl CombatData,
+        caster: &FightEntityInfo,
+        target_uid: i64,
+        multiplier: f32,
+        builder: FightStepBuilder,
+    ) -> FightStepBuilder {
+        let Some(target) = self.get_entity(target_uid) else {
+            return builder;
+        };
+
+        let damage = skill::calc_damage(
+            &buff::modified_attr(data, caster),
+            &buff::modified_attr(data, target),
+            skill::entity_damage_type(data, caster),
+            multiplier,
+            skill::career_modifier(caster.career.unwrap_or(0), target.career.unwrap_or(0)),
+        );
+
+        let builder = self.deal_damage(data, target_uid, damage, builder);
+        self.trigger_buffs(data, target_uid, BuffTrigger::OnHit, builder)
+    }
+
+    /// Takes damage off shields first and then hp, no triggers
+    fn deal_damage(
+        &mut self,
+        data: &impl CombatData,
+        target_uid: i64,
+        damage: i32,
+        mut builder: FightStepBuilder,
+    ) -> FightStepBuilder {
+        let Some(target) = self.get_entity_mut(target_uid) else {
+            return builder;
+        };
+
+        let (damage, changes) = buff::absorb_damage(data, target, damage);
+        for change in changes {
+            builder = builder.add_buff_change(target_uid, change);
+        }
+
+        let was_alive = is_alive(target);
+        let remaining = (target.current_hp.unwrap_or(0) - damage).max(0);
+        target.current_hp = Some(remaining);
+        builder = builder.add_damage(target_uid, damage);
+
+        if remaining == 0 {
+            tracing::info!("Entity {} died", target_uid);
+            builder = builder.add_death(target_uid);
+
+            let gain = match target.team_type {
+                Some(1) => self.gains.hero_death,
+                _ => self.gains.defeat,
+            };
+            if was_alive {
+                self.gain_power(gain);
+            }
+        }
+
+        builder
+    }
+
+    fn add_buff(
+        &mut self,
+        data: &impl CombatData,
+        caster: &FightEntityInfo,
+        target_uid: i64,
+        buff_id: i32,
+        builder: FightStepBuilder,
+    ) -> FightStepBuilder {
+        let Some(template) = data.buff(buff_id) else {
+            tracing::warn!("Buff {} has no template", buff_id);
+            return builder;
+        };
+
+        let shield = match template.effect {
+            BuffEffect::Shield { ratio } => {
+                (buff::modified_attr(data, caster).attack.unwrap_or(0) as f32 * ratio).round()
+                    as i32
+            }
+            _ => 0,
+        };
+
+        let uid = self.next_buff_uid;
+        let Some(target) = self.get_entity_mut(target_uid).filter(|e| is_alive(e)) else {
+            return builder;
+        };
+
+        let change = buff::add_buff(target, &template, uid, caster.uid.unwrap_or(0), shield);
+        self.next_buff_uid += 1;
+
+        builder.add_buff_change(target_uid, change)
+    }
+
+    fn apply_skill_buffs(
+        &mut self,
+        data: &impl CombatData,
+        caster: &FightEntityInfo,
+        target_uid: i64,
+        skill_id: i32,
+        mut builder: FightStepBuilder,
+    ) -> FightStepBuilder {
+        for link in data.skill_buffs(skill_id) {
+            let to = if link.on_caster {
+                caster.uid.unwrap_or(0)
+            } else {
+                target_uid
+            };
+            builder = self.add_buff(data, caster, to, link.buff_id, builder);
+        }
+
+        builder
+    }
+
+    /// Fires the entity's buffs for a trigger: damage over time hurts, stat buffs stack up
+    fn trigger_buffs(
+        &mut self,
+        data: &impl CombatData,
+        uid: i64,
+        trigger: BuffTrigger,
+        mut builder: FightStepBuilder,
+    ) -> FightStepBuilder {
+        let Some(entity) = self.get_entity(uid).filter(|e| is_alive(e)) else {
+            return builder;
+        };
+
+        let max_hp = entity.attr.and_then(|a| a.hp).unwrap_or(0);
+
+        for (buff, template) in buff::triggered(data, entity, trigger) {
+            match template.effect {
+                BuffEffect::DamageOverTime { ratio } => {
+                    if !self.get_entity(uid).is_some_and(is_alive) {
+                        break;
+                    }
+                    let layer = buff.layer.unwrap_or(1) as f32;
+                    let damage = ((max_hp as f32 * ratio * layer).round() as i32).max(1);
+                    builder = self.deal_damage(data, uid, damage, builder);
+                }
+                BuffEffect::Attr { .. } => {
+                    let change = self
+                        .get_entity_mut(uid)
+                        .and_then(|e| buff::stack_buff(data, e, buff.uid.unwrap_or(0)));
+                    if let Some(change) = change {
+                        builder = builder.add_buff_change(uid, change);
+                    }
+                }
+                BuffEffect::Shield { .. } | BuffEffect::Control(_) => {}
+            }
+        }
+
+        builder
+    }
+
+    /// Round start triggers for everyone still standing
+    fn round_start_buffs(&mut self, data: &impl CombatData) -> Option<FightStep> {
+        let mut uids: Vec<i64> = self
+            .entities
+            .values()
+            .filter(|e| is_alive(e))
+            .filter_map(|e| e.uid)
+            .collect();
+        uids.sort();
+
+        let mut builder = FightStepBuilder::new_effect();
+        for uid in uids {
+            builder = self.trigger_buffs(data, uid, BuffTrigger::RoundStart, builder);
+        }
+
+        let step = builder.build();
+        (!step.act_effect.is_empty()).then_some(step)
+    }
+
+    /// Counts buff durations down at round end
+    fn tick_buffs(&mut self) -> Option<FightStep> {
+        let mut uids: Vec<i64> = self.entities.keys().copied().collect();
+        uids.sort();
+
+        let mut builder = FightStepBuilder::new_effect();
+        for uid in uids {
+            let changes = self
+                .get_entity_mut(uid)
+                .map(buff::tick_round_end)
+                .unwrap_or_default();
+            for change in changes {
+                builder = builder.add_buff_change(uid, change);
+            }
+        }
+
+        let step = builder.build();
+        (!step.act_effect.is_empty()).then_some(step)
+    }
+
+    fn apply_heal(
+        &mut self,
+        data: &impl CombatData,
+        caster: &FightEntityInfo,
+        target_uid: i64,
+        multiplier: f32,
+        builder: FightStepBuilder,
+    ) -> FightStepBuilder {
+        if self.get_entity(target_uid).is_none() {
+            return builder;
+        }
+
+        let heal = skill::calc_heal(&buff::modified_attr(data, caster), multiplier);
+        self.restore_hp(target_uid, heal, builder)
+    }
+
+    /// Heals up to max hp
+    fn restore_hp(
+        &mut self,
+        target_uid: i64,
+        amount: i32,
+        builder: FightStepBuilder,
+    ) -> FightStepBuilder {
+        let Some(target) = self.get_entity_mut(target_uid) else {
+            return builder;
+        };
+
+        let max_hp = target.attr.and_then(|a| a.hp).unwrap_or(0);
+        let current_hp = target.current_hp.unwrap_or(0);
+        let heal = amount.min(max_hp - current_hp).max(0);
+
+        target.current_hp = Some(current_hp + heal);
+        builder.add_heal(target_uid, heal)
+    }
+
+    /// The requested entity when the card may land on it. Attacks otherwise
+    /// go to the front enemy, heals to the caster, support cards take anyone
+    fn resolve_target(
+        &self,
+        caster: &FightEntityInfo,
+        requested: i64,
+        kind: SkillKind,
+    ) -> Option<i64> {
+        let allowed = |e: &FightEntityInfo| match kind {
+            SkillKind::Damage => e.team_type != caster.team_type,
+            SkillKind::Heal => e.team_type == caster.team_type,
+            SkillKind::Support => true,
+        };
+        if requested != 0
+            && self
+                .get_entity(requested)
+                .is_some_and(|e| is_alive(e) && allowed(e))
+        {
+            return Some(requested);
+        }
+
+        if kind != SkillKind::Damage {
+            return caster.uid;
+        }
+
         self.entities
             .values()
+            .filter(|e| e.team_type != caster.team_type && is_alive(e))
+            .min_by_key(|e| (e.position.unwrap_or(i32::MAX), e.uid))
+            .and_then(|e| e.uid)
+    }
+
+    fn build_ex_point_info(&self) -> Vec<sonettobuf::FightExPointInfo> {
+        let mut entities: Vec<&FightEntityInfo> = self.entities.values().collect();
+        entities.sort_by_key(|e| e.uid);
+
+        entities
+            .into_iter()
             .map(|entity| sonettobuf::FightExPointInfo {
                 uid: entity.uid,
                 ex_point: entity.ex_point,
@@ -321,3 +1126,205 @@ impl RoundState {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::battle::fixtures::{Fixture, FixtureData, simulate};
+    use crate::state::battle::skill::SkillEffect;
+
+    #[test]
+    fn round_rng_is_stable_per_seed_and_round() {
+        let draw = |seed, round| {
+            let mut rng = round_rng(seed, round);
+            (0..8).map(|_| rng.gen_range(0..100)).collect::<Vec<i32>>()
+        };
+
+        assert_eq!(draw(42, 3), draw(42, 3));
+        assert_ne!(draw(42, 3), draw(42, 4));
+        assert_ne!(draw(42, 3), draw(43, 3));
+    }
+
+    #[test]
+    fn moves_spend_an_action_point_and_earn_power() {
+        let mut fixture = Fixture::sweep();
+        fixture.data.cloths.insert(
+            1,
+            ClothRules {
+                initial_power: 0,
+                max_power: 10,
+                gains: PowerGains {
+                    move_card: 2,
+                    ..PowerGains::default()
+                },
+                skills: vec![],
+            },
+        );
+        fixture.input.cloth_id = 1;
+
+        let act_point = fixture.input.act_point;
+        let move_card = BeginRoundOper {
+            oper_type: Some(2),
+            param1: Some(1),
+            param2: Some(2),
+            to_id: None,
+            param3: None,
+        };
+
+        let mut simulator = fixture.simulator(fixture.fight());
+        let mut state = simulator.round_state(fixture.hand(), act_point).unwrap();
+        simulator.execute_operation(&mut state, move_card).unwrap();
+
+        assert_eq!(state.act_point, act_point - 1);
+        assert_eq!(state.power, 2);
+    }
+
+    #[test]
+    fn cleared_waves_bring_in_the_next_group() {
+        let mut fixture = Fixture::sweep();
+        let battle_id = fixture.input.battle_id;
+        let battle = fixture.data.battles.get_mut(&battle_id).unwrap();
+        battle.more_waves = vec![vec![100101]];
+
+        let replay = simulate(&fixture.data, &fixture.input).unwrap();
+        let changed = replay.new_waves.iter().position(|&new| new).unwrap();
+
+        // the first wave falls where the single-wave fight was won
+        assert_eq!(replay.rounds[changed].is_finish, Some(false));
+        assert!(
+            replay.rounds[changed]
+                .fight_step
+                .iter()
+                .any(|s| s.act_type == Some(fight_step::ActType::Changewave.into()))
+        );
+
+        let wave_fight = &replay.wave_fights[0];
+        assert_eq!(wave_fight.cur_wave, Some(2));
+        let defender = wave_fight.defender.as_ref().unwrap();
+        let uids: Vec<_> = defender.entitys.iter().map(|e| e.uid).collect();
+        assert_eq!(uids, vec![Some(-3)]);
+
+        // heroes walk into the new wave with what they had left
+        for hero in &wave_fight.attacker.as_ref().unwrap().entitys {
+            let after_round = replay.rounds[changed]
+                .ex_point_info
+                .iter()
+                .find(|e| e.uid == hero.uid)
+                .unwrap();
+            assert_eq!(after_round.current_hp, hero.current_hp);
+        }
+    }
+
+    #[test]
+    fn monsters_past_monster_max_fill_in_as_others_fall() {
+        let mut fixture = Fixture::sweep();
+        let battle_id = fixture.input.battle_id;
+        fixture
+            .data
+            .battles
+            .get_mut(&battle_id)
+            .unwrap()
+            .monster_max = 1;
+
+        let defender = fixture.fight().defender.unwrap();
+        assert_eq!(defender.entitys.len(), 1);
+        assert_eq!(defender.sub_entitys.len(), 1);
+        assert_eq!(defender.sub_entitys[0].position, Some(-1));
+
+        let replay = simulate(&fixture.data, &fixture.input).unwrap();
+        let filled_in = replay
+            .rounds
+            .iter()
+            .flat_map(|r| &r.fight_step)
+            .find(|s| s.act_type == Some(fight_step::ActType::Changehero.into()))
+            .unwrap();
+        assert_eq!(filled_in.from_id, Some(-1));
+        assert_eq!(filled_in.to_id, Some(-2));
+
+        // one group is still one wave, the fight goes on until the reserve falls too
+        assert!(replay.new_waves.iter().all(|&new| !new));
+        assert_eq!(replay.result, Some(FightResult::Win));
+        let defender = replay.fight.defender.as_ref().unwrap();
+        assert!(defender.sub_entitys.is_empty());
+        assert_eq!(defender.entitys[0].uid, Some(-2));
+        assert_eq!(defender.entitys[0].position, Some(1));
+    }
+
+    #[test]
+    fn cards_on_allies_follow_the_skill_kind() {
+        let mut fixture = Fixture::sweep();
+        let hand = fixture.hand();
+        let ally = fixture.input.heroes[0].uid;
+        let play = vec![BeginRoundOper {
+            oper_type: Some(1),
+            param1: Some(1),
+            param2: None,
+            to_id: Some(ally),
+            param3: None,
+        }];
+
+        let target_of_first_card = |fixture: &Fixture| {
+            let mut simulator = fixture.simulator(fixture.fight());
+            let round = simulator
+                .process_round(play.clone(), hand.clone(), fixture.input.act_point)
+                .unwrap();
+            round.fight_step[0].to_id.unwrap()
+        };
+
+        // an attack can't land on an ally, so the target falls back to an enemy
+        assert!(target_of_first_card(&fixture) < 0);
+
+        let skill_id = hand[0].skill_id.unwrap();
+        fixture.data.skill_effects.insert(
+            skill_id,
+            SkillEffect {
+                kind: SkillKind::Heal,
+                rate: 0.5,
+            },
+        );
+        assert_eq!(target_of_first_card(&fixture), ally);
+    }
+
+    #[test]
+    fn monster_heals_mend_monsters() {
+        let mut fixture = Fixture::sweep();
+        let data: &mut FixtureData = &mut fixture.data;
+        let skills: Vec<i32> = data
+            .monsters
+            .values()
+            .flat_map(|m| m.skill_group1.iter().chain(&m.skill_group2).copied())
+            .collect();
+        for skill_id in skills {
+            data.skill_effects.insert(
+                skill_id,
+                SkillEffect {
+                    kind: SkillKind::Heal,
+                    rate: 0.5,
+                },
+            );
+        }
+
+        let mut fight = fixture.fight();
+        let monster = &mut fight.defender.as_mut().unwrap().entitys[1];
+        monster.current_hp = Some(1);
+        let monster_uid = monster.uid;
+
+        let mut simulator = fixture.simulator(fight);
+        let round = simulator
+            .process_round(vec![], vec![], fixture.input.act_point)
+            .unwrap();
+
+        let hp = |uid| {
+            round
+                .ex_point_info
+                .iter()
+                .find(|e| e.uid == uid)
+                .and_then(|e| e.current_hp)
+                .unwrap()
+        };
+        assert!(hp(monster_uid) > 1);
+        for hero in &fixture.input.heroes {
+            assert_eq!(Some(hp(Some(hero.uid))), hero.attr.hp);
+        }
+    }
+}
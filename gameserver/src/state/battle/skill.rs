@@ -0,0 +1,251 @@
+use serde::Deserialize;
+use sonettobuf::{FightEntityInfo, HeroAttribute};
+
+use super::data::CombatData;
+
+// Damage never drops below this share of the raw hit, even against high defense
+const MIN_DAMAGE_RATIO: f32 = 0.1;
+const CAREER_ADVANTAGE: f32 = 1.3;
+const CAREER_DISADVANTAGE: f32 = 0.8;
+// Skills the data can't classify hit for the caster's attack
+const DEFAULT_EFFECT: SkillEffect = SkillEffect {
+    kind: SkillKind::Damage,
+    rate: 1.0,
+};
+
+/// How hard a skill hits, from the `skill` and `skill_ex_level` tables
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkillProfile {
+    pub skill_id: i32,
+    pub rank: i32,
+    pub is_ex: bool,
+    pub multiplier: f32,
+    pub kind: SkillKind,
+}
+
+/// A skill's own effect: what it does and its share of the caster's attack
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct SkillEffect {
+    pub kind: SkillKind,
+    pub rate: f32,
+}
+
+/// What a card does to its target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SkillKind {
+    /// Hits an enemy
+    Damage,
+    /// Restores an ally's hp
+    Heal,
+    /// Only lays buffs or debuffs, no hp change
+    Support,
+}
+
+impl SkillKind {
+    /// `skill.battleTag` is a `#` list of tags like "attack#debuff". Any damage
+    /// tag makes an attack, a heal tag without one a heal, and buff, debuff or
+    /// counter tags alone a support card. None when no tag is recognised
+    pub fn from_battle_tag(battle_tag: &str) -> Option<Self> {
+        let tags = battle_tags(battle_tag);
+        let has = |names: &[&str]| tags.iter().any(|t| names.iter().any(|n| t.contains(n)));
+
+        if has(&["attack", "damage", "dmg", "aoe"]) {
+            Some(Self::Damage)
+        } else if has(&["heal", "restore", "regen"]) {
+            Some(Self::Heal)
+        } else if has(&["buff", "debuff", "counter", "shield", "control", "purify"]) {
+            Some(Self::Support)
+        } else {
+            None
+        }
+    }
+}
+
+/// The lowercased tags of a `skill.battleTag`
+pub fn battle_tags(battle_tag: &str) -> Vec<String> {
+    battle_tag
+        .split('#')
+        .map(|t| t.trim().to_ascii_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DamageType {
+    Reality, // against defense
+    Mental,  // against mdefense
+}
+
+pub fn resolve_skill(data: &impl CombatData, hero_id: i32, skill_id: i32) -> SkillProfile {
+    let is_ex = data.is_ex_skill(hero_id, skill_id);
+
+    let rank = match data.skill_rank(skill_id) {
+        Some(rank) => rank,
+        None => {
+            tracing::warn!("Skill {} not found in skill table", skill_id);
+            1
+        }
+    };
+
+    let effect = skill_effect(data, skill_id);
+
+    SkillProfile {
+        skill_id,
+        rank,
+        is_ex,
+        multiplier: effect.rate,
+        kind: effect.kind,
+    }
+}
+
+/// Monster skills are mostly missing from the skill table, those count as rank 1
+pub fn resolve_monster_skill(
+    data: &impl CombatData,
+    skill_id: i32,
+    is_unique: bool,
+) -> SkillProfile {
+    let rank = data.skill_rank(skill_id).unwrap_or(1);
+
+    let effect = skill_effect(data, skill_id);
+
+    SkillProfile {
+        skill_id,
+        rank,
+        is_ex: is_unique,
+        multiplier: effect.rate,
+        kind: effect.kind,
+    }
+}
+
+fn skill_effect(data: &impl CombatData, skill_id: i32) -> SkillEffect {
+    data.skill_effect(skill_id).unwrap_or_else(|| {
+        tracing::warn!("Skill {} has no effect the server runs", skill_id);
+        DEFAULT_EFFECT
+    })
+}
+
+/// Heroes take it from `character.dmgType`, monsters from their skill template
+pub fn entity_damage_type(data: &impl CombatData, entity: &FightEntityInfo) -> DamageType {
+    let model_id = entity.model_id.unwrap_or(0);
+
+    let dmg_type = match entity.entity_type.unwrap_or(0) {
+        1 => data.hero(model_id).map(|h| h.dmg_type),
+        2 => data.monster(model_id).map(|m| m.dmg_type),
+        _ => None,
+    };
+
+    match dmg_type {
+        Some(2) => DamageType::Mental,
+        _ => DamageType::Reality,
+    }
+}
+
+/// Careers 1-4 counter the next one round the cycle, 5 and 6 counter each other
+pub fn career_modifier(attacker: i32, target: i32) -> f32 {
+    let counters = |a: i32, b: i32| match a {
+        1..=4 => (1..=4).contains(&b) && b == a % 4 + 1,
+        5 => b == 6,
+        6 => b == 5,
+        _ => false,
+    };
+
+    if counters(attacker, target) {
+        CAREER_ADVANTAGE
+    } else if counters(target, attacker) {
+        CAREER_DISADVANTAGE
+    } else {
+        1.0
+    }
+}
+
+pub fn calc_damage(
+    attacker: &HeroAttribute,
+    target: &HeroAttribute,
+    damage_type: DamageType,
+    multiplier: f32,
+    career_modifier: f32,
+) -> i32 {
+    let attack = attacker.attack.unwrap_or(0) as f32;
+    let defense = match damage_type {
+        DamageType::Reality => target.defense.unwrap_or(0),
+        DamageType::Mental => target.mdefense.unwrap_or(0),
+    } as f32;
+
+    let base = (attack - defense).max(attack * MIN_DAMAGE_RATIO);
+
+    ((base * multiplier * career_modifier).round() as i32).max(1)
+}
+
+pub fn calc_heal(healer: &HeroAttribute, multiplier: f32) -> i32 {
+    let attack = healer.attack.unwrap_or(0) as f32;
+
+    ((attack * multiplier).round() as i32).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attr(attack: i32, defense: i32, mdefense: i32) -> HeroAttribute {
+        HeroAttribute {
+            hp: Some(1000),
+            attack: Some(attack),
+            defense: Some(defense),
+            mdefense: Some(mdefense),
+            technic: Some(0),
+            multi_hp_idx: Some(0),
+            multi_hp_num: Some(0),
+        }
+    }
+
+    #[test]
+    fn battle_tags_pick_the_card_kind() {
+        assert_eq!(
+            SkillKind::from_battle_tag("Attack#Debuff"),
+            Some(SkillKind::Damage)
+        );
+        assert_eq!(
+            SkillKind::from_battle_tag("heal#buff"),
+            Some(SkillKind::Heal)
+        );
+        assert_eq!(SkillKind::from_battle_tag("buff"), Some(SkillKind::Support));
+        assert_eq!(SkillKind::from_battle_tag(""), None);
+    }
+
+    #[test]
+    fn damage_uses_the_matching_defense() {
+        let attacker = attr(500, 0, 0);
+        let target = attr(0, 100, 300);
+
+        assert_eq!(
+            calc_damage(&attacker, &target, DamageType::Reality, 1.0, 1.0),
+            400
+        );
+        assert_eq!(
+            calc_damage(&attacker, &target, DamageType::Mental, 1.0, 1.0),
+            200
+        );
+    }
+
+    #[test]
+    fn damage_has_a_floor_against_high_defense() {
+        let attacker = attr(500, 0, 0);
+        let target = attr(0, 2000, 0);
+
+        assert_eq!(
+            calc_damage(&attacker, &target, DamageType::Reality, 2.0, 1.0),
+            100
+        );
+    }
+
+    #[test]
+    fn careers_counter_in_a_cycle() {
+        assert_eq!(career_modifier(1, 2), CAREER_ADVANTAGE);
+        assert_eq!(career_modifier(4, 1), CAREER_ADVANTAGE);
+        assert_eq!(career_modifier(2, 1), CAREER_DISADVANTAGE);
+        assert_eq!(career_modifier(5, 6), CAREER_ADVANTAGE);
+        assert_eq!(career_modifier(1, 3), 1.0);
+        assert_eq!(career_modifier(0, 2), 1.0);
+    }
+}
@@ -0,0 +1,321 @@
+use sonettobuf::{HeroAttribute, HeroExAttribute};
+use std::ops::Add;
+
+/// Psychube stats in `equip_strengthen` are for a fully levelled psychube
+pub const PSYCHUBE_MAX_LEVEL: i32 = 60;
+
+/// A hero's combat stats, or the share one source adds to them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub hp: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub mdefense: i32,
+    pub technic: i32,
+    pub cri: i32,
+    pub recri: i32,
+    pub cri_dmg: i32,
+    pub cri_def: i32,
+    pub add_dmg: i32,
+    pub drop_dmg: i32,
+}
+
+impl Stats {
+    /// Every stat times `factor`, rounded
+    pub fn scale(self, factor: f32) -> Self {
+        let s = |v: i32| (v as f32 * factor).round() as i32;
+
+        Self {
+            hp: s(self.hp),
+            attack: s(self.attack),
+            defense: s(self.defense),
+            mdefense: s(self.mdefense),
+            technic: s(self.technic),
+            cri: s(self.cri),
+            recri: s(self.recri),
+            cri_dmg: s(self.cri_dmg),
+            cri_def: s(self.cri_def),
+            add_dmg: s(self.add_dmg),
+            drop_dmg: s(self.drop_dmg),
+        }
+    }
+
+    /// The five base stats grown by a per-mille bonus, ex stats untouched
+    pub fn grow_base(self, permille: i32) -> Self {
+        let g = |v: i32| v + v * permille / 1000;
+
+        Self {
+            hp: g(self.hp),
+            attack: g(self.attack),
+            defense: g(self.defense),
+            mdefense: g(self.mdefense),
+            technic: g(self.technic),
+            ..self
+        }
+    }
+
+    pub fn attr(&self) -> HeroAttribute {
+        HeroAttribute {
+            hp: Some(self.hp),
+            attack: Some(self.attack),
+            defense: Some(self.defense),
+            mdefense: Some(self.mdefense),
+            technic: Some(self.technic),
+            multi_hp_idx: Some(0),
+            multi_hp_num: Some(0),
+        }
+    }
+
+    pub fn ex_attr(&self) -> HeroExAttribute {
+        HeroExAttribute {
+            cri: Some(self.cri),
+            recri: Some(self.recri),
+            cri_dmg: Some(self.cri_dmg),
+            cri_def: Some(self.cri_def),
+            add_dmg: Some(self.add_dmg),
+            drop_dmg: Some(self.drop_dmg),
+        }
+    }
+}
+
+impl Add for Stats {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            hp: self.hp + rhs.hp,
+            attack: self.attack + rhs.attack,
+            defense: self.defense + rhs.defense,
+            mdefense: self.mdefense + rhs.mdefense,
+            technic: self.technic + rhs.technic,
+            cri: self.cri + rhs.cri,
+            recri: self.recri + rhs.recri,
+            cri_dmg: self.cri_dmg + rhs.cri_dmg,
+            cri_def: self.cri_def + rhs.cri_def,
+            add_dmg: self.add_dmg + rhs.add_dmg,
+            drop_dmg: self.drop_dmg + rhs.drop_dmg,
+        }
+    }
+}
+
+/// `battle.heroEffectiveness` / `equipEffectiveness` / `talentEffectiveness`,
+/// how much of each part of a hero counts in that battle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Effectiveness {
+    pub hero: f32,
+    pub equip: f32,
+    pub talent: f32,
+}
+
+impl Default for Effectiveness {
+    fn default() -> Self {
+        Self {
+            hero: 1.0,
+            equip: 1.0,
+            talent: 1.0,
+        }
+    }
+}
+
+impl Effectiveness {
+    /// Most battles leave the columns at 0, which means full strength
+    pub fn from_battle(hero: f32, equip: f32, talent: f32) -> Self {
+        let or_full = |v: f32| if v > 0.0 { v } else { 1.0 };
+
+        Self {
+            hero: or_full(hero),
+            equip: or_full(equip),
+            talent: or_full(talent),
+        }
+    }
+}
+
+/// The equipped psychube at its level and refinement
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PsychubeStats {
+    /// `equip_strengthen`, scaled down to the psychube's level
+    pub strengthen: Stats,
+    /// `equip_skill.attack` / `hp` at the refine level, per-mille of the hero's own stats
+    pub attack_permille: i32,
+    pub hp_permille: i32,
+    /// The crit and damage bonuses of `equip_skill`
+    pub ex: Stats,
+}
+
+impl PsychubeStats {
+    /// `equip_strengthen` has one max-level row per strengthType and no level
+    /// column, so lower levels scale that row down
+    pub fn at_level(max_level_stats: Stats, level: i32) -> Stats {
+        max_level_stats.scale(level.clamp(1, PSYCHUBE_MAX_LEVEL) as f32 / PSYCHUBE_MAX_LEVEL as f32)
+    }
+}
+
+/// Everything that adds up to a hero's fight stats
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatSources {
+    /// `character_level` at the hero's level
+    pub level: Stats,
+    /// Insight bonus to the base stats, per-mille, over every reached rank
+    pub insight_permille: i32,
+    pub psychube: Option<PsychubeStats>,
+    /// One entry per resonance cube on the board
+    pub cubes: Vec<Stats>,
+    /// Every destiny slot reached so far
+    pub destiny_stone: Stats,
+}
+
+/// `talent_scheme.talenScheme`, "#" separated "cubeId,direction,posX,posY"
+/// placements -> the cube ids
+pub fn scheme_cube_ids(scheme: &str) -> Vec<i32> {
+    scheme
+        .split('#')
+        .filter_map(|cube| cube.split(',').next()?.trim().parse().ok())
+        .collect()
+}
+
+/// `character_rank.effect`, "|" separated: "1#level" raises the level cap,
+/// "2#permille" grows the base stats, the rest unlock skills and skins
+pub fn insight_permille(effect: &str) -> i32 {
+    effect
+        .split('|')
+        .filter_map(|entry| {
+            let mut parts = entry.split('#').map(|s| s.trim().parse::<i32>().ok());
+            match (parts.next()??, parts.next()??) {
+                (2, permille) => Some(permille),
+                _ => None,
+            }
+        })
+        .sum()
+}
+
+/// Folds the sources into the final stats. Psychube refine bonuses are a share
+/// of the hero's own stats, then the battle scalars weigh hero, psychube and
+/// resonance separately
+pub fn aggregate(sources: &StatSources, effectiveness: &Effectiveness) -> Stats {
+    let hero = sources.level.grow_base(sources.insight_permille) + sources.destiny_stone;
+
+    let equip = sources
+        .psychube
+        .map(|p| {
+            let refine = Stats {
+                hp: hero.hp * p.hp_permille / 1000,
+                attack: hero.attack * p.attack_permille / 1000,
+                ..Stats::default()
+            };
+            p.strengthen + refine + p.ex
+        })
+        .unwrap_or_default();
+
+    let talent = sources
+        .cubes
+        .iter()
+        .fold(Stats::default(), |sum, &cube| sum + cube);
+
+    hero.scale(effectiveness.hero)
+        + equip.scale(effectiveness.equip)
+        + talent.scale(effectiveness.talent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base(hp: i32, attack: i32) -> Stats {
+        Stats {
+            hp,
+            attack,
+            defense: 100,
+            mdefense: 100,
+            technic: 50,
+            cri_dmg: 1300,
+            ..Stats::default()
+        }
+    }
+
+    #[test]
+    fn insight_reads_only_stat_entries() {
+        assert_eq!(insight_permille("1#30|2#50"), 50);
+        assert_eq!(insight_permille("2#50|3#1|2#25"), 75);
+        assert_eq!(insight_permille(""), 0);
+        assert_eq!(insight_permille("2#"), 0);
+    }
+
+    #[test]
+    fn scheme_lists_the_placed_cubes() {
+        assert_eq!(
+            scheme_cube_ids("10,1,1,0#10,0,0,0#61,1,2,0"),
+            vec![10, 10, 61]
+        );
+        assert_eq!(scheme_cube_ids(""), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn every_source_adds_up() {
+        let sources = StatSources {
+            level: base(2000, 400),
+            insight_permille: 100,
+            psychube: Some(PsychubeStats {
+                strengthen: PsychubeStats::at_level(
+                    Stats {
+                        hp: 600,
+                        attack: 120,
+                        ..Stats::default()
+                    },
+                    30,
+                ),
+                attack_permille: 50,
+                hp_permille: 0,
+                ex: Stats {
+                    cri: 80,
+                    ..Stats::default()
+                },
+            }),
+            cubes: vec![
+                Stats {
+                    attack: 10,
+                    ..Stats::default()
+                },
+                Stats {
+                    cri_dmg: 40,
+                    ..Stats::default()
+                },
+            ],
+            destiny_stone: Stats::default(),
+        };
+
+        let stats = aggregate(&sources, &Effectiveness::default());
+
+        // 2000 * 1.1 + 600 / 2
+        assert_eq!(stats.hp, 2500);
+        // 400 * 1.1 + 120 / 2 + 440 * 5% + 10
+        assert_eq!(stats.attack, 440 + 60 + 22 + 10);
+        assert_eq!(stats.defense, 110);
+        assert_eq!(stats.cri, 80);
+        assert_eq!(stats.cri_dmg, 1340);
+    }
+
+    #[test]
+    fn battle_scalars_weigh_each_part() {
+        let sources = StatSources {
+            level: base(1000, 200),
+            psychube: Some(PsychubeStats {
+                strengthen: Stats {
+                    hp: 500,
+                    ..Stats::default()
+                },
+                ..PsychubeStats::default()
+            }),
+            ..StatSources::default()
+        };
+
+        let full = aggregate(&sources, &Effectiveness::from_battle(0.0, 0.0, 0.0));
+        assert_eq!(full.hp, 1500);
+
+        let no_psychube = aggregate(&sources, &Effectiveness::from_battle(1.0, 0.01, 1.0));
+        assert_eq!(no_psychube.hp, 1005);
+
+        let halved = aggregate(&sources, &Effectiveness::from_battle(0.5, 1.0, 1.0));
+        assert_eq!(halved.hp, 1000);
+        assert_eq!(halved.attack, 100);
+    }
+}
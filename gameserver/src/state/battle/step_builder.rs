@@ -1,10 +1,13 @@
 use sonettobuf::{ActEffect, BuffInfo, FightStep, fight_step};
 
+use super::buff::BuffChange;
+
 pub struct FightStepBuilder {
     act_type: fight_step::ActType,
     from_id: i64,
     to_id: i64,
     act_id: i32,
+    card_index: i32,
     effects: Vec<ActEffect>,
 }
 
@@ -16,6 +19,7 @@ impl FightStepBuilder {
             from_id: 0,
             to_id: 0,
             act_id: 0,
+            card_index: 0,
             effects: Vec::new(),
         }
     }
@@ -26,10 +30,41 @@ impl FightStepBuilder {
             from_id,
             to_id,
             act_id: skill_id,
+            card_index: 0,
             effects: Vec::new(),
         }
     }
 
+    /// The next wave walking in, `act_id` is its 1-based number
+    pub fn new_change_wave(wave: i32) -> Self {
+        Self {
+            act_type: fight_step::ActType::Changewave,
+            from_id: 0,
+            to_id: 0,
+            act_id: wave,
+            card_index: 0,
+            effects: Vec::new(),
+        }
+    }
+
+    /// `to_id` takes the slot of the fallen `from_id`
+    pub fn new_change_hero(from_id: i64, to_id: i64) -> Self {
+        Self {
+            act_type: fight_step::ActType::Changehero,
+            from_id,
+            to_id,
+            act_id: 0,
+            card_index: 0,
+            effects: Vec::new(),
+        }
+    }
+
+    /// 1-based position of the played card in the hand
+    pub fn card_index(mut self, card_index: i32) -> Self {
+        self.card_index = card_index;
+        self
+    }
+
     pub fn add_nested_step(mut self, step: FightStep) -> Self {
         self.effects.push(ActEffect {
             target_id: Some(0),
@@ -62,6 +97,27 @@ impl FightStepBuilder {
         self
     }
 
+    pub fn add_buff_change(mut self, target_id: i64, change: BuffChange) -> Self {
+        let (effect_type, buff) = match change {
+            BuffChange::Added(buff) => (5, buff),   // BUFFADD
+            BuffChange::Removed(buff) => (6, buff), // BUFFDEL
+            BuffChange::Updated(buff) => (7, buff), // BUFFUPDATE
+        };
+
+        self.effects.push(ActEffect {
+            target_id: Some(target_id),
+            effect_type: Some(effect_type),
+            effect_num: Some(if effect_type == 5 {
+                buff.buff_id.unwrap_or(0)
+            } else {
+                0
+            }),
+            buff: Some(buff),
+            ..Default::default()
+        });
+        self
+    }
+
     pub fn add_indicator_change(mut self, target_id: i64) -> Self {
         self.effects.push(ActEffect {
             target_id: Some(target_id),
@@ -72,6 +128,38 @@ impl FightStepBuilder {
         self
     }
 
+    pub fn add_damage(mut self, target_id: i64, damage: i32) -> Self {
+        self.effects.push(ActEffect {
+            target_id: Some(target_id),
+            effect_type: Some(3), // DAMAGE
+            effect_num: Some(damage),
+            config_effect: Some(-1),
+            ..Default::default()
+        });
+        self
+    }
+
+    pub fn add_heal(mut self, target_id: i64, heal: i32) -> Self {
+        self.effects.push(ActEffect {
+            target_id: Some(target_id),
+            effect_type: Some(4), // HEAL
+            effect_num: Some(heal),
+            config_effect: Some(-1),
+            ..Default::default()
+        });
+        self
+    }
+
+    pub fn add_death(mut self, target_id: i64) -> Self {
+        self.effects.push(ActEffect {
+            target_id: Some(target_id),
+            effect_type: Some(9), // DEAD
+            effect_num: Some(0),
+            ..Default::default()
+        });
+        self
+    }
+
     pub fn add_card_distribution(
         mut self,
         cards: Vec<sonettobuf::CardInfo>,
@@ -97,6 +185,29 @@ impl FightStepBuilder {
         self
     }
 
+    pub fn add_power_change(mut self, change: i32) -> Self {
+        self.effects.push(ActEffect {
+            target_id: Some(0),
+            effect_type: Some(128), // POWERCHANGE
+            effect_num: Some(change),
+            team_type: Some(1),
+            ..Default::default()
+        });
+        self
+    }
+
+    /// The fight as it stands once the new wave is in
+    pub fn add_new_wave(mut self, wave: i32, fight: sonettobuf::Fight) -> Self {
+        self.effects.push(ActEffect {
+            target_id: Some(0),
+            effect_type: Some(337), // NEWCHANGEWAVE
+            effect_num: Some(wave),
+            fight: Some(fight),
+            ..Default::default()
+        });
+        self
+    }
+
     pub fn add_effect_type(mut self, effect_type: i32) -> Self {
         self.effects.push(ActEffect {
             target_id: Some(0),
@@ -140,7 +251,7 @@ impl FightStepBuilder {
             to_id: Some(self.to_id),
             act_id: Some(self.act_id),
             act_effect: self.effects,
-            card_index: Some(0),
+            card_index: Some(self.card_index),
             support_hero_id: Some(0),
             fake_timeline: Some(false),
         }
@@ -0,0 +1,111 @@
+use crate::error::AppError;
+use database::db::game::tower::{
+    update_tower_episode_status, update_tower_layer_score, update_tower_pass_layer,
+};
+use sqlx::SqlitePool;
+
+use super::outcome::FightResult;
+
+/// Which tower layer a battle belongs to, from `ActiveBattle`
+#[derive(Debug, Clone, Copy)]
+pub struct TowerBattle {
+    pub tower_type: i32,
+    pub tower_id: i32,
+    pub layer_id: i32,
+}
+
+/// Faster clears score higher, one point per round left plus one for the clear.
+/// Holding out to the round limit scores a single point, a loss nothing
+pub fn tower_score(result: FightResult, max_round: i32, rounds_used: i32) -> i32 {
+    match result {
+        FightResult::Win => (max_round - rounds_used).max(0) + 1,
+        FightResult::Timeout => 1,
+        FightResult::Lose => 0,
+    }
+}
+
+/// Records a tower fight. A win passes the episode and the layer, a timeout
+/// only keeps its score on the layer and a loss leaves the tower as it was
+pub async fn update_tower_progress(
+    pool: &SqlitePool,
+    user_id: i64,
+    tower: TowerBattle,
+    episode_id: i32,
+    result: FightResult,
+    score: i32,
+) -> Result<(), AppError> {
+    if result == FightResult::Lose {
+        tracing::info!(
+            "Tower {}/{} layer {} lost",
+            tower.tower_type,
+            tower.tower_id,
+            tower.layer_id
+        );
+        return Ok(());
+    }
+
+    update_tower_layer_score(
+        pool,
+        user_id,
+        tower.tower_type,
+        tower.tower_id,
+        tower.layer_id,
+        score,
+    )
+    .await?;
+
+    if result == FightResult::Timeout {
+        tracing::info!(
+            "Tower {}/{} layer {} held to the round limit, score {}",
+            tower.tower_type,
+            tower.tower_id,
+            tower.layer_id,
+            score
+        );
+        return Ok(());
+    }
+
+    update_tower_episode_status(
+        pool,
+        user_id,
+        tower.tower_type,
+        tower.tower_id,
+        tower.layer_id,
+        episode_id,
+        1, // passed
+    )
+    .await?;
+
+    update_tower_pass_layer(
+        pool,
+        user_id,
+        tower.tower_type,
+        tower.tower_id,
+        tower.layer_id,
+        score,
+    )
+    .await?;
+
+    tracing::info!(
+        "Tower {}/{} layer {} passed with score {}",
+        tower.tower_type,
+        tower.tower_id,
+        tower.layer_id,
+        score
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_clears_score_for_rounds_left() {
+        assert_eq!(tower_score(FightResult::Win, 10, 4), 7);
+        assert_eq!(tower_score(FightResult::Win, 10, 12), 1);
+        assert_eq!(tower_score(FightResult::Timeout, 10, 10), 1);
+        assert_eq!(tower_score(FightResult::Lose, 10, 3), 0);
+    }
+}
@@ -2,17 +2,24 @@ use common::time::ServerTime;
 use prost::Message;
 use std::collections::VecDeque;
 use std::sync::Arc;
-use tokio::net::TcpStream;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Notify, mpsc};
 
 use crate::error::AppError;
-use crate::utils::common::{encode_message, send_raw_server_message};
+use crate::utils::capture::{CaptureRecorder, CapturedFrame};
+use crate::utils::common::encode_message;
 use sonettobuf::CmdId;
 
-use super::{AppState, CommandPacket, PlayerState};
+use super::session::{FIXED_DOWN_TAG, SessionState};
+use super::{AppState, ClothState, CommandPacket, PlayerState};
+
+/// Packets waiting for the writer task, bounded so a stalled client applies backpressure
+pub const OUTBOUND_QUEUE_SIZE: usize = 256;
 
 pub struct ConnectionContext {
-    pub socket: Arc<Mutex<TcpStream>>,
+    /// Feeds the connection's writer task, see `client::write_packets`
+    outbound: mpsc::Sender<CommandPacket>,
+    /// Wakes the reader task when the server closes the connection (kick, shutdown)
+    shutdown: Arc<Notify>,
     pub state: Arc<AppState>,
     pub player_id: Option<i64>,
     pub send_queue: VecDeque<CommandPacket>,
@@ -20,9 +27,12 @@ pub struct ConnectionContext {
     pub player_state: Option<PlayerState>,
     pub logged_in: bool,
 
-    next_sequence: u32,
+    /// down_tag / sequence / resend state, survives a relogin of the same player
+    pub session: SessionState,
 
     pub active_battle: Option<ActiveBattle>,
+    /// Set when `[capture]` is enabled
+    pub capture: Option<CaptureRecorder>,
 }
 
 #[allow(dead_code)]
@@ -39,25 +49,43 @@ pub struct ActiveBattle {
     pub act_point: i32, //  Remaining action points
     pub power: i32,
     pub current_deck: Vec<sonettobuf::CardInfo>,
+    pub cloth_opers: Vec<sonettobuf::UseClothSkillOperRecord>, // used this round, saved with its opers
+    pub move_opers: Vec<sonettobuf::BeginRoundOper>, // MoveCard moves, played ahead of the round's opers
+    pub cloth: ClothState, // level and skill uses, never sent to the client
     pub fight_group: Option<sonettobuf::FightGroup>,
     pub fight_id: Option<i64>,
     pub is_replay: Option<bool>,
     pub replay_episode_id: Option<i32>,
     pub multiplication: Option<i32>,
+    pub seed: u64, // fight RNG, kept in battle_records for replays
 }
 
 #[allow(dead_code)]
 impl ConnectionContext {
-    pub fn new(socket: Arc<Mutex<TcpStream>>, state: Arc<AppState>) -> Self {
+    pub fn new(outbound: mpsc::Sender<CommandPacket>, state: Arc<AppState>) -> Self {
         Self {
-            socket,
+            outbound,
+            shutdown: Arc::new(Notify::new()),
             state,
             player_id: None,
             send_queue: VecDeque::new(),
             player_state: None,
             logged_in: false,
-            next_sequence: 0,
+            session: SessionState::new(),
             active_battle: None,
+            capture: None,
+        }
+    }
+
+    /// Write a frame to the capture file, if there is one. A failing
+    /// recorder is dropped so the connection itself keeps working.
+    pub fn capture(&mut self, frame: impl FnOnce() -> CapturedFrame) {
+        let Some(recorder) = self.capture.as_mut() else {
+            return;
+        };
+        if let Err(e) = recorder.record(&frame()) {
+            tracing::warn!("Stopping capture to {}: {}", recorder.path().display(), e);
+            self.capture = None;
         }
     }
 
@@ -222,10 +250,11 @@ impl ConnectionContext {
         self.player_state.as_ref()
     }
 
-    pub fn next_sequence(&mut self) -> u32 {
-        let seq = self.next_sequence;
-        self.next_sequence = self.next_sequence.wrapping_add(1);
-        seq
+    /// True if a reply for `up_tag` is already waiting to be flushed
+    pub fn has_pending_reply(&self, up_tag: u8) -> bool {
+        self.send_queue.iter().any(
+            |packet| matches!(packet, CommandPacket::Reply { up_tag: tag, .. } if *tag == up_tag),
+        )
     }
 
     pub fn queue_packet(&mut self, packet: CommandPacket) {
@@ -234,7 +263,7 @@ impl ConnectionContext {
 
     pub async fn send_push<T: Message>(&mut self, cmd_id: CmdId, msg: T) -> Result<(), AppError> {
         let body = encode_message(&msg)?;
-        let down_tag = self.state.reserve_down_tag().await;
+        let down_tag = self.session.reserve_down_tag();
 
         let packet = CommandPacket::Push {
             cmd_id,
@@ -254,7 +283,7 @@ impl ConnectionContext {
         up_tag: u8,
     ) -> Result<(), AppError> {
         let body = encode_message(&msg)?;
-        let down_tag = self.state.reserve_down_tag().await;
+        let down_tag = self.session.reserve_down_tag();
 
         let packet = CommandPacket::Reply {
             cmd_id,
@@ -275,7 +304,7 @@ impl ConnectionContext {
         result_code: i16,
         up_tag: u8,
     ) -> Result<(), AppError> {
-        let down_tag = 255;
+        let down_tag = FIXED_DOWN_TAG;
         let packet = CommandPacket::Reply {
             cmd_id,
             body,
@@ -297,7 +326,7 @@ impl ConnectionContext {
         up_tag: u8,
     ) -> Result<(), AppError> {
         let body = encode_message(&msg)?;
-        let down_tag = 255;
+        let down_tag = FIXED_DOWN_TAG;
 
         let packet = CommandPacket::Reply {
             cmd_id,
@@ -318,7 +347,7 @@ impl ConnectionContext {
         result_code: i16,
         up_tag: u8,
     ) -> Result<(), AppError> {
-        let down_tag = self.state.reserve_down_tag().await;
+        let down_tag = self.session.reserve_down_tag();
 
         let packet = CommandPacket::Reply {
             cmd_id,
@@ -332,41 +361,71 @@ impl ConnectionContext {
         Ok(())
     }
 
+    /// Hands everything queued so far to the writer task.
+    /// Never touches the socket, so it is safe to call from any task holding the context.
     pub async fn flush_send_queue(&mut self) -> Result<(), AppError> {
-        let mut socket = self.socket.lock().await;
-
         while let Some(packet) = self.send_queue.pop_front() {
-            match packet {
-                CommandPacket::Push {
-                    cmd_id,
-                    body,
-                    down_tag,
-                } => {
-                    send_raw_server_message(&mut socket, cmd_id, body, 0, 255, down_tag).await?;
-                }
-                CommandPacket::Reply {
-                    cmd_id,
-                    body,
-                    result_code,
-                    up_tag,
-                    down_tag,
-                } => {
-                    send_raw_server_message(
-                        &mut socket,
-                        cmd_id,
-                        body,
-                        result_code,
-                        up_tag,
-                        down_tag,
-                    )
-                    .await?;
-                }
-            }
+            self.session.record_sent(&packet);
+            self.capture(|| CapturedFrame::down(&packet.clone().into_server_packet()));
+            self.send_to_writer(packet).await?;
         }
 
         Ok(())
     }
 
+    /// Resend packets the client missed, they keep their original down_tags
+    /// and are not recorded again
+    pub async fn replay_packets(&mut self, packets: Vec<CommandPacket>) -> Result<(), AppError> {
+        self.flush_send_queue().await?;
+
+        for packet in packets {
+            self.capture(|| CapturedFrame::down(&packet.clone().into_server_packet()));
+            self.send_to_writer(packet).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn send_to_writer(&self, packet: CommandPacket) -> Result<(), AppError> {
+        self.outbound.send(packet).await.map_err(|_| {
+            AppError::Io(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "connection writer closed",
+            ))
+        })
+    }
+
+    /// Queue a push and deliver it right away, for server initiated messages
+    /// sent from outside the connection's own dispatch loop
+    pub async fn push_now<T: Message>(&mut self, cmd_id: CmdId, msg: T) -> Result<(), AppError> {
+        self.send_push(cmd_id, msg).await?;
+        self.flush_send_queue().await
+    }
+
+    pub fn is_connected(&self) -> bool {
+        !self.outbound.is_closed()
+    }
+
+    pub fn shutdown_signal(&self) -> Arc<Notify> {
+        Arc::clone(&self.shutdown)
+    }
+
+    /// Stop the reader task, the writer finishes whatever was already flushed
+    pub fn close(&self) {
+        self.shutdown.notify_one();
+    }
+
+    pub async fn send_raw_push(&mut self, cmd_id: CmdId, body: Vec<u8>) -> Result<(), AppError> {
+        let down_tag = self.session.reserve_down_tag();
+
+        self.queue_packet(CommandPacket::Push {
+            cmd_id,
+            body,
+            down_tag,
+        });
+        Ok(())
+    }
+
     pub async fn register(ctx: Arc<Mutex<Self>>) {
         let ctx_lock = ctx.lock().await;
         if let Some(player_id) = ctx_lock.player_id {
@@ -0,0 +1,288 @@
+use data::exceldb::summon_pool::SummonPool;
+use sonettobuf::MaterialData;
+
+pub const MATERIAL_ITEM: u32 = 1;
+pub const MATERIAL_CURRENCY: u32 = 2;
+
+/// One `type#id#quantity` entry of a cost string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Material {
+    pub material_type: u32,
+    pub id: u32,
+    pub quantity: i32,
+}
+
+impl Material {
+    pub fn same_kind(&self, other: &Material) -> bool {
+        self.material_type == other.material_type && self.id == other.id
+    }
+
+    fn times(self, n: i32) -> Self {
+        Self {
+            quantity: self.quantity * n,
+            ..self
+        }
+    }
+}
+
+impl From<Material> for MaterialData {
+    fn from(m: Material) -> Self {
+        MaterialData {
+            materil_type: Some(m.material_type),
+            materil_id: Some(m.id),
+            quantity: Some(m.quantity),
+        }
+    }
+}
+
+/// "1#140001#1|2#2#180" -> the ways to pay, in the pool's order
+pub fn parse_materials(raw: &str) -> Vec<Material> {
+    raw.split('|')
+        .filter_map(|part| {
+            let mut parts = part.split('#').map(|s| s.trim());
+            Some(Material {
+                material_type: parts.next()?.parse().ok()?,
+                id: parts.next()?.parse().ok()?,
+                quantity: parts.next()?.parse().ok()?,
+            })
+        })
+        .filter(|m| m.quantity > 0)
+        .collect()
+}
+
+/// What a pool charges, from its `summon_pool` row
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SummonCost {
+    pub single: Vec<Material>,
+    pub ten: Vec<Material>,
+    /// `discountCost10`, for the pool's first `discountTime10` ten-pulls
+    pub discount_ten: Vec<Material>,
+    /// `priorCost1/10`, spent ahead of everything else when the player has them
+    pub prior_single: Vec<Material>,
+    pub prior_ten: Vec<Material>,
+    /// The pool's own ticket, one per pull
+    pub ticket_id: i32,
+}
+
+/// The materials a pull takes, `discounted` when it used up a discounted ten-pull
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Payment {
+    pub materials: Vec<Material>,
+    pub discounted: bool,
+}
+
+impl SummonCost {
+    pub fn from_pool(pool: &SummonPool) -> Self {
+        Self {
+            single: parse_materials(&pool.cost1),
+            ten: parse_materials(&pool.cost10),
+            discount_ten: parse_materials(&pool.discount_cost10),
+            prior_single: parse_materials(&pool.prior_cost1),
+            prior_ten: parse_materials(&pool.prior_cost10),
+            ticket_id: pool.ticket_id,
+        }
+    }
+
+    /// Every material the pool could charge, to look up what the player owns
+    pub fn materials(&self) -> Vec<Material> {
+        let costs = [
+            &self.prior_single,
+            &self.prior_ten,
+            &self.single,
+            &self.ten,
+            &self.discount_ten,
+        ];
+
+        let mut all: Vec<Material> = Vec::new();
+        for material in costs
+            .into_iter()
+            .flatten()
+            .copied()
+            .chain(self.pool_ticket())
+        {
+            if !all.iter().any(|m| m.same_kind(&material)) {
+                all.push(material);
+            }
+        }
+        all
+    }
+
+    /// How `count` pulls get paid: a prior cost the player can cover, else
+    /// tickets one pull at a time and currency for the rest. None when the
+    /// player can't pay
+    pub fn payment(
+        &self,
+        count: i32,
+        discount_left: bool,
+        owned: impl Fn(&Material) -> i32,
+    ) -> Option<Payment> {
+        let prior = if count == 10 {
+            &self.prior_ten
+        } else {
+            &self.prior_single
+        };
+        if let Some(&material) = prior.iter().find(|m| owned(m) >= m.quantity) {
+            return Some(Payment {
+                materials: vec![material],
+                discounted: false,
+            });
+        }
+
+        let mut materials = Vec::new();
+        let mut left = count;
+
+        let tickets = self
+            .single
+            .iter()
+            .copied()
+            .filter(|m| m.material_type == MATERIAL_ITEM)
+            .chain(self.pool_ticket());
+        for ticket in tickets {
+            if materials.iter().any(|m: &Material| m.same_kind(&ticket)) {
+                continue;
+            }
+
+            let pulls = (owned(&ticket) / ticket.quantity).min(left);
+            if pulls > 0 {
+                materials.push(ticket.times(pulls));
+                left -= pulls;
+            }
+        }
+
+        if left == 0 {
+            return Some(Payment {
+                materials,
+                discounted: false,
+            });
+        }
+
+        let currency = |costs: &[Material]| {
+            costs
+                .iter()
+                .copied()
+                .filter(|m| m.material_type == MATERIAL_CURRENCY)
+                .collect::<Vec<_>>()
+        };
+
+        // the ten-pull prices only hold for a ten-pull paid in full with currency
+        let mut options = Vec::new();
+        let mut discounted = false;
+        if left == 10 {
+            if discount_left && !currency(&self.discount_ten).is_empty() {
+                options = currency(&self.discount_ten);
+                discounted = true;
+            } else {
+                options = currency(&self.ten);
+            }
+        }
+        if options.is_empty() {
+            options = currency(&self.single)
+                .into_iter()
+                .map(|m| m.times(left))
+                .collect();
+            discounted = false;
+        }
+
+        let paid = options.into_iter().find(|m| owned(m) >= m.quantity)?;
+        materials.push(paid);
+
+        Some(Payment {
+            materials,
+            discounted,
+        })
+    }
+
+    fn pool_ticket(&self) -> Option<Material> {
+        u32::try_from(self.ticket_id)
+            .ok()
+            .filter(|&id| id != 0)
+            .map(|id| Material {
+                material_type: MATERIAL_ITEM,
+                id,
+                quantity: 1,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICKET: u32 = 140001;
+    const CLEAR_DROPS: u32 = 2;
+
+    fn cost() -> SummonCost {
+        SummonCost {
+            single: parse_materials("1#140001#1|2#2#180"),
+            ten: parse_materials("1#140001#10|2#2#1800"),
+            discount_ten: parse_materials("2#2#900"),
+            ..SummonCost::default()
+        }
+    }
+
+    fn wallet(tickets: i32, drops: i32) -> impl Fn(&Material) -> i32 {
+        move |m| match (m.material_type, m.id) {
+            (MATERIAL_ITEM, TICKET) => tickets,
+            (MATERIAL_CURRENCY, CLEAR_DROPS) => drops,
+            _ => 0,
+        }
+    }
+
+    fn material(material_type: u32, id: u32, quantity: i32) -> Material {
+        Material {
+            material_type,
+            id,
+            quantity,
+        }
+    }
+
+    #[test]
+    fn parses_cost_strings() {
+        assert_eq!(
+            parse_materials("1#140001#1|2#2#180"),
+            vec![material(1, TICKET, 1), material(2, CLEAR_DROPS, 180)]
+        );
+        assert!(parse_materials("").is_empty());
+        assert!(parse_materials("1#140001").is_empty());
+    }
+
+    #[test]
+    fn tickets_go_before_currency() {
+        let paid = cost().payment(10, false, wallet(4, 5000)).unwrap();
+        assert_eq!(
+            paid.materials,
+            vec![material(1, TICKET, 4), material(2, CLEAR_DROPS, 1080)]
+        );
+
+        let paid = cost().payment(1, true, wallet(1, 0)).unwrap();
+        assert_eq!(paid.materials, vec![material(1, TICKET, 1)]);
+        assert!(!paid.discounted);
+    }
+
+    #[test]
+    fn first_ten_pulls_take_the_discount() {
+        let paid = cost().payment(10, true, wallet(0, 1000)).unwrap();
+        assert_eq!(paid.materials, vec![material(2, CLEAR_DROPS, 900)]);
+        assert!(paid.discounted);
+
+        let paid = cost().payment(10, false, wallet(0, 2000)).unwrap();
+        assert_eq!(paid.materials, vec![material(2, CLEAR_DROPS, 1800)]);
+        assert!(!paid.discounted);
+    }
+
+    #[test]
+    fn prior_cost_wins_and_shortfalls_are_refused() {
+        let prior = SummonCost {
+            prior_single: parse_materials("1#140002#1"),
+            ..cost()
+        };
+        let owned = |m: &Material| if m.id == 140002 { 1 } else { 0 };
+        assert_eq!(
+            prior.payment(1, false, owned).unwrap().materials,
+            vec![material(1, 140002, 1)]
+        );
+
+        assert_eq!(cost().payment(10, false, wallet(3, 1000)), None);
+        assert_eq!(cost().payment(1, false, wallet(0, 179)), None);
+    }
+}
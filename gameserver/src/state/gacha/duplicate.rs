@@ -0,0 +1,45 @@
+use super::cost::{Material, parse_materials};
+use data::exceldb::character::Character;
+
+/// Duplicates past this many stop feeding Portray and convert instead
+pub const PORTRAY_CAP: i32 = 5;
+
+/// What the `duplicate_count`-th copy of a hero turns into: `duplicateItem`
+/// while Portray is below the cap, `duplicateItem2` after it
+pub fn duplicate_materials(character: &Character, duplicate_count: i32) -> Vec<Material> {
+    resolve(
+        &character.duplicate_item,
+        &character.duplicate_item2,
+        duplicate_count,
+    )
+}
+
+fn resolve(portray: &str, converted: &str, duplicate_count: i32) -> Vec<Material> {
+    // heroes without a conversion keep handing out their portray materials
+    if duplicate_count <= PORTRAY_CAP || converted.trim().is_empty() {
+        parse_materials(portray)
+    } else {
+        parse_materials(converted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::gacha::cost::{MATERIAL_CURRENCY, MATERIAL_ITEM};
+
+    #[test]
+    fn duplicates_feed_portray_then_convert() {
+        let portray = resolve("1#130003#1|2#5#10", "2#5#25", PORTRAY_CAP);
+        assert_eq!(portray.len(), 2);
+        assert_eq!(portray[0].material_type, MATERIAL_ITEM);
+        assert_eq!(portray[0].id, 130003);
+
+        let converted = resolve("1#130003#1|2#5#10", "2#5#25", PORTRAY_CAP + 1);
+        assert_eq!(converted.len(), 1);
+        assert_eq!(converted[0].material_type, MATERIAL_CURRENCY);
+        assert_eq!(converted[0].quantity, 25);
+
+        assert_eq!(resolve("1#130003#1", "", PORTRAY_CAP + 3).len(), 1);
+    }
+}
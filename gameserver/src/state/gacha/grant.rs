@@ -0,0 +1,35 @@
+use super::cost::{MATERIAL_CURRENCY, MATERIAL_ITEM, Material};
+use database::db::game::currencies::add_currency;
+use database::db::game::items::add_item_quantity;
+use sqlx::{Sqlite, Transaction};
+
+/// Hands the material over, false for kinds that can't be granted yet
+pub async fn grant_material(
+    tx: &mut Transaction<'_, Sqlite>,
+    user_id: i64,
+    material: &Material,
+) -> sqlx::Result<bool> {
+    match material.material_type {
+        MATERIAL_ITEM => add_item_quantity(tx, user_id, material.id, material.quantity).await?,
+        MATERIAL_CURRENCY => {
+            add_currency(tx, user_id, material.id as i32, material.quantity).await?
+        }
+        other => {
+            tracing::warn!("Grant of material type {} skipped", other);
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Ids of the materials of one type, each once, for the change pushes
+pub fn changed_ids(materials: &[Material], material_type: u32) -> Vec<u32> {
+    let mut ids = Vec::new();
+    for m in materials {
+        if m.material_type == material_type && !ids.contains(&m.id) {
+            ids.push(m.id);
+        }
+    }
+    ids
+}
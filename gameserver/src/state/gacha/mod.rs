@@ -1,23 +1,40 @@
-use anyhow::Result;
+use crate::error::AppError;
+use common::config::SummonConfig;
 use data::exceldb;
 use database::models::game::summon::SpPoolInfo;
 
+mod cost;
+mod duplicate;
+mod grant;
 mod helpers;
+mod pity;
+mod progress;
+mod rates;
 mod result;
 mod state;
 
+pub use cost::{MATERIAL_CURRENCY, MATERIAL_ITEM, Material, SummonCost};
+pub use duplicate::duplicate_materials;
+pub use grant::{changed_ids, grant_material};
 pub use helpers::{parse_id_list, parse_up_heroes};
+pub use pity::PityGroup;
+pub use progress::{ProgressReward, parse_progress_rewards};
+pub use rates::RateModel;
 pub use result::{GachaPool, GachaResult};
 pub use state::{BannerType, GachaState, load_gacha_state, save_gacha_state};
 
-pub async fn build_gacha(pool_id: i32, sp_pool_info: Option<&SpPoolInfo>) -> Result<GachaPool> {
+pub async fn build_gacha(
+    pool_id: i32,
+    sp_pool_info: Option<&SpPoolInfo>,
+    overrides: &SummonConfig,
+) -> Result<GachaPool, AppError> {
     let game_data = exceldb::get();
 
     let pool_cfg = game_data
         .summon_pool
         .iter()
         .find(|p| p.id == pool_id)
-        .expect("Summon pool not found");
+        .ok_or(AppError::InvalidRequest)?;
 
     let banner_type = match &sp_pool_info {
         Some(sp) => BannerType::from(sp.sp_type),
@@ -27,8 +44,8 @@ pub async fn build_gacha(pool_id: i32, sp_pool_info: Option<&SpPoolInfo>) -> Res
     let (six_up, five_up) = match banner_type {
         BannerType::RateUp => parse_up_heroes(pool_cfg.up_weight.as_str()),
 
-        BannerType::Ripple => {
-            let sp = sp_pool_info.as_ref().unwrap();
+        BannerType::Ripple | BannerType::MultiUp => {
+            let sp = sp_pool_info.ok_or(AppError::InvalidRequest)?;
 
             (sp.up_hero_ids.clone(), Vec::new())
         }
@@ -43,12 +60,16 @@ pub async fn build_gacha(pool_id: i32, sp_pool_info: Option<&SpPoolInfo>) -> Res
     let mut four = Vec::new();
     let mut three = Vec::new();
     let mut two = Vec::new();
+    let mut lucky_bags = Vec::new();
 
     for summon in summons {
         let ids = parse_id_list(&summon.summon_id);
 
         match summon.rare {
-            5 => six_all.extend(ids),
+            5 => {
+                six_all.extend(ids);
+                lucky_bags.extend(parse_id_list(&summon.lucky_bag_id));
+            }
             4 => five_all.extend(ids),
             3 => four.extend(ids),
             2 => three.extend(ids),
@@ -75,5 +96,17 @@ pub async fn build_gacha(pool_id: i32, sp_pool_info: Option<&SpPoolInfo>) -> Res
         four,
         three,
         two,
+        lucky_bags,
+        rates: RateModel::from_pool(pool_cfg, overrides),
     })
 }
+
+/// The pool's 6* heroes, from its `summon` rows
+pub fn six_star_heroes(pool_id: i32) -> Vec<i32> {
+    exceldb::get()
+        .summon
+        .iter()
+        .filter(|s| s.id == pool_id && s.rare == 5)
+        .flat_map(|s| parse_id_list(&s.summon_id))
+        .collect()
+}
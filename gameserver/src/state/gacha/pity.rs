@@ -0,0 +1,47 @@
+use data::exceldb;
+
+/// Pools that carry one pity counter and pull count between them: every
+/// `summon_pool` of the same type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PityGroup {
+    /// The pools' `type`
+    pub id: i32,
+    pub pool_ids: Vec<i32>,
+}
+
+impl PityGroup {
+    pub fn of(pool_id: i32) -> Option<Self> {
+        let pools = &exceldb::get().summon_pool;
+        let pool_type = pools.get(pool_id)?.r#type;
+
+        Some(Self::from_pools(
+            pool_type,
+            pools.iter().map(|p| (p.id, p.r#type)),
+        ))
+    }
+
+    /// `pools` as (pool id, type)
+    fn from_pools(pool_type: i32, pools: impl Iterator<Item = (i32, i32)>) -> Self {
+        Self {
+            id: pool_type,
+            pool_ids: pools
+                .filter(|(_, t)| *t == pool_type)
+                .map(|(id, _)| id)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pools_of_a_type_share_a_group() {
+        let pools = [(16131, 3), (1, 1), (15111, 3), (27161, 12)];
+        let group = PityGroup::from_pools(3, pools.into_iter());
+
+        assert_eq!(group.id, 3);
+        assert_eq!(group.pool_ids, vec![16131, 15111]);
+    }
+}
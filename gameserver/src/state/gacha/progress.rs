@@ -0,0 +1,64 @@
+use super::cost::{Material, parse_materials};
+
+/// A `progressRewards` milestone, claimable once the pool has seen `progress` pulls
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressReward {
+    pub progress: i32,
+    pub materials: Vec<Material>,
+}
+
+/// "40#1#140001#2|80#2#2#300" -> milestones in pull order, one per pull count
+pub fn parse_progress_rewards(raw: &str) -> Vec<ProgressReward> {
+    let mut rewards: Vec<ProgressReward> = Vec::new();
+
+    for part in raw.split('|') {
+        let Some((progress, material)) = part.split_once('#') else {
+            continue;
+        };
+        let Ok(progress) = progress.trim().parse::<i32>() else {
+            continue;
+        };
+        let materials = parse_materials(material);
+        if materials.is_empty() {
+            continue;
+        }
+
+        match rewards.iter_mut().find(|r| r.progress == progress) {
+            Some(reward) => reward.materials.extend(materials),
+            None => rewards.push(ProgressReward {
+                progress,
+                materials,
+            }),
+        }
+    }
+
+    rewards.sort_by_key(|r| r.progress);
+    rewards
+}
+
+impl ProgressReward {
+    /// Reached with `summon_count` pulls and not in `claimed` yet
+    pub fn is_due(&self, summon_count: i32, claimed: &[i32]) -> bool {
+        summon_count >= self.progress && !claimed.contains(&self.progress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn milestones_group_by_pull_count() {
+        let rewards = parse_progress_rewards("80#2#2#300|40#1#140001#2|40#2#5#10|oops");
+
+        assert_eq!(
+            rewards.iter().map(|r| r.progress).collect::<Vec<_>>(),
+            vec![40, 80]
+        );
+        assert_eq!(rewards[0].materials.len(), 2);
+        assert!(rewards[0].is_due(45, &[]));
+        assert!(!rewards[0].is_due(45, &[40]));
+        assert!(!rewards[1].is_due(45, &[]));
+        assert!(parse_progress_rewards("").is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+use common::config::SummonConfig;
+use data::exceldb::summon_pool::SummonPool;
+
+use super::helpers::parse_id_list;
+
+/// Weights out of this when `totalPosibility` is unset
+const DEFAULT_TOTAL: f64 = 1000.0;
+/// (star, weight): 1.5% 6*, 8.5% 5*, 40% 4*, 45% 3*, 5% 2*
+const DEFAULT_WEIGHTS: [(u8, f64); 5] = [(6, 15.0), (5, 85.0), (4, 400.0), (3, 450.0), (2, 50.0)];
+const DEFAULT_SOFT_PITY_START: u32 = 60;
+const DEFAULT_SOFT_PITY_STEP: f64 = 25.0;
+const DEFAULT_HARD_PITY: u32 = 70;
+const DEFAULT_UP_CHANCE: f64 = 0.5;
+
+/// The odds a pool pulls with.
+///
+/// `summon_pool` columns, rares counted like the `summon` table (5 = 6*, the
+/// same mapping `build_gacha` sorts hero lists by):
+/// - `initWeight` "rare#weight|...", out of `totalPosibility` (or their sum)
+/// - `changeWeight` "start#step[#hard]", 6* weight added per dry pull past start
+/// - `doubleSsrUpRates` "weight#weight", how the 6* UP heroes split a win
+/// - `guaranteeSRParam` "pulls#rare", a ten-pull's window that holds that rare or better
+///
+/// Only the rare mapping is backed by data in this tree. The column layouts
+/// above are read off the field names and the "a#b|c#d" shape the other
+/// tables use, and haven't been checked against exported pool rows. A column
+/// that doesn't parse keeps the default curve, and `[summon]` in the config
+/// overrides any of them
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateModel {
+    /// (star, chance), summing to 1
+    pub rarity: Vec<(u8, f64)>,
+    pub soft_pity_start: u32,
+    pub soft_pity_step: f64,
+    pub hard_pity: u32,
+    pub six_up_chance: f64,
+    pub five_up_chance: f64,
+    /// Weights between the 6* UP heroes, empty picks them evenly
+    pub six_up_weights: Vec<f64>,
+    /// (pulls, star)
+    pub sr_guarantee: Option<(usize, u8)>,
+}
+
+impl Default for RateModel {
+    fn default() -> Self {
+        Self {
+            rarity: normalized(&DEFAULT_WEIGHTS, DEFAULT_TOTAL),
+            soft_pity_start: DEFAULT_SOFT_PITY_START,
+            soft_pity_step: DEFAULT_SOFT_PITY_STEP / DEFAULT_TOTAL,
+            hard_pity: DEFAULT_HARD_PITY,
+            six_up_chance: DEFAULT_UP_CHANCE,
+            five_up_chance: DEFAULT_UP_CHANCE,
+            six_up_weights: Vec::new(),
+            sr_guarantee: Some((10, 5)),
+        }
+    }
+}
+
+impl RateModel {
+    pub fn from_pool(pool: &SummonPool, overrides: &SummonConfig) -> Self {
+        let mut rates = Self::default();
+
+        let weights = parse_pairs(&pool.init_weight)
+            .into_iter()
+            .map(|(rare, weight)| (star(rare), weight as f64))
+            .collect::<Vec<_>>();
+        let total = if pool.total_posibility > 0 {
+            pool.total_posibility as f64
+        } else if weights.is_empty() {
+            DEFAULT_TOTAL
+        } else {
+            weights.iter().map(|(_, w)| w).sum()
+        };
+        if !weights.is_empty() {
+            rates.rarity = normalized(&weights, total);
+        }
+
+        let change = parse_id_list(&pool.change_weight);
+        if let [start, step, rest @ ..] = change.as_slice() {
+            rates.soft_pity_start = *start as u32;
+            rates.soft_pity_step = *step as f64 / total;
+            if let Some(hard) = rest.first() {
+                rates.hard_pity = *hard as u32;
+            }
+        }
+
+        rates.six_up_weights = parse_id_list(&pool.double_ssr_up_rates)
+            .into_iter()
+            .map(f64::from)
+            .collect();
+
+        if let Some((pulls, rare)) = parse_pairs(&pool.guarantee_srparam).first() {
+            rates.sr_guarantee = Some((*pulls as usize, star(*rare)));
+        }
+
+        rates.apply(overrides);
+        rates
+    }
+
+    pub fn apply(&mut self, overrides: &SummonConfig) {
+        if let Some(rate) = overrides.six_star_rate {
+            self.set_rarity(6, rate);
+        }
+        if let Some(rate) = overrides.five_star_rate {
+            self.set_rarity(5, rate);
+        }
+        if let Some(start) = overrides.soft_pity_start {
+            self.soft_pity_start = start;
+        }
+        if let Some(step) = overrides.soft_pity_step {
+            self.soft_pity_step = step;
+        }
+        if let Some(hard) = overrides.hard_pity {
+            self.hard_pity = hard;
+        }
+        if let Some(chance) = overrides.six_up_chance {
+            self.six_up_chance = chance;
+        }
+        if let Some(chance) = overrides.five_up_chance {
+            self.five_up_chance = chance;
+        }
+    }
+
+    pub fn rarity_chance(&self, star: u8) -> f64 {
+        self.rarity
+            .iter()
+            .find(|(s, _)| *s == star)
+            .map_or(0.0, |(_, chance)| *chance)
+    }
+
+    /// 6* odds on the `pity`-th pull since the last one
+    pub fn six_star_chance(&self, pity: u32) -> f64 {
+        let base = self.rarity_chance(6);
+        if pity >= self.hard_pity {
+            1.0
+        } else if pity >= self.soft_pity_start {
+            let climbed = (pity - self.soft_pity_start + 1) as f64 * self.soft_pity_step;
+            (base + climbed).min(1.0)
+        } else {
+            base
+        }
+    }
+
+    /// Pins one star's odds and rescales the rest to fill what's left
+    fn set_rarity(&mut self, star: u8, chance: f64) {
+        let chance = chance.clamp(0.0, 1.0);
+        let others: f64 = self
+            .rarity
+            .iter()
+            .filter(|(s, _)| *s != star)
+            .map(|(_, c)| c)
+            .sum();
+        let scale = if others > 0.0 {
+            (1.0 - chance) / others
+        } else {
+            0.0
+        };
+
+        for (s, c) in &mut self.rarity {
+            if *s != star {
+                *c *= scale;
+            }
+        }
+        match self.rarity.iter_mut().find(|(s, _)| *s == star) {
+            Some((_, c)) => *c = chance,
+            None => self.rarity.push((star, chance)),
+        }
+    }
+}
+
+/// `summon` table rare -> hero stars
+fn star(rare: i32) -> u8 {
+    (rare + 1).clamp(2, 6) as u8
+}
+
+fn parse_pairs(raw: &str) -> Vec<(i32, i32)> {
+    raw.split('|')
+        .filter_map(|part| match parse_id_list(part).as_slice() {
+            [a, b, ..] => Some((*a, *b)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn normalized(weights: &[(u8, f64)], total: f64) -> Vec<(u8, f64)> {
+    weights.iter().map(|(s, w)| (*s, w / total)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn defaults_keep_the_old_curve() {
+        let rates = RateModel::default();
+        assert!(close(rates.six_star_chance(1), 0.015));
+        assert!(close(rates.six_star_chance(60), 0.04));
+        assert!(close(rates.six_star_chance(69), 0.265));
+        assert!(close(rates.six_star_chance(70), 1.0));
+        assert!(close(rates.rarity.iter().map(|(_, c)| c).sum::<f64>(), 1.0));
+    }
+
+    #[test]
+    fn overrides_pin_a_rarity_and_rescale_the_rest() {
+        let mut rates = RateModel::default();
+        rates.apply(&SummonConfig {
+            six_star_rate: Some(0.1),
+            hard_pity: Some(50),
+            ..SummonConfig::default()
+        });
+
+        assert!(close(rates.rarity_chance(6), 0.1));
+        assert!(close(rates.rarity.iter().map(|(_, c)| c).sum::<f64>(), 1.0));
+        assert!(close(
+            rates.rarity_chance(4) / rates.rarity_chance(3),
+            400.0 / 450.0
+        ));
+        assert!(close(rates.six_star_chance(50), 1.0));
+    }
+
+    #[test]
+    fn parses_the_table_columns() {
+        assert_eq!(parse_pairs("5#20|4#80"), vec![(5, 20), (4, 80)]);
+        assert_eq!(parse_pairs("10#4"), vec![(10, 4)]);
+        assert!(parse_pairs("").is_empty());
+        assert_eq!(star(5), 6);
+        assert_eq!(star(1), 2);
+    }
+}
@@ -1,3 +1,5 @@
+use super::RateModel;
+
 #[derive(Debug)]
 pub struct GachaPool {
     pub six_up: Vec<i32>,
@@ -9,9 +11,45 @@ pub struct GachaPool {
     pub four: Vec<i32>,
     pub three: Vec<i32>,
     pub two: Vec<i32>,
+
+    /// Bags a 6* turns into on lucky-bag pools, from the `summon` rows' `luckyBagId`
+    pub lucky_bags: Vec<i32>,
+
+    pub rates: RateModel,
+}
+
+impl GachaPool {
+    /// Whether any hero of that star can come out of the pool
+    pub fn has_star(&self, star: u8) -> bool {
+        match star {
+            6 => !self.six_up.is_empty() || !self.six_normal.is_empty(),
+            5 => !self.five_up.is_empty() || !self.five_normal.is_empty(),
+            4 => !self.four.is_empty(),
+            3 => !self.three.is_empty(),
+            2 => !self.two.is_empty(),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum GachaResult {
-    Hero { hero_id: i32, rare: u8, is_up: bool },
+    Hero {
+        hero_id: i32,
+        rare: u8,
+        is_up: bool,
+    },
+    /// A 6* on a lucky-bag pool, opened later for a hero of the player's choice
+    LuckyBag {
+        bag_id: i32,
+    },
+}
+
+impl GachaResult {
+    pub fn rare(&self) -> u8 {
+        match self {
+            GachaResult::Hero { rare, .. } => *rare,
+            GachaResult::LuckyBag { .. } => 6,
+        }
+    }
 }
@@ -1,5 +1,5 @@
 use super::helpers::*;
-use super::{GachaPool, GachaResult};
+use super::{GachaPool, GachaResult, PityGroup};
 use rand::{Rng, seq::SliceRandom};
 
 #[derive(Debug, Clone, Copy)]
@@ -7,12 +7,15 @@ pub enum BannerType {
     RateUp,
     Ripple,
     Standard,
+    /// The player picks the UP heroes with `ChooseMultiUpHeroCmd`
+    MultiUp,
 }
 
 impl BannerType {
     pub fn from(t: i32) -> Self {
         match t {
             12 => BannerType::Ripple,
+            7 => BannerType::MultiUp,
             2 => BannerType::Standard,
             _ => BannerType::RateUp,
         }
@@ -26,23 +29,31 @@ pub struct GachaState {
 }
 
 impl GachaState {
+    /// `min_star` floors the pull, a 6* floor forces the 6* roll. An empty UP
+    /// or normal list falls back to the other one, `None` when the pool has no
+    /// hero at all for the star it rolled
     pub fn single_pull(
         &mut self,
         banner_type: BannerType,
         pool: &GachaPool,
         rng: &mut impl Rng,
-        force_five_star: bool,
-    ) -> GachaResult {
+        min_star: Option<u8>,
+    ) -> Option<GachaResult> {
         self.pity_6 += 1;
 
-        let six_rate = six_star_probability(self.pity_6);
+        let min_star = min_star.unwrap_or(2);
+        let six_rate = pool.rates.six_star_chance(self.pity_6);
         let roll: f64 = rng.r#gen();
 
-        if roll < six_rate {
+        if roll < six_rate || min_star >= 6 {
             self.pity_6 = 0;
 
+            if let Some(&bag_id) = pool.lucky_bags.choose(rng) {
+                return Some(GachaResult::LuckyBag { bag_id });
+            }
+
             let (hero_id, is_up) = match banner_type {
-                BannerType::RateUp | BannerType::Standard => {
+                BannerType::RateUp | BannerType::Standard | BannerType::MultiUp => {
                     let has_up = !pool.six_up.is_empty();
 
                     let is_up = if has_up {
@@ -50,7 +61,7 @@ impl GachaState {
                             self.up_guaranteed = false;
                             true
                         } else {
-                            let hit = rng.gen_bool(0.5);
+                            let hit = rng.gen_bool(pool.rates.six_up_chance.clamp(0.0, 1.0));
                             if !hit {
                                 self.up_guaranteed = true;
                             }
@@ -61,142 +72,330 @@ impl GachaState {
                         false
                     };
 
-                    let hero_id = if is_up {
-                        *pool.six_up.choose(rng).unwrap()
+                    if is_up {
+                        (pick_six_up(pool, rng)?, true)
                     } else {
-                        *pool.six_normal.choose(rng).expect("six_normal empty")
-                    };
-
-                    (hero_id, is_up)
+                        match pool.six_normal.choose(rng) {
+                            Some(&hero_id) => (hero_id, false),
+                            // only UP heroes left, the lost 50/50 is spent on one
+                            None => {
+                                self.up_guaranteed = false;
+                                (pick_six_up(pool, rng)?, true)
+                            }
+                        }
+                    }
                 }
 
                 BannerType::Ripple => {
                     self.up_guaranteed = false;
 
-                    let hero_id = *pool
-                        .six_up
-                        .choose(rng)
-                        .expect("Ripple banner requires at least one UP hero");
-
-                    (hero_id, true)
+                    match pick_six_up(pool, rng) {
+                        Some(hero_id) => (hero_id, true),
+                        None => (*pool.six_normal.choose(rng)?, false),
+                    }
                 }
             };
 
-            return GachaResult::Hero {
+            return Some(GachaResult::Hero {
                 hero_id,
                 rare: 6,
                 is_up,
-            };
+            });
         }
 
-        let remaining = 1.0 - six_rate;
+        let mut rarity_weights: Vec<(u8, f64)> = pool
+            .rates
+            .rarity
+            .iter()
+            .copied()
+            .filter(|(star, _)| *star < 6 && pool.has_star(*star))
+            .collect();
 
-        let mut rarity_weights = [(5u8, RATE_5), (4u8, RATE_4), (3u8, RATE_3), (2u8, RATE_2)]
-            .map(|(r, w)| (r, w / remaining))
-            .to_vec();
-
-        if force_five_star {
-            rarity_weights.retain(|(r, _)| *r >= 5);
+        if rarity_weights.iter().any(|(star, _)| *star >= min_star) {
+            rarity_weights.retain(|(star, _)| *star >= min_star);
+        }
+        if rarity_weights.is_empty() {
+            return None;
         }
 
         let rare = pick_weighted(&rarity_weights, rng);
 
         let hero_id = match rare {
             5 => {
-                if !pool.five_up.is_empty() && rng.gen_bool(0.5) {
-                    *pool.five_up.choose(rng).unwrap()
+                let up_chance = pool.rates.five_up_chance.clamp(0.0, 1.0);
+                if !pool.five_up.is_empty()
+                    && (pool.five_normal.is_empty() || rng.gen_bool(up_chance))
+                {
+                    *pool.five_up.choose(rng)?
                 } else {
-                    *pool.five_normal.choose(rng).unwrap()
+                    *pool.five_normal.choose(rng)?
                 }
             }
-            4 => *pool.four.choose(rng).unwrap(),
-            3 => *pool.three.choose(rng).unwrap(),
-            2 => *pool.two.choose(rng).unwrap(),
-            _ => unreachable!(),
+            4 => *pool.four.choose(rng)?,
+            3 => *pool.three.choose(rng)?,
+            2 => *pool.two.choose(rng)?,
+            _ => return None,
         };
 
-        GachaResult::Hero {
+        Some(GachaResult::Hero {
             hero_id,
             rare,
             is_up: false,
-        }
+        })
     }
 
+    /// Ten pulls, the last of each `sr_guarantee` window is floored when the
+    /// window came up short. `None` as soon as one pull comes up empty
     pub fn ten_pull(
         &mut self,
         banner_type: BannerType,
         pool: &GachaPool,
         rng: &mut impl Rng,
-    ) -> Vec<GachaResult> {
-        let mut results = Vec::with_capacity(10);
+    ) -> Option<Vec<GachaResult>> {
+        let mut results: Vec<GachaResult> = Vec::with_capacity(10);
+        let guarantee = pool.rates.sr_guarantee.filter(|(pulls, _)| *pulls > 0);
 
-        results.push(self.single_pull(banner_type, pool, rng, true));
+        for i in 0..10 {
+            let min_star = guarantee.and_then(|(pulls, star)| {
+                let closes_window = i % pulls == pulls - 1;
+                let window = &results[(i + 1).saturating_sub(pulls)..];
+                (closes_window && window.iter().all(|r| r.rare() < star)).then_some(star)
+            });
 
-        for _ in 1..10 {
-            results.push(self.single_pull(banner_type, pool, rng, false));
+            results.push(self.single_pull(banner_type, pool, rng, min_star)?);
         }
 
-        results
+        Some(results)
+    }
+}
+
+/// One of the 6* UP heroes, split by `doubleSsrUpRates` when it covers them all
+fn pick_six_up(pool: &GachaPool, rng: &mut impl Rng) -> Option<i32> {
+    let weights = &pool.rates.six_up_weights;
+    if !pool.six_up.is_empty()
+        && weights.len() == pool.six_up.len()
+        && weights.iter().any(|w| *w > 0.0)
+    {
+        let weighted: Vec<(i32, f64)> = pool.six_up.iter().copied().zip(weights.clone()).collect();
+        return Some(pick_weighted(&weighted, rng));
     }
+
+    pool.six_up.choose(rng).copied()
 }
 
 #[derive(Debug)]
 pub struct UserGachaState {
     pub pity_6: u32,
     pub up_guaranteed: bool,
+    /// Pulls made across the whole pity group
+    pub summon_count: i32,
 }
 
+/// The group's counters. Before its first pull the group starts from the pulls
+/// its pools already had. Rows migration 041 left under `-pool_id`, for pools
+/// it couldn't place, are folded in: the furthest along keeps its pity
 pub async fn load_gacha_state(
     pool: &sqlx::SqlitePool,
     user_id: i64,
-    pool_id: i32,
+    group: &PityGroup,
 ) -> sqlx::Result<UserGachaState> {
-    let row = sqlx::query_as::<_, (i64, i64)>(
-        r#"
-        SELECT pity_6, up_guaranteed
-        FROM user_gacha_state
-        WHERE user_id = ? AND pool_id = ?
-        "#,
-    )
-    .bind(user_id)
-    .bind(pool_id)
-    .fetch_optional(pool)
-    .await?;
+    let placeholders = vec!["?"; group.pool_ids.len()].join(", ");
 
-    Ok(match row {
-        Some((pity, up)) => UserGachaState {
+    let sql = format!(
+        "SELECT pity_6, up_guaranteed, summon_count FROM user_gacha_pity
+         WHERE user_id = ? AND (pity_group = ? OR -pity_group IN ({placeholders}))
+         ORDER BY pity_6 DESC, up_guaranteed DESC"
+    );
+    let mut query = sqlx::query_as::<_, (i64, i64, i32)>(&sql)
+        .bind(user_id)
+        .bind(group.id);
+    for pool_id in &group.pool_ids {
+        query = query.bind(pool_id);
+    }
+    let rows = query.fetch_all(pool).await?;
+
+    if let Some(&(pity, up, _)) = rows.first() {
+        return Ok(UserGachaState {
             pity_6: pity as u32,
             up_guaranteed: up != 0,
-        },
-        None => UserGachaState {
-            pity_6: 0,
-            up_guaranteed: false,
-        },
+            summon_count: rows.iter().map(|(_, _, count)| count).sum(),
+        });
+    }
+
+    let sql = format!(
+        "SELECT COALESCE(SUM(summon_count), 0) FROM user_summon_pools
+         WHERE user_id = ? AND pool_id IN ({placeholders})"
+    );
+    let mut query = sqlx::query_scalar::<_, i32>(&sql).bind(user_id);
+    for pool_id in &group.pool_ids {
+        query = query.bind(pool_id);
+    }
+    let summon_count = if group.pool_ids.is_empty() {
+        0
+    } else {
+        query.fetch_one(pool).await?
+    };
+
+    Ok(UserGachaState {
+        pity_6: 0,
+        up_guaranteed: false,
+        summon_count,
     })
 }
 
+/// Saves the group's counters, which `load_gacha_state` already folded the
+/// group's leftover per-pool rows into
 pub async fn save_gacha_state(
-    pool: &sqlx::SqlitePool,
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
     user_id: i64,
-    pool_id: i32,
+    group: &PityGroup,
     gacha: &GachaState,
+    summon_count: i32,
 ) -> sqlx::Result<()> {
+    if !group.pool_ids.is_empty() {
+        let placeholders = vec!["?"; group.pool_ids.len()].join(", ");
+        let sql = format!(
+            "DELETE FROM user_gacha_pity WHERE user_id = ? AND -pity_group IN ({placeholders})"
+        );
+        let mut query = sqlx::query(&sql).bind(user_id);
+        for pool_id in &group.pool_ids {
+            query = query.bind(pool_id);
+        }
+        query.execute(&mut **tx).await?;
+    }
+
     sqlx::query(
         r#"
-        INSERT INTO user_gacha_state (user_id, pool_id, pity_6, up_guaranteed)
-        VALUES (?, ?, ?, ?)
-        ON CONFLICT(user_id, pool_id)
+        INSERT INTO user_gacha_pity (
+            user_id, pity_group, pity_6, up_guaranteed, summon_count, last_pull_at
+        )
+        VALUES (?, ?, ?, ?, ?, ?)
+        ON CONFLICT(user_id, pity_group)
         DO UPDATE SET
             pity_6 = excluded.pity_6,
-            up_guaranteed = excluded.up_guaranteed
+            up_guaranteed = excluded.up_guaranteed,
+            summon_count = excluded.summon_count,
+            last_pull_at = excluded.last_pull_at
         "#,
     )
     .bind(user_id)
-    .bind(pool_id)
+    .bind(group.id)
     .bind(gacha.pity_6 as i32)
     .bind(gacha.up_guaranteed)
-    .execute(pool)
+    .bind(summon_count)
+    .bind(common::time::ServerTime::now_sec_i32())
+    .execute(&mut **tx)
     .await?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::gacha::RateModel;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn pool(rates: RateModel) -> GachaPool {
+        GachaPool {
+            six_up: vec![3003],
+            six_normal: vec![3004],
+            five_up: Vec::new(),
+            five_normal: vec![3005],
+            four: vec![3006],
+            three: vec![3007],
+            two: vec![3008],
+            lucky_bags: Vec::new(),
+            rates,
+        }
+    }
+
+    #[test]
+    fn dry_ten_pull_ends_on_the_guaranteed_star() {
+        let rates = RateModel {
+            rarity: vec![(6, 0.0), (5, 0.0), (2, 1.0)],
+            ..RateModel::default()
+        };
+        let pool = pool(rates);
+        let mut gacha = GachaState {
+            pity_6: 0,
+            up_guaranteed: false,
+        };
+
+        let pulls = gacha
+            .ten_pull(BannerType::RateUp, &pool, &mut StdRng::seed_from_u64(7))
+            .unwrap();
+        let rares: Vec<u8> = pulls.iter().map(GachaResult::rare).collect();
+        assert_eq!(rares, [2, 2, 2, 2, 2, 2, 2, 2, 2, 5]);
+        assert_eq!(gacha.pity_6, 10);
+    }
+
+    #[test]
+    fn hard_pity_lands_a_six_star() {
+        let rates = RateModel {
+            rarity: vec![(6, 0.0), (2, 1.0)],
+            hard_pity: 3,
+            ..RateModel::default()
+        };
+        let pool = pool(rates);
+        let mut gacha = GachaState {
+            pity_6: 0,
+            up_guaranteed: false,
+        };
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let pulls: Vec<u8> = (0..3)
+            .map(|_| {
+                gacha
+                    .single_pull(BannerType::Standard, &pool, &mut rng, None)
+                    .unwrap()
+                    .rare()
+            })
+            .collect();
+        assert_eq!(pulls, [2, 2, 6]);
+        assert_eq!(gacha.pity_6, 0);
+    }
+
+    #[test]
+    fn empty_lists_fall_back_to_the_other_one() {
+        let rates = RateModel {
+            rarity: vec![(6, 1.0)],
+            ..RateModel::default()
+        };
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut gacha = GachaState {
+            pity_6: 0,
+            up_guaranteed: false,
+        };
+
+        let mut no_normals = pool(rates.clone());
+        no_normals.six_normal.clear();
+        for _ in 0..10 {
+            let pull = gacha.single_pull(BannerType::RateUp, &no_normals, &mut rng, None);
+            assert!(matches!(
+                pull,
+                Some(GachaResult::Hero { hero_id: 3003, .. })
+            ));
+        }
+
+        let mut no_ups = pool(rates.clone());
+        no_ups.six_up.clear();
+        let pull = gacha.single_pull(BannerType::Ripple, &no_ups, &mut rng, None);
+        assert!(matches!(
+            pull,
+            Some(GachaResult::Hero {
+                hero_id: 3004,
+                is_up: false,
+                ..
+            })
+        ));
+
+        no_ups.six_normal.clear();
+        assert!(
+            gacha
+                .single_pull(BannerType::Ripple, &no_ups, &mut rng, None)
+                .is_none()
+        );
+    }
+}
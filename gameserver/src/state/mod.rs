@@ -5,18 +5,27 @@ mod connection;
 mod gacha;
 mod packet;
 mod player;
+mod session;
 
 pub use app::AppState;
 pub use battle::{
-    BattleContext, create_battle, default_max_ap, end_fight::send_end_fight_push,
-    generate_auto_opers, generate_initial_deck, rewards::generate_dungeon_rewards,
-    simulator::BattleSimulator,
+    BattleContext, cloth::ClothState, create_battle, data::ExcelData, default_max_ap,
+    end_fight::end_fight_push, entity_builder::entity_detail, fight_builder::battle_rules,
+    fight_builder::cloth_level, finish::finish_fight, generate_auto_opers, generate_initial_deck,
+    outcome::FightResult, outcome::MAX_STARS, replay::REPLAY_VERSION, replay::replay_battle,
+    replay::run_rounds, rewards::clear_stars, rewards::generate_dungeon_rewards,
+    round_builder::build_initial_round, simulator::BattleSimulator, tower::TowerBattle,
+    tower::tower_score, tower::update_tower_progress,
 };
 
 pub use connection::ActiveBattle;
-pub use connection::ConnectionContext;
+pub use connection::{ConnectionContext, OUTBOUND_QUEUE_SIZE};
 pub use gacha::{
-    BannerType, GachaResult, GachaState, build_gacha, load_gacha_state, save_gacha_state,
+    BannerType, GachaPool, GachaResult, GachaState, MATERIAL_CURRENCY, MATERIAL_ITEM, Material,
+    PityGroup, ProgressReward, RateModel, SummonCost, build_gacha, changed_ids,
+    duplicate_materials, grant_material, load_gacha_state, parse_progress_rewards,
+    save_gacha_state, six_star_heroes,
 };
 pub use packet::CommandPacket;
 pub use player::PlayerState;
+pub use session::{SequenceCheck, SessionState};
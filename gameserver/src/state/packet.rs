@@ -1,6 +1,7 @@
+use crate::packet::ServerPacket;
 use sonettobuf::CmdId;
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub enum CommandPacket {
     Reply {
         cmd_id: CmdId,
@@ -15,3 +16,43 @@ pub enum CommandPacket {
         down_tag: u8,
     },
 }
+
+impl CommandPacket {
+    pub fn down_tag(&self) -> u8 {
+        match self {
+            CommandPacket::Reply { down_tag, .. } | CommandPacket::Push { down_tag, .. } => {
+                *down_tag
+            }
+        }
+    }
+
+    /// Wire form, pushes go out with up_tag 255
+    pub fn into_server_packet(self) -> ServerPacket {
+        match self {
+            CommandPacket::Reply {
+                cmd_id,
+                body,
+                result_code,
+                up_tag,
+                down_tag,
+            } => ServerPacket {
+                cmd_id: cmd_id as i16,
+                result_code: result_code as u16,
+                up_tag,
+                down_tag,
+                data: body,
+            },
+            CommandPacket::Push {
+                cmd_id,
+                body,
+                down_tag,
+            } => ServerPacket {
+                cmd_id: cmd_id as i16,
+                result_code: 0,
+                up_tag: 255,
+                down_tag,
+                data: body,
+            },
+        }
+    }
+}
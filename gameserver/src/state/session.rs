@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+
+use super::CommandPacket;
+
+/// down_tag is 7 bits on the wire, 255 is reserved for fixed tag replies
+pub const DOWN_TAG_MASK: u8 = 0x7F;
+pub const FIXED_DOWN_TAG: u8 = 255;
+
+/// One full down_tag cycle, older packets can't be told apart by the client anyway
+const RESEND_BUFFER_SIZE: usize = DOWN_TAG_MASK as usize + 1;
+/// How many upstream sequences we remember to spot resent requests
+const SEQUENCE_WINDOW: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceCheck {
+    /// Next expected sequence (or the first one we see)
+    InOrder,
+    /// Same sequence and up_tag as a request we already processed (client resend)
+    Duplicate,
+    /// Sequence we haven't seen but not the next one either
+    OutOfOrder { expected: i32 },
+}
+
+/// Per-session protocol bookkeeping: down_tag allocation, upstream sequence
+/// tracking and the buffer of recent downstream packets used for reconnect replay
+#[derive(Debug, Clone, Default)]
+pub struct SessionState {
+    next_down_tag: u8,
+    last_up_sequence: Option<i32>,
+    seen_sequences: VecDeque<(i32, u8)>,
+    resend_buffer: VecDeque<CommandPacket>,
+    /// What the previous connection sent before this one took over. Kept apart so a
+    /// reconnect from one of its tags doesn't replay what this socket already delivered
+    taken_over: VecDeque<CommandPacket>,
+}
+
+impl SessionState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reserve_down_tag(&mut self) -> u8 {
+        let current = self.next_down_tag & DOWN_TAG_MASK;
+        self.next_down_tag = (self.next_down_tag + 1) & DOWN_TAG_MASK;
+        current
+    }
+
+    /// Tag the next downstream packet will get, i.e. where a replay would start
+    pub fn peek_down_tag(&self) -> u8 {
+        self.next_down_tag & DOWN_TAG_MASK
+    }
+
+    /// Classify an upstream sequence and remember it
+    pub fn check_up_sequence(&mut self, sequence: i32, up_tag: u8) -> SequenceCheck {
+        if self.seen_sequences.contains(&(sequence, up_tag)) {
+            return SequenceCheck::Duplicate;
+        }
+
+        let check = match self.last_up_sequence {
+            Some(last) if sequence != last.wrapping_add(1) => SequenceCheck::OutOfOrder {
+                expected: last.wrapping_add(1),
+            },
+            _ => SequenceCheck::InOrder,
+        };
+
+        if self.seen_sequences.len() == SEQUENCE_WINDOW {
+            self.seen_sequences.pop_front();
+        }
+        self.seen_sequences.push_back((sequence, up_tag));
+        self.last_up_sequence = Some(
+            self.last_up_sequence
+                .map_or(sequence, |last| last.max(sequence)),
+        );
+
+        check
+    }
+
+    /// Keep a copy of a downstream packet for replay, fixed tag packets are never replayed
+    pub fn record_sent(&mut self, packet: &CommandPacket) {
+        if packet.down_tag() == FIXED_DOWN_TAG {
+            return;
+        }
+
+        // both buffers share one tag cycle, the previous connection's packets go first
+        if self.resend_buffer.len() + self.taken_over.len() == RESEND_BUFFER_SIZE
+            && self.taken_over.pop_front().is_none()
+        {
+            self.resend_buffer.pop_front();
+        }
+        self.resend_buffer.push_back(packet.clone());
+    }
+
+    /// Oldest down_tag still in the resend buffer
+    pub fn resend_start_tag(&self) -> u8 {
+        self.taken_over
+            .front()
+            .or_else(|| self.resend_buffer.front())
+            .map(|p| p.down_tag())
+            .unwrap_or_else(|| self.peek_down_tag())
+    }
+
+    /// Everything sent after `last_received`. None when the client has nothing
+    /// or a tag we no longer hold, it can't be caught up and has to log in again.
+    /// A tag from before a takeover only replays what the previous connection sent
+    pub fn packets_after(&self, last_received: Option<u8>) -> Option<Vec<CommandPacket>> {
+        let tag = last_received?;
+
+        // nothing replayable was sent since the client's last packet
+        let last_sent = self.peek_down_tag().wrapping_sub(1) & DOWN_TAG_MASK;
+        if self.resend_buffer.is_empty() && self.taken_over.is_empty() && tag == last_sent {
+            return Some(Vec::new());
+        }
+
+        let buffer = if self.resend_buffer.iter().any(|p| p.down_tag() == tag) {
+            &self.resend_buffer
+        } else {
+            &self.taken_over
+        };
+        let start = buffer.iter().rposition(|p| p.down_tag() == tag)? + 1;
+
+        Some(buffer.iter().skip(start).cloned().collect())
+    }
+
+    /// Most recent reply sent for `up_tag` on this connection, used to answer
+    /// duplicate requests
+    pub fn last_reply_for(&self, up_tag: u8) -> Option<CommandPacket> {
+        self.resend_buffer
+            .iter()
+            .rev()
+            .find(|p| matches!(p, CommandPacket::Reply { up_tag: tag, .. } if *tag == up_tag))
+            .cloned()
+    }
+
+    /// Carry down_tag and replay state over from a previous connection of the same player.
+    /// Upstream sequences restart with the new socket so they are not carried.
+    pub fn resume_from(&mut self, previous: &SessionState) {
+        self.next_down_tag = previous.next_down_tag;
+        self.taken_over = previous
+            .taken_over
+            .iter()
+            .chain(&previous.resend_buffer)
+            .cloned()
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sonettobuf::CmdId;
+
+    fn send_pushes(session: &mut SessionState, count: usize) {
+        for _ in 0..count {
+            let packet = CommandPacket::Push {
+                cmd_id: CmdId::HeroHeroUpdatePushCmd,
+                body: Vec::new(),
+                down_tag: session.reserve_down_tag(),
+            };
+            session.record_sent(&packet);
+        }
+    }
+
+    #[test]
+    fn replay_starts_after_the_clients_last_tag() {
+        let mut session = SessionState::new();
+        send_pushes(&mut session, 5);
+
+        let tags: Vec<u8> = session
+            .packets_after(Some(2))
+            .unwrap()
+            .iter()
+            .map(CommandPacket::down_tag)
+            .collect();
+        assert_eq!(tags, [3, 4]);
+        assert_eq!(session.packets_after(Some(4)).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn takeover_replays_only_what_the_previous_connection_sent() {
+        let mut previous = SessionState::new();
+        send_pushes(&mut previous, 5);
+
+        let mut session = SessionState::new();
+        session.resume_from(&previous);
+        // login pushes on the new socket
+        send_pushes(&mut session, 3);
+
+        let tags = |after: u8| -> Vec<u8> {
+            session
+                .packets_after(Some(after))
+                .unwrap()
+                .iter()
+                .map(CommandPacket::down_tag)
+                .collect()
+        };
+        assert_eq!(tags(2), [3, 4]);
+        assert_eq!(tags(5), [6, 7]);
+        assert_eq!(session.resend_start_tag(), 0);
+    }
+
+    #[test]
+    fn unknown_tag_cant_be_resumed() {
+        let mut session = SessionState::new();
+        send_pushes(&mut session, 5);
+
+        assert!(session.packets_after(None).is_none());
+        assert!(session.packets_after(Some(9)).is_none());
+        assert!(
+            SessionState::new()
+                .packets_after(Some(DOWN_TAG_MASK))
+                .is_some()
+        );
+    }
+}
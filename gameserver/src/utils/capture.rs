@@ -0,0 +1,122 @@
+//! Optional per-connection packet capture, enabled with `[capture]` in Config.toml.
+//! Every frame in both directions becomes one JSON line, the testclient `capture`
+//! tool decodes and replays these files.
+
+use crate::error::AppError;
+use crate::packet::{ClientPacket, ServerPacket};
+use common::time::ServerTime;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+/// One captured frame, header fields as they were on the wire and the body as hex
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapturedFrame {
+    pub time: i64,
+    pub dir: Direction,
+    pub cmd_id: i16,
+    /// Client frames only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<i32>,
+    pub up_tag: u8,
+    /// Server frames only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub down_tag: Option<u8>,
+    /// Server frames only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result_code: Option<u16>,
+    pub body: String,
+}
+
+impl CapturedFrame {
+    pub fn up(packet: &ClientPacket) -> Self {
+        Self {
+            time: ServerTime::now_ms(),
+            dir: Direction::Up,
+            cmd_id: packet.cmd_id,
+            sequence: Some(packet.sequence),
+            up_tag: packet.up_tag,
+            down_tag: None,
+            result_code: None,
+            body: hex::encode(&packet.data),
+        }
+    }
+
+    pub fn down(packet: &ServerPacket) -> Self {
+        Self {
+            time: ServerTime::now_ms(),
+            dir: Direction::Down,
+            cmd_id: packet.cmd_id,
+            sequence: None,
+            up_tag: packet.up_tag,
+            down_tag: Some(packet.down_tag),
+            result_code: Some(packet.result_code),
+            body: hex::encode(&packet.data),
+        }
+    }
+
+    pub fn body_bytes(&self) -> Result<Vec<u8>, AppError> {
+        hex::decode(&self.body).map_err(|e| AppError::Custom(format!("bad capture body: {}", e)))
+    }
+}
+
+/// Appends frames of one connection to `<directory>/<unix ms>-<ip>_<port>.jsonl`.
+/// Every line is flushed right away so a crash still leaves a usable file.
+pub struct CaptureRecorder {
+    path: PathBuf,
+    file: BufWriter<File>,
+}
+
+impl CaptureRecorder {
+    pub fn create(directory: &Path, peer: SocketAddr) -> Result<Self, AppError> {
+        std::fs::create_dir_all(directory)?;
+
+        let name = format!(
+            "{}-{}_{}.jsonl",
+            ServerTime::now_ms(),
+            peer.ip().to_string().replace(':', "-"),
+            peer.port()
+        );
+        let path = directory.join(name);
+        let file = BufWriter::new(File::create(&path)?);
+
+        Ok(Self { path, file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&mut self, frame: &CapturedFrame) -> Result<(), AppError> {
+        serde_json::to_writer(&mut self.file, frame)?;
+        self.file.write_all(b"\n")?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads a capture file back, blank lines are skipped
+pub fn read_capture(path: &Path) -> Result<Vec<CapturedFrame>, AppError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut frames = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        frames.push(serde_json::from_str(&line)?);
+    }
+
+    Ok(frames)
+}
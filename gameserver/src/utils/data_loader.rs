@@ -48,7 +48,7 @@ macro_rules! send_reply {
 #[macro_export]
 macro_rules! load_message {
     ($msg_type:ty, $path:expr) => {
-        $crate::data_loader::GameDataLoader::load_struct::<$msg_type>($path).map_err(|e| {
+        $crate::utils::data_loader::GameDataLoader::load_struct::<$msg_type>($path).map_err(|e| {
             $crate::error::AppError::Custom(format!(
                 "Failed to load {} from {}: {}",
                 stringify!($msg_type),
@@ -59,6 +59,28 @@ macro_rules! load_message {
     };
 }
 
+/// CmdHandler that answers with a reply loaded from JSON
+/// Usage: static_reply!(GetBpInfo, CmdId::GetBpInfoCmd, GetBpInfoRequest => GetBpInfoReply, "bp/bp_info.json");
+#[macro_export]
+macro_rules! static_reply {
+    ($name:ident, $cmd_id:expr, $request:ty => $reply:ty, $path:expr) => {
+        pub struct $name;
+
+        impl $crate::handler::CmdHandler for $name {
+            const CMD: ::sonettobuf::CmdId = $cmd_id;
+            type Request = $request;
+            type Reply = $reply;
+
+            async fn handle(
+                _session: &mut $crate::handler::Session,
+                _request: Self::Request,
+            ) -> Result<Self::Reply, $crate::error::AppError> {
+                $crate::load_message!($reply, $path)
+            }
+        }
+    };
+}
+
 #[allow(dead_code)]
 pub struct GameDataLoader;
 
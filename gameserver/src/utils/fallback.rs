@@ -0,0 +1,164 @@
+use crate::error::{AppError, CmdError};
+use crate::state::{AppState, ConnectionContext};
+use common::{command_config, config::UnhandledCmdPolicy, data_directory};
+use serde::Deserialize;
+use sonettobuf::CmdId;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Canned reply for a cmd we don't implement yet
+/// Lives at `static/<CmdName>.json`, e.g. `static/GetAct186InfoCmd.json`:
+/// { "resultCode": 0, "body": "0a0208011001" }   (body = hex encoded reply message)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CannedReply {
+    #[serde(default)]
+    pub result_code: i16,
+    #[serde(default)]
+    pub body: String,
+}
+
+impl CannedReply {
+    pub fn path(cmd_id: CmdId) -> std::path::PathBuf {
+        data_directory().join(format!("{}.json", cmd_id.as_str_name()))
+    }
+
+    pub fn load(cmd_id: CmdId) -> Result<Option<Self>, AppError> {
+        let path = Self::path(cmd_id);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let json = std::fs::read_to_string(&path)?;
+        Ok(Some(serde_json::from_str(&json)?))
+    }
+
+    pub fn decode_body(&self) -> Result<Vec<u8>, AppError> {
+        hex::decode(self.body.trim())
+            .map_err(|e| AppError::Custom(format!("invalid canned reply body: {}", e)))
+    }
+}
+
+/// Answer a cmd that has no handler according to `[commands] unhandled_policy`
+pub async fn reply_unhandled(
+    ctx: Arc<Mutex<ConnectionContext>>,
+    cmd_id: CmdId,
+    up_tag: u8,
+) -> Result<(), AppError> {
+    let cfg = command_config();
+
+    let state = {
+        let ctx_guard = ctx.lock().await;
+        ctx_guard.state.clone()
+    };
+
+    let count = state.record_unhandled_cmd(cmd_id);
+    tracing::warn!("Unhandled Cmd: {:?} (seen {} times)", cmd_id, count);
+
+    if let Some(report) = &cfg.unhandled_report
+        && let Err(e) = write_unhandled_report(&state, report).await
+    {
+        tracing::warn!("Failed to write unhandled cmd report: {}", e);
+    }
+
+    match cfg.unhandled_policy {
+        UnhandledCmdPolicy::Disconnect => {
+            return Err(AppError::Cmd(CmdError::UnhandledCmd(cmd_id)));
+        }
+        UnhandledCmdPolicy::StaticJson => {
+            if let Some((body, result_code)) = load_canned_body(cmd_id) {
+                tracing::info!("Serving canned reply for {:?}", cmd_id);
+                let mut ctx_guard = ctx.lock().await;
+                ctx_guard
+                    .send_empty_reply(cmd_id, body, result_code, up_tag)
+                    .await?;
+                return Ok(());
+            }
+        }
+        UnhandledCmdPolicy::EmptyReply => {}
+    }
+
+    let mut ctx_guard = ctx.lock().await;
+    ctx_guard
+        .send_empty_reply(cmd_id, Vec::new(), cfg.unhandled_result_code, up_tag)
+        .await?;
+
+    Ok(())
+}
+
+/// Turn a failed handler into an error reply for its up_tag instead of dropping the socket.
+/// `CmdError::ResultCode` picks the result code. Only socket errors are passed back up
+/// to close the connection.
+pub async fn reply_error(
+    ctx: Arc<Mutex<ConnectionContext>>,
+    cmd_id: CmdId,
+    up_tag: u8,
+    err: AppError,
+) -> Result<(), AppError> {
+    let result_code = match err {
+        AppError::Io(_) => return Err(err),
+        AppError::Cmd(CmdError::ResultCode(code)) => {
+            tracing::info!("{:?} answered with result code {}", cmd_id, code);
+            code
+        }
+        AppError::NotLoggedIn => {
+            tracing::warn!("Rejected {:?} before login", cmd_id);
+            command_config().error_result_code
+        }
+        _ => {
+            tracing::error!("Handler for {:?} failed: {}", cmd_id, err);
+            command_config().error_result_code
+        }
+    };
+
+    let mut ctx_guard = ctx.lock().await;
+
+    // handler already answered (login_error etc), don't send a second reply
+    if ctx_guard.has_pending_reply(up_tag) {
+        return Ok(());
+    }
+
+    ctx_guard
+        .send_empty_reply(cmd_id, Vec::new(), result_code, up_tag)
+        .await?;
+
+    Ok(())
+}
+
+fn load_canned_body(cmd_id: CmdId) -> Option<(Vec<u8>, i16)> {
+    let canned = match CannedReply::load(cmd_id) {
+        Ok(Some(canned)) => canned,
+        Ok(None) => return None,
+        Err(e) => {
+            tracing::warn!("Failed to load canned reply for {:?}: {}", cmd_id, e);
+            return None;
+        }
+    };
+
+    match canned.decode_body() {
+        Ok(body) => Some((body, canned.result_code)),
+        Err(e) => {
+            tracing::warn!("Failed to load canned reply for {:?}: {}", cmd_id, e);
+            None
+        }
+    }
+}
+
+async fn write_unhandled_report(state: &AppState, path: &Path) -> Result<(), AppError> {
+    let report: Vec<_> = state
+        .unhandled_cmd_counts()
+        .into_iter()
+        .map(|(cmd_id, count)| {
+            serde_json::json!({
+                "cmd": cmd_id.as_str_name(),
+                "cmdId": cmd_id as i32,
+                "count": count,
+            })
+        })
+        .collect();
+
+    let json = serde_json::to_string_pretty(&report)?;
+    tokio::fs::write(path, json).await?;
+    Ok(())
+}
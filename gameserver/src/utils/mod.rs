@@ -1,3 +1,5 @@
+pub mod capture;
 pub mod common;
 pub mod data_loader;
+pub mod fallback;
 pub mod push;
@@ -1,9 +1,11 @@
 use crate::error::AppError;
 use crate::state::ConnectionContext;
-use database::db::game::{items, red_dots};
+use database::db::game::{currencies, items, red_dots};
 use sonettobuf::{
-    CmdId, EndDungeonPush, ItemChangePush, MaterialChangePush, MaterialData, UpdateRedDotPush,
+    CmdId, CurrencyChangePush, EndDungeonPush, ItemChangePush, MaterialChangePush, MaterialData,
+    UpdateRedDotPush,
 };
+use sqlx::SqlitePool;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -12,24 +14,10 @@ pub async fn send_red_dot_push(
     user_id: i64,
     define_ids: Option<Vec<i32>>,
 ) -> Result<(), AppError> {
-    let red_dot_records = {
-        let ctx_guard = ctx.lock().await;
-        match define_ids {
-            Some(ids) => {
-                red_dots::red_dots::get_red_dots_by_defines(&ctx_guard.state.db, user_id, &ids)
-                    .await?
-            }
-            None => red_dots::red_dots::get_red_dots(&ctx_guard.state.db, user_id).await?,
-        }
-    };
+    let pool = ctx.lock().await.state.db.clone();
 
-    if !red_dot_records.is_empty() {
-        let groups = red_dots::red_dots::group_red_dots(red_dot_records);
+    if let Some(push) = red_dot_push(&pool, user_id, define_ids).await? {
         let mut ctx_guard = ctx.lock().await;
-        let push = UpdateRedDotPush {
-            red_dot_infos: groups.into_iter().map(Into::into).collect(),
-            replace_all: Some(true),
-        };
         ctx_guard
             .send_push(CmdId::UpdateRedDotPushCmd, push)
             .await?;
@@ -38,40 +26,37 @@ pub async fn send_red_dot_push(
     Ok(())
 }
 
+/// The player's red dots of `define_ids` (all of them for None), None when there are none
+pub async fn red_dot_push(
+    pool: &SqlitePool,
+    user_id: i64,
+    define_ids: Option<Vec<i32>>,
+) -> Result<Option<UpdateRedDotPush>, AppError> {
+    let red_dot_records = match define_ids {
+        Some(ids) => red_dots::red_dots::get_red_dots_by_defines(pool, user_id, &ids).await?,
+        None => red_dots::red_dots::get_red_dots(pool, user_id).await?,
+    };
+
+    if red_dot_records.is_empty() {
+        return Ok(None);
+    }
+
+    let groups = red_dots::red_dots::group_red_dots(red_dot_records);
+    Ok(Some(UpdateRedDotPush {
+        red_dot_infos: groups.into_iter().map(Into::into).collect(),
+        replace_all: Some(true),
+    }))
+}
+
 pub async fn send_item_change_push(
     ctx: Arc<Mutex<ConnectionContext>>,
     user_id: i64,
     changed_item_ids: Vec<u32>,
 ) -> Result<(), AppError> {
-    if changed_item_ids.is_empty() {
-        return Ok(());
-    }
-
-    let (items_list, power_items_list, insight_items_list) = {
-        let ctx_guard = ctx.lock().await;
-        let pool = &ctx_guard.state.db;
-
-        let mut items = Vec::new();
-        for item_id in &changed_item_ids {
-            if let Some(item) = items::get_item(pool, user_id, *item_id).await? {
-                items.push(item);
-            }
-        }
+    let pool = ctx.lock().await.state.db.clone();
 
-        let power_items = items::get_all_power_items(pool, user_id).await?;
-
-        let insight_items = items::get_all_insight_items(pool, user_id).await?;
-
-        (items, power_items, insight_items)
-    };
-
-    if !items_list.is_empty() || !power_items_list.is_empty() || !insight_items_list.is_empty() {
+    if let Some(push) = item_change_push(&pool, user_id, changed_item_ids).await? {
         let mut ctx_guard = ctx.lock().await;
-        let push = ItemChangePush {
-            items: items_list.into_iter().map(Into::into).collect(),
-            power_items: power_items_list.into_iter().map(Into::into).collect(),
-            insight_items: insight_items_list.into_iter().map(Into::into).collect(),
-        };
         ctx_guard
             .send_push(CmdId::ItemChangePushCmd, push.clone())
             .await?;
@@ -87,6 +72,79 @@ pub async fn send_item_change_push(
     Ok(())
 }
 
+/// The changed items with every power and insight item, None when nothing changed
+pub async fn item_change_push(
+    pool: &SqlitePool,
+    user_id: i64,
+    changed_item_ids: Vec<u32>,
+) -> Result<Option<ItemChangePush>, AppError> {
+    if changed_item_ids.is_empty() {
+        return Ok(None);
+    }
+
+    let mut items_list = Vec::new();
+    for item_id in &changed_item_ids {
+        if let Some(item) = items::get_item(pool, user_id, *item_id).await? {
+            items_list.push(item);
+        }
+    }
+
+    let power_items_list = items::get_all_power_items(pool, user_id).await?;
+
+    let insight_items_list = items::get_all_insight_items(pool, user_id).await?;
+
+    if items_list.is_empty() && power_items_list.is_empty() && insight_items_list.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(ItemChangePush {
+        items: items_list.into_iter().map(Into::into).collect(),
+        power_items: power_items_list.into_iter().map(Into::into).collect(),
+        insight_items: insight_items_list.into_iter().map(Into::into).collect(),
+    }))
+}
+
+pub async fn send_currency_change_push(
+    ctx: Arc<Mutex<ConnectionContext>>,
+    user_id: i64,
+    changed_currency_ids: Vec<i32>,
+) -> Result<(), AppError> {
+    let pool = ctx.lock().await.state.db.clone();
+
+    let Some(push) = currency_change_push(&pool, user_id, changed_currency_ids).await? else {
+        return Ok(());
+    };
+
+    let mut ctx_guard = ctx.lock().await;
+    ctx_guard
+        .send_push(CmdId::CurrencyChangePushCmd, push.clone())
+        .await?;
+
+    tracing::info!(
+        "Sent CurrencyChangePush: {} currencies",
+        push.change_currency.len()
+    );
+
+    Ok(())
+}
+
+/// The changed currencies, None when nothing changed
+pub async fn currency_change_push(
+    pool: &SqlitePool,
+    user_id: i64,
+    changed_currency_ids: Vec<i32>,
+) -> Result<Option<CurrencyChangePush>, AppError> {
+    if changed_currency_ids.is_empty() {
+        return Ok(None);
+    }
+
+    let changed = currencies::get_currencies(pool, user_id, &changed_currency_ids).await?;
+
+    Ok(Some(CurrencyChangePush {
+        change_currency: changed.into_iter().map(Into::into).collect(),
+    }))
+}
+
 /// Send material change push (reward notification popup)
 /// Use raw tuples: (material_type, material_id, quantity)
 pub async fn send_material_change_push(
@@ -124,31 +182,34 @@ pub async fn send_material_change_push(
     Ok(())
 }
 
-pub async fn send_end_dungeon_push(
-    ctx: Arc<Mutex<ConnectionContext>>,
+pub fn end_dungeon_push(
     chapter_id: i32,
     episode_id: i32,
+    star: i32,
     normal_bonus: Vec<(u32, u32, i32)>,
-) -> Result<(), AppError> {
-    let normal_bonus = normal_bonus
-        .into_iter()
-        .map(|(t, id, q)| MaterialData {
-            materil_type: Some(t),
-            materil_id: Some(id),
-            quantity: Some(q),
-        })
-        .collect();
-
-    let push = EndDungeonPush {
+    advanced_bonus: Vec<(u32, u32, i32)>,
+) -> EndDungeonPush {
+    let materials = |bonus: Vec<(u32, u32, i32)>| {
+        bonus
+            .into_iter()
+            .map(|(t, id, q)| MaterialData {
+                materil_type: Some(t),
+                materil_id: Some(id),
+                quantity: Some(q),
+            })
+            .collect()
+    };
+
+    EndDungeonPush {
         chapter_id: Some(chapter_id),
         episode_id: Some(episode_id),
 
         player_exp: Some(0),
-        star: Some(2),
+        star: Some(star),
 
         first_bonus: vec![],
-        normal_bonus,
-        advenced_bonus: vec![],
+        normal_bonus: materials(normal_bonus),
+        advenced_bonus: materials(advanced_bonus),
         addition_bonus: vec![],
         time_first_bonus: vec![],
         drop_bonus: vec![],
@@ -163,18 +224,11 @@ pub async fn send_end_dungeon_push(
         assist_user_id: Some(0),
         assist_nickname: Some(String::new()),
         total_round: Some(0),
-    };
-
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_push(CmdId::DungeonEndDungeonPushCmd, push)
-        .await?;
-
-    Ok(())
+    }
 }
 
-pub async fn send_dungeon_update_push(
-    ctx: Arc<Mutex<ConnectionContext>>,
+#[allow(clippy::too_many_arguments)]
+pub fn dungeon_update_push(
     chapter_id: i32,
     episode_id: i32,
     star: i32,
@@ -183,7 +237,7 @@ pub async fn send_dungeon_update_push(
     chapter_type: i32,        // e.g., 6 for episode chapter
     chapter_today_pass: i32,  // Today's completions for this chapter type
     chapter_today_total: i32, // Today's total attempts for this chapter type
-) -> Result<(), AppError> {
+) -> sonettobuf::DungeonUpdatePush {
     let dungeon_info = sonettobuf::UserDungeon {
         chapter_id: Some(chapter_id),
         episode_id: Some(episode_id),
@@ -201,15 +255,8 @@ pub async fn send_dungeon_update_push(
         today_total_num: Some(chapter_today_total),
     }];
 
-    let push = sonettobuf::DungeonUpdatePush {
+    sonettobuf::DungeonUpdatePush {
         dungeon_info: Some(dungeon_info),
         chapter_type_nums,
-    };
-
-    let mut ctx_guard = ctx.lock().await;
-    ctx_guard
-        .send_push(CmdId::DungeonUpdatePushCmd, push)
-        .await?;
-
-    Ok(())
+    }
 }
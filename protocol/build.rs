@@ -1,4 +1,6 @@
-use std::path::Path;
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
 
 fn main() {
     println!("cargo::rustc-check-cfg=cfg(rust_analyzer)");
@@ -21,4 +23,102 @@ fn main() {
             .compile_protos(&proto_files, &["."])
             .expect("Failed to compile proto files");
     }
+
+    println!("cargo::rerun-if-changed=include/_.rs");
+    let generated = std::fs::read_to_string("include/_.rs").expect("Failed to read include/_.rs");
+    let out = PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("cmd_registry.rs");
+    std::fs::write(out, cmd_registry(&generated)).expect("Failed to write cmd_registry.rs");
+}
+
+type EntrySlot = fn(&CmdEntry) -> &Option<String>;
+
+#[derive(Default)]
+struct CmdEntry {
+    variant: String,
+    request: Option<String>,
+    reply: Option<String>,
+    push: Option<String>,
+}
+
+/// The cmd_id proto keeps the lua names in comments, prost turns them into
+/// `/// / Original: XxxRequest / XxxReply` above every CmdId variant
+fn cmd_registry(generated: &str) -> String {
+    let messages: HashSet<&str> = generated
+        .lines()
+        .filter_map(|l| l.strip_prefix("pub struct "))
+        .filter_map(|l| l.split_whitespace().next())
+        .collect();
+
+    let mut entries = Vec::new();
+    let mut original: Vec<&str> = Vec::new();
+    let mut in_enum = false;
+
+    for line in generated.lines() {
+        if !in_enum {
+            in_enum = line.starts_with("pub enum CmdId ");
+            continue;
+        }
+        if line.starts_with('}') {
+            break;
+        }
+
+        let line = line.trim();
+        if let Some(names) = line.strip_prefix("/// / Original:") {
+            original = names.split('/').map(str::trim).collect();
+            continue;
+        }
+        let Some((variant, _)) = line.split_once(" = ") else {
+            continue;
+        };
+
+        let mut entry = CmdEntry {
+            variant: variant.to_string(),
+            ..Default::default()
+        };
+        let names: Vec<&str> = std::mem::take(&mut original)
+            .into_iter()
+            .filter(|n| messages.contains(n))
+            .collect();
+
+        for (idx, name) in names.iter().enumerate() {
+            let slot = if name.ends_with("Push") || variant.ends_with("PushCmd") {
+                &mut entry.push
+            } else if idx > 0 || name.ends_with("Reply") || name.ends_with("Response") {
+                &mut entry.reply
+            } else {
+                &mut entry.request
+            };
+            slot.get_or_insert_with(|| name.to_string());
+        }
+        entries.push(entry);
+    }
+
+    let mut code = String::from("// @generated by build.rs from include/_.rs\n");
+    let kinds: [(&str, EntrySlot); 3] = [
+        ("request_type", |e| &e.request),
+        ("reply_type", |e| &e.reply),
+        ("push_type", |e| &e.push),
+    ];
+
+    for (func, get) in kinds {
+        writeln!(
+            code,
+            "\npub(crate) fn {func}(cmd_id: CmdId) -> Option<MessageType> {{"
+        )
+        .unwrap();
+        writeln!(code, "    match cmd_id {{").unwrap();
+        for entry in &entries {
+            if let Some(name) = get(entry) {
+                writeln!(
+                    code,
+                    "        CmdId::{} => Some(MessageType::of::<crate::{name}>(\"{name}\")),",
+                    entry.variant
+                )
+                .unwrap();
+            }
+        }
+        writeln!(code, "        _ => None,\n    }}\n}}").unwrap();
+    }
+
+    code
 }
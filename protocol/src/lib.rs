@@ -1,3 +1,5 @@
 pub use prost;
 
+pub mod registry;
+
 include!("../include/_.rs");
@@ -0,0 +1,148 @@
+//! Which message travels with which `CmdId`, generated by build.rs from the
+//! `Original:` comments of the cmd_id proto. Lets tools decode any frame
+//! without knowing its type up front.
+
+use crate::CmdId;
+use prost::{DecodeError, Message};
+use serde::Serialize;
+use std::fmt::{self, Debug};
+
+/// Direction of a body for a given cmd
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    Request,
+    Reply,
+    Push,
+}
+
+/// A decoded message of any type
+pub trait AnyMessage: Debug + Send + Sync {
+    fn to_json(&self) -> serde_json::Value;
+    fn encode_body(&self) -> Vec<u8>;
+}
+
+impl<T> AnyMessage for T
+where
+    T: Message + Serialize + Debug + Send + Sync,
+{
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    fn encode_body(&self) -> Vec<u8> {
+        self.encode_to_vec()
+    }
+}
+
+/// A message type from the registry, `name` is the rust (and original) type name
+#[derive(Clone, Copy)]
+pub struct MessageType {
+    pub name: &'static str,
+    decode: DecodeFn,
+}
+
+type DecodeFn = fn(&[u8]) -> Result<Box<dyn AnyMessage>, DecodeError>;
+
+impl MessageType {
+    fn of<T>(name: &'static str) -> Self
+    where
+        T: Message + Default + Serialize + Debug + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            decode: |body| Ok(Box::new(T::decode(body)?)),
+        }
+    }
+
+    pub fn decode(&self, body: &[u8]) -> Result<Box<dyn AnyMessage>, DecodeError> {
+        (self.decode)(body)
+    }
+
+    pub fn decode_json(&self, body: &[u8]) -> Result<serde_json::Value, DecodeError> {
+        Ok(self.decode(body)?.to_json())
+    }
+}
+
+impl Debug for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+/// Message type for `cmd_id` in the given direction, None if the cmd has no body
+/// of that kind (or the proto doesn't know it)
+pub fn message_type(cmd_id: CmdId, kind: MessageKind) -> Option<MessageType> {
+    match kind {
+        MessageKind::Request => request_type(cmd_id),
+        MessageKind::Reply => reply_type(cmd_id),
+        MessageKind::Push => push_type(cmd_id),
+    }
+}
+
+/// Decode a body, None when there is no registered type for it
+pub fn decode(
+    cmd_id: CmdId,
+    kind: MessageKind,
+    body: &[u8],
+) -> Option<Result<Box<dyn AnyMessage>, DecodeError>> {
+    message_type(cmd_id, kind).map(|t| t.decode(body))
+}
+
+pub fn decode_json(
+    cmd_id: CmdId,
+    kind: MessageKind,
+    body: &[u8],
+) -> Option<Result<serde_json::Value, DecodeError>> {
+    message_type(cmd_id, kind).map(|t| t.decode_json(body))
+}
+
+include!(concat!(env!("OUT_DIR"), "/cmd_registry.rs"));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UpdateActivityPush;
+
+    #[test]
+    fn pairs_request_and_reply() {
+        let request = message_type(CmdId::GetAchievementInfoCmd, MessageKind::Request).unwrap();
+        let reply = message_type(CmdId::GetAchievementInfoCmd, MessageKind::Reply).unwrap();
+        assert_eq!(request.name, "GetAchievementInfoRequest");
+        assert_eq!(reply.name, "GetAchievementInfoReply");
+        assert!(message_type(CmdId::GetAchievementInfoCmd, MessageKind::Push).is_none());
+    }
+
+    #[test]
+    fn push_cmds_only_have_a_push() {
+        let push = message_type(CmdId::UpdateActivityPushCmd, MessageKind::Push).unwrap();
+        assert_eq!(push.name, "UpdateActivityPush");
+        assert!(message_type(CmdId::UpdateActivityPushCmd, MessageKind::Request).is_none());
+        assert!(message_type(CmdId::UpdateActivityPushCmd, MessageKind::Reply).is_none());
+    }
+
+    #[test]
+    fn decodes_body_to_json() {
+        let body = UpdateActivityPush {
+            activity_info: None,
+            time: Some(42),
+        }
+        .encode_to_vec();
+
+        let json = decode_json(CmdId::UpdateActivityPushCmd, MessageKind::Push, &body)
+            .unwrap()
+            .unwrap();
+        assert_eq!(json["time"], 42);
+
+        let message = decode(CmdId::UpdateActivityPushCmd, MessageKind::Push, &body)
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.encode_body(), body);
+        assert!(format!("{message:?}").contains("time: Some(42)"));
+    }
+
+    #[test]
+    fn garbage_body_is_a_decode_error() {
+        let result = decode(CmdId::GetAct109InfoCmd, MessageKind::Reply, &[0xff, 0xff]).unwrap();
+        assert!(result.is_err());
+    }
+}
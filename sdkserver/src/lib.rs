@@ -0,0 +1,36 @@
+use axum::Router;
+use gameserver::state::AppState as GameState;
+use reqwest::Client;
+use std::sync::Arc;
+
+pub mod handlers;
+pub mod middleware;
+pub mod models;
+
+use middleware::crypto::sdk_encryption;
+use middleware::logging::full_logger;
+
+#[derive(Clone)]
+pub struct SdkState {
+    pub http_client: Client,
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    pub sdk: SdkState,
+    pub game: Arc<GameState>,
+}
+
+/// Full SDK router, account routes go through the sdk crypto layer
+pub fn app(state: AppState) -> Router {
+    let with_encryption = handlers::router::account_router()
+        .layer(axum::middleware::from_fn(full_logger))
+        .layer(axum::middleware::from_fn(sdk_encryption));
+
+    let without_encryption = handlers::router::game_router()
+        .merge(handlers::router::jsp_router())
+        .merge(handlers::router::index_router())
+        .layer(axum::middleware::from_fn(full_logger));
+
+    with_encryption.merge(without_encryption).with_state(state)
+}
@@ -2,29 +2,12 @@ use common::{config, excel_data_directory, host, http_port, init_config, init_tr
 use database::{DatabaseSettings, connect_to, run_migrations};
 use gameserver::state::AppState as GameState;
 use reqwest::Client;
+use sdkserver::{AppState, SdkState};
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tracing::info;
 
-mod handlers;
-mod middleware;
-mod models;
-
-use middleware::crypto::sdk_encryption;
-use middleware::logging::full_logger;
-
-#[derive(Clone)]
-pub struct SdkState {
-    pub http_client: Client,
-}
-
-#[derive(Clone)]
-pub struct AppState {
-    pub sdk: SdkState,
-    pub game: Arc<GameState>,
-}
-
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     init_tracing();
@@ -68,17 +51,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         game: Arc::new(GameState::new(db)),
     };
 
-    // Build router
-    let with_encryption = handlers::router::account_router()
-        .layer(axum::middleware::from_fn(full_logger))
-        .layer(axum::middleware::from_fn(sdk_encryption));
-
-    let without_encryption = handlers::router::game_router()
-        .merge(handlers::router::jsp_router())
-        .merge(handlers::router::index_router())
-        .layer(axum::middleware::from_fn(full_logger));
-
-    let app = with_encryption.merge(without_encryption).with_state(state);
+    let app = sdkserver::app(state);
 
     let addr: SocketAddr = format!("{}:{}", host(), http_port()).parse()?;
     info!("HTTP Server listening on http://{}", addr);
@@ -37,6 +37,26 @@ fn swap_each_two_bytes(bytes: &mut Vec<u8>) {
     }
 }
 
+/// Byte swap + gzip, the same transform both ways so clients (and tests) can reuse it
+pub fn encrypt_body(plain: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut raw = plain.to_vec();
+    swap_each_two_bytes(&mut raw);
+
+    let mut encoder = GzEncoder::new(&*raw, Compression::default());
+    let mut encrypted = Vec::with_capacity(raw.len());
+    encoder.read_to_end(&mut encrypted)?;
+    Ok(encrypted)
+}
+
+pub fn decrypt_body(encrypted: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut decoder = GzDecoder::new(encrypted);
+    let mut decompressed = Vec::with_capacity(encrypted.len());
+    decoder.read_to_end(&mut decompressed)?;
+
+    swap_each_two_bytes(&mut decompressed);
+    Ok(decompressed)
+}
+
 pub async fn sdk_encryption(req: Request<Body>, next: Next) -> Response<Body> {
     let method = req.method().clone();
     let uri = req.uri().clone();
@@ -46,18 +66,16 @@ pub async fn sdk_encryption(req: Request<Body>, next: Next) -> Response<Body> {
         .await
         .unwrap_or_else(|_| Bytes::new());
 
-    let mut decoder = GzDecoder::new(&req_body_bytes[..]);
-    let mut decompressed = Vec::with_capacity(req_body_bytes.len());
-
-    if let Err(e) = decoder.read_to_end(&mut decompressed) {
-        tracing::error!("Failed to decompress request: {}", e);
-        return Response::builder()
-            .status(StatusCode::BAD_REQUEST)
-            .body(Body::from("Invalid request body"))
-            .unwrap();
-    }
-
-    swap_each_two_bytes(&mut decompressed);
+    let decompressed = match decrypt_body(&req_body_bytes) {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!("Failed to decompress request: {}", e);
+            return Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Body::from("Invalid request body"))
+                .unwrap();
+        }
+    };
 
     // Build new request with Content-Type header
     let mut req_builder = Request::builder().method(method).uri(uri);
@@ -83,19 +101,16 @@ pub async fn sdk_encryption(req: Request<Body>, next: Next) -> Response<Body> {
         .await
         .unwrap_or_else(|_| Bytes::new());
 
-    let mut raw = res_body_bytes.to_vec();
-    swap_each_two_bytes(&mut raw);
-
-    let mut encoder = GzEncoder::new(&*raw, Compression::default());
-    let mut encrypted = Vec::with_capacity(raw.len());
-
-    if let Err(e) = encoder.read_to_end(&mut encrypted) {
-        tracing::error!("Failed to compress response: {}", e);
-        return Response::builder()
-            .status(StatusCode::INTERNAL_SERVER_ERROR)
-            .body(Body::from("Failed to encode response"))
-            .unwrap();
-    }
+    let encrypted = match encrypt_body(&res_body_bytes) {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!("Failed to compress response: {}", e);
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from("Failed to encode response"))
+                .unwrap();
+        }
+    };
 
     let mut res_builder = Response::builder().status(res_status);
     res_builder = res_headers
@@ -0,0 +1,113 @@
+use anyhow::{Context, bail};
+use common::init_tracing;
+use gameserver::utils::capture::read_capture;
+use std::path::PathBuf;
+use testclient::capture::{frames_to_json, replay};
+use testclient::{Account, Target, TestServer};
+
+const USAGE: &str = "\
+usage: capture decode <capture file>
+       capture replay [options] <capture file>
+
+  decode prints the frames as a JSON array with known bodies decoded, replay
+  resends the client frames against a server and reports result codes that
+  differ from the capture.
+
+  --in-process          replay against a fresh in-process server
+  --sdk <url>           sdkserver base url      (default http://127.0.0.1:21000)
+  --game <host:port>    gameserver address      (default 127.0.0.1:23301)
+  --email <email>       account used for the replay (default replay@sonetto.local)
+  --password <pwd>      password                (default testclient)";
+
+enum Mode {
+    Decode,
+    Replay,
+}
+
+struct Args {
+    mode: Mode,
+    file: PathBuf,
+    in_process: bool,
+    target: Target,
+    account: Account,
+}
+
+fn parse_args() -> anyhow::Result<Args> {
+    let mut args = std::env::args().skip(1);
+    let mode = match args.next().as_deref() {
+        Some("decode") => Mode::Decode,
+        Some("replay") => Mode::Replay,
+        Some("-h" | "--help") => {
+            println!("{USAGE}");
+            std::process::exit(0);
+        }
+        _ => bail!("{}", USAGE),
+    };
+
+    let mut file = None;
+    let mut in_process = false;
+    let mut target = Target {
+        sdk_url: "http://127.0.0.1:21000".into(),
+        game_addr: "127.0.0.1:23301".into(),
+    };
+    let mut account = Account::new("replay@sonetto.local", "testclient");
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .with_context(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--in-process" => in_process = true,
+            "--sdk" => target.sdk_url = value()?,
+            "--game" => target.game_addr = value()?,
+            "--email" => account.email = value()?,
+            "--password" => account.password = value()?,
+            _ if arg.starts_with("--") => bail!("unknown option {}\n\n{}", arg, USAGE),
+            _ => file = Some(PathBuf::from(arg)),
+        }
+    }
+
+    Ok(Args {
+        mode,
+        file: file.with_context(|| format!("no capture file\n\n{USAGE}"))?,
+        in_process,
+        target,
+        account,
+    })
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    init_tracing();
+
+    let args = parse_args()?;
+    let frames =
+        read_capture(&args.file).with_context(|| format!("reading {}", args.file.display()))?;
+
+    match args.mode {
+        Mode::Decode => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&frames_to_json(&frames))?
+            );
+        }
+        Mode::Replay => {
+            let (_server, target) = if args.in_process {
+                let server = TestServer::start().await?;
+                let target = server.target();
+                (Some(server), target)
+            } else {
+                (None, args.target)
+            };
+
+            let report = replay(&frames, &target, &args.account).await?;
+            println!("{report}");
+            if !report.mismatches.is_empty() {
+                std::process::exit(1);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use anyhow::{Context, bail};
+use common::config::{ServerConfig, SummonConfig};
+use common::init_tracing;
+use gameserver::state::{BannerType, GachaResult, GachaState, build_gacha};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::path::PathBuf;
+use testclient::harness::init_game_data;
+
+const USAGE: &str = "\
+usage: gacha_sim [options] <pool id>
+
+  pulls a summon_pool with the server's rate model and prints the empirical
+  distribution next to the pool's base odds. Excel data comes from JSON_DATA_DIR.
+
+  --pulls <n>       pulls to simulate             (default 1000000)
+  --ten             pull in tens so the SR guarantee applies
+  --config <file>   apply that config's [summon] overrides
+  --seed <n>        rng seed                      (default random)";
+
+struct Args {
+    pool_id: i32,
+    pulls: u64,
+    ten: bool,
+    overrides: SummonConfig,
+    seed: Option<u64>,
+}
+
+fn parse_args() -> anyhow::Result<Args> {
+    let mut args = std::env::args().skip(1);
+    let mut pool_id = None;
+    let mut pulls = 1_000_000;
+    let mut ten = false;
+    let mut overrides = SummonConfig::default();
+    let mut seed = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .with_context(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            "--pulls" => pulls = value()?.parse()?,
+            "--ten" => ten = true,
+            "--config" => overrides = ServerConfig::load(PathBuf::from(value()?))?.summon,
+            "--seed" => seed = Some(value()?.parse()?),
+            _ if arg.starts_with("--") => bail!("unknown option {}\n\n{}", arg, USAGE),
+            _ => pool_id = Some(arg.parse().with_context(|| format!("bad pool id {arg}"))?),
+        }
+    }
+
+    Ok(Args {
+        pool_id: pool_id.with_context(|| format!("no pool id\n\n{USAGE}"))?,
+        pulls,
+        ten,
+        overrides,
+        seed,
+    })
+}
+
+#[derive(Default)]
+struct Tally {
+    pulls: u64,
+    /// index = stars
+    by_star: [u64; 7],
+    six_up: u64,
+    drought: u64,
+    longest_drought: u64,
+}
+
+impl Tally {
+    fn add(&mut self, result: &GachaResult) {
+        let rare = result.rare();
+        let is_up = matches!(result, GachaResult::Hero { is_up: true, .. });
+
+        self.pulls += 1;
+        self.by_star[usize::from(rare).min(6)] += 1;
+        self.drought += 1;
+        if rare == 6 {
+            self.six_up += u64::from(is_up);
+            self.longest_drought = self.longest_drought.max(self.drought);
+            self.drought = 0;
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    init_tracing();
+
+    let args = parse_args()?;
+    init_game_data().map_err(anyhow::Error::msg)?;
+
+    let game_data = data::exceldb::get();
+    if !game_data.summon_pool.iter().any(|p| p.id == args.pool_id) {
+        bail!("summon_pool {} not found", args.pool_id);
+    }
+
+    let pool = build_gacha(args.pool_id, None, &args.overrides).await?;
+    let banner_type = if pool.six_up.is_empty() {
+        BannerType::Standard
+    } else {
+        BannerType::RateUp
+    };
+
+    let mut rng = match args.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let mut gacha = GachaState {
+        pity_6: 0,
+        up_guaranteed: false,
+    };
+    let mut tally = Tally::default();
+
+    while tally.pulls < args.pulls {
+        let results = if args.ten {
+            gacha.ten_pull(banner_type, &pool, &mut rng)
+        } else {
+            gacha
+                .single_pull(banner_type, &pool, &mut rng, None)
+                .map(|result| vec![result])
+        };
+        let Some(results) = results else {
+            bail!("pool {} has no hero for a rolled star", args.pool_id);
+        };
+        for result in &results {
+            tally.add(result);
+        }
+    }
+
+    println!(
+        "pool {}: {} pulls in {}",
+        args.pool_id,
+        tally.pulls,
+        if args.ten { "tens" } else { "singles" }
+    );
+    println!("star      base  observed     count");
+    for star in (2..=6).rev() {
+        let count = tally.by_star[star];
+        println!(
+            "{}*    {:>6.2}%   {:>6.2}%  {:>8}",
+            star,
+            pool.rates.rarity_chance(star as u8) * 100.0,
+            count as f64 * 100.0 / tally.pulls as f64,
+            count
+        );
+    }
+
+    let sixes = tally.by_star[6];
+    if sixes > 0 {
+        println!(
+            "6* every {:.1} pulls, {:.1}% of them UP, longest drought {}",
+            tally.pulls as f64 / sixes as f64,
+            tally.six_up as f64 * 100.0 / sixes as f64,
+            tally.longest_drought
+        );
+    }
+
+    Ok(())
+}
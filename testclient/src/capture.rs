@@ -0,0 +1,197 @@
+//! Working with the gameserver's `[capture]` files: dump them as readable JSON
+//! and replay the client half against a fresh server.
+
+use crate::game::{GameClient, PUSH_UP_TAG, cmd_name};
+use crate::scenario::{Account, Target};
+use crate::sdk::SdkClient;
+use anyhow::Context;
+use gameserver::utils::capture::{CapturedFrame, Direction};
+use serde_json::{Value, json};
+use sonettobuf::CmdId;
+use sonettobuf::registry::{self, MessageKind};
+use std::fmt;
+
+/// One JSON object per frame with the command name next to the raw header fields.
+/// Bodies the registry knows are decoded into `message`, the rest stay hex.
+pub fn frames_to_json(frames: &[CapturedFrame]) -> Value {
+    let first = frames.first().map(|f| f.time).unwrap_or_default();
+
+    let frames = frames
+        .iter()
+        .map(|frame| {
+            let mut value = serde_json::to_value(frame).unwrap_or_default();
+            if let Value::Object(map) = &mut value {
+                map.insert("cmd".into(), json!(cmd_name(frame.cmd_id)));
+                map.insert("offsetMs".into(), json!(frame.time - first));
+
+                match decode_body(frame) {
+                    Some(Ok((name, message))) => {
+                        map.remove("body");
+                        map.insert("type".into(), json!(name));
+                        map.insert("message".into(), message);
+                    }
+                    Some(Err(e)) => {
+                        map.insert("decodeError".into(), json!(e));
+                    }
+                    None => {}
+                }
+            }
+            value
+        })
+        .collect();
+
+    Value::Array(frames)
+}
+
+/// Requests going up, replies or pushes (up_tag 255) coming down
+fn message_kind(frame: &CapturedFrame) -> MessageKind {
+    match frame.dir {
+        Direction::Up => MessageKind::Request,
+        Direction::Down if frame.up_tag == PUSH_UP_TAG => MessageKind::Push,
+        Direction::Down => MessageKind::Reply,
+    }
+}
+
+/// (type name, message) of the frame's body, None for unknown cmds and empty bodies
+/// without a registered type
+fn decode_body(frame: &CapturedFrame) -> Option<Result<(&'static str, Value), String>> {
+    let cmd_id = CmdId::try_from(frame.cmd_id as i32).ok()?;
+    let message_type = registry::message_type(cmd_id, message_kind(frame))?;
+
+    let decoded = frame
+        .body_bytes()
+        .map_err(|e| e.to_string())
+        .and_then(|body| message_type.decode_json(&body).map_err(|e| e.to_string()))
+        .map(|message| (message_type.name, message));
+    Some(decoded)
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub sent: usize,
+    /// Unknown cmd ids, nothing to send them as
+    pub skipped: usize,
+    pub mismatches: Vec<String>,
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} requests sent, {} skipped, {} result mismatches",
+            self.sent,
+            self.skipped,
+            self.mismatches.len()
+        )?;
+        for mismatch in &self.mismatches {
+            write!(f, "\n  {mismatch}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Sends every upstream frame of a capture again, in order, and compares the
+/// result codes with what was recorded. The recorded login is swapped for a
+/// fresh SDK login of `account`, so the replay runs on that account's data.
+pub async fn replay(
+    frames: &[CapturedFrame],
+    target: &Target,
+    account: &Account,
+) -> anyhow::Result<ReplayReport> {
+    let login = SdkClient::new(&target.sdk_url)
+        .login_mail(&account.email, &account.password)
+        .await?;
+    let mut client = GameClient::connect(&target.game_addr).await?;
+    let mut report = ReplayReport::default();
+
+    for (idx, frame) in frames.iter().enumerate() {
+        if frame.dir != Direction::Up {
+            continue;
+        }
+        let Ok(cmd_id) = CmdId::try_from(frame.cmd_id as i32) else {
+            tracing::warn!("Skipping unknown cmd {} at frame {}", frame.cmd_id, idx);
+            report.skipped += 1;
+            continue;
+        };
+
+        // the reply the original session got, if any
+        let recorded = frames[idx + 1..].iter().find(|f| {
+            f.dir == Direction::Down && f.up_tag == frame.up_tag && f.cmd_id == frame.cmd_id
+        });
+        report.sent += 1;
+
+        if cmd_id == CmdId::LoginRequestCmd {
+            client.login(&login).await?;
+            continue;
+        }
+
+        let body = frame.body_bytes()?;
+        let Some(recorded) = recorded else {
+            client.send_raw(cmd_id, body).await?;
+            continue;
+        };
+
+        let reply = client
+            .request_raw(cmd_id, body)
+            .await
+            .with_context(|| format!("replaying frame {} ({})", idx, cmd_name(frame.cmd_id)))?;
+
+        let expected = recorded.result_code.unwrap_or_default();
+        if reply.result_code != expected {
+            report.mismatches.push(format!(
+                "frame {} {}: result_code {} (recorded {})",
+                idx,
+                cmd_name(frame.cmd_id),
+                reply.result_code,
+                expected
+            ));
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sonettobuf::UpdateActivityPush;
+    use sonettobuf::prost::Message;
+
+    fn frame(dir: Direction, cmd_id: CmdId, up_tag: u8, body: &[u8]) -> CapturedFrame {
+        CapturedFrame {
+            time: 0,
+            dir,
+            cmd_id: cmd_id as i16,
+            sequence: None,
+            up_tag,
+            down_tag: None,
+            result_code: None,
+            body: hex::encode(body),
+        }
+    }
+
+    #[test]
+    fn known_bodies_are_decoded() {
+        let push = UpdateActivityPush {
+            activity_info: None,
+            time: Some(42),
+        }
+        .encode_to_vec();
+        let frames = [
+            frame(
+                Direction::Down,
+                CmdId::UpdateActivityPushCmd,
+                PUSH_UP_TAG,
+                &push,
+            ),
+            frame(Direction::Down, CmdId::GetAct109InfoCmd, 3, &[0xff, 0xff]),
+        ];
+
+        let json = frames_to_json(&frames);
+        assert_eq!(json[0]["type"], "UpdateActivityPush");
+        assert_eq!(json[0]["message"]["time"], 42);
+        assert!(json[0].get("body").is_none());
+        assert_eq!(json[1]["body"], "ffff");
+        assert!(json[1]["decodeError"].is_string());
+    }
+}
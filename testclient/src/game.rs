@@ -0,0 +1,175 @@
+use crate::sdk::SdkLogin;
+use anyhow::{Context, bail};
+use futures::{SinkExt, StreamExt};
+use gameserver::codec::{ClientFrameCodec, ServerFrameCodec};
+use gameserver::packet::{ClientPacket, ServerPacket};
+use prost::Message;
+use sonettobuf::CmdId;
+use std::time::Duration;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+/// up_tag the server puts on pushes
+pub const PUSH_UP_TAG: u8 = 255;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Game port client. Replies are matched to requests by up_tag, pushes that
+/// arrive in between are kept until a test asks for them.
+pub struct GameClient {
+    reader: FramedRead<OwnedReadHalf, ServerFrameCodec>,
+    writer: FramedWrite<OwnedWriteHalf, ClientFrameCodec>,
+    sequence: i32,
+    up_tag: u8,
+    timeout: Duration,
+    pushes: Vec<ServerPacket>,
+}
+
+impl GameClient {
+    pub async fn connect(addr: impl ToSocketAddrs) -> anyhow::Result<Self> {
+        let socket = TcpStream::connect(addr)
+            .await
+            .context("connecting to game port")?;
+        let (reader, writer) = socket.into_split();
+
+        Ok(Self {
+            reader: FramedRead::new(reader, ServerFrameCodec::default()),
+            writer: FramedWrite::new(writer, ClientFrameCodec::default()),
+            sequence: 0,
+            up_tag: 0,
+            timeout: DEFAULT_TIMEOUT,
+            pushes: Vec::new(),
+        })
+    }
+
+    /// How long to wait for a reply or push before giving up
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// LoginRequestCmd with the token from the SDK login, fails unless result_code is 0
+    pub async fn login(&mut self, login: &SdkLogin) -> anyhow::Result<ServerPacket> {
+        let mut body = Vec::new();
+        for field in [login.account_id(), login.token.clone()] {
+            body.extend_from_slice(&(field.len() as u16).to_be_bytes());
+            body.extend_from_slice(field.as_bytes());
+        }
+
+        let reply = self.request_raw(CmdId::LoginRequestCmd, body).await?;
+        expect_ok(&reply)?;
+        Ok(reply)
+    }
+
+    pub async fn request<T: Message>(
+        &mut self,
+        cmd_id: CmdId,
+        msg: &T,
+    ) -> anyhow::Result<ServerPacket> {
+        self.request_raw(cmd_id, msg.encode_to_vec()).await
+    }
+
+    /// Sends a request and waits for the reply carrying its up_tag
+    pub async fn request_raw(
+        &mut self,
+        cmd_id: CmdId,
+        body: Vec<u8>,
+    ) -> anyhow::Result<ServerPacket> {
+        let up_tag = self.send_raw(cmd_id, body).await?;
+
+        loop {
+            let packet = self
+                .recv()
+                .await
+                .with_context(|| format!("waiting for {:?} reply", cmd_id))?;
+
+            if packet.up_tag == PUSH_UP_TAG {
+                self.pushes.push(packet);
+            } else if packet.up_tag == up_tag {
+                return Ok(packet);
+            } else {
+                tracing::warn!(
+                    "Reply for unexpected up_tag {} (cmd {}) while waiting for {}",
+                    packet.up_tag,
+                    packet.cmd_id,
+                    up_tag
+                );
+            }
+        }
+    }
+
+    /// Sends without waiting, returns the up_tag used
+    pub async fn send_raw(&mut self, cmd_id: CmdId, body: Vec<u8>) -> anyhow::Result<u8> {
+        self.sequence = self.sequence.wrapping_add(1);
+        // 255 would look like a push when it comes back
+        self.up_tag = self.up_tag % (PUSH_UP_TAG - 1) + 1;
+
+        let packet = ClientPacket {
+            sequence: self.sequence,
+            cmd_id: cmd_id as i16,
+            up_tag: self.up_tag,
+            data: body,
+        };
+        self.writer.send(packet).await?;
+        Ok(self.up_tag)
+    }
+
+    /// Next frame from the server, whatever it is
+    pub async fn recv(&mut self) -> anyhow::Result<ServerPacket> {
+        match tokio::time::timeout(self.timeout, self.reader.next()).await {
+            Ok(Some(packet)) => Ok(packet?),
+            Ok(None) => bail!("server closed the connection"),
+            Err(_) => bail!("nothing received within {:?}", self.timeout),
+        }
+    }
+
+    /// First push of `cmd_id`, from the ones already received or the ones still coming
+    pub async fn wait_push(&mut self, cmd_id: CmdId) -> anyhow::Result<ServerPacket> {
+        if let Some(idx) = self.pushes.iter().position(|p| p.cmd_id == cmd_id as i16) {
+            return Ok(self.pushes.remove(idx));
+        }
+
+        loop {
+            let packet = self
+                .recv()
+                .await
+                .with_context(|| format!("waiting for {:?}", cmd_id))?;
+
+            if packet.up_tag == PUSH_UP_TAG && packet.cmd_id == cmd_id as i16 {
+                return Ok(packet);
+            }
+            self.pushes.push(packet);
+        }
+    }
+
+    /// Pushes received so far and not taken by `wait_push`
+    pub fn pushes(&self) -> &[ServerPacket] {
+        &self.pushes
+    }
+
+    pub fn take_pushes(&mut self) -> Vec<ServerPacket> {
+        std::mem::take(&mut self.pushes)
+    }
+}
+
+/// Name of a packet's cmd for messages, falls back to the raw id
+pub fn cmd_name(cmd_id: i16) -> String {
+    CmdId::try_from(cmd_id as i32)
+        .map(|c| c.as_str_name().to_string())
+        .unwrap_or_else(|_| cmd_id.to_string())
+}
+
+pub fn expect_ok(reply: &ServerPacket) -> anyhow::Result<()> {
+    if reply.result_code != 0 {
+        bail!(
+            "{} failed with result_code {}",
+            cmd_name(reply.cmd_id),
+            reply.result_code
+        );
+    }
+    Ok(())
+}
+
+pub fn decode<T: Message + Default>(packet: &ServerPacket) -> anyhow::Result<T> {
+    Ok(packet.decode_message()?)
+}
@@ -0,0 +1,162 @@
+//! sdkserver + gameserver running inside the test process on random ports,
+//! each `TestServer` with its own temporary SQLite file.
+//!
+//! Game data comes from `JSON_DATA_DIR` (same as the build). Without it the
+//! servers start on `fixtures/excel`, empty tables that are enough to create an
+//! account and log in but not to summon or fight.
+
+use crate::scenario::Target;
+use common::config::{
+    CaptureConfig, CombatConfig, CommandConfig, DatabaseConfig, NetworkConfig, PathConfig,
+    ServerConfig, ServerSettings, SummonConfig,
+};
+use database::{DatabaseSettings, connect_to, run_migrations};
+use gameserver::state::AppState as GameState;
+use sdkserver::{AppState, SdkState};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, OnceLock};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+static GAME_DATA: OnceLock<Result<(), String>> = OnceLock::new();
+static NEXT_SERVER: AtomicU32 = AtomicU32::new(0);
+
+fn workspace_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_default()
+}
+
+fn excel_data_dir() -> PathBuf {
+    std::env::var_os("JSON_DATA_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/excel"))
+}
+
+/// Sets up the global config and loads the excel tables, once per process.
+/// Err explains what's missing.
+pub fn init_game_data() -> Result<(), String> {
+    GAME_DATA
+        .get_or_init(|| {
+            let data_dir = workspace_dir().join("data");
+            let excel_data = excel_data_dir();
+
+            common::init_config(ServerConfig {
+                server: ServerSettings {
+                    host: "127.0.0.1".into(),
+                    dns: "localhost".into(),
+                    http_port: 0,
+                    game_port: 0,
+                },
+                paths: PathConfig {
+                    static_data: data_dir.join("static"),
+                    data_dir,
+                    excel_data: excel_data.clone(),
+                },
+                // every TestServer brings its own database
+                database: DatabaseConfig {
+                    path: std::env::temp_dir().join("sonetto-testclient.db"),
+                },
+                commands: CommandConfig::default(),
+                network: NetworkConfig::default(),
+                capture: CaptureConfig::default(),
+                summon: SummonConfig::default(),
+                combat: CombatConfig::default(),
+            });
+
+            data::exceldb::init(&excel_data.to_string_lossy()).map_err(|e| {
+                format!(
+                    "game data not available at {} ({}), set JSON_DATA_DIR",
+                    excel_data.display(),
+                    e
+                )
+            })
+        })
+        .clone()
+}
+
+pub struct TestServer {
+    pub game_addr: SocketAddr,
+    pub sdk_url: String,
+    pub state: Arc<GameState>,
+    dir: PathBuf,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl TestServer {
+    pub async fn start() -> anyhow::Result<Self> {
+        init_game_data().map_err(anyhow::Error::msg)?;
+
+        let dir = std::env::temp_dir().join(format!(
+            "sonetto-test-{}-{}",
+            std::process::id(),
+            NEXT_SERVER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)?;
+
+        let db_settings = DatabaseSettings {
+            db_name: dir.join("sonetto.db").to_string_lossy().to_string(),
+        };
+        let db = connect_to(&db_settings).await?;
+        run_migrations(&db).await?;
+
+        // both servers share one state like they share the db file in production
+        let state = Arc::new(GameState::new(db));
+
+        let game_listener = TcpListener::bind("127.0.0.1:0").await?;
+        let game_addr = game_listener.local_addr()?;
+        let sdk_listener = TcpListener::bind("127.0.0.1:0").await?;
+        let sdk_addr = sdk_listener.local_addr()?;
+
+        let game_state = state.clone();
+        let game = tokio::spawn(async move {
+            if let Err(e) = gameserver::server::run(game_listener, game_state).await {
+                tracing::error!("Test gameserver stopped: {e}");
+            }
+        });
+
+        let app = sdkserver::app(AppState {
+            sdk: SdkState {
+                http_client: reqwest::Client::new(),
+            },
+            game: state.clone(),
+        });
+        let sdk = tokio::spawn(async move {
+            if let Err(e) = axum::serve(sdk_listener, app).await {
+                tracing::error!("Test sdkserver stopped: {e}");
+            }
+        });
+
+        Ok(Self {
+            game_addr,
+            sdk_url: format!("http://{}", sdk_addr),
+            state,
+            dir,
+            tasks: vec![game, sdk],
+        })
+    }
+
+    pub fn target(&self) -> Target {
+        Target {
+            sdk_url: self.sdk_url.clone(),
+            game_addr: self.game_addr.to_string(),
+        }
+    }
+
+    pub fn db_path(&self) -> PathBuf {
+        self.dir.join("sonetto.db")
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+        // best effort, the pool may still hold the file open on windows
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
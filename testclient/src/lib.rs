@@ -0,0 +1,15 @@
+//! Headless client for the sdkserver + gameserver pair: SDK login over HTTP,
+//! the game protocol over TCP, a scenario DSL on top and an in-process harness
+//! to run it all against a throwaway database.
+//! `capture` decodes and replays the gameserver's packet captures.
+
+pub mod capture;
+pub mod game;
+pub mod harness;
+pub mod scenario;
+pub mod sdk;
+
+pub use game::GameClient;
+pub use harness::TestServer;
+pub use scenario::{Account, Scenario, Session, Step, Target};
+pub use sdk::{SdkClient, SdkLogin};
@@ -0,0 +1,93 @@
+use anyhow::{Context, bail};
+use common::init_tracing;
+use std::path::PathBuf;
+use testclient::{Account, Scenario, Target, TestServer};
+
+const USAGE: &str = "\
+usage: testclient [options] <scenario file>
+
+  --in-process          start sdkserver + gameserver in this process on a temp database
+  --sdk <url>           sdkserver base url      (default http://127.0.0.1:21000)
+  --game <host:port>    gameserver address      (default 127.0.0.1:23301)
+  --email <email>       account, created on first login (default testclient@sonetto.local)
+  --password <pwd>      password                (default testclient)";
+
+struct Args {
+    scenario: PathBuf,
+    in_process: bool,
+    target: Target,
+    account: Account,
+}
+
+fn parse_args() -> anyhow::Result<Args> {
+    let mut scenario = None;
+    let mut in_process = false;
+    let mut target = Target {
+        sdk_url: "http://127.0.0.1:21000".into(),
+        game_addr: "127.0.0.1:23301".into(),
+    };
+    let mut account = Account::new("testclient@sonetto.local", "testclient");
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .with_context(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--in-process" => in_process = true,
+            "--sdk" => target.sdk_url = value()?,
+            "--game" => target.game_addr = value()?,
+            "--email" => account.email = value()?,
+            "--password" => account.password = value()?,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            _ if arg.starts_with("--") => bail!("unknown option {}\n\n{}", arg, USAGE),
+            _ => scenario = Some(PathBuf::from(arg)),
+        }
+    }
+
+    Ok(Args {
+        scenario: scenario.with_context(|| format!("no scenario file\n\n{USAGE}"))?,
+        in_process,
+        target,
+        account,
+    })
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    init_tracing();
+
+    let args = parse_args()?;
+    let text = std::fs::read_to_string(&args.scenario)
+        .with_context(|| format!("reading {}", args.scenario.display()))?;
+    let name = args
+        .scenario
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let scenario = Scenario::parse(name, &text)?;
+
+    // keep the server alive until the scenario is done
+    let (_server, target) = if args.in_process {
+        let server = TestServer::start().await?;
+        let target = server.target();
+        (Some(server), target)
+    } else {
+        (None, args.target)
+    };
+
+    let session = scenario.run(&target, &args.account).await?;
+
+    println!(
+        "scenario `{}` passed: {} steps, user_id {}, {} unclaimed pushes",
+        scenario.name,
+        scenario.steps.len(),
+        session.login.user_id,
+        session.client.pushes().len()
+    );
+    Ok(())
+}
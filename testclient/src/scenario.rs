@@ -0,0 +1,536 @@
+//! Small scenario DSL, usable from rust:
+//!
+//! ```ignore
+//! Scenario::new("first fight")
+//!     .login()
+//!     .summon(pool_id, 10)
+//!     .start_dungeon(chapter_id, episode_id)
+//!     .begin_round_until_end(true)
+//!     .expect_push(CmdId::FightEndFightPushCmd)
+//!     .end_fight(false)
+//!     .run(&target, &account)
+//!     .await?;
+//! ```
+//!
+//! or from a text file, one step per line, `#` starts a comment:
+//!
+//! ```text
+//! login
+//! summon 1 10
+//! start_dungeon 101 10101        # optional hero uids after the episode
+//! begin_round auto               # one round
+//! begin_round_until_end auto     # rounds until the fight is over
+//! expect_push FightEndFightPushCmd
+//! end_fight
+//! request GetServerTimeCmd       # optional hex body
+//! expect_result 0
+//! ```
+
+use crate::game::{GameClient, cmd_name, decode, expect_ok};
+use crate::sdk::{SdkClient, SdkLogin};
+use anyhow::{Context, anyhow, bail};
+use gameserver::packet::ServerPacket;
+use sonettobuf::{
+    BeginRoundReply, BeginRoundRequest, CmdId, EndFightRequest, FightGroup, GetHeroGroupListReply,
+    GetHeroGroupListRequest, StartDungeonRequest, SummonRequest,
+};
+use std::fmt;
+
+/// `begin_round_until_end` gives up after this many rounds
+const MAX_FIGHT_ROUNDS: usize = 100;
+
+/// Where the servers are
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub sdk_url: String,
+    pub game_addr: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Account {
+    pub email: String,
+    pub password: String,
+}
+
+impl Account {
+    pub fn new(email: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            email: email.into(),
+            password: password.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// SDK `/login/mail` then LoginRequestCmd on the game port
+    Login,
+    Summon {
+        pool_id: i32,
+        count: i32,
+    },
+    /// Empty `heroes` uses the first non empty hero group of the account
+    StartDungeon {
+        chapter_id: i32,
+        episode_id: i32,
+        heroes: Vec<i64>,
+    },
+    BeginRound {
+        auto: bool,
+    },
+    /// BeginRound again and again until a round comes back finished
+    BeginRoundUntilEnd {
+        auto: bool,
+    },
+    EndFight {
+        abort: bool,
+    },
+    /// Any cmd with a raw body, the result code isn't checked
+    Request {
+        cmd_id: CmdId,
+        body: Vec<u8>,
+    },
+    /// result_code of the last reply
+    ExpectResult(u16),
+    ExpectPush(CmdId),
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Login => write!(f, "login"),
+            Step::Summon { pool_id, count } => write!(f, "summon {} {}", pool_id, count),
+            Step::StartDungeon {
+                chapter_id,
+                episode_id,
+                heroes,
+            } => {
+                write!(f, "start_dungeon {} {}", chapter_id, episode_id)?;
+                for hero in heroes {
+                    write!(f, " {}", hero)?;
+                }
+                Ok(())
+            }
+            Step::BeginRound { auto: true } => write!(f, "begin_round auto"),
+            Step::BeginRound { auto: false } => write!(f, "begin_round"),
+            Step::BeginRoundUntilEnd { auto: true } => write!(f, "begin_round_until_end auto"),
+            Step::BeginRoundUntilEnd { auto: false } => write!(f, "begin_round_until_end"),
+            Step::EndFight { abort: true } => write!(f, "end_fight abort"),
+            Step::EndFight { abort: false } => write!(f, "end_fight"),
+            Step::Request { cmd_id, body } if body.is_empty() => {
+                write!(f, "request {}", cmd_id.as_str_name())
+            }
+            Step::Request { cmd_id, body } => {
+                write!(f, "request {} {}", cmd_id.as_str_name(), hex::encode(body))
+            }
+            Step::ExpectResult(code) => write!(f, "expect_result {}", code),
+            Step::ExpectPush(cmd_id) => write!(f, "expect_push {}", cmd_id.as_str_name()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub name: String,
+    pub steps: Vec<Step>,
+}
+
+impl Scenario {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn step(mut self, step: Step) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn login(self) -> Self {
+        self.step(Step::Login)
+    }
+
+    pub fn summon(self, pool_id: i32, count: i32) -> Self {
+        self.step(Step::Summon { pool_id, count })
+    }
+
+    pub fn start_dungeon(self, chapter_id: i32, episode_id: i32) -> Self {
+        self.step(Step::StartDungeon {
+            chapter_id,
+            episode_id,
+            heroes: Vec::new(),
+        })
+    }
+
+    pub fn begin_round(self, auto: bool) -> Self {
+        self.step(Step::BeginRound { auto })
+    }
+
+    pub fn begin_round_until_end(self, auto: bool) -> Self {
+        self.step(Step::BeginRoundUntilEnd { auto })
+    }
+
+    pub fn end_fight(self, abort: bool) -> Self {
+        self.step(Step::EndFight { abort })
+    }
+
+    pub fn request(self, cmd_id: CmdId, body: Vec<u8>) -> Self {
+        self.step(Step::Request { cmd_id, body })
+    }
+
+    pub fn expect_result(self, code: u16) -> Self {
+        self.step(Step::ExpectResult(code))
+    }
+
+    pub fn expect_push(self, cmd_id: CmdId) -> Self {
+        self.step(Step::ExpectPush(cmd_id))
+    }
+
+    pub fn parse(name: impl Into<String>, text: &str) -> anyhow::Result<Self> {
+        let mut scenario = Self::new(name);
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let step = parse_step(line).with_context(|| format!("line {}: `{}`", idx + 1, line))?;
+            scenario.steps.push(step);
+        }
+
+        Ok(scenario)
+    }
+
+    /// Runs every step in order, the first failing one aborts with its line in the error
+    pub async fn run(&self, target: &Target, account: &Account) -> anyhow::Result<Session> {
+        let mut runner = Runner {
+            target,
+            account,
+            session: None,
+        };
+
+        for (idx, step) in self.steps.iter().enumerate() {
+            tracing::info!("[{}] step {}: {}", self.name, idx + 1, step);
+            runner
+                .run_step(step)
+                .await
+                .with_context(|| format!("[{}] step {} `{}` failed", self.name, idx + 1, step))?;
+        }
+
+        runner
+            .session
+            .ok_or_else(|| anyhow!("[{}] never logged in", self.name))
+    }
+}
+
+fn parse_step(line: &str) -> anyhow::Result<Step> {
+    let mut words = line.split_whitespace();
+    let op = words.next().unwrap_or_default();
+    let args: Vec<&str> = words.collect();
+
+    let int = |idx: usize| -> anyhow::Result<i32> {
+        let arg = args
+            .get(idx)
+            .ok_or_else(|| anyhow!("missing argument {}", idx + 1))?;
+        arg.parse()
+            .with_context(|| format!("`{}` is not a number", arg))
+    };
+    let flag = |name: &str| args.first().is_some_and(|a| *a == name);
+    let cmd = |idx: usize| -> anyhow::Result<CmdId> {
+        let arg = args.get(idx).ok_or_else(|| anyhow!("missing cmd name"))?;
+        CmdId::from_str_name(arg).ok_or_else(|| anyhow!("unknown cmd `{}`", arg))
+    };
+
+    Ok(match op {
+        "login" => Step::Login,
+        "summon" => Step::Summon {
+            pool_id: int(0)?,
+            count: if args.len() > 1 { int(1)? } else { 1 },
+        },
+        "start_dungeon" => Step::StartDungeon {
+            chapter_id: int(0)?,
+            episode_id: int(1)?,
+            heroes: args
+                .iter()
+                .skip(2)
+                .map(|a| a.parse().with_context(|| format!("bad hero uid `{}`", a)))
+                .collect::<anyhow::Result<_>>()?,
+        },
+        "begin_round" => Step::BeginRound { auto: flag("auto") },
+        "begin_round_until_end" => Step::BeginRoundUntilEnd { auto: flag("auto") },
+        "end_fight" => Step::EndFight {
+            abort: flag("abort"),
+        },
+        "request" => Step::Request {
+            cmd_id: cmd(0)?,
+            body: match args.get(1) {
+                Some(body) => hex::decode(body).context("body is not hex")?,
+                None => Vec::new(),
+            },
+        },
+        "expect_result" => Step::ExpectResult(int(0)? as u16),
+        "expect_push" => Step::ExpectPush(cmd(0)?),
+        _ => bail!("unknown step `{}`", op),
+    })
+}
+
+/// A logged in connection, handed back after a scenario so tests can keep going
+pub struct Session {
+    pub login: SdkLogin,
+    pub client: GameClient,
+    pub last_reply: Option<ServerPacket>,
+}
+
+struct Runner<'a> {
+    target: &'a Target,
+    account: &'a Account,
+    session: Option<Session>,
+}
+
+impl Runner<'_> {
+    fn session(&mut self) -> anyhow::Result<&mut Session> {
+        self.session
+            .as_mut()
+            .ok_or_else(|| anyhow!("not logged in, add a `login` step first"))
+    }
+
+    async fn run_step(&mut self, step: &Step) -> anyhow::Result<()> {
+        match step {
+            Step::Login => {
+                let sdk = SdkClient::new(&self.target.sdk_url);
+                let login = sdk
+                    .login_mail(&self.account.email, &self.account.password)
+                    .await?;
+
+                let mut client = GameClient::connect(&self.target.game_addr).await?;
+                let reply = client.login(&login).await?;
+
+                self.session = Some(Session {
+                    login,
+                    client,
+                    last_reply: Some(reply),
+                });
+            }
+            Step::Summon { pool_id, count } => {
+                let req = SummonRequest {
+                    pool_id: Some(*pool_id),
+                    count: Some(*count),
+                    ..Default::default()
+                };
+                self.checked_request(CmdId::SummonCmd, &req).await?;
+            }
+            Step::StartDungeon {
+                chapter_id,
+                episode_id,
+                heroes,
+            } => {
+                let hero_list = if heroes.is_empty() {
+                    self.default_heroes().await?
+                } else {
+                    heroes.clone()
+                };
+
+                let req = StartDungeonRequest {
+                    chapter_id: Some(*chapter_id),
+                    episode_id: Some(*episode_id),
+                    fight_group: Some(FightGroup {
+                        hero_list,
+                        ..Default::default()
+                    }),
+                    multiplication: Some(1),
+                    ..Default::default()
+                };
+                self.checked_request(CmdId::StartDungeonCmd, &req).await?;
+            }
+            Step::BeginRound { auto } => {
+                let req = BeginRoundRequest {
+                    opers: Vec::new(),
+                    auto_oper: Some(*auto),
+                };
+                self.checked_request(CmdId::BeginRoundCmd, &req).await?;
+            }
+            Step::BeginRoundUntilEnd { auto } => {
+                let req = BeginRoundRequest {
+                    opers: Vec::new(),
+                    auto_oper: Some(*auto),
+                };
+
+                for _ in 0..MAX_FIGHT_ROUNDS {
+                    self.checked_request(CmdId::BeginRoundCmd, &req).await?;
+
+                    let reply: BeginRoundReply = decode(self.last_reply()?)?;
+                    if reply.round.and_then(|r| r.is_finish).unwrap_or(false) {
+                        return Ok(());
+                    }
+                }
+                bail!("fight still going after {} rounds", MAX_FIGHT_ROUNDS);
+            }
+            Step::EndFight { abort } => {
+                let req = EndFightRequest {
+                    is_abort: Some(*abort),
+                };
+                self.checked_request(CmdId::FightEndFightCmd, &req).await?;
+            }
+            Step::Request { cmd_id, body } => {
+                let session = self.session()?;
+                let reply = session.client.request_raw(*cmd_id, body.clone()).await?;
+                session.last_reply = Some(reply);
+            }
+            Step::ExpectResult(code) => {
+                let reply = self.last_reply()?;
+                if reply.result_code != *code {
+                    bail!(
+                        "{} returned result_code {}, expected {}",
+                        cmd_name(reply.cmd_id),
+                        reply.result_code,
+                        code
+                    );
+                }
+            }
+            Step::ExpectPush(cmd_id) => {
+                self.session()?.client.wait_push(*cmd_id).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn last_reply(&mut self) -> anyhow::Result<&ServerPacket> {
+        self.session()?
+            .last_reply
+            .as_ref()
+            .ok_or_else(|| anyhow!("no reply yet"))
+    }
+
+    async fn checked_request<T: prost::Message>(
+        &mut self,
+        cmd_id: CmdId,
+        msg: &T,
+    ) -> anyhow::Result<()> {
+        let session = self.session()?;
+        let reply = session.client.request(cmd_id, msg).await?;
+        expect_ok(&reply)?;
+        session.last_reply = Some(reply);
+        Ok(())
+    }
+
+    async fn default_heroes(&mut self) -> anyhow::Result<Vec<i64>> {
+        let session = self.session()?;
+        let reply = session
+            .client
+            .request(CmdId::GetHeroGroupListCmd, &GetHeroGroupListRequest {})
+            .await?;
+        expect_ok(&reply)?;
+
+        let groups: GetHeroGroupListReply = decode(&reply)?;
+        groups
+            .group_info_list
+            .into_iter()
+            .map(|g| g.hero_list)
+            .find(|heroes| heroes.iter().any(|&uid| uid != 0))
+            .ok_or_else(|| anyhow!("account has no hero group to fight with"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_step() {
+        let text = "
+            # full run
+            login
+            summon 1 10
+            summon 2
+            start_dungeon 101 10101
+            start_dungeon 101 10102 11 12   # explicit heroes
+            begin_round auto
+            begin_round
+            begin_round_until_end auto
+            end_fight abort
+            end_fight
+            request GetServerTimeCmd
+            request GetServerTimeCmd 0a00
+            expect_result 0
+            expect_push FightEndFightPushCmd
+        ";
+
+        let scenario = Scenario::parse("all", text).unwrap();
+        assert_eq!(
+            scenario.steps,
+            vec![
+                Step::Login,
+                Step::Summon {
+                    pool_id: 1,
+                    count: 10
+                },
+                Step::Summon {
+                    pool_id: 2,
+                    count: 1
+                },
+                Step::StartDungeon {
+                    chapter_id: 101,
+                    episode_id: 10101,
+                    heroes: vec![]
+                },
+                Step::StartDungeon {
+                    chapter_id: 101,
+                    episode_id: 10102,
+                    heroes: vec![11, 12]
+                },
+                Step::BeginRound { auto: true },
+                Step::BeginRound { auto: false },
+                Step::BeginRoundUntilEnd { auto: true },
+                Step::EndFight { abort: true },
+                Step::EndFight { abort: false },
+                Step::Request {
+                    cmd_id: CmdId::GetServerTimeCmd,
+                    body: vec![]
+                },
+                Step::Request {
+                    cmd_id: CmdId::GetServerTimeCmd,
+                    body: vec![0x0a, 0x00]
+                },
+                Step::ExpectResult(0),
+                Step::ExpectPush(CmdId::FightEndFightPushCmd),
+            ]
+        );
+    }
+
+    #[test]
+    fn display_parses_back() {
+        let scenario = Scenario::new("builder")
+            .login()
+            .summon(3, 10)
+            .step(Step::StartDungeon {
+                chapter_id: 1,
+                episode_id: 2,
+                heroes: vec![5],
+            })
+            .begin_round(true)
+            .begin_round_until_end(false)
+            .end_fight(false)
+            .request(CmdId::GetServerTimeCmd, vec![1, 2])
+            .expect_result(1)
+            .expect_push(CmdId::FightEndFightPushCmd);
+
+        let text: Vec<String> = scenario.steps.iter().map(|s| s.to_string()).collect();
+        let parsed = Scenario::parse("text", &text.join("\n")).unwrap();
+        assert_eq!(parsed.steps, scenario.steps);
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let err = Scenario::parse("bad", "login\nsummon x").unwrap_err();
+        assert!(format!("{err:#}").contains("line 2"));
+
+        assert!(Scenario::parse("bad", "fly 1").is_err());
+        assert!(Scenario::parse("bad", "expect_push NotACmd").is_err());
+        assert!(Scenario::parse("bad", "start_dungeon 1").is_err());
+        assert!(Scenario::parse("bad", "request GetServerTimeCmd zz").is_err());
+    }
+}
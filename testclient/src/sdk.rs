@@ -0,0 +1,129 @@
+use anyhow::{Context, bail};
+use sdkserver::middleware::crypto::{decrypt_body, encrypt_body};
+use serde_json::{Value, json};
+
+/// What the game port needs from an SDK login
+#[derive(Debug, Clone)]
+pub struct SdkLogin {
+    pub user_id: i64,
+    pub token: String,
+}
+
+impl SdkLogin {
+    /// `channelId_userId`, the account id format LoginRequestCmd expects
+    pub fn account_id(&self) -> String {
+        format!("200_{}", self.user_id)
+    }
+}
+
+/// Talks to the sdkserver account routes the same way the game's SDK does:
+/// json bodies going through the byte swap + gzip of `middleware::crypto`.
+pub struct SdkClient {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl SdkClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// `/login/mail`, creates the account on first use
+    pub async fn login_mail(&self, email: &str, password: &str) -> anyhow::Result<SdkLogin> {
+        let body = json!({
+            "deviceInfo": device_info(),
+            "appPackageInfo": app_package_info(),
+            "reactivate": false,
+            "account": email,
+            "pwd": password,
+        });
+
+        let rsp = self.post_encrypted("/login/mail", &body).await?;
+
+        let code = rsp["code"].as_u64().unwrap_or_default();
+        if code != 200 {
+            bail!("login/mail failed ({}): {}", code, rsp["msg"]);
+        }
+
+        let data = &rsp["data"];
+        Ok(SdkLogin {
+            user_id: data["userId"]
+                .as_i64()
+                .context("login/mail reply without userId")?,
+            token: data["token"]
+                .as_str()
+                .context("login/mail reply without token")?
+                .to_string(),
+        })
+    }
+
+    async fn post_encrypted(&self, route: &str, body: &Value) -> anyhow::Result<Value> {
+        let plain = serde_json::to_vec(body)?;
+
+        let rsp = self
+            .http
+            .post(format!("{}{}", self.base_url, route))
+            .body(encrypt_body(&plain)?)
+            .send()
+            .await
+            .with_context(|| format!("POST {} failed", route))?;
+
+        let status = rsp.status();
+        let bytes = rsp.bytes().await?;
+        if !status.is_success() {
+            bail!("POST {} returned {}", route, status);
+        }
+
+        let plain = decrypt_body(&bytes).with_context(|| format!("bad {} reply", route))?;
+        Ok(serde_json::from_slice(&plain)?)
+    }
+}
+
+fn device_info() -> Value {
+    json!({
+        "networkName": "wifi",
+        "deviceId": "testclient",
+        "cnadid": "",
+        "oaId": "",
+        "androidId": "",
+        "imsi": "",
+        "imei": "",
+        "uuid": "00000000-0000-0000-0000-000000000000",
+        "deviceName": "testclient",
+        "deviceManufacturer": "sonetto",
+        "osType": 3,
+        "osVersion": "headless",
+        "apiLevel": "",
+        "language": "en",
+        "displayWidth": "1920",
+        "displayHeight": "1080",
+        "hardware": "",
+        "buildName": "",
+        "distinctId": "",
+        "anonymousId": "",
+    })
+}
+
+fn app_package_info() -> Value {
+    json!({
+        "appPackageName": "testclient",
+        "appVersion": 0,
+        "appVersionName": "",
+        "gameId": 60001,
+        "gameCode": "",
+        "gameName": "",
+        "channelId": "200",
+        "subChannelId": "",
+        "appInstallTime": "",
+        "appUpdateTime": "",
+        "appSignature": "",
+        "sdkVersion": "",
+        "channelVersion": "",
+        "adFid": "",
+        "gclid": "",
+        "dataAppId": "",
+    })
+}
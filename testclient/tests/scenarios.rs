@@ -0,0 +1,116 @@
+//! End to end runs against an in-process server. Login runs on the empty
+//! fixture tables, anything that summons or fights needs the real excel data
+//! and is ignored by default:
+//!   JSON_DATA_DIR=... cargo test -p testclient -- --ignored
+
+use sonettobuf::{CmdId, GetServerTimeReply, SummonReply};
+use testclient::game::decode;
+use testclient::{Account, Scenario, TestServer};
+
+async fn test_server() -> TestServer {
+    TestServer::start()
+        .await
+        .unwrap_or_else(|e| panic!("test server failed to start: {e:#}"))
+}
+
+fn account(name: &str) -> Account {
+    Account::new(format!("{name}@testclient.local"), "testclient")
+}
+
+/// First episode of the main story that actually has a fight
+fn first_battle_episode() -> (i32, i32) {
+    data::exceldb::get()
+        .episode
+        .iter()
+        .filter(|e| e.battle_id != 0)
+        .min_by_key(|e| (e.chapter_id, e.id))
+        .map(|e| (e.chapter_id, e.id))
+        .expect("no battle episode in game data")
+}
+
+#[tokio::test]
+async fn login_and_server_time() {
+    let server = test_server().await;
+
+    let session = Scenario::new("login")
+        .login()
+        .request(CmdId::GetServerTimeCmd, Vec::new())
+        .expect_result(0)
+        .run(&server.target(), &account("login"))
+        .await
+        .unwrap();
+
+    let reply: GetServerTimeReply = decode(session.last_reply.as_ref().unwrap()).unwrap();
+    assert!(reply.server_time.unwrap_or_default() > 0);
+    assert!(session.login.user_id > 0);
+}
+
+#[tokio::test]
+async fn relogin_keeps_account() {
+    let server = test_server().await;
+    let account = account("relogin");
+
+    let first = Scenario::new("first").login();
+    let a = first.run(&server.target(), &account).await.unwrap();
+    let b = first.run(&server.target(), &account).await.unwrap();
+
+    assert_eq!(a.login.user_id, b.login.user_id);
+}
+
+#[tokio::test]
+async fn wrong_password_is_rejected() {
+    let server = test_server().await;
+
+    Scenario::new("create")
+        .login()
+        .run(&server.target(), &account("password"))
+        .await
+        .unwrap();
+
+    let wrong = Account::new("password@testclient.local", "not the password");
+    let err = Scenario::new("wrong password")
+        .login()
+        .run(&server.target(), &wrong)
+        .await
+        .err()
+        .expect("login with a wrong password succeeded");
+    assert!(format!("{err:#}").contains("login/mail failed"));
+}
+
+#[tokio::test]
+#[ignore = "needs the excel data in JSON_DATA_DIR"]
+async fn summon_ten() {
+    let server = test_server().await;
+
+    let session = Scenario::new("summon")
+        .login()
+        .summon(1, 10)
+        .run(&server.target(), &account("summon"))
+        .await
+        .unwrap();
+
+    let reply: SummonReply = decode(session.last_reply.as_ref().unwrap()).unwrap();
+    assert_eq!(reply.summon_result.len(), 10);
+}
+
+#[tokio::test]
+#[ignore = "needs the excel data in JSON_DATA_DIR"]
+async fn first_fight() {
+    let server = test_server().await;
+    let (chapter_id, episode_id) = first_battle_episode();
+
+    let session = Scenario::new("fight")
+        .login()
+        .start_dungeon(chapter_id, episode_id)
+        .begin_round_until_end(true)
+        .expect_push(CmdId::FightEndFightPushCmd)
+        .end_fight(false)
+        .run(&server.target(), &account("fight"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        session.last_reply.unwrap().cmd_id,
+        CmdId::FightEndFightCmd as i16
+    );
+}